@@ -17,7 +17,9 @@ pub struct TrackedActivity {
     pub id: String,
     pub agent_id: String,
     pub context: ActivityContext,
-    pub classification: Option<ActivityClassification>,
+    /// Most recent output the activity was classified from
+    pub output: String,
+    pub classification: Option<ActivityClass>,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub end_time: Option<chrono::DateTime<chrono::Utc>>,
     pub outcome: ActivityOutcome,
@@ -58,17 +60,43 @@ impl ActivityTracker {
         }
     }
     
+    /// The classifier activities are classified with
+    pub fn classifier(&self) -> &ActivityClassifier {
+        &self.classifier
+    }
+
+    /// Mutable access to the classifier, e.g. to swap its keyword rules
+    pub fn classifier_mut(&mut self) -> &mut ActivityClassifier {
+        &mut self.classifier
+    }
+
     /// Start tracking a new activity
-    pub async fn start_activity(&mut self, agent_id: String, context: ActivityContext) -> Result<String, BamlError> {
-        let activity_id = uuid::Uuid::new_v4().to_string();
+    pub async fn start_activity(&mut self, agent_id: String, output: &str, mut context: ActivityContext) -> Result<String, ClassificationError> {
+        context.agent_id.get_or_insert_with(|| agent_id.clone());
         
         // Classify the activity
-        let classification = self.classifier.classify(&context).await.ok();
+        let classification = self.classifier.classify_with_context(output, Some(&context)).await.ok();
+        Ok(self.start_classified_activity(agent_id, output, context, classification))
+    }
+    
+    /// Start tracking a new activity that was already classified
+    ///
+    /// Lets callers classify without holding the tracker, e.g. across a model call.
+    pub fn start_classified_activity(
+        &mut self,
+        agent_id: String,
+        output: &str,
+        mut context: ActivityContext,
+        classification: Option<ActivityClass>,
+    ) -> String {
+        let activity_id = uuid::Uuid::new_v4().to_string();
+        context.agent_id.get_or_insert_with(|| agent_id.clone());
         
         let tracked_activity = TrackedActivity {
             id: activity_id.clone(),
             agent_id: agent_id.clone(),
             context,
+            output: output.to_string(),
             classification,
             start_time: chrono::Utc::now(),
             end_time: None,
@@ -80,27 +108,63 @@ impl ActivityTracker {
             .or_default()
             .push(tracked_activity);
         
-        Ok(activity_id)
+        activity_id
     }
     
     /// Update an ongoing activity
-    pub async fn update_activity(&mut self, agent_id: &str, activity_id: &str, context: ActivityContext) -> Result<(), BamlError> {
+    pub async fn update_activity(&mut self, agent_id: &str, activity_id: &str, output: &str, context: ActivityContext) -> Result<(), ClassificationError> {
+        // Re-classify with updated context
+        let classification = self.classifier.classify_with_context(output, Some(&context)).await.ok();
+        self.update_classified_activity(agent_id, activity_id, output, context, classification);
+        Ok(())
+    }
+    
+    /// Update an ongoing activity with a classification made outside the tracker
+    ///
+    /// The previous classification is kept when `classification` is `None`.
+    pub fn update_classified_activity(
+        &mut self,
+        agent_id: &str,
+        activity_id: &str,
+        output: &str,
+        context: ActivityContext,
+        classification: Option<ActivityClass>,
+    ) {
         if let Some(activities) = self.activities.get_mut(agent_id) {
             if let Some(activity) = activities.iter_mut().find(|a| a.id == activity_id) {
                 activity.context = context;
-                
-                // Re-classify with updated context
-                if let Ok(classification) = self.classifier.classify(&activity.context).await {
+                activity.output = output.to_string();
+                if let Some(classification) = classification {
                     activity.classification = Some(classification);
                 }
             }
         }
-        
-        Ok(())
     }
     
+    /// Get the activity an agent is currently engaged in, if any
+    pub fn current_activity(&self, agent_id: &str) -> Option<&TrackedActivity> {
+        self.activities
+            .get(agent_id)
+            .and_then(|activities| activities.iter().rev().find(|a| matches!(a.outcome, ActivityOutcome::InProgress)))
+    }
+    
+    /// The activity each agent is currently engaged in
+    pub fn current_activities(&self) -> impl Iterator<Item = &TrackedActivity> {
+        self.activities.keys().filter_map(|agent_id| self.current_activity(agent_id))
+    }
+
+    /// Record how long the agent's current activity is predicted to have left
+    pub fn set_estimated_completion(&mut self, agent_id: &str, remaining: Duration) {
+        let current = self.activities
+            .get_mut(agent_id)
+            .and_then(|activities| activities.iter_mut().rev().find(|a| matches!(a.outcome, ActivityOutcome::InProgress)));
+        if let Some(classification) = current.and_then(|activity| activity.classification.as_mut()) {
+            classification.estimated_completion = Some(remaining);
+        }
+    }
+
     /// Complete an activity
-    pub fn complete_activity(&mut self, agent_id: &str, activity_id: &str, completion: CompletionDetails) -> Result<(), BamlError> {
+    pub fn complete_activity(&mut self, agent_id: &str, activity_id: &str, completion: CompletionDetails) -> Result<(), ClassificationError> {
         if let Some(activities) = self.activities.get_mut(agent_id) {
             if let Some(activity) = activities.iter_mut().find(|a| a.id == activity_id) {
                 activity.end_time = Some(chrono::Utc::now());
@@ -112,7 +176,7 @@ impl ActivityTracker {
     }
     
     /// Mark an activity as failed
-    pub fn fail_activity(&mut self, agent_id: &str, activity_id: &str, failure: FailureDetails) -> Result<(), BamlError> {
+    pub fn fail_activity(&mut self, agent_id: &str, activity_id: &str, failure: FailureDetails) -> Result<(), ClassificationError> {
         if let Some(activities) = self.activities.get_mut(agent_id) {
             if let Some(activity) = activities.iter_mut().find(|a| a.id == activity_id) {
                 activity.end_time = Some(chrono::Utc::now());
@@ -196,8 +260,8 @@ impl ActivityTracker {
         let mut category_counts = HashMap::new();
         for activity in activities {
             if let Some(ref classification) = activity.classification {
-                let category = classification.primary_category.as_str();
-                *category_counts.entry(category.to_string()).or_insert(0) += 1;
+                let category = format!("{:?}", classification.primary);
+                *category_counts.entry(category).or_insert(0) += 1;
             }
         }
         
@@ -278,10 +342,11 @@ impl ActivityPatternAnalyzer {
         
         for activity in activities {
             if let Some(ref classification) = activity.classification {
-                match classification.primary_category {
-                    Activities::CodeGeneration(_) => code_gen_count += 1,
-                    Activities::ProblemSolving(_) => problem_solving_count += 1,
-                    Activities::Collaboration(_) => collaboration_count += 1,
+                match classification.primary {
+                    ActivityType::Scaffolding | ActivityType::Implementing | ActivityType::Testing => code_gen_count += 1,
+                    ActivityType::Debugging | ActivityType::Researching => problem_solving_count += 1,
+                    ActivityType::Waiting | ActivityType::Stuck => collaboration_count += 1,
+                    ActivityType::Idle => {}
                 }
             }
         }
@@ -329,21 +394,21 @@ impl ActivityPatternAnalyzer {
     
     fn identify_focus_areas(&self, activities: &[TrackedActivity]) -> Vec<String> {
         let mut focus_areas = Vec::new();
-        let mut file_type_counts = HashMap::new();
+        let mut activity_type_counts = HashMap::new();
         
         for activity in activities {
-            for file_type in &activity.context.file_types {
-                *file_type_counts.entry(file_type.clone()).or_insert(0) += 1;
+            for activity_type in &activity.context.recent_activities {
+                *activity_type_counts.entry(format!("{:?}", activity_type)).or_insert(0) += 1;
             }
         }
         
-        // Get the most common file types as focus areas
-        let mut sorted_types: Vec<_> = file_type_counts.into_iter().collect();
-        sorted_types.sort_by(|a, b| b.1.cmp(&a.1));
+        // Get the most common activity types as focus areas
+        let mut sorted_types: Vec<_> = activity_type_counts.into_iter().collect();
+        sorted_types.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
         
-        for (file_type, count) in sorted_types.into_iter().take(3) {
+        for (activity_type, count) in sorted_types.into_iter().take(3) {
             if count > 1 {
-                focus_areas.push(file_type);
+                focus_areas.push(activity_type);
             }
         }
         
@@ -406,7 +471,7 @@ impl ActivityPatternAnalyzer {
             .filter(|a| {
                 a.classification
                     .as_ref()
-                    .map(|c| matches!(c.primary_category, Activities::Collaboration(_)))
+                    .map(|c| matches!(c.primary, ActivityType::Waiting | ActivityType::Stuck))
                     .unwrap_or(false)
             })
             .count();
@@ -469,4 +534,58 @@ pub enum ProductivityTrend {
     Declining,
     Stable,
     Volatile,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_activity_lifecycle() {
+        let mut tracker = ActivityTracker::new(ActivityClassifier::rule_based());
+
+        let activity_id = tracker
+            .start_activity("agent-1".to_string(), "Running tests to verify functionality", ActivityContext::new())
+            .await
+            .unwrap();
+
+        let current = tracker.current_activity("agent-1").unwrap();
+        assert_eq!(current.id, activity_id);
+        assert_eq!(current.classification.as_ref().unwrap().primary, ActivityType::Testing);
+
+        tracker
+            .update_activity("agent-1", &activity_id, "I'm stuck and need help", ActivityContext::new())
+            .await
+            .unwrap();
+        let current = tracker.current_activity("agent-1").unwrap();
+        assert_eq!(current.output, "I'm stuck and need help");
+        assert!(current.classification.as_ref().unwrap().needs_help);
+
+        tracker
+            .complete_activity("agent-1", &activity_id, CompletionDetails {
+                success_indicators: vec!["tests pass".to_string()],
+                artifacts_created: Vec::new(),
+                time_to_completion: Duration::from_secs(60),
+                quality_score: 0.9,
+            })
+            .unwrap();
+        assert!(tracker.current_activity("agent-1").is_none());
+
+        let stats = tracker.get_statistics("agent-1").unwrap();
+        assert_eq!(stats.total_activities, 1);
+        assert_eq!(stats.completed_activities, 1);
+        assert_eq!(stats.category_distribution.get("Stuck"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn test_pattern_analysis_uses_activity_types() {
+        let mut tracker = ActivityTracker::new(ActivityClassifier::rule_based());
+
+        for output in ["Writing the parser", "Adding the lexer", "Implementing codegen"] {
+            tracker.start_activity("agent-1".to_string(), output, ActivityContext::new()).await.unwrap();
+        }
+
+        let analysis = tracker.analyze_patterns("agent-1").unwrap();
+        assert!(matches!(analysis.pattern_type, PatternType::CodeFocused));
+        assert!(tracker.analyze_patterns("unknown").is_none());
+    }
+}
@@ -0,0 +1,459 @@
+//! Content-addressed cache of model replies
+//!
+//! Pane output is reclassified every tick even when little has changed. The
+//! cache keys each request on its normalized input and the model asked, so
+//! repeated requests are answered locally until the entry expires.
+
+use chrono::{DateTime, Utc};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Version of the on-disk cache format
+const CACHE_FILE_VERSION: u32 = 1;
+
+/// How long replies are kept, how many, and where
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    #[serde(with = "dfcoder_types::duration_serde")]
+    pub ttl: Duration,
+    /// Least recently used entries are evicted beyond this
+    pub max_entries: usize,
+    /// File the cache is loaded from and saved to, if any
+    pub path: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(600),
+            max_entries: 1024,
+            path: None,
+        }
+    }
+}
+
+/// Digest identifying one request
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Key a request to `model` on its parts, in order
+    pub fn new(model: &str, parts: &[&str]) -> Self {
+        let mut hasher = Sha256::new();
+        for part in std::iter::once(model).chain(parts.iter().copied()) {
+            // Length-prefixed so ("ab", "c") and ("a", "bc") differ
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        Self(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Reduce pane output to what matters for classification
+///
+/// Terminal escape sequences, spinner glyphs and trailing whitespace are
+/// dropped, runs of whitespace are collapsed, and the numbers in timestamps,
+/// elapsed times, percentages and `3/10` counters are masked, so output that
+/// differs only in a timer or progress count keys the same. Other numbers,
+/// such as how many tests failed or an exit code, change the answer and are
+/// kept.
+pub fn normalize_output(output: &str) -> String {
+    let mut normalized = String::with_capacity(output.len());
+    let mut chars = output.chars().peekable();
+    let mut pending_space = false;
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            skip_escape(&mut chars);
+            continue;
+        }
+        if ('\u{2800}'..='\u{28ff}').contains(&c) || c.is_control() && !c.is_whitespace() {
+            continue;
+        }
+        if c.is_whitespace() {
+            pending_space = !normalized.is_empty();
+            continue;
+        }
+        if pending_space {
+            normalized.push(' ');
+            pending_space = false;
+        }
+        normalized.push(c);
+    }
+
+    volatile_numbers()
+        .replace_all(&normalized, |volatile: &Captures| mask_digits(&volatile[0]))
+        .into_owned()
+}
+
+/// Numbers that measure time or progress rather than outcome
+fn volatile_numbers() -> &'static Regex {
+    static VOLATILE: OnceLock<Regex> = OnceLock::new();
+    VOLATILE.get_or_init(|| {
+        Regex::new(
+            r"(?x)
+            \d{4}-\d{2}-\d{2}(?:[T\ ]\d{1,2}:\d{2}(?::\d{2})?(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?)?  # dates and timestamps
+            | \b\d{1,2}:\d{2}(?::\d{2})?(?:\.\d+)?\b                                           # clock times
+            | \b(?:\d+(?:\.\d+)?(?:ns|us|µs|ms|s|m|h))+\b                                        # elapsed times: 12s, 1m30s
+            | \b\d+(?:\.\d+)?\ ?(?:seconds?|secs?|minutes?|mins?)\b                                # elapsed times, spelled out
+            | \b\d+(?:\.\d+)?%                                                                  # percentages
+            | \b\d+/\d+\b                                                                       # progress counters
+            ",
+        )
+        .expect("volatile number pattern is valid")
+    })
+}
+
+/// Replace each run of digits with `#`
+fn mask_digits(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_ascii_digit() {
+            masked.push(c);
+        } else if !masked.ends_with('#') {
+            masked.push('#');
+        }
+    }
+    masked
+}
+
+/// Skip the rest of an escape sequence after ESC
+fn skip_escape(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    match chars.next() {
+        // CSI: parameters then a final byte in @..~
+        Some('[') => {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+        // OSC: terminated by BEL or ESC \
+        Some(']') => {
+            while let Some(c) = chars.next() {
+                if c == '\u{7}' {
+                    break;
+                }
+                if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                    chars.next();
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Cache effectiveness counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to stay under `max_entries`
+    pub evictions: u64,
+    /// Entries found past their TTL
+    pub expirations: u64,
+    pub entries: usize,
+}
+
+impl CacheStats {
+    /// Share of lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    reply: String,
+    stored_at: DateTime<Utc>,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Logical clock for least-recently-used ordering
+    clock: u64,
+    dirty: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: Vec<(CacheKey, CacheEntry)>,
+}
+
+/// Model replies keyed by request, shared by every clone of a client
+///
+/// Only replies that were parsed successfully should be stored. A cache with
+/// a `path` is loaded when created and saved by `save` and when dropped.
+#[derive(Debug)]
+pub struct ResponseCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    expirations: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Create a cache, loading unexpired entries from `config.path` if it exists
+    ///
+    /// An unreadable cache file is logged and ignored; the cache only saves
+    /// API calls and is never required.
+    pub fn new(config: CacheConfig) -> Self {
+        let mut state = CacheState::default();
+        if let Some(path) = &config.path {
+            match load(path) {
+                Ok(entries) => {
+                    let now = Utc::now();
+                    state.entries = entries.into_iter()
+                        .filter(|(_, entry)| !is_expired(entry, config.ttl, now))
+                        .collect();
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Ignoring classification cache {}: {}", path.display(), e),
+            }
+        }
+
+        let cache = Self {
+            config,
+            state: Mutex::new(state),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            expirations: AtomicU64::new(0),
+        };
+        cache.evict(&mut cache.state.lock().unwrap());
+        cache
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// The stored reply for a request, if it has not expired
+    pub fn get(&self, key: &CacheKey) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        let expired = match state.entries.get_mut(key) {
+            Some(entry) if is_expired(entry, self.config.ttl, Utc::now()) => true,
+            Some(entry) => {
+                entry.last_used = clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Some(entry.reply.clone());
+            }
+            None => false,
+        };
+
+        if expired {
+            state.entries.remove(key);
+            state.dirty = true;
+            self.expirations.fetch_add(1, Ordering::Relaxed);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Store a reply, evicting the least recently used entries if full
+    pub fn insert(&self, key: CacheKey, reply: String) {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let entry = CacheEntry { reply, stored_at: Utc::now(), last_used: state.clock };
+        state.entries.insert(key, entry);
+        state.dirty = true;
+        self.evict(&mut state);
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.dirty |= !state.entries.is_empty();
+        state.entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().entries.len(),
+        }
+    }
+
+    /// Write unexpired entries to `config.path`, if set and anything changed
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.config.path else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        if !state.dirty {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let file = CacheFile {
+            version: CACHE_FILE_VERSION,
+            entries: state.entries.iter()
+                .filter(|(_, entry)| !is_expired(entry, self.config.ttl, now))
+                .map(|(key, entry)| (key.clone(), entry.clone()))
+                .collect(),
+        };
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp, path)?;
+        state.dirty = false;
+        Ok(())
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        let max_entries = self.config.max_entries.max(1);
+        while state.entries.len() > max_entries {
+            let Some(oldest) = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            state.entries.remove(&oldest);
+            state.dirty = true;
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for ResponseCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            tracing::warn!("Failed to save classification cache: {}", e);
+        }
+    }
+}
+
+fn is_expired(entry: &CacheEntry, ttl: Duration, now: DateTime<Utc>) -> bool {
+    (now - entry.stored_at).to_std().is_ok_and(|age| age > ttl)
+}
+
+fn load(path: &Path) -> std::io::Result<Vec<(CacheKey, CacheEntry)>> {
+    let file: CacheFile = serde_json::from_slice(&std::fs::read(path)?)?;
+    if file.version != CACHE_FILE_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unsupported cache version {}", file.version),
+        ));
+    }
+    Ok(file.entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str) -> CacheKey {
+        CacheKey::new("model", &[text])
+    }
+
+    #[test]
+    fn test_normalization_ignores_noise() {
+        let first = normalize_output("\u{1b}[32m⠋ Compiling dfcoder v0.1.0\u{1b}[0m   (12s)\r\n");
+        let second = normalize_output("⠙ Compiling dfcoder v0.1.0 (13s)");
+        assert_eq!(first, second);
+        assert_eq!(first, "Compiling dfcoder v0.1.0 (#s)");
+        assert_ne!(normalize_output("error: build failed"), normalize_output("warning: build failed"));
+        assert_eq!(normalize_output("\u{1b}]0;title\u{7}ok"), "ok");
+        assert_eq!(
+            normalize_output("[2024-05-01T12:30:05Z] 45% 3/10 done in 1m30s, 0.52 seconds at 09:15"),
+            "[#-#-#T#:#:#Z] #% #/# done in #m#s, #.# seconds at #:#",
+        );
+    }
+
+    #[test]
+    fn test_outcomes_survive_normalization() {
+        assert_ne!(key(&normalize_output("0 failed")), key(&normalize_output("2 failed")));
+        assert_eq!(
+            normalize_output("test result: 10 passed; 2 failed; finished in 0.41s"),
+            "test result: 10 passed; 2 failed; finished in #.#s",
+        );
+        assert_ne!(normalize_output("exit code: 0"), normalize_output("exit code: 1"));
+    }
+
+    #[test]
+    fn test_keys_separate_parts_and_models() {
+        assert_eq!(CacheKey::new("m", &["ab", "c"]), CacheKey::new("m", &["ab", "c"]));
+        assert_ne!(CacheKey::new("m", &["ab", "c"]), CacheKey::new("m", &["a", "bc"]));
+        assert_ne!(CacheKey::new("m", &["ab"]), CacheKey::new("n", &["ab"]));
+        assert_eq!(CacheKey::new("m", &[]).as_str().len(), 64);
+    }
+
+    #[test]
+    fn test_hits_misses_and_lru_eviction() {
+        let cache = ResponseCache::new(CacheConfig { max_entries: 2, ..CacheConfig::default() });
+        assert_eq!(cache.get(&key("a")), None);
+
+        cache.insert(key("a"), "A".to_string());
+        cache.insert(key("b"), "B".to_string());
+        assert_eq!(cache.get(&key("a")).as_deref(), Some("A"));
+
+        // "b" is the least recently used
+        cache.insert(key("c"), "C".to_string());
+        assert_eq!(cache.get(&key("b")), None);
+        assert_eq!(cache.get(&key("c")).as_deref(), Some("C"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (2, 2, 1, 2));
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = ResponseCache::new(CacheConfig { ttl: Duration::ZERO, ..CacheConfig::default() });
+        cache.insert(key("a"), "A".to_string());
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(cache.get(&key("a")), None);
+        assert_eq!(cache.stats().expirations, 1);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_cache_persists_across_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CacheConfig { path: Some(dir.path().join("cache/replies.json")), ..CacheConfig::default() };
+
+        {
+            let cache = ResponseCache::new(config.clone());
+            cache.insert(key("a"), "A".to_string());
+        }
+
+        let cache = ResponseCache::new(config.clone());
+        assert_eq!(cache.get(&key("a")).as_deref(), Some("A"));
+
+        std::fs::write(config.path.as_ref().unwrap(), "not json").unwrap();
+        let cache = ResponseCache::new(config);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
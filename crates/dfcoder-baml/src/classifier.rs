@@ -1,5 +1,9 @@
 //! BAML-based activity classification for agent output
 
+use crate::cache::{normalize_output, CacheKey, CacheStats, ResponseCache};
+use crate::client::{extract_json, BamlClient, BamlConfig, BamlError, CancellationToken};
+use crate::rules::RuleSource;
+use crate::usage::{UsageScope, UsageSummary};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error;
@@ -17,6 +21,23 @@ pub struct ActivityClass {
     pub emotional_state: EmotionalState,
     /// Estimated time until completion
     pub estimated_completion: Option<Duration>,
+    /// Which classifier produced this result
+    #[serde(default)]
+    pub source: ClassificationSource,
+}
+
+/// Which classifier produced an `ActivityClass`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClassificationSource {
+    /// The language model
+    Llm,
+    /// A cached language model reply to an equivalent request
+    Cached,
+    /// Keyword rules, because no model is configured
+    #[default]
+    Rules,
+    /// Keyword rules, because the model call failed for `reason`
+    Fallback { reason: String },
 }
 
 /// Types of activities an agent can be performing
@@ -40,6 +61,20 @@ pub enum ActivityType {
     Idle,
 }
 
+impl ActivityType {
+    /// Every activity, in declaration order
+    pub const ALL: [ActivityType; 8] = [
+        ActivityType::Scaffolding,
+        ActivityType::Implementing,
+        ActivityType::Debugging,
+        ActivityType::Testing,
+        ActivityType::Researching,
+        ActivityType::Waiting,
+        ActivityType::Stuck,
+        ActivityType::Idle,
+    ];
+}
+
 /// Emotional/confidence state of the agent
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EmotionalState {
@@ -55,6 +90,17 @@ pub enum EmotionalState {
     Desperate,
 }
 
+impl EmotionalState {
+    /// Every state, from most to least confident
+    pub const ALL: [EmotionalState; 5] = [
+        EmotionalState::Confident,
+        EmotionalState::Focused,
+        EmotionalState::Cautious,
+        EmotionalState::Frustrated,
+        EmotionalState::Desperate,
+    ];
+}
+
 /// Errors that can occur during classification
 #[derive(Debug, Error)]
 pub enum ClassificationError {
@@ -68,32 +114,139 @@ pub enum ClassificationError {
     AuthError,
     #[error("Rate limit exceeded")]
     RateLimitError,
+    #[error("Classification timed out")]
+    Timeout,
+    #[error("Invalid classification: {0}")]
+    InvalidResponse(String),
+    #[error("Classification cancelled")]
+    Cancelled,
+    #[error("Usage budget exceeded: {0}")]
+    BudgetExceeded(String),
+}
+
+impl ClassificationError {
+    /// Whether the rule-based classifier should answer instead
+    ///
+    /// Transient failures, unusable replies and a spent budget fall back; authentication and
+    /// other API errors point at misconfiguration and are returned.
+    pub fn should_fall_back(&self) -> bool {
+        matches!(
+            self,
+            ClassificationError::Timeout
+                | ClassificationError::RateLimitError
+                | ClassificationError::NetworkError(_)
+                | ClassificationError::JsonError(_)
+                | ClassificationError::InvalidResponse(_)
+                | ClassificationError::BudgetExceeded(_)
+        )
+    }
+}
+
+impl From<BamlError> for ClassificationError {
+    fn from(error: BamlError) -> Self {
+        match error {
+            BamlError::RateLimited => ClassificationError::RateLimitError,
+            BamlError::Timeout => ClassificationError::Timeout,
+            BamlError::AuthError(_) => ClassificationError::AuthError,
+            BamlError::JsonError(e) => ClassificationError::JsonError(e),
+            BamlError::InvalidResponse(message) => ClassificationError::InvalidResponse(message),
+            BamlError::ClientError(message) | BamlError::Unavailable(message) => ClassificationError::NetworkError(message),
+            BamlError::Cancelled => ClassificationError::Cancelled,
+            BamlError::BudgetExceeded(reason) => ClassificationError::BudgetExceeded(reason),
+            BamlError::ApiError(message)
+            | BamlError::ConfigError(message)
+            | BamlError::ClassificationError(message) => ClassificationError::ApiError(message),
+        }
+    }
 }
 
-/// BAML client for activity classification
+/// Activity classifier backed by a language model, with keyword rules as fallback
 #[derive(Debug, Clone)]
 pub struct ActivityClassifier {
-    api_key: String,
-    base_url: String,
-    model: String,
+    /// `None` when only the rule-based classifier is used
+    client: Option<BamlClient>,
+    /// Keyword rules for when the model is not used
+    rules: RuleSource,
 }
 
 impl ActivityClassifier {
-    /// Create a new classifier with API configuration
+    /// Create a classifier using the default model; an empty key uses rules only
     pub fn new(api_key: String) -> Self {
-        Self {
-            api_key,
-            base_url: "https://api.baml.ai/v1".to_string(),
-            model: "gpt-4".to_string(), // Default model
+        if api_key.is_empty() {
+            return Self::rule_based();
         }
+        Self::from_config(BamlConfig {
+            api_key: Some(api_key),
+            ..BamlConfig::default()
+        })
+    }
+
+    /// Create a classifier that only uses keyword rules
+    pub fn rule_based() -> Self {
+        Self { client: None, rules: RuleSource::builtin() }
     }
 
     /// Create classifier with custom configuration
     pub fn with_config(api_key: String, base_url: String, model: String) -> Self {
-        Self {
-            api_key,
-            base_url,
+        Self::from_config(BamlConfig {
+            endpoint: base_url,
+            api_key: (!api_key.is_empty()).then_some(api_key),
             model,
+            ..BamlConfig::default()
+        })
+    }
+
+    /// Create a classifier from full client settings
+    pub fn from_config(config: BamlConfig) -> Self {
+        match BamlClient::new(config) {
+            Ok(client) => Self::with_client(client),
+            Err(e) => {
+                tracing::warn!("Falling back to rule-based classification: {}", e);
+                Self::rule_based()
+            }
+        }
+    }
+
+    /// Create a classifier using an existing client
+    pub fn with_client(client: BamlClient) -> Self {
+        Self { client: Some(client), rules: RuleSource::builtin() }
+    }
+
+    /// Use these keyword rules instead of the built-in ones
+    pub fn with_rules(mut self, rules: RuleSource) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Replace the keyword rules
+    pub fn set_rules(&mut self, rules: RuleSource) {
+        self.rules = rules;
+    }
+
+    /// The keyword rules in use
+    pub fn rules(&self) -> &RuleSource {
+        &self.rules
+    }
+
+    /// Whether classifications go to the language model first
+    pub fn uses_llm(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Reply cache counters, if caching is enabled
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.client.as_ref()?.cache().map(ResponseCache::stats)
+    }
+
+    /// Tokens and cost of the model calls made so far, if a model is configured
+    pub fn usage(&self) -> Option<UsageSummary> {
+        self.client.as_ref().map(|client| client.usage().summary())
+    }
+
+    /// Resume usage totals, e.g. after a restart, so budgets carry over
+    pub fn restore_usage(&self, summary: UsageSummary) {
+        if let Some(client) = &self.client {
+            client.usage().restore(summary);
         }
     }
 
@@ -108,11 +261,75 @@ impl ActivityClassifier {
         output: &str,
         context: Option<&ActivityContext>,
     ) -> Result<ActivityClass, ClassificationError> {
+        let Some(client) = &self.client else {
+            return Ok(self.rule_based_classify(output));
+        };
+
+        let key = self.cache_key(client, output, context);
         let prompt = self.build_classification_prompt(output, context);
-        
-        // For now, implement a simple rule-based classifier
-        // In a real implementation, this would call the BAML API
-        Ok(self.rule_based_classify(output))
+        let result = client.generate_cached(&key, &prompt, &usage_scope(context), parse_llm_classification).await;
+        self.finish(output, result)
+    }
+
+    /// Classify several outputs at once, sharing the client's concurrency and rate limits
+    ///
+    /// Each output gets its own result, in order, with the same fallback as
+    /// `classify_with_context`. Outputs not classified before `cancel` fires
+    /// fail with `Cancelled`.
+    pub async fn classify_batch(
+        &self,
+        outputs: &[(&str, Option<&ActivityContext>)],
+        cancel: &CancellationToken,
+    ) -> Vec<Result<ActivityClass, ClassificationError>> {
+        let Some(client) = &self.client else {
+            return outputs.iter().map(|(output, _)| Ok(self.rule_based_classify(output))).collect();
+        };
+
+        let requests: Vec<(CacheKey, String, UsageScope)> = outputs.iter()
+            .map(|(output, context)| {
+                let key = self.cache_key(client, output, *context);
+                (key, self.build_classification_prompt(output, *context), usage_scope(*context))
+            })
+            .collect();
+        client.generate_batch_cached(&requests, parse_llm_classification, cancel).await
+            .into_iter()
+            .zip(outputs)
+            .map(|(result, (output, _))| self.finish(output, result))
+            .collect()
+    }
+
+    /// Key for the reply cache
+    ///
+    /// Time spent working changes every tick and is left out, as is any
+    /// detail of the output that `normalize_output` discards. The agent is
+    /// left out too, so idle agents printing the same thing share a reply.
+    fn cache_key(&self, client: &BamlClient, output: &str, context: Option<&ActivityContext>) -> CacheKey {
+        let context = context
+            .map(|context| {
+                let prompted = (&context.recent_activities, &context.current_task, context.error_count, &context.agent_role);
+                serde_json::to_string(&prompted).unwrap_or_default()
+            })
+            .unwrap_or_default();
+        client.cache_key(&[&normalize_output(output), &context])
+    }
+
+    /// Take the model's classification, falling back to rules when it is unusable
+    fn finish(&self, output: &str, result: Result<(ActivityClass, bool), ClassificationError>) -> Result<ActivityClass, ClassificationError> {
+        match result {
+            Ok((mut class, cached)) => {
+                if cached {
+                    class.source = ClassificationSource::Cached;
+                }
+                Ok(class)
+            }
+            Err(e) if e.should_fall_back() => {
+                tracing::warn!("LLM classification failed, using rules instead: {}", e);
+                let mut class = self.rule_based_classify(output);
+                class.source = ClassificationSource::Fallback { reason: e.to_string() };
+                Ok(class)
+            }
+            Err(e) => Err(e),
+        }
     }
 
     /// Build the prompt for BAML classification
@@ -158,64 +375,86 @@ Please respond with JSON in this format:
         prompt
     }
 
-    /// Simple rule-based classifier for demonstration
+    /// Classify by the current keyword rules
     fn rule_based_classify(&self, output: &str) -> ActivityClass {
-        let output_lower = output.to_lowercase();
-        
-        // Detect activity type based on keywords
-        let primary = if output_lower.contains("error") || output_lower.contains("failed") || output_lower.contains("exception") {
-            if output_lower.contains("fixing") || output_lower.contains("debug") {
-                ActivityType::Debugging
-            } else {
-                ActivityType::Stuck
-            }
-        } else if output_lower.contains("test") || output_lower.contains("spec") || output_lower.contains("assert") {
-            ActivityType::Testing
-        } else if output_lower.contains("mkdir") || output_lower.contains("cargo init") || output_lower.contains("setup") {
-            ActivityType::Scaffolding
-        } else if output_lower.contains("implementing") || output_lower.contains("writing") || output_lower.contains("adding") {
-            ActivityType::Implementing
-        } else if output_lower.contains("reading") || output_lower.contains("docs") || output_lower.contains("researching") {
-            ActivityType::Researching
-        } else if output_lower.contains("waiting") || output_lower.contains("pending") {
-            ActivityType::Waiting
-        } else if output_lower.contains("stuck") || output_lower.contains("confused") || output_lower.contains("help") {
-            ActivityType::Stuck
-        } else {
-            ActivityType::Implementing // Default
-        };
+        self.rules.current().classify(output)
+    }
+}
 
-        // Determine confidence based on emotional indicators
-        let (confidence, emotional_state) = if output_lower.contains("stuck") && (output_lower.contains("confused") || output_lower.contains("help")) {
-            (0.1, EmotionalState::Desperate)
-        } else if output_lower.contains("error") || output_lower.contains("failed") {
-            if output_lower.contains("stuck") {
-                (0.2, EmotionalState::Frustrated)
-            } else {
-                (0.4, EmotionalState::Frustrated)
-            }
-        } else if output_lower.contains("trying") || output_lower.contains("attempting") {
-            (0.6, EmotionalState::Cautious)
-        } else if output_lower.contains("completed") || output_lower.contains("success") || output_lower.contains("done") {
-            (0.9, EmotionalState::Confident)
-        } else {
-            (0.7, EmotionalState::Focused)
-        };
+/// Whether an agent needs help - only for really stuck situations
+pub(crate) fn needs_help(primary: &ActivityType, confidence: f32, emotional_state: &EmotionalState) -> bool {
+    matches!(primary, ActivityType::Stuck)
+        || (confidence < 0.3 && matches!(emotional_state, EmotionalState::Desperate))
+}
+
+/// Who a classification's model call is charged to
+fn usage_scope(context: Option<&ActivityContext>) -> UsageScope {
+    context
+        .map(|context| UsageScope::new(context.agent_id.clone(), context.current_task.clone()))
+        .unwrap_or_default()
+}
 
-        // Determine if help is needed - only for really stuck situations
-        let needs_help = matches!(primary, ActivityType::Stuck) || 
-                        (confidence < 0.3 && matches!(emotional_state, EmotionalState::Desperate));
+/// Parse and validate a model reply
+fn parse_llm_classification(reply: &str) -> Result<ActivityClass, ClassificationError> {
+    let reply: LlmClassification = serde_json::from_str(extract_json(reply))?;
+    reply.validate()
+}
+
+/// Classification as the model returns it
+#[derive(Debug, Deserialize)]
+struct LlmClassification {
+    primary: ActivityType,
+    confidence: f32,
+    emotional_state: EmotionalState,
+    #[serde(default)]
+    needs_help: Option<bool>,
+    #[serde(default)]
+    estimated_completion: Option<String>,
+}
 
-        ActivityClass {
-            primary,
-            confidence,
-            needs_help,
-            emotional_state,
-            estimated_completion: Some(Duration::from_secs(300)), // Default 5 minutes
+impl LlmClassification {
+    fn validate(self) -> Result<ActivityClass, ClassificationError> {
+        if !(0.0..=1.0).contains(&self.confidence) {
+            return Err(ClassificationError::InvalidResponse(format!("confidence {} is outside 0..1", self.confidence)));
         }
+        let estimated_completion = self.estimated_completion
+            .as_deref()
+            .map(|text| {
+                parse_duration(text).ok_or_else(|| {
+                    ClassificationError::InvalidResponse(format!("estimated_completion {:?} is not a duration", text))
+                })
+            })
+            .transpose()?;
+
+        Ok(ActivityClass {
+            needs_help: self.needs_help
+                .unwrap_or_else(|| needs_help(&self.primary, self.confidence, &self.emotional_state)),
+            primary: self.primary,
+            confidence: self.confidence,
+            emotional_state: self.emotional_state,
+            estimated_completion,
+            source: ClassificationSource::Llm,
+        })
     }
 }
 
+/// Parse durations such as "90s", "5m", "1h" or a bare number of seconds
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "s"),
+    };
+    let value: u64 = number.parse().ok()?;
+    let secs = match unit.trim() {
+        "s" | "sec" | "secs" | "seconds" => value,
+        "m" | "min" | "mins" | "minutes" => value * 60,
+        "h" | "hr" | "hrs" | "hours" => value * 3600,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
 /// Context information for activity classification
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityContext {
@@ -230,6 +469,9 @@ pub struct ActivityContext {
     pub error_count: u32,
     /// Agent's role
     pub agent_role: Option<String>,
+    /// Agent the output came from, for attributing model usage
+    #[serde(default)]
+    pub agent_id: Option<String>,
 }
 
 impl ActivityContext {
@@ -241,6 +483,7 @@ impl ActivityContext {
             current_task: None,
             error_count: 0,
             agent_role: None,
+            agent_id: None,
         }
     }
 
@@ -273,17 +516,21 @@ impl Default for ActivityContext {
 
 /// Simple function to classify agent output - main API
 pub async fn classify_activity(output: &str) -> ActivityClass {
-    let classifier = ActivityClassifier::new("demo-key".to_string());
+    let classifier = ActivityClassifier::rule_based();
     classifier.rule_based_classify(output)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cache::CacheConfig;
+    use crate::usage::UsageBudget;
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_rule_based_classification() {
-        let classifier = ActivityClassifier::new("test-key".to_string());
+        let classifier = ActivityClassifier::rule_based();
 
         // Test error detection
         let result = classifier.rule_based_classify("Error: compilation failed");
@@ -322,7 +569,7 @@ mod tests {
 
     #[test]
     fn test_confidence_thresholds() {
-        let classifier = ActivityClassifier::new("test-key".to_string());
+        let classifier = ActivityClassifier::rule_based();
 
         let high_confidence = classifier.rule_based_classify("Everything working perfectly");
         assert!(high_confidence.confidence > 0.8);
@@ -332,6 +579,181 @@ mod tests {
         assert!(low_confidence.needs_help);
     }
 
+    async fn stub_model(response: ResponseTemplate) -> (MockServer, ActivityClassifier) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("x-api-key", "stub-key"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+
+        let classifier = ActivityClassifier::from_config(BamlConfig {
+            endpoint: format!("{}/v1/messages", server.uri()),
+            api_key: Some("stub-key".to_string()),
+            timeout: Duration::from_millis(200),
+            ..BamlConfig::default()
+        });
+        (server, classifier)
+    }
+
+    fn model_reply(text: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "content": [{ "type": "text", "text": text }],
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_llm_classification_is_used_when_valid() {
+        let (_server, classifier) = stub_model(model_reply(
+            r#"Here you go: {"primary": "Researching", "confidence": 0.85, "emotional_state": "Focused", "estimated_completion": "10m"}"#,
+        )).await;
+        assert!(classifier.uses_llm());
+
+        // The rules would call this Stuck
+        let result = classifier.classify_activity("Error: reading the docs for this crate").await.unwrap();
+        assert_eq!(result.source, ClassificationSource::Llm);
+        assert_eq!(result.primary, ActivityType::Researching);
+        assert_eq!(result.emotional_state, EmotionalState::Focused);
+        assert_eq!(result.estimated_completion, Some(Duration::from_secs(600)));
+        assert!(!result.needs_help);
+    }
+
+    #[tokio::test]
+    async fn test_llm_failures_fall_back_to_rules() {
+        let cases = [
+            ("rate limited", ResponseTemplate::new(429)),
+            ("timed out", model_reply("{}").set_delay(Duration::from_secs(2))),
+            ("malformed JSON", model_reply("I think the agent is debugging")),
+            ("unknown category", model_reply(r#"{"primary": "Dancing", "confidence": 0.9, "emotional_state": "Focused"}"#)),
+            ("confidence out of range", model_reply(r#"{"primary": "Stuck", "confidence": 7, "emotional_state": "Desperate"}"#)),
+            ("bad duration", model_reply(r#"{"primary": "Testing", "confidence": 0.5, "emotional_state": "Focused", "estimated_completion": "soon"}"#)),
+        ];
+
+        for (case, response) in cases {
+            let (_server, classifier) = stub_model(response).await;
+            let result = classifier.classify_activity("Running tests to verify functionality").await.unwrap();
+            assert!(matches!(result.source, ClassificationSource::Fallback { .. }), "{}: {:?}", case, result.source);
+            assert_eq!(result.primary, ActivityType::Testing, "{}", case);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_errors_are_not_hidden_by_fallback() {
+        let (_server, classifier) = stub_model(ResponseTemplate::new(401).set_body_string("invalid x-api-key")).await;
+        let result = classifier.classify_activity("Running tests").await;
+        assert!(matches!(result, Err(ClassificationError::AuthError)));
+    }
+
+    #[tokio::test]
+    async fn test_batch_classification_falls_back_per_output() {
+        let server = MockServer::start().await;
+        Mock::given(body_string_contains("reading the tokio docs"))
+            .respond_with(model_reply(r#"{"primary": "Researching", "confidence": 0.8, "emotional_state": "Focused"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(body_string_contains("Running tests"))
+            .respond_with(model_reply("not json"))
+            .mount(&server)
+            .await;
+        let classifier = ActivityClassifier::from_config(BamlConfig {
+            endpoint: format!("{}/v1/messages", server.uri()),
+            ..BamlConfig::default()
+        });
+
+        let context = ActivityContext::new();
+        let outputs = [("reading the tokio docs", Some(&context)), ("Running tests", None)];
+        let results = classifier.classify_batch(&outputs, &CancellationToken::new()).await;
+        let first = results[0].as_ref().unwrap();
+        assert_eq!((first.primary.clone(), first.source.clone()), (ActivityType::Researching, ClassificationSource::Llm));
+        let second = results[1].as_ref().unwrap();
+        assert_eq!(second.primary, ActivityType::Testing);
+        assert!(matches!(second.source, ClassificationSource::Fallback { .. }));
+
+        let results = ActivityClassifier::rule_based().classify_batch(&outputs, &CancellationToken::new()).await;
+        assert!(results.iter().all(|result| result.as_ref().unwrap().source == ClassificationSource::Rules));
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_charged_to_the_agent_until_its_budget_runs_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [{ "type": "text", "text": r#"{"primary": "Testing", "confidence": 0.9, "emotional_state": "Confident"}"# }],
+                "usage": { "input_tokens": 1000, "output_tokens": 100 },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let classifier = ActivityClassifier::from_config(BamlConfig {
+            endpoint: format!("{}/v1/messages", server.uri()),
+            budget: UsageBudget { agent_cost_usd: Some(0.001), ..UsageBudget::default() },
+            ..BamlConfig::default()
+        });
+        let mut context = ActivityContext::new();
+        context.agent_id = Some("agent-1".to_string());
+        context.current_task = Some("task-1".to_string());
+
+        let first = classifier.classify_with_context("running tests", Some(&context)).await.unwrap();
+        assert_eq!(first.source, ClassificationSource::Llm);
+
+        let usage = classifier.usage().unwrap();
+        assert_eq!(usage.by_agent["agent-1"].input_tokens, 1000);
+        assert_eq!(usage.by_task["task-1"].output_tokens, 100);
+        assert!((usage.total.cost_usd - 0.0012).abs() < 1e-9);
+
+        // The agent's budget is spent, so the rules answer without a request
+        let second = classifier.classify_with_context("running more tests", Some(&context)).await.unwrap();
+        assert!(matches!(second.source, ClassificationSource::Fallback { ref reason } if reason.contains("budget")));
+        assert_eq!(classifier.usage().unwrap().total.calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_output_is_answered_from_cache() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(model_reply(r#"{"primary": "Implementing", "confidence": 0.8, "emotional_state": "Focused"}"#))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let classifier = ActivityClassifier::from_config(BamlConfig {
+            endpoint: format!("{}/v1/messages", server.uri()),
+            cache: Some(CacheConfig::default()),
+            ..BamlConfig::default()
+        });
+        let mut context = ActivityContext::new();
+
+        let first = classifier.classify_with_context("⠋ Writing parser (3s)", Some(&context)).await.unwrap();
+        assert_eq!(first.source, ClassificationSource::Llm);
+
+        // Only the spinner, timer and time working changed
+        context.update_working_time(Duration::from_secs(30));
+        let second = classifier.classify_with_context("⠙ Writing parser (4s)", Some(&context)).await.unwrap();
+        assert_eq!(second.source, ClassificationSource::Cached);
+        assert_eq!(second.primary, ActivityType::Implementing);
+
+        // New history is a different request
+        context.add_activity(ActivityType::Implementing);
+        let outputs = [("Writing parser (5s)", Some(&context)), ("Writing parser (6s)", Some(&context))];
+        let results = classifier.classify_batch(&outputs, &CancellationToken::new()).await;
+        assert_eq!(results[0].as_ref().unwrap().source, ClassificationSource::Llm);
+        // Equivalent outputs in one batch share a single request
+        assert_eq!(results[1].as_ref().unwrap().source, ClassificationSource::Cached);
+
+        let stats = classifier.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 3, 2));
+    }
+
+    #[test]
+    fn test_rule_based_classifier_reports_its_source() {
+        let classifier = ActivityClassifier::new(String::new());
+        assert!(!classifier.uses_llm());
+        assert_eq!(classifier.rule_based_classify("Writing code").source, ClassificationSource::Rules);
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2 h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("-5m"), None);
+    }
+
     #[tokio::test]
     async fn test_classify_activity_function() {
         let result = classify_activity("Error: cannot find module").await;
@@ -1,85 +1,420 @@
-use crate::*;
+//! HTTP client for the language model behind classification
+
+use crate::cache::{normalize_output, CacheConfig, CacheKey, ResponseCache};
+use crate::providers::{LlmProvider, ProviderKind};
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::streaming::ResponseStream;
+use crate::usage::{PriceTable, UsageBudget, UsageLedger, UsageScope};
+use dfcoder_types::{ErrorType, RetryPolicy};
+use futures::StreamExt;
 use reqwest::Client;
-use serde_json::json;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Arc;
 use std::time::Duration;
 
+pub use tokio_util::sync::CancellationToken;
+
+/// Connection and sampling settings for the language model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BamlConfig {
+    /// API format spoken by `endpoint`
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// Endpoint the prompt is posted to
+    pub endpoint: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    /// Classifications below this confidence are treated as uncertain
+    pub confidence_threshold: f32,
+    /// How long a request may wait for the reply to start; whole replies must also arrive within it
+    #[serde(with = "dfcoder_types::duration_serde")]
+    pub timeout: Duration,
+    /// How long a streamed reply may go without receiving anything before it fails
+    #[serde(default = "default_stream_idle_timeout", with = "dfcoder_types::duration_serde")]
+    pub stream_idle_timeout: Duration,
+    /// Requests a batch may have in flight at once
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Cap on the rate of every request the client sends, if any
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Backoff for batch requests that were rate limited or hit a server error
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Reuse replies to repeated requests instead of calling the API again
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    /// Prices used to cost each call, by model
+    #[serde(default)]
+    pub pricing: PriceTable,
+    /// Limits past which calls are refused
+    #[serde(default)]
+    pub budget: UsageBudget,
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+fn default_stream_idle_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+impl BamlConfig {
+    /// Default settings for a provider, using its usual endpoint and a small model
+    pub fn for_provider(provider: ProviderKind) -> Self {
+        Self {
+            provider,
+            endpoint: provider.default_endpoint().to_string(),
+            model: provider.default_model().to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for BamlConfig {
+    fn default() -> Self {
+        let provider = ProviderKind::default();
+        Self {
+            provider,
+            endpoint: provider.default_endpoint().to_string(),
+            api_key: None,
+            model: provider.default_model().to_string(),
+            temperature: 0.0,
+            max_tokens: 256,
+            confidence_threshold: 0.5,
+            timeout: Duration::from_secs(10),
+            stream_idle_timeout: default_stream_idle_timeout(),
+            max_concurrency: default_max_concurrency(),
+            rate_limit: None,
+            retry: RetryPolicy::default(),
+            cache: None,
+            pricing: PriceTable::default(),
+            budget: UsageBudget::default(),
+        }
+    }
+}
+
 /// BAML client for communicating with language models
 #[derive(Debug, Clone)]
 pub struct BamlClient {
     client: Client,
     config: BamlConfig,
+    provider: Arc<dyn LlmProvider>,
+    /// Shared by clones so they draw from the same budget
+    limiter: Option<Arc<RateLimiter>>,
+    cache: Option<Arc<ResponseCache>>,
+    usage: Arc<UsageLedger>,
 }
 
 impl BamlClient {
-    /// Create a new BAML client
+    /// Create a new BAML client for the configured provider
     pub fn new(config: BamlConfig) -> Result<Self, BamlError> {
+        let provider = config.provider.provider();
+        Self::with_provider(config, provider)
+    }
+
+    /// Create a client speaking a custom provider's API
+    pub fn with_provider(config: BamlConfig, provider: Arc<dyn LlmProvider>) -> Result<Self, BamlError> {
+        // Streams may run far longer than `timeout`, so it is applied per request
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .connect_timeout(config.timeout)
             .build()
             .map_err(|e| BamlError::ClientError(format!("Failed to create HTTP client: {}", e)))?;
         
-        Ok(Self { client, config })
+        let limiter = config.rate_limit.map(|limit| Arc::new(RateLimiter::new(limit)));
+        let cache = config.cache.clone().map(|cache| Arc::new(ResponseCache::new(cache)));
+        let usage = Arc::new(UsageLedger::new(config.pricing.clone(), config.budget));
+        Ok(Self { client, config, provider, limiter, cache, usage })
+    }
+
+    /// The provider requests are formatted for
+    pub fn provider(&self) -> &dyn LlmProvider {
+        self.provider.as_ref()
+    }
+
+    /// Settings this client was built with
+    pub fn config(&self) -> &BamlConfig {
+        &self.config
+    }
+
+    /// Reply cache, if caching is enabled
+    pub fn cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_deref()
+    }
+
+    /// Tokens and cost of every call this client and its clones made
+    pub fn usage(&self) -> &UsageLedger {
+        &self.usage
+    }
+
+    /// Cache key for a request to this client's model
+    pub fn cache_key(&self, parts: &[&str]) -> CacheKey {
+        CacheKey::new(&format!("{}:{}", self.provider.name(), self.config.model), parts)
+    }
+
+    /// Answer from the cache if a stored reply still parses, otherwise ask the model
+    ///
+    /// Replies are only cached once `parse` accepts them. The flag is true
+    /// when the result came from the cache.
+    pub async fn generate_cached<T, E>(
+        &self,
+        key: &CacheKey,
+        prompt: &str,
+        scope: &UsageScope,
+        parse: impl Fn(&str) -> Result<T, E>,
+    ) -> Result<(T, bool), E>
+    where
+        E: From<BamlError>,
+    {
+        if let Some(parsed) = self.cached(key, &parse) {
+            return Ok((parsed, true));
+        }
+        let reply = self.generate_response_for(prompt, scope).await?;
+        self.store(key, reply, &parse).map(|parsed| (parsed, false))
     }
     
     /// Generate a response from the language model
     pub async fn generate_response(&self, prompt: &str) -> Result<String, BamlError> {
-        let request_body = json!({
-            "model": self.config.model,
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ]
-        });
-        
-        let mut request = self.client
-            .post(&self.config.endpoint)
-            .json(&request_body);
-        
-        if let Some(api_key) = &self.config.api_key {
-            request = request.header("x-api-key", api_key);
+        self.generate_response_for(prompt, &UsageScope::default()).await
+    }
+
+    /// Generate a response, charging its tokens to `scope`
+    ///
+    /// Fails with `BudgetExceeded` without sending anything once the
+    /// configured budget for `scope` is spent.
+    pub async fn generate_response_for(&self, prompt: &str, scope: &UsageScope) -> Result<String, BamlError> {
+        if let Some(reason) = self.usage.over_budget(scope) {
+            return Err(BamlError::BudgetExceeded(reason));
         }
-        
-        let response = request
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+
+        let response = self.provider
+            .build_request(&self.client, &self.config, prompt)
+            .timeout(self.config.timeout)
             .send()
             .await
-            .map_err(|e| BamlError::ClientError(format!("Request failed: {}", e)))?;
+            .map_err(request_error)?;
         
-        if !response.status().is_success() {
-            let status = response.status();
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(BamlError::ApiError(format!("API error {}: {}", status, error_text)));
+            let error = self.provider.map_error(status, &error_text);
+            tracing::debug!("{} request failed with {}: {}", self.provider.name(), status, error);
+            return Err(error);
         }
         
-        let response_json: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| BamlError::JsonError(e))?;
-        
-        self.extract_content_from_response(&response_json)
+        let body = response.text().await.map_err(request_error)?;
+        let response_json: serde_json::Value = serde_json::from_str(&body)?;
+
+        let usage = self.provider.parse_usage(&response_json).unwrap_or_default();
+        let cost = self.usage.record(&self.config.model, scope, usage);
+        tracing::debug!(
+            "{} call used {} input and {} output tokens (${:.6})",
+            self.provider.name(), usage.input_tokens, usage.output_tokens, cost
+        );
+
+        self.provider.parse_response(&response_json)
     }
     
+    /// Stream a response from the language model as it is generated
+    ///
+    /// Resolves once the model has accepted the request; text then arrives
+    /// through the returned stream. Cancelling `cancel`, or dropping the
+    /// stream, abandons the reply mid-flight. The call is subject to the
+    /// same budget and rate limit as `generate_response_for`.
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+        scope: &UsageScope,
+        cancel: &CancellationToken,
+    ) -> Result<ResponseStream, BamlError> {
+        if let Some(reason) = self.usage.over_budget(scope) {
+            return Err(BamlError::BudgetExceeded(reason));
+        }
+        let request = self.provider
+            .build_stream_request(&self.client, &self.config, prompt)
+            .ok_or_else(|| BamlError::ConfigError(format!("{} does not support streaming", self.provider.name())))?;
+
+        let send = async {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            // Only the wait for the reply to start is bounded; the stream then
+            // fails if it stalls for `stream_idle_timeout`
+            tokio::time::timeout(self.config.timeout, request.send()).await
+                .map_err(|_| BamlError::Timeout)?
+                .map_err(request_error)
+        };
+        let response = tokio::select! {
+            _ = cancel.cancelled() => return Err(BamlError::Cancelled),
+            response = send => response?,
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            let error = self.provider.map_error(status, &error_text);
+            tracing::debug!("{} stream request failed with {}: {}", self.provider.name(), status, error);
+            return Err(error);
+        }
+
+        Ok(ResponseStream::new(
+            response.bytes_stream().boxed(),
+            self.provider.clone(),
+            self.usage.clone(),
+            self.config.model.clone(),
+            scope.clone(),
+            cancel.clone(),
+            self.config.stream_idle_timeout,
+        ))
+    }
+
     /// Classify text using a structured prompt
     pub async fn classify(&self, text: &str, categories: &[&str]) -> Result<ClassificationResponse, BamlError> {
+        let key = self.classification_key(text, categories);
         let prompt = self.build_classification_prompt(text, categories);
-        let response = self.generate_response(&prompt).await?;
-        self.parse_classification_response(&response)
+        self.generate_cached(&key, &prompt, &UsageScope::default(), |reply| self.parse_classification_response(reply)).await
+            .map(|(response, _)| response)
     }
     
-    /// Batch classify multiple texts
-    pub async fn classify_batch(&self, texts: &[&str], categories: &[&str]) -> Result<Vec<ClassificationResponse>, BamlError> {
-        let mut results = Vec::new();
-        
-        // For now, process sequentially. In production, this could be parallelized
-        for text in texts {
-            let result = self.classify(text, categories).await?;
-            results.push(result);
+    /// Send several prompts, up to `max_concurrency` at a time
+    ///
+    /// Rate-limited and unavailable responses are retried with the
+    /// configured backoff. Results are in the order of `prompts`; prompts
+    /// still waiting or in flight when `cancel` fires fail with `Cancelled`.
+    pub async fn generate_batch(&self, prompts: &[String], cancel: &CancellationToken) -> Vec<Result<String, BamlError>> {
+        let scope = UsageScope::default();
+        let prompts: Vec<(&str, &UsageScope)> = prompts.iter().map(|prompt| (prompt.as_str(), &scope)).collect();
+        self.send_batch(&prompts, cancel).await
+    }
+
+    /// `generate_batch` for keyed prompts, answering from the cache where possible
+    ///
+    /// Only cache misses are sent. With a cache configured each distinct key
+    /// is sent once, and requests sharing it reuse the reply flagged as cached;
+    /// its tokens are charged to the scope of the request that was sent.
+    /// Results are in the order of `requests`.
+    pub async fn generate_batch_cached<T, E>(
+        &self,
+        requests: &[(CacheKey, String, UsageScope)],
+        parse: impl Fn(&str) -> Result<T, E>,
+        cancel: &CancellationToken,
+    ) -> Vec<Result<(T, bool), E>>
+    where
+        E: From<BamlError>,
+    {
+        let mut results: Vec<Option<Result<(T, bool), E>>> = requests.iter()
+            .map(|(key, _, _)| self.cached(key, &parse).map(|parsed| Ok((parsed, true))))
+            .collect();
+
+        let mut slots: HashMap<&CacheKey, usize> = HashMap::new();
+        let mut sent = Vec::new();
+        let mut duplicates = Vec::new();
+        for index in (0..requests.len()).filter(|&index| results[index].is_none()) {
+            match slots.entry(&requests[index].0) {
+                Entry::Occupied(slot) if self.cache.is_some() => duplicates.push((index, *slot.get())),
+                Entry::Occupied(_) => sent.push(index),
+                Entry::Vacant(slot) => {
+                    slot.insert(sent.len());
+                    sent.push(index);
+                }
+            }
+        }
+
+        let prompts: Vec<(&str, &UsageScope)> = sent.iter()
+            .map(|&index| (requests[index].1.as_str(), &requests[index].2))
+            .collect();
+        let replies = self.send_batch(&prompts, cancel).await;
+
+        for (index, slot) in duplicates {
+            let result = match &replies[slot] {
+                Ok(reply) => parse(reply).map(|parsed| (parsed, true)),
+                Err(e) => Err(E::from(e.duplicate())),
+            };
+            results[index] = Some(result);
+        }
+        for (index, reply) in sent.into_iter().zip(replies) {
+            let result = reply
+                .map_err(E::from)
+                .and_then(|reply| self.store(&requests[index].0, reply, &parse))
+                .map(|parsed| (parsed, false));
+            results[index] = Some(result);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    /// Batch classify multiple texts, with a result for each
+    pub async fn classify_batch(
+        &self,
+        texts: &[&str],
+        categories: &[&str],
+        cancel: &CancellationToken,
+    ) -> Vec<Result<ClassificationResponse, BamlError>> {
+        let requests: Vec<(CacheKey, String, UsageScope)> = texts.iter()
+            .map(|text| {
+                let key = self.classification_key(text, categories);
+                (key, self.build_classification_prompt(text, categories), UsageScope::default())
+            })
+            .collect();
+        self.generate_batch_cached(&requests, |reply| self.parse_classification_response(reply), cancel).await
+            .into_iter()
+            .map(|result| result.map(|(response, _)| response))
+            .collect()
+    }
+
+    fn cached<T, E>(&self, key: &CacheKey, parse: impl Fn(&str) -> Result<T, E>) -> Option<T> {
+        let reply = self.cache.as_ref()?.get(key)?;
+        // A reply that no longer parses is treated as a miss and replaced
+        parse(&reply).ok()
+    }
+
+    fn store<T, E>(&self, key: &CacheKey, reply: String, parse: impl Fn(&str) -> Result<T, E>) -> Result<T, E> {
+        let parsed = parse(&reply)?;
+        if let Some(cache) = &self.cache {
+            cache.insert(key.clone(), reply);
+        }
+        Ok(parsed)
+    }
+
+    fn classification_key(&self, text: &str, categories: &[&str]) -> CacheKey {
+        self.cache_key(&[&normalize_output(text), &categories.join("\n")])
+    }
+
+    async fn send_batch(&self, prompts: &[(&str, &UsageScope)], cancel: &CancellationToken) -> Vec<Result<String, BamlError>> {
+        futures::stream::iter(prompts.iter().map(|(prompt, scope)| self.generate_with_retry(prompt, scope, cancel)))
+            .buffered(self.config.max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn generate_with_retry(&self, prompt: &str, scope: &UsageScope, cancel: &CancellationToken) -> Result<String, BamlError> {
+        let policy = &self.config.retry;
+        let mut attempt = 1;
+        loop {
+            let result = tokio::select! {
+                _ = cancel.cancelled() => return Err(BamlError::Cancelled),
+                result = self.generate_response_for(prompt, scope) => result,
+            };
+
+            match result {
+                Err(e) if attempt < policy.max_attempts && policy.should_retry(&e.error_type()) => {
+                    let backoff = policy.calculate_backoff(attempt);
+                    tracing::debug!("{} request attempt {} failed, retrying in {:?}: {}", self.provider.name(), attempt, backoff, e);
+                    tokio::select! {
+                        _ = cancel.cancelled() => return Err(BamlError::Cancelled),
+                        _ = tokio::time::sleep(backoff) => {}
+                    }
+                    attempt += 1;
+                }
+                result => return result,
+            }
         }
-        
-        Ok(results)
     }
     
     /// Extract structured data using a schema
@@ -96,8 +431,7 @@ impl BamlClient {
         );
         
         let response = self.generate_response(&prompt).await?;
-        serde_json::from_str(&response)
-            .map_err(|e| BamlError::JsonError(e))
+        Ok(serde_json::from_str(extract_json(&response))?)
     }
     
     /// Analyze sentiment and intent
@@ -111,8 +445,7 @@ impl BamlClient {
         );
         
         let response = self.generate_response(&prompt).await?;
-        serde_json::from_str(&response)
-            .map_err(|e| BamlError::JsonError(e))
+        Ok(serde_json::from_str(extract_json(&response))?)
     }
     
     fn build_classification_prompt(&self, text: &str, categories: &[&str]) -> String {
@@ -130,35 +463,23 @@ impl BamlClient {
     }
     
     fn parse_classification_response(&self, response: &str) -> Result<ClassificationResponse, BamlError> {
-        // Try to extract JSON from the response
-        let json_start = response.find('{');
-        let json_end = response.rfind('}');
-        
-        match (json_start, json_end) {
-            (Some(start), Some(end)) if start < end => {
-                let json_str = &response[start..=end];
-                serde_json::from_str(json_str)
-                    .map_err(|e| BamlError::JsonError(e))
-            }
-            _ => {
-                // Fallback: try to parse the entire response
-                serde_json::from_str(response)
-                    .map_err(|e| BamlError::JsonError(e))
-            }
-        }
+        Ok(serde_json::from_str(extract_json(response))?)
     }
-    
-    fn extract_content_from_response(&self, response: &serde_json::Value) -> Result<String, BamlError> {
-        // Handle different API response formats
-        if let Some(content) = response["content"][0]["text"].as_str() {
-            Ok(content.to_string())
-        } else if let Some(content) = response["choices"][0]["message"]["content"].as_str() {
-            Ok(content.to_string())
-        } else if let Some(content) = response["text"].as_str() {
-            Ok(content.to_string())
-        } else {
-            Err(BamlError::ApiError("Unable to extract content from response".to_string()))
-        }
+}
+
+/// The JSON object in a model reply, ignoring any prose or code fences around it
+pub fn extract_json(response: &str) -> &str {
+    match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => response,
+    }
+}
+
+pub(crate) fn request_error(error: reqwest::Error) -> BamlError {
+    if error.is_timeout() {
+        BamlError::Timeout
+    } else {
+        BamlError::ClientError(format!("Request failed: {}", error))
     }
 }
 
@@ -193,6 +514,59 @@ pub enum BamlError {
     ConfigError(String),
     #[error("Classification error: {0}")]
     ClassificationError(String),
+    #[error("Rate limit exceeded")]
+    RateLimited,
+    #[error("Request timed out")]
+    Timeout,
+    #[error("Authentication failed: {0}")]
+    AuthError(String),
+    #[error("Unexpected response: {0}")]
+    InvalidResponse(String),
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+    #[error("Request cancelled")]
+    Cancelled,
+    #[error("Usage budget exceeded: {0}")]
+    BudgetExceeded(String),
+}
+
+impl BamlError {
+    /// An equivalent error for another request that shared this one's reply
+    ///
+    /// JSON errors cannot be copied and become `InvalidResponse`, which the
+    /// retry policy and classifier treat the same way.
+    fn duplicate(&self) -> BamlError {
+        match self {
+            BamlError::ClientError(message) => BamlError::ClientError(message.clone()),
+            BamlError::ApiError(message) => BamlError::ApiError(message.clone()),
+            BamlError::JsonError(e) => BamlError::InvalidResponse(e.to_string()),
+            BamlError::ConfigError(message) => BamlError::ConfigError(message.clone()),
+            BamlError::ClassificationError(message) => BamlError::ClassificationError(message.clone()),
+            BamlError::RateLimited => BamlError::RateLimited,
+            BamlError::Timeout => BamlError::Timeout,
+            BamlError::AuthError(message) => BamlError::AuthError(message.clone()),
+            BamlError::InvalidResponse(message) => BamlError::InvalidResponse(message.clone()),
+            BamlError::Unavailable(message) => BamlError::Unavailable(message.clone()),
+            BamlError::Cancelled => BamlError::Cancelled,
+            BamlError::BudgetExceeded(reason) => BamlError::BudgetExceeded(reason.clone()),
+        }
+    }
+
+    /// How the retry policy should treat this error
+    pub fn error_type(&self) -> ErrorType {
+        match self {
+            BamlError::RateLimited => ErrorType::RateLimitError,
+            BamlError::Timeout | BamlError::ClientError(_) => ErrorType::NetworkError,
+            BamlError::Unavailable(_) => ErrorType::ResourceUnavailable,
+            BamlError::AuthError(_) => ErrorType::AuthError,
+            BamlError::JsonError(_) | BamlError::InvalidResponse(_) => ErrorType::ParseError,
+            BamlError::ApiError(_)
+            | BamlError::ConfigError(_)
+            | BamlError::ClassificationError(_)
+            | BamlError::Cancelled
+            | BamlError::BudgetExceeded(_) => ErrorType::Fatal,
+        }
+    }
 }
 
 /// Builder for BAML client configuration
@@ -206,6 +580,23 @@ impl BamlClientBuilder {
             config: BamlConfig::default(),
         }
     }
+
+    /// Talk to `provider`, resetting the endpoint and model to its defaults
+    ///
+    /// Call before `endpoint` or `model` to override them.
+    pub fn provider(mut self, provider: ProviderKind) -> Self {
+        let BamlConfig { api_key, temperature, max_tokens, confidence_threshold, timeout, stream_idle_timeout, .. } = self.config;
+        self.config = BamlConfig {
+            api_key,
+            temperature,
+            max_tokens,
+            confidence_threshold,
+            timeout,
+            stream_idle_timeout,
+            ..BamlConfig::for_provider(provider)
+        };
+        self
+    }
     
     pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
         self.config.endpoint = endpoint.into();
@@ -237,6 +628,16 @@ impl BamlClientBuilder {
         self
     }
     
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+    
+    pub fn stream_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.stream_idle_timeout = timeout;
+        self
+    }
+    
     pub fn build(self) -> Result<BamlClient, BamlError> {
         BamlClient::new(self.config)
     }
@@ -251,6 +652,10 @@ impl Default for BamlClientBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::streaming::StreamEvent;
+    use crate::usage::TokenUsage;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
     
     #[tokio::test]
     async fn test_client_creation() {
@@ -268,4 +673,342 @@ mod tests {
         assert!(prompt.contains("positive"));
         assert!(prompt.contains("I love this!"));
     }
+
+    async fn replay(kind: ProviderKind, status: u16, fixture: &str) -> Result<String, BamlError> {
+        let body = std::fs::read_to_string(format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/endpoint"))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = BamlClientBuilder::new()
+            .provider(kind)
+            .endpoint(format!("{}/endpoint", server.uri()))
+            .api_key("key")
+            .build()
+            .unwrap();
+        assert_eq!(client.provider().name(), kind.to_string());
+        client.generate_response("Classify this").await
+    }
+
+    #[tokio::test]
+    async fn test_generate_response_replays_provider_fixtures() {
+        let reply = replay(ProviderKind::Anthropic, 200, "anthropic/message.json").await.unwrap();
+        assert!(reply.contains("Debugging"));
+        let reply = replay(ProviderKind::OpenAi, 200, "openai/chat_completion.json").await.unwrap();
+        assert!(reply.contains("Testing"));
+        let reply = replay(ProviderKind::Ollama, 200, "ollama/chat.json").await.unwrap();
+        assert!(reply.contains("Implementing"));
+
+        assert!(matches!(
+            replay(ProviderKind::Anthropic, 429, "anthropic/rate_limit_error.json").await,
+            Err(BamlError::RateLimited)
+        ));
+        assert!(matches!(
+            replay(ProviderKind::OpenAi, 401, "openai/invalid_api_key.json").await,
+            Err(BamlError::AuthError(_))
+        ));
+        assert!(matches!(
+            replay(ProviderKind::Ollama, 404, "ollama/model_not_found.json").await,
+            Err(BamlError::ConfigError(_))
+        ));
+    }
+
+    async fn stream_client(kind: ProviderKind, status: u16, fixture: &str) -> (MockServer, BamlClient) {
+        let body = std::fs::read_to_string(format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("\"stream\":true"))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = BamlClientBuilder::new()
+            .provider(kind)
+            .endpoint(format!("{}/endpoint", server.uri()))
+            .build()
+            .unwrap();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_replays_provider_fixtures() {
+        let scope = UsageScope::new(Some("agent-1".to_string()), None);
+        for (kind, fixture, input_tokens, output_tokens) in [
+            (ProviderKind::Anthropic, "anthropic/message_stream.txt", 212, 14),
+            (ProviderKind::OpenAi, "openai/chat_completion_stream.txt", 205, 12),
+            (ProviderKind::Ollama, "ollama/chat_stream.jsonl", 198, 13),
+        ] {
+            let (_server, client) = stream_client(kind, 200, fixture).await;
+            let mut stream = client.generate_stream("Help", &scope, &CancellationToken::new()).await.unwrap();
+
+            let mut deltas = Vec::new();
+            let mut done = None;
+            while let Some(event) = stream.next().await {
+                match event.unwrap() {
+                    StreamEvent::Delta(delta) => deltas.push(delta),
+                    StreamEvent::Done(usage) => done = Some(usage),
+                }
+            }
+            assert!(deltas.len() > 1, "{} should arrive in pieces", kind);
+            assert_eq!(deltas.concat(), "Check the failing assertion, then rerun the tests.");
+            assert_eq!(done, Some(TokenUsage { input_tokens, output_tokens }));
+
+            let usage = client.usage().summary();
+            assert_eq!(usage.by_agent["agent-1"].calls, 1);
+            assert_eq!(usage.by_agent["agent-1"].tokens(), input_tokens + output_tokens);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors_and_cancellation_end_the_stream() {
+        let (_server, client) = stream_client(ProviderKind::Anthropic, 200, "anthropic/overloaded_stream.txt").await;
+        let mut stream = client.generate_stream("Help", &UsageScope::default(), &CancellationToken::new()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), StreamEvent::Delta("Check".to_string()));
+        assert!(matches!(stream.next().await, Some(Err(BamlError::RateLimited))));
+        assert!(stream.next().await.is_none());
+
+        let (_server, client) = stream_client(ProviderKind::Anthropic, 200, "anthropic/message_stream.txt").await;
+        let cancel = CancellationToken::new();
+        let mut stream = client.generate_stream("Help", &UsageScope::default(), &cancel).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), StreamEvent::Delta("Check the failing".to_string()));
+        cancel.cancel();
+        assert!(matches!(stream.next().await, Some(Err(BamlError::Cancelled))));
+        assert!(stream.next().await.is_none());
+        // What the abandoned reply used so far is still charged
+        assert_eq!(client.usage().summary().total.input_tokens, 212);
+
+        let (_server, client) = stream_client(ProviderKind::OpenAi, 429, "openai/rate_limit_error.json").await;
+        assert!(matches!(
+            client.generate_stream("Help", &UsageScope::default(), &CancellationToken::new()).await,
+            Err(BamlError::RateLimited)
+        ));
+    }
+
+    /// Serve one streamed reply a frame at a time, then stall instead of ending it if `stall`
+    async fn trickle_server(fixture: &str, frames: usize, gap: Duration, stall: bool) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let body = std::fs::read_to_string(format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+        let frames: Vec<String> = body.split_inclusive("\n\n").take(frames).map(str::to_string).collect();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/endpoint", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            while !String::from_utf8_lossy(&request).contains("\"stream\":true") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+
+            socket.write_all(b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n").await.unwrap();
+            for frame in frames {
+                tokio::time::sleep(gap).await;
+                socket.write_all(format!("{:x}\r\n{}\r\n", frame.len(), frame).as_bytes()).await.unwrap();
+            }
+            if stall {
+                std::future::pending::<()>().await;
+            }
+            socket.write_all(b"0\r\n\r\n").await.unwrap();
+        });
+        uri
+    }
+
+    #[tokio::test]
+    async fn test_stream_timeouts_bound_the_start_and_stalls_not_the_length() {
+        let fixture = "anthropic/message_stream.txt";
+        let client = |endpoint: String| BamlClientBuilder::new()
+            .endpoint(endpoint)
+            .timeout(Duration::from_millis(150))
+            .stream_idle_timeout(Duration::from_millis(300))
+            .build()
+            .unwrap();
+
+        // A reply that takes longer than `timeout` to start fails
+        let server = MockServer::start().await;
+        let body = std::fs::read_to_string(format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_raw(body, "text/event-stream")
+                .set_delay(Duration::from_millis(600)))
+            .mount(&server)
+            .await;
+        let slow = client(format!("{}/endpoint", server.uri()));
+        assert!(matches!(
+            slow.generate_stream("Help", &UsageScope::default(), &CancellationToken::new()).await,
+            Err(BamlError::Timeout)
+        ));
+
+        // One that keeps arriving may run well past it
+        let trickling = client(trickle_server(fixture, usize::MAX, Duration::from_millis(50), false).await);
+        let stream = trickling.generate_stream("Help", &UsageScope::default(), &CancellationToken::new()).await.unwrap();
+        let (text, _) = stream.text().await.unwrap();
+        assert_eq!(text, "Check the failing assertion, then rerun the tests.");
+
+        // But not stall for longer than the idle timeout
+        let stalling = client(trickle_server(fixture, 4, Duration::ZERO, true).await);
+        let mut stream = stalling.generate_stream("Help", &UsageScope::default(), &CancellationToken::new()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), StreamEvent::Delta("Check the failing".to_string()));
+        assert!(matches!(stream.next().await, Some(Err(BamlError::Timeout))));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn test_builder_provider_keeps_explicit_settings() {
+        let client = BamlClientBuilder::new()
+            .api_key("key")
+            .max_tokens(64)
+            .provider(ProviderKind::Ollama)
+            .build()
+            .unwrap();
+        let config = client.config();
+        assert_eq!(config.endpoint, "http://localhost:11434/api/chat");
+        assert_eq!(config.model, "llama3.1");
+        assert_eq!(config.api_key.as_deref(), Some("key"));
+        assert_eq!(config.max_tokens, 64);
+    }
+
+    fn classification(category: &str) -> ResponseTemplate {
+        let text = format!(r#"{{"category": "{}", "confidence": 0.9, "reasoning": "", "indicators": []}}"#, category);
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({ "content": [{ "type": "text", "text": text }] }))
+    }
+
+    fn batch_client(server: &MockServer, config: BamlConfig) -> BamlClient {
+        BamlClient::new(BamlConfig {
+            endpoint: format!("{}/v1/messages", server.uri()),
+            retry: RetryPolicy {
+                initial_backoff: Duration::from_millis(10),
+                ..RetryPolicy::default()
+            },
+            ..config
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_runs_concurrently_and_keeps_order() {
+        let server = MockServer::start().await;
+        for pane in 0..6 {
+            Mock::given(body_string_contains(format!("pane {} output", pane)))
+                .respond_with(classification(&format!("category-{}", pane)).set_delay(Duration::from_millis(200)))
+                .mount(&server)
+                .await;
+        }
+        let client = batch_client(&server, BamlConfig { max_concurrency: 3, ..BamlConfig::default() });
+
+        let texts: Vec<String> = (0..6).map(|pane| format!("pane {} output", pane)).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let started = std::time::Instant::now();
+        let results = client.classify_batch(&texts, &["a", "b"], &CancellationToken::new()).await;
+        let elapsed = started.elapsed();
+
+        let categories: Vec<_> = results.into_iter().map(|result| result.unwrap().category).collect();
+        assert_eq!(categories, (0..6).map(|pane| format!("category-{}", pane)).collect::<Vec<_>>());
+        // Two waves of three, not six requests in series
+        assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_batch_retries_transient_errors_and_reports_each_item() {
+        let server = MockServer::start().await;
+        Mock::given(body_string_contains("flaky"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(body_string_contains("flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(body_string_contains("flaky"))
+            .respond_with(classification("recovered"))
+            .mount(&server)
+            .await;
+        Mock::given(body_string_contains("forbidden"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(body_string_contains("fine"))
+            .respond_with(classification("fine"))
+            .mount(&server)
+            .await;
+        let client = batch_client(&server, BamlConfig::default());
+
+        let results = client.classify_batch(&["flaky", "forbidden", "fine"], &["a"], &CancellationToken::new()).await;
+        assert_eq!(results[0].as_ref().unwrap().category, "recovered");
+        assert!(matches!(results[1], Err(BamlError::AuthError(_))));
+        assert_eq!(results[2].as_ref().unwrap().category, "fine");
+    }
+
+    #[tokio::test]
+    async fn test_batch_gives_up_after_max_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let client = batch_client(&server, BamlConfig::default());
+
+        let results = client.classify_batch(&["busy"], &["a"], &CancellationToken::new()).await;
+        assert!(matches!(results[0], Err(BamlError::RateLimited)));
+    }
+
+    #[tokio::test]
+    async fn test_batch_can_be_cancelled() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(classification("slow").set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+        let client = batch_client(&server, BamlConfig { max_concurrency: 2, ..BamlConfig::default() });
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let results = client.classify_batch(&["a", "b", "c"], &["a"], &cancel).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| matches!(result, Err(BamlError::Cancelled))));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_spaces_out_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(classification("ok"))
+            .mount(&server)
+            .await;
+        let client = batch_client(&server, BamlConfig {
+            max_concurrency: 8,
+            rate_limit: Some(RateLimit { requests_per_second: 20.0, burst: 1 }),
+            ..BamlConfig::default()
+        });
+
+        let started = std::time::Instant::now();
+        let results = client.classify_batch(&["a", "b", "c", "d", "e"], &["a"], &CancellationToken::new()).await;
+        assert!(results.iter().all(Result::is_ok));
+        // The first request goes straight away, the other four wait 50ms each
+        assert!(started.elapsed() >= Duration::from_millis(200), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_extract_json_strips_surrounding_text() {
+        assert_eq!(extract_json("Sure! ```json\n{\"a\": {\"b\": 1}}\n```"), r#"{"a": {"b": 1}}"#);
+        assert_eq!(extract_json("no json here"), "no json here");
+    }
 }
\ No newline at end of file
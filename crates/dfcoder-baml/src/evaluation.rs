@@ -0,0 +1,147 @@
+//! Scoring a classifier against labelled output
+
+use serde::Serialize;
+use std::fmt;
+
+/// Counts of expected against predicted labels
+///
+/// Rows are the expected label and columns the prediction. Labels are kept in
+/// the order given to `new`, with any others appended as they are recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfusionMatrix<L> {
+    labels: Vec<L>,
+    counts: Vec<Vec<u32>>,
+}
+
+impl<L: PartialEq + Clone + fmt::Debug> ConfusionMatrix<L> {
+    pub fn new(labels: impl IntoIterator<Item = L>) -> Self {
+        let labels: Vec<L> = labels.into_iter().collect();
+        let counts = vec![vec![0; labels.len()]; labels.len()];
+        Self { labels, counts }
+    }
+
+    /// Count one sample
+    pub fn record(&mut self, expected: &L, predicted: &L) {
+        let row = self.index(expected);
+        let column = self.index(predicted);
+        self.counts[row][column] += 1;
+    }
+
+    pub fn labels(&self) -> &[L] {
+        &self.labels
+    }
+
+    /// Samples labelled `expected` that were predicted as `predicted`
+    pub fn count(&self, expected: &L, predicted: &L) -> u32 {
+        match (self.position(expected), self.position(predicted)) {
+            (Some(row), Some(column)) => self.counts[row][column],
+            _ => 0,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().flatten().sum()
+    }
+
+    /// Share of samples predicted correctly
+    pub fn accuracy(&self) -> f64 {
+        let correct: u32 = (0..self.labels.len()).map(|i| self.counts[i][i]).sum();
+        ratio(correct, self.total()).unwrap_or(0.0)
+    }
+
+    /// Share of predictions of `label` that were right; `None` if it was never predicted
+    pub fn precision(&self, label: &L) -> Option<f64> {
+        let column = self.position(label)?;
+        let predicted = self.counts.iter().map(|row| row[column]).sum();
+        ratio(self.counts[column][column], predicted)
+    }
+
+    /// Share of samples labelled `label` that were found; `None` if there were none
+    pub fn recall(&self, label: &L) -> Option<f64> {
+        let row = self.position(label)?;
+        ratio(self.counts[row][row], self.counts[row].iter().sum())
+    }
+
+    fn position(&self, label: &L) -> Option<usize> {
+        self.labels.iter().position(|known| known == label)
+    }
+
+    fn index(&mut self, label: &L) -> usize {
+        if let Some(index) = self.position(label) {
+            return index;
+        }
+        self.labels.push(label.clone());
+        for row in &mut self.counts {
+            row.push(0);
+        }
+        self.counts.push(vec![0; self.labels.len()]);
+        self.labels.len() - 1
+    }
+}
+
+fn ratio(part: u32, whole: u32) -> Option<f64> {
+    (whole > 0).then(|| part as f64 / whole as f64)
+}
+
+impl<L: PartialEq + Clone + fmt::Debug> fmt::Display for ConfusionMatrix<L> {
+    /// The matrix with expected labels down the side, then precision and recall per label
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.labels.iter().map(|label| format!("{:?}", label)).collect();
+        let side = names.iter().map(String::len).max().unwrap_or(0).max("expected".len());
+        let width = names.iter().map(String::len).max().unwrap_or(0).max(5);
+
+        write!(f, "{:side$}", "expected", side = side)?;
+        for name in &names {
+            write!(f, " {:>width$}", name, width = width)?;
+        }
+        writeln!(f)?;
+        for (name, row) in names.iter().zip(&self.counts) {
+            write!(f, "{:side$}", name, side = side)?;
+            for count in row {
+                write!(f, " {:>width$}", count, width = width)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:side$} {:>9} {:>9}", "", "precision", "recall", side = side)?;
+        for (name, label) in names.iter().zip(&self.labels) {
+            let percent = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.1}%", value * 100.0));
+            writeln!(
+                f,
+                "{:side$} {:>9} {:>9}",
+                name, percent(self.precision(label)), percent(self.recall(label)),
+                side = side
+            )?;
+        }
+        write!(f, "accuracy {:.1}% over {} samples", self.accuracy() * 100.0, self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precision_recall_and_rendering() {
+        let mut matrix = ConfusionMatrix::new(["a", "b"]);
+        matrix.record(&"a", &"a");
+        matrix.record(&"a", &"b");
+        matrix.record(&"b", &"b");
+        matrix.record(&"c", &"a");
+
+        assert_eq!(matrix.labels(), &["a", "b", "c"]);
+        assert_eq!(matrix.count(&"a", &"b"), 1);
+        assert_eq!(matrix.total(), 4);
+        assert_eq!(matrix.accuracy(), 0.5);
+        assert_eq!(matrix.precision(&"a"), Some(0.5));
+        assert_eq!(matrix.recall(&"a"), Some(0.5));
+        assert_eq!(matrix.precision(&"c"), None);
+        assert_eq!(matrix.recall(&"c"), Some(0.0));
+
+        let rendered = matrix.to_string();
+        assert!(rendered.starts_with("expected"));
+        assert!(rendered.contains("\"c\""));
+        assert!(rendered.ends_with("accuracy 50.0% over 4 samples"));
+    }
+}
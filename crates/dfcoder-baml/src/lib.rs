@@ -1,8 +1,26 @@
 //! BAML activity classification for agent output
 
+pub mod activities;
+pub mod cache;
 pub mod classifier;
+pub mod client;
+pub mod evaluation;
+pub mod providers;
+pub mod rate_limit;
+pub mod rules;
+pub mod streaming;
+pub mod usage;
 
+pub use activities::*;
+pub use cache::*;
 pub use classifier::*;
+pub use client::*;
+pub use evaluation::*;
+pub use providers::*;
+pub use rate_limit::*;
+pub use rules::*;
+pub use streaming::*;
+pub use usage::*;
 
 /// Re-export for convenience
-pub use classifier::{ActivityClassifier, ActivityClass, ActivityType, EmotionalState, ClassificationError};
\ No newline at end of file
+pub use classifier::{ActivityClassifier, ActivityClass, ActivityType, EmotionalState, ClassificationError};
@@ -0,0 +1,530 @@
+//! Request and response formats of the language model APIs `BamlClient` can talk to
+
+use crate::client::{BamlConfig, BamlError};
+use crate::streaming::StreamFraming;
+use crate::usage::TokenUsage;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Anthropic API version sent with every request
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// One language model API: how to ask it for a completion and read the answer
+///
+/// Providers only build requests and interpret responses; `BamlClient` sends
+/// them, so transport failures are mapped the same way for every provider.
+pub trait LlmProvider: std::fmt::Debug + Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Build the HTTP request asking for a completion of `prompt`
+    fn build_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> RequestBuilder;
+
+    /// Pull the reply text out of a successful response body
+    fn parse_response(&self, body: &Value) -> Result<String, BamlError>;
+
+    /// Tokens the call consumed, if the response body reports them
+    fn parse_usage(&self, _body: &Value) -> Option<TokenUsage> {
+        None
+    }
+
+    /// Map an unsuccessful response to an error
+    fn map_error(&self, status: StatusCode, body: &str) -> BamlError;
+
+    /// Build the HTTP request asking for a streamed completion, if the API can stream
+    fn build_stream_request(&self, _client: &Client, _config: &BamlConfig, _prompt: &str) -> Option<RequestBuilder> {
+        None
+    }
+
+    /// How streamed events are framed in the response body
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::ServerSentEvents
+    }
+
+    /// Read one streamed event: the text it adds, if any
+    ///
+    /// Token counts carried by the event are written to `usage`.
+    fn parse_stream_event(&self, _event: &Value, _usage: &mut TokenUsage) -> Result<Option<String>, BamlError> {
+        Err(BamlError::InvalidResponse(format!("{} does not stream", self.name())))
+    }
+}
+
+/// Built-in providers, selectable from configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// Anthropic Messages API
+    #[default]
+    Anthropic,
+    /// OpenAI-compatible chat completions
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Ollama's local chat endpoint
+    Ollama,
+}
+
+impl ProviderKind {
+    /// Where the provider's API usually lives
+    pub fn default_endpoint(&self) -> &'static str {
+        match self {
+            ProviderKind::Anthropic => "https://api.anthropic.com/v1/messages",
+            ProviderKind::OpenAi => "https://api.openai.com/v1/chat/completions",
+            ProviderKind::Ollama => "http://localhost:11434/api/chat",
+        }
+    }
+
+    /// A small, fast model suited to classification
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::Anthropic => "claude-3-5-haiku-latest",
+            ProviderKind::OpenAi => "gpt-4o-mini",
+            ProviderKind::Ollama => "llama3.1",
+        }
+    }
+
+    /// Environment variable the API key is conventionally read from, if one is needed
+    pub fn api_key_var(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderKind::OpenAi => Some("OPENAI_API_KEY"),
+            ProviderKind::Ollama => None,
+        }
+    }
+
+    /// The provider implementation
+    pub fn provider(&self) -> Arc<dyn LlmProvider> {
+        match self {
+            ProviderKind::Anthropic => Arc::new(AnthropicProvider),
+            ProviderKind::OpenAi => Arc::new(OpenAiProvider),
+            ProviderKind::Ollama => Arc::new(OllamaProvider),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderKind::Anthropic => write!(f, "anthropic"),
+            ProviderKind::OpenAi => write!(f, "openai"),
+            ProviderKind::Ollama => write!(f, "ollama"),
+        }
+    }
+}
+
+/// Anthropic Messages API
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnthropicProvider;
+
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn build_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> RequestBuilder {
+        anthropic_request(client, config, prompt, false)
+    }
+
+    fn build_stream_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> Option<RequestBuilder> {
+        Some(anthropic_request(client, config, prompt, true))
+    }
+
+    fn parse_stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>, BamlError> {
+        match event["type"].as_str() {
+            Some("message_start") => {
+                let counts = &event["message"]["usage"];
+                usage.input_tokens = counts["input_tokens"].as_u64().unwrap_or(usage.input_tokens);
+                usage.output_tokens = counts["output_tokens"].as_u64().unwrap_or(usage.output_tokens);
+                Ok(None)
+            }
+            Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+                Ok(event["delta"]["text"].as_str().map(str::to_string))
+            }
+            // Output tokens are cumulative
+            Some("message_delta") => {
+                usage.output_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or(usage.output_tokens);
+                Ok(None)
+            }
+            Some("error") => Err(self.map_error(StatusCode::OK, &event.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, BamlError> {
+        let blocks = body["content"].as_array()
+            .ok_or_else(|| BamlError::InvalidResponse("missing content blocks".to_string()))?;
+        let text: String = blocks.iter()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        if text.is_empty() {
+            return Err(BamlError::InvalidResponse("no text content block".to_string()));
+        }
+        Ok(text)
+    }
+
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        // {"usage":{"input_tokens":412,"output_tokens":38}}
+        Some(TokenUsage {
+            input_tokens: body["usage"]["input_tokens"].as_u64()?,
+            output_tokens: body["usage"]["output_tokens"].as_u64()?,
+        })
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> BamlError {
+        // {"type":"error","error":{"type":"rate_limit_error","message":"..."}}
+        let error = serde_json::from_str::<Value>(body).ok().map(|value| value["error"].clone());
+        let kind = error.as_ref().and_then(|error| error["type"].as_str()).unwrap_or_default();
+        let message = error.as_ref()
+            .and_then(|error| error["message"].as_str())
+            .unwrap_or(body)
+            .to_string();
+
+        match (status, kind) {
+            (StatusCode::TOO_MANY_REQUESTS, _) | (_, "rate_limit_error" | "overloaded_error") => BamlError::RateLimited,
+            (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN, _) | (_, "authentication_error" | "permission_error") => {
+                BamlError::AuthError(message)
+            }
+            _ if status.is_server_error() => BamlError::Unavailable(format!("{}: {}", status, message)),
+            _ => BamlError::ApiError(format!("API error {}: {}", status, message)),
+        }
+    }
+}
+
+fn anthropic_request(client: &Client, config: &BamlConfig, prompt: &str, stream: bool) -> RequestBuilder {
+    let mut request = client
+        .post(&config.endpoint)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .json(&json!({
+            "model": config.model,
+            "max_tokens": config.max_tokens,
+            "temperature": config.temperature,
+            "stream": stream,
+            "messages": [{ "role": "user", "content": prompt }],
+        }));
+    if let Some(api_key) = &config.api_key {
+        request = request.header("x-api-key", api_key);
+    }
+    request
+}
+
+/// OpenAI-compatible chat completions, as served by OpenAI and most proxies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenAiProvider;
+
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn build_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> RequestBuilder {
+        let body = json!({
+            "model": config.model,
+            "max_tokens": config.max_tokens,
+            "temperature": config.temperature,
+            "messages": [{ "role": "user", "content": prompt }],
+        });
+        with_bearer(client.post(&config.endpoint).json(&body), config)
+    }
+
+    fn build_stream_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> Option<RequestBuilder> {
+        let body = json!({
+            "model": config.model,
+            "max_tokens": config.max_tokens,
+            "temperature": config.temperature,
+            "messages": [{ "role": "user", "content": prompt }],
+            "stream": true,
+            // Usage only arrives, in a final chunk without choices, when asked for
+            "stream_options": { "include_usage": true },
+        });
+        Some(with_bearer(client.post(&config.endpoint).json(&body), config))
+    }
+
+    fn parse_stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>, BamlError> {
+        if event.get("error").is_some() {
+            return Err(self.map_error(StatusCode::OK, &event.to_string()));
+        }
+        if let Some(counts) = event.get("usage").filter(|counts| counts.is_object()) {
+            usage.input_tokens = counts["prompt_tokens"].as_u64().unwrap_or(usage.input_tokens);
+            usage.output_tokens = counts["completion_tokens"].as_u64().unwrap_or(usage.output_tokens);
+        }
+        let delta = &event["choices"][0]["delta"];
+        if let Some(refusal) = delta["refusal"].as_str() {
+            return Err(BamlError::InvalidResponse(format!("model refused: {}", refusal)));
+        }
+        Ok(delta["content"].as_str().map(str::to_string))
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, BamlError> {
+        let choice = &body["choices"][0];
+        if let Some(refusal) = choice["message"]["refusal"].as_str() {
+            return Err(BamlError::InvalidResponse(format!("model refused: {}", refusal)));
+        }
+        choice["message"]["content"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| BamlError::InvalidResponse("missing choices[0].message.content".to_string()))
+    }
+
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        // {"usage":{"prompt_tokens":405,"completion_tokens":41,"total_tokens":446}}
+        Some(TokenUsage {
+            input_tokens: body["usage"]["prompt_tokens"].as_u64()?,
+            output_tokens: body["usage"]["completion_tokens"].as_u64()?,
+        })
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> BamlError {
+        // {"error":{"message":"...","type":"...","code":"..."}}
+        let error = serde_json::from_str::<Value>(body).ok().map(|value| value["error"].clone());
+        let code = error.as_ref().and_then(|error| error["code"].as_str()).unwrap_or_default();
+        let message = error.as_ref()
+            .and_then(|error| error["message"].as_str())
+            .unwrap_or(body)
+            .to_string();
+
+        match status {
+            // Waiting will not refill an exhausted quota
+            StatusCode::TOO_MANY_REQUESTS if code == "insufficient_quota" => {
+                BamlError::ApiError(format!("API error {}: {}", status, message))
+            }
+            StatusCode::TOO_MANY_REQUESTS => BamlError::RateLimited,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BamlError::AuthError(message),
+            _ if status.is_server_error() => BamlError::Unavailable(format!("{}: {}", status, message)),
+            _ => BamlError::ApiError(format!("API error {}: {}", status, message)),
+        }
+    }
+}
+
+fn with_bearer(request: RequestBuilder, config: &BamlConfig) -> RequestBuilder {
+    match &config.api_key {
+        Some(api_key) => request.bearer_auth(api_key),
+        None => request,
+    }
+}
+
+/// Ollama's `/api/chat`, usually on the local machine without auth
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OllamaProvider;
+
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn build_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> RequestBuilder {
+        ollama_request(client, config, prompt, false)
+    }
+
+    fn build_stream_request(&self, client: &Client, config: &BamlConfig, prompt: &str) -> Option<RequestBuilder> {
+        Some(ollama_request(client, config, prompt, true))
+    }
+
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::JsonLines
+    }
+
+    fn parse_stream_event(&self, event: &Value, usage: &mut TokenUsage) -> Result<Option<String>, BamlError> {
+        if let Some(error) = event["error"].as_str() {
+            return Err(BamlError::ApiError(error.to_string()));
+        }
+        // Counts only come with the final, `done` line
+        if let Some(counts) = self.parse_usage(event) {
+            *usage = counts;
+        }
+        Ok(event["message"]["content"].as_str().map(str::to_string))
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, BamlError> {
+        if body["done"] == false {
+            return Err(BamlError::InvalidResponse("response is incomplete".to_string()));
+        }
+        body["message"]["content"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| BamlError::InvalidResponse("missing message.content".to_string()))
+    }
+
+    fn parse_usage(&self, body: &Value) -> Option<TokenUsage> {
+        // Counts are left out when the prompt was already evaluated
+        let input_tokens = body["prompt_eval_count"].as_u64();
+        let output_tokens = body["eval_count"].as_u64();
+        if input_tokens.is_none() && output_tokens.is_none() {
+            return None;
+        }
+        Some(TokenUsage {
+            input_tokens: input_tokens.unwrap_or_default(),
+            output_tokens: output_tokens.unwrap_or_default(),
+        })
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> BamlError {
+        // {"error":"model \"llama9\" not found, try pulling it first"}
+        let message = serde_json::from_str::<Value>(body).ok()
+            .and_then(|value| value["error"].as_str().map(str::to_string))
+            .unwrap_or_else(|| body.to_string());
+
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => BamlError::RateLimited,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BamlError::AuthError(message),
+            StatusCode::NOT_FOUND => BamlError::ConfigError(message),
+            _ if status.is_server_error() => BamlError::Unavailable(format!("{}: {}", status, message)),
+            _ => BamlError::ApiError(format!("API error {}: {}", status, message)),
+        }
+    }
+}
+
+fn ollama_request(client: &Client, config: &BamlConfig, prompt: &str, stream: bool) -> RequestBuilder {
+    let body = json!({
+        "model": config.model,
+        "stream": stream,
+        "messages": [{ "role": "user", "content": prompt }],
+        "options": {
+            "temperature": config.temperature,
+            "num_predict": config.max_tokens,
+        },
+    });
+    // Only sent when Ollama sits behind an authenticating proxy
+    with_bearer(client.post(&config.endpoint).json(&body), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(kind: ProviderKind, api_key: Option<&str>) -> BamlConfig {
+        BamlConfig {
+            provider: kind,
+            endpoint: kind.default_endpoint().to_string(),
+            model: kind.default_model().to_string(),
+            api_key: api_key.map(str::to_string),
+            ..BamlConfig::default()
+        }
+    }
+
+    fn built(kind: ProviderKind, api_key: Option<&str>) -> (reqwest::Request, Value) {
+        let request = kind.provider()
+            .build_request(&Client::new(), &config(kind, api_key), "Classify this")
+            .build()
+            .unwrap();
+        let body = serde_json::from_slice(request.body().unwrap().as_bytes().unwrap()).unwrap();
+        (request, body)
+    }
+
+    fn fixture(name: &str) -> Value {
+        let path = format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+    }
+
+    fn fixture_text(name: &str) -> String {
+        fixture(name).to_string()
+    }
+
+    #[test]
+    fn test_anthropic_request_and_fixtures() {
+        let (request, body) = built(ProviderKind::Anthropic, Some("sk-ant"));
+        assert_eq!(request.url().as_str(), "https://api.anthropic.com/v1/messages");
+        assert_eq!(request.headers()["x-api-key"], "sk-ant");
+        assert_eq!(request.headers()["anthropic-version"], ANTHROPIC_VERSION);
+        assert!(request.headers().get("authorization").is_none());
+        assert_eq!(body["messages"][0]["content"], "Classify this");
+        assert_eq!(body["max_tokens"], 256);
+
+        let provider = AnthropicProvider;
+        let reply = provider.parse_response(&fixture("anthropic/message.json")).unwrap();
+        assert!(reply.contains("\"primary\": \"Debugging\""));
+        assert_eq!(
+            provider.parse_usage(&fixture("anthropic/message.json")),
+            Some(TokenUsage { input_tokens: 412, output_tokens: 38 })
+        );
+
+        assert!(matches!(
+            provider.map_error(StatusCode::TOO_MANY_REQUESTS, &fixture_text("anthropic/rate_limit_error.json")),
+            BamlError::RateLimited
+        ));
+        assert!(matches!(
+            provider.map_error(StatusCode::from_u16(529).unwrap(), &fixture_text("anthropic/overloaded_error.json")),
+            BamlError::RateLimited
+        ));
+        match provider.map_error(StatusCode::UNAUTHORIZED, &fixture_text("anthropic/authentication_error.json")) {
+            BamlError::AuthError(message) => assert_eq!(message, "invalid x-api-key"),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(matches!(
+            provider.map_error(StatusCode::BAD_GATEWAY, "<html>bad gateway</html>"),
+            BamlError::Unavailable(_)
+        ));
+        assert!(matches!(
+            provider.map_error(StatusCode::BAD_REQUEST, "not json"),
+            BamlError::ApiError(message) if message.contains("not json")
+        ));
+    }
+
+    #[test]
+    fn test_openai_request_and_fixtures() {
+        let (request, body) = built(ProviderKind::OpenAi, Some("sk-openai"));
+        assert_eq!(request.url().as_str(), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(request.headers()["authorization"], "Bearer sk-openai");
+        assert!(request.headers().get("x-api-key").is_none());
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["messages"][0]["role"], "user");
+
+        let provider = OpenAiProvider;
+        let reply = provider.parse_response(&fixture("openai/chat_completion.json")).unwrap();
+        assert!(reply.contains("\"primary\": \"Testing\""));
+        assert_eq!(
+            provider.parse_usage(&fixture("openai/chat_completion.json")),
+            Some(TokenUsage { input_tokens: 405, output_tokens: 41 })
+        );
+        assert!(matches!(
+            provider.parse_response(&fixture("openai/refusal.json")),
+            Err(BamlError::InvalidResponse(_))
+        ));
+
+        assert!(matches!(
+            provider.map_error(StatusCode::TOO_MANY_REQUESTS, &fixture_text("openai/rate_limit_error.json")),
+            BamlError::RateLimited
+        ));
+        assert!(matches!(
+            provider.map_error(StatusCode::TOO_MANY_REQUESTS, &fixture_text("openai/insufficient_quota.json")),
+            BamlError::ApiError(_)
+        ));
+        assert!(matches!(
+            provider.map_error(StatusCode::UNAUTHORIZED, &fixture_text("openai/invalid_api_key.json")),
+            BamlError::AuthError(message) if message.starts_with("Incorrect API key")
+        ));
+    }
+
+    #[test]
+    fn test_ollama_request_and_fixtures() {
+        let (request, body) = built(ProviderKind::Ollama, None);
+        assert_eq!(request.url().as_str(), "http://localhost:11434/api/chat");
+        assert!(request.headers().get("authorization").is_none());
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["options"]["num_predict"], 256);
+
+        let (request, _) = built(ProviderKind::Ollama, Some("proxy-token"));
+        assert_eq!(request.headers()["authorization"], "Bearer proxy-token");
+
+        let provider = OllamaProvider;
+        let reply = provider.parse_response(&fixture("ollama/chat.json")).unwrap();
+        assert!(reply.contains("\"primary\": \"Implementing\""));
+        assert_eq!(
+            provider.parse_usage(&fixture("ollama/chat.json")),
+            Some(TokenUsage { input_tokens: 398, output_tokens: 36 })
+        );
+        assert_eq!(provider.parse_usage(&serde_json::json!({"done": true})), None);
+        assert!(matches!(
+            provider.map_error(StatusCode::NOT_FOUND, &fixture_text("ollama/model_not_found.json")),
+            BamlError::ConfigError(message) if message.contains("not found")
+        ));
+    }
+
+    #[test]
+    fn test_provider_kind_names() {
+        let kind: ProviderKind = serde_json::from_str("\"openai\"").unwrap();
+        assert_eq!(kind, ProviderKind::OpenAi);
+        assert_eq!(kind.to_string(), "openai");
+        assert_eq!(kind.provider().name(), "openai");
+        assert_eq!(ProviderKind::default(), ProviderKind::Anthropic);
+    }
+}
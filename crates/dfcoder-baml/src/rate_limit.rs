@@ -0,0 +1,120 @@
+//! Token-bucket rate limiting for language model requests
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Sustained request rate and how far it may be exceeded in a burst
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    /// Requests that may be sent at once after a quiet period
+    pub burst: u32,
+}
+
+/// Token bucket shared by every request a client sends
+///
+/// Each request takes a token; tokens refill at `requests_per_second` up to
+/// `burst`. A request that finds the bucket empty reserves the next token and
+/// sleeps until it is due, so waiters are served in the order they arrived.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Negative when tokens have been reserved ahead of time
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let limit = RateLimit {
+            requests_per_second: limit.requests_per_second.max(f64::MIN_POSITIVE),
+            burst: limit.burst.max(1),
+        };
+        Self {
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+            limit,
+        }
+    }
+
+    /// The configured limit
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Wait until a request may be sent
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token if one is available right now
+    pub fn try_acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take the next token, returning how long until it is due
+    fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.limit.requests_per_second)
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.limit.requests_per_second).min(self.limit.burst as f64);
+        bucket.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_then_steady_rate() {
+        let limiter = RateLimiter::new(RateLimit { requests_per_second: 2.0, burst: 3 });
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(!limiter.try_acquire());
+
+        // Two more at 2/s take a second
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        // A quiet period refills the bucket, but only up to the burst size
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        for _ in 0..3 {
+            assert!(limiter.try_acquire());
+        }
+        assert!(!limiter.try_acquire());
+    }
+}
@@ -0,0 +1,561 @@
+//! Keyword rules for classifying output without a language model
+//!
+//! Rules are read from TOML; the built-in set lives in `rules/default.toml`,
+//! which also documents how rules match and how files layer on top of each
+//! other. A `RuleSource` re-reads its files when they change.
+
+use crate::classifier::{needs_help, ActivityClass, ActivityType, ClassificationSource, EmotionalState};
+use crate::evaluation::ConfusionMatrix;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+const BUILTIN_RULES: &str = include_str!("../rules/default.toml");
+
+/// How often a `RuleSource` checks its files for changes by default
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Errors loading a rule file
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid rule file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid pattern in rule {rule}: {source}")]
+    Pattern {
+        rule: String,
+        source: regex::Error,
+    },
+}
+
+/// A rule file as written
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleFile {
+    /// Drop the rules of the files below this one instead of adding to them
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub replace: bool,
+    /// Classification when no rule matches
+    pub defaults: RuleDefaults,
+    pub activity: Vec<ActivityRule>,
+    pub emotion: Vec<EmotionRule>,
+}
+
+/// Classification when no rule matches; unset fields keep the value from below
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub activity: Option<ActivityType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emotional_state: Option<EmotionalState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+/// Evidence for an activity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActivityRule {
+    /// Lets a later file replace this rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub activity: ActivityType,
+    pub patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+/// Emotional state and confidence for output that matches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmotionRule {
+    /// Lets a later file replace this rule
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub emotional_state: EmotionalState,
+    pub confidence: f32,
+    pub patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl RuleFile {
+    /// Read a rule file from disk
+    pub fn load(path: &Path) -> Result<Self, RuleError> {
+        let text = std::fs::read_to_string(path).map_err(|source| RuleError::Io { path: path.to_path_buf(), source })?;
+        toml::from_str(&text).map_err(|source| RuleError::Parse { path: path.to_path_buf(), source })
+    }
+
+    /// Layer `other` on top of these rules
+    ///
+    /// Rules named like an existing one replace it where it stands. Other
+    /// activity rules are added at the end, other emotion rules in front, so
+    /// a project's emotion rules are tried first.
+    pub fn overlay(&mut self, other: RuleFile) {
+        if other.replace {
+            self.activity.clear();
+            self.emotion.clear();
+        }
+
+        let defaults = other.defaults;
+        self.defaults.activity = defaults.activity.or(self.defaults.activity.take());
+        self.defaults.emotional_state = defaults.emotional_state.or(self.defaults.emotional_state.take());
+        self.defaults.confidence = defaults.confidence.or(self.defaults.confidence);
+
+        for rule in other.activity {
+            match self.activity.iter_mut().find(|existing| rule.name.is_some() && existing.name == rule.name) {
+                Some(existing) => *existing = rule,
+                None => self.activity.push(rule),
+            }
+        }
+
+        let mut added = Vec::new();
+        for rule in other.emotion {
+            match self.emotion.iter_mut().find(|existing| rule.name.is_some() && existing.name == rule.name) {
+                Some(existing) => *existing = rule,
+                None => added.push(rule),
+            }
+        }
+        self.emotion.splice(0..0, added);
+    }
+}
+
+/// Compiled patterns of one rule
+#[derive(Debug, Clone)]
+struct Matcher {
+    patterns: Regex,
+    requires: Option<Regex>,
+}
+
+impl Matcher {
+    fn new(rule: String, patterns: &[String], requires: &[String]) -> Result<Self, RuleError> {
+        let compile = |patterns: &[String]| {
+            let alternation = patterns.iter().map(|pattern| format!("(?:{})", pattern)).collect::<Vec<_>>().join("|");
+            RegexBuilder::new(&alternation)
+                .case_insensitive(true)
+                .build()
+                .map_err(|source| RuleError::Pattern { rule: rule.clone(), source })
+        };
+        if patterns.is_empty() {
+            // An empty alternation would match everything
+            return Err(RuleError::Pattern { rule, source: regex::Error::Syntax("no patterns given".to_string()) });
+        }
+        Ok(Self {
+            patterns: compile(patterns)?,
+            requires: if requires.is_empty() { None } else { Some(compile(requires)?) },
+        })
+    }
+
+    fn matches(&self, output: &str) -> bool {
+        self.patterns.is_match(output) && self.requires.as_ref().is_none_or(|requires| requires.is_match(output))
+    }
+}
+
+/// A compiled set of rules
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    file: RuleFile,
+    activity: Vec<Matcher>,
+    emotion: Vec<Matcher>,
+}
+
+impl RuleSet {
+    /// The rules shipped with dfcoder
+    pub fn builtin() -> Self {
+        builtin_rules().as_ref().clone()
+    }
+
+    /// Compile rules
+    pub fn new(file: RuleFile) -> Result<Self, RuleError> {
+        let label = |name: &Option<String>, kind: &str, index: usize| {
+            name.clone().unwrap_or_else(|| format!("{} #{}", kind, index + 1))
+        };
+        let activity = file.activity.iter().enumerate()
+            .map(|(index, rule)| Matcher::new(label(&rule.name, "activity", index), &rule.patterns, &rule.requires))
+            .collect::<Result<_, _>>()?;
+        let emotion = file.emotion.iter().enumerate()
+            .map(|(index, rule)| Matcher::new(label(&rule.name, "emotion", index), &rule.patterns, &rule.requires))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { file, activity, emotion })
+    }
+
+    /// The built-in rules with each of `paths` layered on top in order
+    ///
+    /// Files that do not exist are skipped, so an override can be created
+    /// later and picked up by a `RuleSource`.
+    pub fn load(paths: &[PathBuf]) -> Result<Self, RuleError> {
+        let mut file = builtin_rules().file.clone();
+        for path in paths {
+            if path.exists() {
+                file.overlay(RuleFile::load(path)?);
+            }
+        }
+        Self::new(file)
+    }
+
+    /// The rules as written
+    pub fn rules(&self) -> &RuleFile {
+        &self.file
+    }
+
+    /// Write the rules back out, e.g. after `train`
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(&self.file).unwrap_or_default()
+    }
+
+    /// Classify output by these rules
+    pub fn classify(&self, output: &str) -> ActivityClass {
+        let defaults = &self.file.defaults;
+        let primary = self.activity(output);
+        let (emotional_state, confidence) = self.file.emotion.iter().zip(&self.emotion)
+            .find(|(_, matcher)| matcher.matches(output))
+            .map(|(rule, _)| (rule.emotional_state.clone(), rule.confidence))
+            .unwrap_or_else(|| {
+                (defaults.emotional_state.clone().unwrap_or(EmotionalState::Focused), defaults.confidence.unwrap_or(0.7))
+            });
+
+        ActivityClass {
+            needs_help: needs_help(&primary, confidence, &emotional_state),
+            primary,
+            confidence,
+            emotional_state,
+            // Keywords say nothing about how long is left
+            estimated_completion: None,
+            source: ClassificationSource::Rules,
+        }
+    }
+
+    /// Score each labelled output against these rules
+    pub fn evaluate<S: AsRef<str>>(&self, samples: &[(S, ActivityType)]) -> ConfusionMatrix<ActivityType> {
+        let mut matrix = ConfusionMatrix::new(ActivityType::ALL);
+        for (output, expected) in samples {
+            matrix.record(expected, &self.activity(output.as_ref()));
+        }
+        matrix
+    }
+
+    /// Adjust activity weights to fit labelled output
+    ///
+    /// For every misclassified sample, the rules that matched for the right
+    /// activity gain `rate` and those that matched for the wrong one lose it,
+    /// down to zero. Patterns are left alone, so output no rule matches stays
+    /// misclassified. Returns the evaluation after the last pass.
+    pub fn train<S: AsRef<str>>(&mut self, samples: &[(S, ActivityType)], epochs: usize, rate: f32) -> ConfusionMatrix<ActivityType> {
+        for _ in 0..epochs {
+            let mut changed = false;
+            for (output, expected) in samples {
+                let output = output.as_ref();
+                let predicted = self.activity(output);
+                if predicted == *expected {
+                    continue;
+                }
+                for (rule, matcher) in self.file.activity.iter_mut().zip(&self.activity) {
+                    if !matcher.matches(output) {
+                        continue;
+                    }
+                    if rule.activity == *expected {
+                        rule.weight += rate;
+                        changed = true;
+                    } else if rule.activity == predicted && rule.weight > 0.0 {
+                        rule.weight = (rule.weight - rate).max(0.0);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        self.evaluate(samples)
+    }
+
+    /// The activity with the highest summed weight of matching rules
+    fn activity(&self, output: &str) -> ActivityType {
+        let mut scores: Vec<(ActivityType, f32)> = Vec::new();
+        for (rule, matcher) in self.file.activity.iter().zip(&self.activity) {
+            if !matcher.matches(output) {
+                continue;
+            }
+            match scores.iter_mut().find(|(activity, _)| *activity == rule.activity) {
+                Some((_, score)) => *score += rule.weight,
+                None => scores.push((rule.activity.clone(), rule.weight)),
+            }
+        }
+
+        // Scores are in order of each activity's first matching rule, and
+        // only a strictly higher score displaces an earlier one
+        let mut best: Option<(ActivityType, f32)> = None;
+        for (activity, score) in scores {
+            if score > 0.0 && best.as_ref().is_none_or(|(_, best)| score > *best) {
+                best = Some((activity, score));
+            }
+        }
+        best.map(|(activity, _)| activity)
+            .or_else(|| self.file.defaults.activity.clone())
+            .unwrap_or(ActivityType::Implementing)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn builtin_rules() -> &'static Arc<RuleSet> {
+    static BUILTIN: OnceLock<Arc<RuleSet>> = OnceLock::new();
+    BUILTIN.get_or_init(|| {
+        let file = toml::from_str(BUILTIN_RULES).expect("built-in rules parse");
+        Arc::new(RuleSet::new(file).expect("built-in rules compile"))
+    })
+}
+
+/// Rules backed by files, reloaded when they change
+///
+/// Clones share the loaded rules. Files are checked at most once per check
+/// interval, when the rules are next asked for; if a changed file no longer
+/// loads, the previous rules stay in use until it is fixed.
+#[derive(Debug, Clone)]
+pub struct RuleSource {
+    paths: Arc<[PathBuf]>,
+    check_interval: Duration,
+    state: Arc<Mutex<SourceState>>,
+}
+
+#[derive(Debug)]
+struct SourceState {
+    rules: Arc<RuleSet>,
+    /// Modification time and length of each file when last checked
+    stamps: Vec<Option<(SystemTime, u64)>>,
+    checked_at: Instant,
+}
+
+impl RuleSource {
+    /// Only the built-in rules
+    pub fn builtin() -> Self {
+        Self::with_rules(builtin_rules().clone(), Vec::new())
+    }
+
+    /// The built-in rules overlaid by `paths` in order
+    pub fn load(paths: Vec<PathBuf>) -> Result<Self, RuleError> {
+        let rules = RuleSet::load(&paths)?;
+        Ok(Self::with_rules(Arc::new(rules), paths))
+    }
+
+    /// The built-in rules overlaid by the user's and then the project's rule file
+    ///
+    /// These are `$XDG_CONFIG_HOME/dfcoder/rules.toml` (or
+    /// `~/.config/dfcoder/rules.toml`) and `<project>/.dfcoder/rules.toml`;
+    /// either may be missing.
+    pub fn for_project(project: &Path) -> Result<Self, RuleError> {
+        let user = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|config| config.join("dfcoder").join("rules.toml"));
+        let project = project.join(".dfcoder").join("rules.toml");
+        Self::load(user.into_iter().chain(Some(project)).collect())
+    }
+
+    fn with_rules(rules: Arc<RuleSet>, paths: Vec<PathBuf>) -> Self {
+        let stamps = paths.iter().map(|path| stamp(path)).collect();
+        Self {
+            paths: paths.into(),
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            state: Arc::new(Mutex::new(SourceState { rules, stamps, checked_at: Instant::now() })),
+        }
+    }
+
+    /// Check files for changes this often
+    pub fn with_check_interval(mut self, interval: Duration) -> Self {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Files layered over the built-in rules
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The current rules, reloading them first if a file changed
+    pub fn current(&self) -> Arc<RuleSet> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !self.paths.is_empty() && state.checked_at.elapsed() >= self.check_interval {
+            state.checked_at = Instant::now();
+            let stamps: Vec<_> = self.paths.iter().map(|path| stamp(path)).collect();
+            if stamps != state.stamps {
+                state.stamps = stamps;
+                match RuleSet::load(&self.paths) {
+                    Ok(rules) => {
+                        tracing::info!("Reloaded classification rules");
+                        state.rules = Arc::new(rules);
+                    }
+                    Err(e) => tracing::warn!("Keeping previous classification rules: {}", e),
+                }
+            }
+        }
+        state.rules.clone()
+    }
+
+    /// Reload the files now, whether or not they changed
+    pub fn reload(&self) -> Result<(), RuleError> {
+        let rules = RuleSet::load(&self.paths)?;
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.stamps = self.paths.iter().map(|path| stamp(path)).collect();
+        state.checked_at = Instant::now();
+        state.rules = Arc::new(rules);
+        Ok(())
+    }
+}
+
+impl Default for RuleSource {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_builtin_rules_classify_output() {
+        let rules = RuleSet::builtin();
+
+        let class = rules.classify("Error: compilation failed, fixing the import");
+        assert_eq!(class.primary, ActivityType::Debugging);
+        assert_eq!(class.emotional_state, EmotionalState::Frustrated);
+        assert_eq!(class.confidence, 0.4);
+
+        let class = rules.classify("I'm stuck and confused, need help");
+        assert_eq!(class.primary, ActivityType::Stuck);
+        assert_eq!(class.emotional_state, EmotionalState::Desperate);
+        assert!(class.needs_help);
+
+        let class = rules.classify("Running tests... all passed, done");
+        assert_eq!(class.primary, ActivityType::Testing);
+        assert_eq!(class.emotional_state, EmotionalState::Confident);
+
+        let class = rules.classify("hmm");
+        assert_eq!(class.primary, ActivityType::Implementing);
+        assert_eq!((class.emotional_state, class.confidence), (EmotionalState::Focused, 0.7));
+    }
+
+    #[test]
+    fn test_overrides_replace_named_rules_and_add_new_ones() {
+        let mut file = builtin_rules().file.clone();
+        file.overlay(toml::from_str(r#"
+            [defaults]
+            activity = "Idle"
+
+            [[activity]]
+            name = "waiting"
+            activity = "Waiting"
+            patterns = ["waiting", "pending", "queued"]
+            weight = 2.0
+
+            [[activity]]
+            activity = "Researching"
+            patterns = ['grep\s+-r']
+            weight = 9.0
+
+            [[emotion]]
+            emotional_state = "Cautious"
+            confidence = 0.5
+            patterns = ["hmm"]
+        "#).unwrap());
+        let rules = RuleSet::new(file).unwrap();
+
+        assert_eq!(rules.rules().activity.iter().filter(|rule| rule.name.as_deref() == Some("waiting")).count(), 1);
+        assert_eq!(rules.classify("job queued").primary, ActivityType::Waiting);
+        assert_eq!(rules.classify("GREP  -r foo while writing tests").primary, ActivityType::Researching);
+        assert_eq!(rules.classify("hmm").primary, ActivityType::Idle);
+        assert_eq!(rules.classify("hmm, done").emotional_state, EmotionalState::Cautious);
+        assert_eq!(rules.classify("all done").emotional_state, EmotionalState::Confident);
+
+        let mut file = builtin_rules().file.clone();
+        file.overlay(toml::from_str("replace = true").unwrap());
+        let rules = RuleSet::new(file).unwrap();
+        assert_eq!(rules.classify("error: tests failed").primary, ActivityType::Implementing);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_reported() {
+        let file: RuleFile = toml::from_str(r#"
+            [[activity]]
+            name = "broken"
+            activity = "Testing"
+            patterns = ["("]
+        "#).unwrap();
+        let error = RuleSet::new(file).unwrap_err();
+        assert!(matches!(error, RuleError::Pattern { ref rule, .. } if rule == "broken"));
+
+        assert!(toml::from_str::<RuleFile>("[[activity]]\nactivity = \"Coding\"\npatterns = []").is_err());
+    }
+
+    #[test]
+    fn test_source_reloads_changed_files_and_keeps_rules_on_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".dfcoder").join("rules.toml");
+        let source = RuleSource::load(vec![path.clone()]).unwrap().with_check_interval(Duration::ZERO);
+        assert_eq!(source.current().classify("deploying").primary, ActivityType::Implementing);
+
+        write(&path, "[[activity]]\nactivity = \"Waiting\"\npatterns = [\"deploying\"]\n");
+        assert_eq!(source.current().classify("deploying").primary, ActivityType::Waiting);
+
+        write(&path, "[[activity]]\nactivity = \"Waiting\"\npatterns = [\"(\"]\n");
+        assert_eq!(source.clone().current().classify("deploying").primary, ActivityType::Waiting);
+        assert!(source.reload().is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(source.current().classify("deploying").primary, ActivityType::Implementing);
+    }
+
+    #[test]
+    fn test_training_fits_weights_to_labelled_output() {
+        let samples = [
+            ("assertion failed in test_parse", ActivityType::Testing),
+            ("2 tests failed: expected 3, got 4", ActivityType::Testing),
+            ("error[E0308]: mismatched types", ActivityType::Stuck),
+            ("Running tests... ok", ActivityType::Testing),
+        ];
+        let mut rules = RuleSet::builtin();
+        let before = rules.evaluate(&samples);
+        assert_eq!(before.count(&ActivityType::Testing, &ActivityType::Stuck), 2);
+
+        let after = rules.train(&samples, 20, 0.5);
+        assert_eq!(after.accuracy(), 1.0);
+        assert!(after.accuracy() > before.accuracy());
+
+        let saved: RuleFile = toml::from_str(&rules.to_toml()).unwrap();
+        assert_eq!(&saved, rules.rules());
+        let reloaded = RuleSet::new(saved).unwrap();
+        assert_eq!(reloaded.evaluate(&samples).accuracy(), 1.0);
+    }
+}
@@ -0,0 +1,197 @@
+//! Incremental model replies
+//!
+//! A streamed reply arrives as a series of provider events. A `FrameDecoder`
+//! splits the response body into event payloads; each provider turns its
+//! payloads into text deltas and token counts, and `ResponseStream` hands the
+//! deltas out as they arrive, finishing with the usage of the whole call.
+
+use crate::client::{request_error, BamlError, CancellationToken};
+use crate::providers::LlmProvider;
+use crate::usage::{TokenUsage, UsageLedger, UsageScope};
+pub use dfcoder_types::framing::{FrameDecoder, StreamFraming};
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A piece of a streamed reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// Text to append to the reply so far
+    Delta(String),
+    /// The reply is complete, having used these tokens
+    Done(TokenUsage),
+}
+
+/// Text deltas of a reply as the model produces them, then its usage
+///
+/// Yields `Delta` events followed by one `Done`. If the request fails part
+/// way, the text received before the failure is yielded, then the error, and
+/// the stream ends; cancellation ends it straight away with `Cancelled`, and a
+/// body that stalls for longer than the idle timeout with `Timeout`. The tokens
+/// reported so far are charged once the stream finishes or is dropped, so
+/// abandoning a reply early still records what it cost.
+pub struct ResponseStream {
+    inner: BoxStream<'static, Result<StreamEvent, BamlError>>,
+}
+
+impl ResponseStream {
+    pub(crate) fn new(
+        body: BoxStream<'static, reqwest::Result<bytes::Bytes>>,
+        provider: Arc<dyn LlmProvider>,
+        ledger: Arc<UsageLedger>,
+        model: String,
+        scope: UsageScope,
+        cancel: CancellationToken,
+        idle_timeout: Duration,
+    ) -> Self {
+        let state = StreamState {
+            decoder: FrameDecoder::new(provider.stream_framing()),
+            body,
+            provider,
+            charge: Charge { ledger, model, scope, usage: TokenUsage::default(), charged: false },
+            cancel,
+            idle_timeout,
+            pending: VecDeque::new(),
+            failure: None,
+            finished: false,
+        };
+        Self { inner: futures::stream::unfold(state, StreamState::next).boxed() }
+    }
+
+    /// Wait for the whole reply
+    pub async fn text(mut self) -> Result<(String, TokenUsage), BamlError> {
+        let mut text = String::new();
+        while let Some(event) = self.next().await {
+            match event? {
+                StreamEvent::Delta(delta) => text.push_str(&delta),
+                StreamEvent::Done(usage) => return Ok((text, usage)),
+            }
+        }
+        Err(BamlError::InvalidResponse("stream ended without completing".to_string()))
+    }
+}
+
+impl Stream for ResponseStream {
+    type Item = Result<StreamEvent, BamlError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl std::fmt::Debug for ResponseStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseStream").finish_non_exhaustive()
+    }
+}
+
+/// Tokens a stream has used, charged to the ledger exactly once
+struct Charge {
+    ledger: Arc<UsageLedger>,
+    model: String,
+    scope: UsageScope,
+    usage: TokenUsage,
+    charged: bool,
+}
+
+impl Charge {
+    fn settle(&mut self) {
+        if !self.charged {
+            self.charged = true;
+            self.ledger.record(&self.model, &self.scope, self.usage);
+        }
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.settle();
+    }
+}
+
+struct StreamState {
+    body: BoxStream<'static, reqwest::Result<bytes::Bytes>>,
+    decoder: FrameDecoder,
+    provider: Arc<dyn LlmProvider>,
+    charge: Charge,
+    cancel: CancellationToken,
+    /// Longest wait for the next chunk of the body
+    idle_timeout: Duration,
+    pending: VecDeque<StreamEvent>,
+    /// Yielded once the events received before it have been
+    failure: Option<BamlError>,
+    finished: bool,
+}
+
+impl StreamState {
+    async fn next(mut self) -> Option<(Result<StreamEvent, BamlError>, Self)> {
+        loop {
+            if !self.finished && self.cancel.is_cancelled() {
+                self.pending.clear();
+                self.stop();
+                return Some((Err(BamlError::Cancelled), self));
+            }
+            if let Some(event) = self.pending.pop_front() {
+                return Some((Ok(event), self));
+            }
+            if let Some(e) = self.failure.take() {
+                return Some((Err(e), self));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let chunk = tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => continue,
+                chunk = tokio::time::timeout(self.idle_timeout, self.body.next()) => chunk,
+            };
+            let result = match chunk {
+                Err(_) => Err(BamlError::Timeout),
+                Ok(Some(Ok(bytes))) => {
+                    let payloads = self.decoder.feed(&bytes);
+                    self.handle(payloads)
+                }
+                Ok(Some(Err(e))) => Err(request_error(e)),
+                Ok(None) => {
+                    let payloads = self.decoder.finish().into_iter().collect();
+                    self.handle(payloads).map(|()| {
+                        self.charge.settle();
+                        self.pending.push_back(StreamEvent::Done(self.charge.usage));
+                        self.finished = true;
+                    })
+                }
+            };
+            if let Err(e) = result {
+                self.stop();
+                self.failure = Some(e);
+            }
+        }
+    }
+
+    fn handle(&mut self, payloads: Vec<String>) -> Result<(), BamlError> {
+        for payload in payloads {
+            // OpenAI-style streams close with a sentinel rather than JSON
+            if payload == "[DONE]" {
+                continue;
+            }
+            let event: serde_json::Value = serde_json::from_str(&payload)?;
+            if let Some(delta) = self.provider.parse_stream_event(&event, &mut self.charge.usage)? {
+                if !delta.is_empty() {
+                    self.pending.push_back(StreamEvent::Delta(delta));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// End the stream early, charging what was used so far
+    fn stop(&mut self) {
+        self.finished = true;
+        self.charge.settle();
+    }
+}
@@ -0,0 +1,321 @@
+//! Token and cost accounting for model calls
+//!
+//! Every reply a provider returns is recorded against the model, the agent and
+//! task it was made for, and the UTC day it was made on. Once a configured
+//! budget is spent the client refuses further calls, so classification falls
+//! back to the keyword rules instead of running up the bill.
+
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Tokens a single call consumed, as reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Price of a model in US dollars per million tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self { input_per_million, output_per_million }
+    }
+
+    /// Cost of `usage` in US dollars
+    pub fn cost(&self, usage: TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Prices by model name
+///
+/// A model is priced by the longest entry its name starts with, so
+/// `claude-3-5-haiku` also covers dated snapshots such as
+/// `claude-3-5-haiku-20241022`. Models without an entry cost nothing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    /// A table with no prices, for free or self-hosted models
+    pub fn empty() -> Self {
+        Self { prices: HashMap::new() }
+    }
+
+    /// Set the price of a model, or of every model starting with `model`
+    pub fn with_price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.prices.insert(model.into(), price);
+        self
+    }
+
+    /// The price `model` is billed at, if it is listed
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        self.prices.iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Cost of `usage` on `model` in US dollars
+    pub fn cost(&self, model: &str, usage: TokenUsage) -> f64 {
+        self.price(model).map_or(0.0, |price| price.cost(usage))
+    }
+}
+
+impl Default for PriceTable {
+    /// List prices of the default hosted models
+    fn default() -> Self {
+        Self::empty()
+            .with_price("claude-3-5-haiku", ModelPrice::new(0.80, 4.00))
+            .with_price("claude-3-haiku", ModelPrice::new(0.25, 1.25))
+            .with_price("claude-3-5-sonnet", ModelPrice::new(3.00, 15.00))
+            .with_price("gpt-4o-mini", ModelPrice::new(0.15, 0.60))
+            .with_price("gpt-4o", ModelPrice::new(2.50, 10.00))
+    }
+}
+
+/// Spending limits; calls are refused once any of them is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageBudget {
+    /// US dollars across all calls in one UTC day
+    pub daily_cost_usd: Option<f64>,
+    /// Input and output tokens across all calls in one UTC day
+    pub daily_tokens: Option<u64>,
+    /// US dollars spent on behalf of a single agent
+    pub agent_cost_usd: Option<f64>,
+    /// US dollars spent on a single task
+    pub task_cost_usd: Option<f64>,
+}
+
+/// What a call is made on behalf of, for attributing its cost
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UsageScope {
+    pub agent_id: Option<String>,
+    pub task_id: Option<String>,
+}
+
+impl UsageScope {
+    pub fn new(agent_id: Option<String>, task_id: Option<String>) -> Self {
+        Self { agent_id, task_id }
+    }
+}
+
+/// Calls, tokens and cost accumulated over some set of calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, usage: TokenUsage, cost_usd: f64) {
+        self.calls += 1;
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cost_usd += cost_usd;
+    }
+}
+
+/// Usage broken down by model, agent, task and UTC day
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageSummary {
+    pub total: UsageTotals,
+    pub by_model: HashMap<String, UsageTotals>,
+    pub by_agent: HashMap<String, UsageTotals>,
+    pub by_task: HashMap<String, UsageTotals>,
+    pub by_day: BTreeMap<NaiveDate, UsageTotals>,
+}
+
+impl UsageSummary {
+    /// Usage on the given UTC day
+    pub fn day(&self, day: NaiveDate) -> UsageTotals {
+        self.by_day.get(&day).copied().unwrap_or_default()
+    }
+
+    fn record(&mut self, model: &str, scope: &UsageScope, day: NaiveDate, usage: TokenUsage, cost_usd: f64) {
+        self.total.add(usage, cost_usd);
+        self.by_model.entry(model.to_string()).or_default().add(usage, cost_usd);
+        if let Some(agent_id) = &scope.agent_id {
+            self.by_agent.entry(agent_id.clone()).or_default().add(usage, cost_usd);
+        }
+        if let Some(task_id) = &scope.task_id {
+            self.by_task.entry(task_id.clone()).or_default().add(usage, cost_usd);
+        }
+        self.by_day.entry(day).or_default().add(usage, cost_usd);
+    }
+}
+
+/// Running usage totals, shared by every clone of a client
+#[derive(Debug)]
+pub struct UsageLedger {
+    prices: PriceTable,
+    budget: UsageBudget,
+    summary: Mutex<UsageSummary>,
+}
+
+impl UsageLedger {
+    pub fn new(prices: PriceTable, budget: UsageBudget) -> Self {
+        Self {
+            prices,
+            budget,
+            summary: Mutex::new(UsageSummary::default()),
+        }
+    }
+
+    pub fn prices(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    pub fn budget(&self) -> &UsageBudget {
+        &self.budget
+    }
+
+    /// Record a call to `model`, returning its cost in US dollars
+    pub fn record(&self, model: &str, scope: &UsageScope, usage: TokenUsage) -> f64 {
+        let cost_usd = self.prices.cost(model, usage);
+        self.summary.lock().unwrap().record(model, scope, today(), usage, cost_usd);
+        cost_usd
+    }
+
+    /// Why a call for `scope` would exceed the budget, if it would
+    pub fn over_budget(&self, scope: &UsageScope) -> Option<String> {
+        let summary = self.summary.lock().unwrap();
+        let day = summary.day(today());
+        let budget = &self.budget;
+
+        if let Some(limit) = budget.daily_cost_usd.filter(|limit| day.cost_usd >= *limit) {
+            return Some(format!("daily cost ${:.4} reached the ${:.4} limit", day.cost_usd, limit));
+        }
+        if let Some(limit) = budget.daily_tokens.filter(|limit| day.tokens() >= *limit) {
+            return Some(format!("daily tokens {} reached the {} limit", day.tokens(), limit));
+        }
+        let spent = |totals: &HashMap<String, UsageTotals>, id: &Option<String>| {
+            id.as_ref().and_then(|id| totals.get(id)).map_or(0.0, |totals| totals.cost_usd)
+        };
+        if let Some(limit) = budget.agent_cost_usd {
+            let cost = spent(&summary.by_agent, &scope.agent_id);
+            if cost >= limit {
+                return Some(format!("agent cost ${:.4} reached the ${:.4} limit", cost, limit));
+            }
+        }
+        if let Some(limit) = budget.task_cost_usd {
+            let cost = spent(&summary.by_task, &scope.task_id);
+            if cost >= limit {
+                return Some(format!("task cost ${:.4} reached the ${:.4} limit", cost, limit));
+            }
+        }
+        None
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        self.summary.lock().unwrap().clone()
+    }
+
+    /// Replace the totals, e.g. with a summary persisted before a restart
+    pub fn restore(&self, summary: UsageSummary) {
+        *self.summary.lock().unwrap() = summary;
+    }
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        Self::new(PriceTable::default(), UsageBudget::default())
+    }
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage { input_tokens, output_tokens }
+    }
+
+    #[test]
+    fn test_prices_match_longest_prefix() {
+        let prices = PriceTable::default();
+        assert_eq!(prices.price("claude-3-5-haiku-20241022"), Some(ModelPrice::new(0.80, 4.00)));
+        assert_eq!(prices.price("gpt-4o-mini-2024-07-18"), Some(ModelPrice::new(0.15, 0.60)));
+        assert_eq!(prices.price("gpt-4o-2024-08-06"), Some(ModelPrice::new(2.50, 10.00)));
+        assert_eq!(prices.price("llama3.1"), None);
+
+        let cost = prices.cost("claude-3-5-haiku-latest", usage(1_000_000, 500_000));
+        assert!((cost - 2.80).abs() < 1e-9);
+        assert_eq!(prices.cost("llama3.1", usage(1_000, 1_000)), 0.0);
+
+        let table: PriceTable = serde_json::from_str(
+            r#"{"local": {"input_per_million": 1.0, "output_per_million": 2.0}}"#,
+        ).unwrap();
+        assert_eq!(table.price("local-7b"), Some(ModelPrice::new(1.0, 2.0)));
+    }
+
+    #[test]
+    fn test_ledger_aggregates_by_agent_task_and_day() {
+        let ledger = UsageLedger::default();
+        let scope = UsageScope::new(Some("agent-1".to_string()), Some("task-1".to_string()));
+        ledger.record("gpt-4o-mini", &scope, usage(1_000, 100));
+        ledger.record("gpt-4o-mini", &scope, usage(1_000, 100));
+        ledger.record("llama3.1", &UsageScope::default(), usage(50, 5));
+
+        let summary = ledger.summary();
+        assert_eq!(summary.total.calls, 3);
+        assert_eq!(summary.total.tokens(), 2_255);
+        assert_eq!(summary.by_agent["agent-1"].calls, 2);
+        assert_eq!(summary.by_task["task-1"].input_tokens, 2_000);
+        assert_eq!(summary.by_model["llama3.1"].cost_usd, 0.0);
+        assert_eq!(summary.day(today()).calls, 3);
+        assert!((summary.total.cost_usd - 0.00042).abs() < 1e-9);
+
+        let restored = UsageLedger::default();
+        restored.restore(summary.clone());
+        assert_eq!(restored.summary(), summary);
+    }
+
+    #[test]
+    fn test_budgets_are_checked_per_scope() {
+        let budget = UsageBudget { agent_cost_usd: Some(0.001), ..UsageBudget::default() };
+        let ledger = UsageLedger::new(PriceTable::default(), budget);
+        let busy = UsageScope::new(Some("busy".to_string()), None);
+        let idle = UsageScope::new(Some("idle".to_string()), None);
+
+        assert_eq!(ledger.over_budget(&busy), None);
+        ledger.record("claude-3-5-haiku-latest", &busy, usage(1_000, 100));
+        assert!(ledger.over_budget(&busy).unwrap().contains("agent cost"));
+        assert_eq!(ledger.over_budget(&idle), None);
+
+        let budget = UsageBudget { daily_tokens: Some(100), ..UsageBudget::default() };
+        let ledger = UsageLedger::new(PriceTable::empty(), budget);
+        ledger.record("llama3.1", &UsageScope::default(), usage(90, 10));
+        assert!(ledger.over_budget(&idle).unwrap().contains("daily tokens"));
+    }
+}
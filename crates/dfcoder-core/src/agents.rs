@@ -1,14 +1,11 @@
 //! Agent system with role-based behavior
 
+use dfcoder_types::{instant_serde, option_instant_serde};
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-/// Unique identifier for agents
-pub type AgentId = String;
-
-/// Unique identifier for tasks
-pub type TaskId = String;
+pub use dfcoder_types::{AgentId, AgentMetrics, AgentRole, AgentStatus, TaskId, TaskPriority, TaskStatus};
 
 /// Core agent with role-based prompting
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,36 +16,13 @@ pub struct Agent {
     pub pane_id: u32,
     pub current_task: Option<TaskId>,
     pub status: AgentStatus,
-    #[serde(skip)]
+    #[serde(with = "instant_serde")]
     pub created_at: Instant,
-    #[serde(skip)]
+    #[serde(with = "instant_serde")]
     pub last_activity: Instant,
     pub metrics: AgentMetrics,
 }
 
-/// Four clear agent roles that map to specific behaviors
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum AgentRole {
-    /// Creates project structure and boilerplate
-    Scaffolder,
-    /// Writes feature code according to specifications
-    Implementer,
-    /// Finds and fixes bugs, improves code quality
-    Debugger,
-    /// Writes comprehensive test coverage
-    Tester,
-}
-
-/// Current status of an agent
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum AgentStatus {
-    Idle,
-    Working,
-    Stuck,
-    NeedsSupervision,
-    Error,
-}
-
 /// Task representation with clear requirements
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -58,27 +32,16 @@ pub struct Task {
     pub description: String,
     pub required_role: AgentRole,
     pub status: TaskStatus,
-    #[serde(skip)]
+    #[serde(with = "instant_serde")]
     pub created_at: Instant,
-    #[serde(skip)]
+    #[serde(with = "option_instant_serde")]
     pub assigned_at: Option<Instant>,
-    #[serde(skip)]
+    #[serde(with = "option_instant_serde")]
     pub completed_at: Option<Instant>,
     pub assignee: Option<AgentId>,
     pub context: TaskContext,
 }
 
-/// Task execution status
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum TaskStatus {
-    Pending,
-    Assigned,
-    InProgress,
-    Completed,
-    Failed,
-    Cancelled,
-}
-
 /// Context information for task execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskContext {
@@ -88,27 +51,6 @@ pub struct TaskContext {
     pub estimated_duration: Option<Duration>,
 }
 
-/// Task priority levels
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub enum TaskPriority {
-    Low,
-    Normal,
-    High,
-    Critical,
-}
-
-/// Performance metrics for agents
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AgentMetrics {
-    pub tasks_completed: u32,
-    pub tasks_failed: u32,
-    #[serde(skip)]
-    pub average_completion_time: Duration,
-    pub success_rate: f32,
-    pub help_requests: u32,
-    pub last_error: Option<String>,
-}
-
 impl Agent {
     /// Create a new agent with the specified role
     pub fn new(role: AgentRole, pane_id: u32) -> Self {
@@ -361,41 +303,6 @@ impl Default for TaskContext {
     }
 }
 
-impl Default for AgentMetrics {
-    fn default() -> Self {
-        Self {
-            tasks_completed: 0,
-            tasks_failed: 0,
-            average_completion_time: Duration::from_secs(0),
-            success_rate: 0.0,
-            help_requests: 0,
-            last_error: None,
-        }
-    }
-}
-
-impl std::fmt::Display for AgentRole {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AgentRole::Scaffolder => write!(f, "Scaffolder"),
-            AgentRole::Implementer => write!(f, "Implementer"),
-            AgentRole::Debugger => write!(f, "Debugger"),
-            AgentRole::Tester => write!(f, "Tester"),
-        }
-    }
-}
-
-impl std::fmt::Display for TaskPriority {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TaskPriority::Low => write!(f, "Low"),
-            TaskPriority::Normal => write!(f, "Normal"),
-            TaskPriority::High => write!(f, "High"),
-            TaskPriority::Critical => write!(f, "Critical"),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,148 @@
+//! Concurrency limits for the workshop
+//!
+//! A task may start only while every scope it falls in is under its limit:
+//! the whole workshop, the agent's role and the agent's pane. When a limit is
+//! lowered below the current load, a [`ShrinkPolicy`] decides whether running
+//! work drains or is pre-empted.
+
+use crate::agents::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub use dfcoder_types::{CapacityScope, ShrinkPolicy};
+
+/// Limit for roles without an explicit one
+pub const DEFAULT_ROLE_CAPACITY: usize = 1;
+
+/// Concurrency limits for the workshop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapacityLimits {
+    /// Tasks running across the whole workshop; `None` for no cap
+    pub global: Option<usize>,
+    /// Tasks running per role; other roles get `DEFAULT_ROLE_CAPACITY`
+    pub per_role: HashMap<AgentRole, usize>,
+    /// Tasks running per pane, unless overridden in `pane_overrides`; `None` for no cap
+    pub per_pane: Option<usize>,
+    pub pane_overrides: HashMap<u32, usize>,
+}
+
+impl CapacityLimits {
+    /// The limit that applies to a scope, `None` if it is unlimited
+    pub fn limit(&self, scope: &CapacityScope) -> Option<usize> {
+        match scope {
+            CapacityScope::Global => self.global,
+            CapacityScope::Role(role) => Some(self.per_role.get(role).copied().unwrap_or(DEFAULT_ROLE_CAPACITY)),
+            CapacityScope::Pane(pane_id) => self.pane_overrides.get(pane_id).copied().or(self.per_pane),
+        }
+    }
+
+    /// The limit explicitly set for a scope
+    pub fn explicit_limit(&self, scope: &CapacityScope) -> Option<usize> {
+        match scope {
+            CapacityScope::Global => self.global,
+            CapacityScope::Role(role) => self.per_role.get(role).copied(),
+            CapacityScope::Pane(pane_id) => self.pane_overrides.get(pane_id).copied(),
+        }
+    }
+
+    /// Set or clear the explicit limit for a scope, returning the previous one
+    ///
+    /// Clearing removes the workshop cap, or falls back to the default for a
+    /// role or pane.
+    pub fn set_limit(&mut self, scope: &CapacityScope, limit: Option<usize>) -> Option<usize> {
+        let old = self.explicit_limit(scope);
+        match (scope, limit) {
+            (CapacityScope::Global, limit) => self.global = limit,
+            (CapacityScope::Role(role), Some(limit)) => {
+                self.per_role.insert(role.clone(), limit);
+            }
+            (CapacityScope::Role(role), None) => {
+                self.per_role.remove(role);
+            }
+            (CapacityScope::Pane(pane_id), Some(limit)) => {
+                self.pane_overrides.insert(*pane_id, limit);
+            }
+            (CapacityScope::Pane(pane_id), None) => {
+                self.pane_overrides.remove(pane_id);
+            }
+        }
+        old
+    }
+
+    /// Every scope an agent's next task would count against
+    pub fn scopes_for(agent: &Agent) -> [CapacityScope; 3] {
+        [
+            CapacityScope::Global,
+            CapacityScope::Role(agent.role.clone()),
+            CapacityScope::Pane(agent.pane_id),
+        ]
+    }
+}
+
+impl Default for CapacityLimits {
+    fn default() -> Self {
+        let mut per_role = HashMap::new();
+        per_role.insert(AgentRole::Scaffolder, 1);    // Only one scaffolder at a time
+        per_role.insert(AgentRole::Implementer, 3);   // Multiple implementers can work in parallel
+        per_role.insert(AgentRole::Debugger, 2);      // A couple debuggers can work simultaneously
+        per_role.insert(AgentRole::Tester, 2);        // Testers can work in parallel
+
+        Self {
+            global: None,
+            per_role,
+            per_pane: None,
+            pane_overrides: HashMap::new(),
+        }
+    }
+}
+
+/// Something that happened because a limit changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapacityEvent {
+    /// A limit was set or cleared
+    LimitChanged {
+        scope: CapacityScope,
+        old_limit: Option<usize>,
+        new_limit: Option<usize>,
+        load: usize,
+    },
+    /// The scope is over its new limit and is left to drain
+    Draining { scope: CapacityScope, load: usize, limit: usize },
+    /// A running task was stopped and put back on the queue
+    ///
+    /// Stopping the agent's process is up to the caller.
+    Preempted { scope: CapacityScope, agent_id: AgentId, task_id: TaskId },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_fall_back_to_defaults() {
+        let mut limits = CapacityLimits::default();
+        assert_eq!(limits.limit(&CapacityScope::Global), None);
+        assert_eq!(limits.limit(&CapacityScope::Role(AgentRole::Implementer)), Some(3));
+        assert_eq!(limits.limit(&CapacityScope::Pane(4)), None);
+
+        assert_eq!(limits.set_limit(&CapacityScope::Pane(4), Some(2)), None);
+        assert_eq!(limits.limit(&CapacityScope::Pane(4)), Some(2));
+        assert_eq!(limits.set_limit(&CapacityScope::Role(AgentRole::Tester), None), Some(2));
+        assert_eq!(limits.limit(&CapacityScope::Role(AgentRole::Tester)), Some(DEFAULT_ROLE_CAPACITY));
+        assert_eq!(limits.explicit_limit(&CapacityScope::Role(AgentRole::Tester)), None);
+    }
+
+    #[test]
+    fn test_limits_deserialize_partially() {
+        let limits: CapacityLimits = serde_json::from_str(r#"{"global":4,"per_role":{"Tester":5}}"#).unwrap();
+        assert_eq!(limits.global, Some(4));
+        assert_eq!(limits.limit(&CapacityScope::Role(AgentRole::Tester)), Some(5));
+        assert_eq!(limits.limit(&CapacityScope::Role(AgentRole::Scaffolder)), Some(DEFAULT_ROLE_CAPACITY));
+        assert_eq!(limits.limit(&CapacityScope::Pane(1)), None);
+
+        let limits: CapacityLimits = serde_json::from_str(r#"{"per_pane":2,"pane_overrides":{"3":1}}"#).unwrap();
+        assert_eq!(limits.limit(&CapacityScope::Pane(1)), Some(2));
+        assert_eq!(limits.limit(&CapacityScope::Pane(3)), Some(1));
+    }
+}
@@ -1,25 +1,36 @@
 //! Workshop capacity management and task coordination
 
 use crate::agents::*;
+use crate::capacity::*;
+use crate::estimation::*;
+use crate::persistence::WorkshopState;
 use crate::retry::*;
+use crate::runner::AgentRunner;
+use dfcoder_baml::{TrackedActivity, UsageSummary};
+use dfcoder_types::{duration_serde, instant_serde};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// Manages workshop capacity and task assignment
 #[derive(Debug)]
 pub struct WorkshopManager {
-    /// Maximum concurrent agents per role
-    max_concurrent: HashMap<AgentRole, usize>,
+    /// Global, per-role and per-pane concurrency limits
+    capacity: CapacityLimits,
     /// Currently active agents per role
     active_agents: HashMap<AgentRole, Vec<AgentId>>,
     /// All registered agents
     agents: HashMap<AgentId, Agent>,
     /// Task queue organized by priority
     task_queue: VecDeque<Task>,
-    /// Completed tasks for dependency checking
+    /// Tasks assigned to an agent and not yet finished
+    in_flight: HashMap<TaskId, Task>,
+    /// Completed tasks, in completion order
     completed_tasks: Vec<TaskId>,
+    /// Dependencies between every task the workshop has seen
+    task_graph: TaskGraph,
     /// Workshop metrics
     metrics: WorkshopMetrics,
     /// Retry executor for failed tasks
@@ -35,19 +46,18 @@ pub struct AgentExpertise {
     pub task_success_rates: HashMap<String, f32>,
     /// Average completion time by task complexity
     pub completion_times: HashMap<TaskComplexity, Duration>,
+    /// Completed tasks behind each average in `completion_times`
+    #[serde(default)]
+    pub completion_samples: HashMap<TaskComplexity, u32>,
     /// Total tasks completed
     pub total_tasks: u32,
     /// Specialization score (0.0 = generalist, 1.0 = highly specialized)
     pub specialization_score: f32,
     /// Last performance update
-    #[serde(skip, default = "instant_now")]
+    #[serde(with = "instant_serde")]
     pub last_updated: Instant,
 }
 
-fn instant_now() -> Instant {
-    Instant::now()
-}
-
 /// Task complexity levels for better assignment
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum TaskComplexity {
@@ -64,7 +74,7 @@ pub struct WorkshopMetrics {
     pub tasks_completed: u32,
     pub tasks_failed: u32,
     pub tasks_retried: u32,
-    #[serde(skip)]
+    #[serde(with = "duration_serde")]
     pub average_task_duration: Duration,
     pub agent_utilization: HashMap<AgentRole, f32>,
     pub queue_length: usize,
@@ -73,8 +83,11 @@ pub struct WorkshopMetrics {
     pub throughput: f32,
     /// Success rate percentage
     pub success_rate: f32,
-    /// Cost efficiency metrics
+    /// Model spend in US dollars per completed task
     pub cost_per_task: f32,
+    /// Tokens and cost of classification model calls
+    #[serde(default)]
+    pub model_usage: UsageSummary,
 }
 
 /// Errors that can occur in workshop management
@@ -92,23 +105,382 @@ pub enum WorkshopError {
     DependenciesNotSatisfied(Vec<TaskId>),
     #[error("Workshop at capacity for role {0}")]
     AtCapacity(AgentRole),
+    #[error("Task {0} is already assigned to agent {1}")]
+    TaskAlreadyAssigned(TaskId, AgentId),
+    #[error("Task {0} was already queued")]
+    DuplicateTask(TaskId),
+    #[error("Unknown task dependencies: {0:?}")]
+    UnknownDependencies(Vec<TaskId>),
+    #[error("Task dependencies form a cycle: {0:?}")]
+    DependencyCycle(Vec<TaskId>),
+}
+
+/// Estimate used for tasks without an `estimated_duration`
+pub const DEFAULT_TASK_ESTIMATE: Duration = Duration::from_secs(3600);
+
+/// How many pruned completed tasks a `TaskGraph` remembers by default
+pub const COMPLETED_TASK_MEMORY: usize = 4096;
+
+/// Dependency DAG over the workshop's tasks
+///
+/// Edges point from a task to the tasks it depends on. Tasks are only added
+/// once everything they depend on is known, so the graph stays acyclic.
+/// Finished tasks that nothing unfinished depends on are pruned as new tasks
+/// arrive; the most recently completed ones are still remembered by id, so
+/// later tasks can depend on them.
+#[derive(Debug, Clone)]
+pub struct TaskGraph {
+    nodes: HashMap<TaskId, TaskNode>,
+    /// Completed tasks that have been pruned
+    completed: HashSet<TaskId>,
+    /// `completed` oldest first, for forgetting the oldest once full
+    completed_order: VecDeque<TaskId>,
+    completed_capacity: usize,
+}
+
+impl Default for TaskGraph {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+            completed: HashSet::new(),
+            completed_order: VecDeque::new(),
+            completed_capacity: COMPLETED_TASK_MEMORY,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TaskNode {
+    dependencies: Vec<TaskId>,
+    dependents: Vec<TaskId>,
+    estimate: Duration,
+    status: TaskStatus,
+}
+
+/// Longest chain of unfinished work through the task graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CriticalPath {
+    /// Tasks in the order they have to run
+    pub tasks: Vec<TaskId>,
+    /// Sum of the tasks' estimates
+    #[serde(with = "duration_serde")]
+    pub duration: Duration,
+}
+
+impl TaskGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember at most `capacity` pruned completed tasks
+    ///
+    /// Tasks depending on one that has been forgotten are rejected as
+    /// depending on an unknown task.
+    pub fn with_completed_capacity(mut self, capacity: usize) -> Self {
+        self.completed_capacity = capacity;
+        self
+    }
+
+    /// Whether the task is in the graph or completed before being pruned
+    pub fn contains(&self, task_id: &TaskId) -> bool {
+        self.nodes.contains_key(task_id) || self.completed.contains(task_id)
+    }
+
+    /// Last known status of a task
+    pub fn status(&self, task_id: &TaskId) -> Option<&TaskStatus> {
+        match self.nodes.get(task_id) {
+            Some(node) => Some(&node.status),
+            None if self.completed.contains(task_id) => Some(&TaskStatus::Completed),
+            None => None,
+        }
+    }
+
+    /// Number of tasks with a node in the graph
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Tasks that depend directly on `task_id`
+    pub fn dependents(&self, task_id: &TaskId) -> &[TaskId] {
+        self.nodes.get(task_id).map(|node| node.dependents.as_slice()).unwrap_or_default()
+    }
+
+    /// Validate and add tasks, which may depend on each other and on known tasks
+    ///
+    /// Nothing is added unless the whole batch is valid.
+    pub fn add_tasks(&mut self, tasks: &[Task]) -> Result<(), WorkshopError> {
+        let mut batch: HashMap<&TaskId, &Task> = HashMap::new();
+        for task in tasks {
+            if self.contains(&task.id) || batch.insert(&task.id, task).is_some() {
+                return Err(WorkshopError::DuplicateTask(task.id.clone()));
+            }
+        }
+
+        let mut unknown = Vec::new();
+        let mut dead = Vec::new();
+        for dependency in tasks.iter().flat_map(|task| &task.context.dependencies) {
+            match self.nodes.get(dependency) {
+                Some(node) if matches!(node.status, TaskStatus::Cancelled | TaskStatus::Failed) => dead.push(dependency.clone()),
+                Some(_) => {}
+                None if batch.contains_key(dependency) || self.completed.contains(dependency) => {}
+                None => unknown.push(dependency.clone()),
+            }
+        }
+        if !unknown.is_empty() {
+            unknown.sort();
+            unknown.dedup();
+            return Err(WorkshopError::UnknownDependencies(unknown));
+        }
+        if !dead.is_empty() {
+            dead.sort();
+            dead.dedup();
+            return Err(WorkshopError::DependenciesNotSatisfied(dead));
+        }
+
+        // Known tasks cannot depend on new ones, so a cycle lies within the batch
+        if let Some(cycle) = find_cycle(&batch) {
+            return Err(WorkshopError::DependencyCycle(cycle));
+        }
+
+        self.insert(tasks.iter());
+        self.prune();
+        Ok(())
+    }
+
+    /// Drop finished tasks that no unfinished task depends on
+    fn prune(&mut self) {
+        let finished: Vec<TaskId> = self.nodes.iter()
+            .filter(|(_, node)| !is_unfinished(&node.status))
+            .filter(|(_, node)| {
+                !node.dependents.iter().any(|dependent| self.nodes.get(dependent).is_some_and(|dependent| is_unfinished(&dependent.status)))
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in finished {
+            let Some(node) = self.nodes.remove(&id) else {
+                continue;
+            };
+            for dependency in &node.dependencies {
+                if let Some(dependency) = self.nodes.get_mut(dependency) {
+                    dependency.dependents.retain(|dependent| *dependent != id);
+                }
+            }
+            if node.status == TaskStatus::Completed {
+                self.remember_completed(id);
+            }
+        }
+    }
+
+    fn remember_completed(&mut self, id: TaskId) {
+        if !self.completed.insert(id.clone()) {
+            return;
+        }
+        self.completed_order.push_back(id);
+        while self.completed_order.len() > self.completed_capacity {
+            if let Some(oldest) = self.completed_order.pop_front() {
+                self.completed.remove(&oldest);
+            }
+        }
+    }
+
+    /// Add tasks without validation, for state restored from disk
+    fn insert<'a>(&mut self, tasks: impl Iterator<Item = &'a Task> + Clone) {
+        for task in tasks.clone() {
+            self.nodes.entry(task.id.clone()).or_insert_with(|| TaskNode {
+                dependencies: task.context.dependencies.clone(),
+                dependents: Vec::new(),
+                estimate: task.context.estimated_duration.unwrap_or(DEFAULT_TASK_ESTIMATE),
+                status: task.status.clone(),
+            });
+        }
+        for task in tasks {
+            for dependency in &task.context.dependencies {
+                if let Some(node) = self.nodes.get_mut(dependency) {
+                    if !node.dependents.contains(&task.id) {
+                        node.dependents.push(task.id.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Record a task's new status, adding it first if it is unknown
+    pub fn set_status(&mut self, task: &Task, status: TaskStatus) {
+        if !self.nodes.contains_key(&task.id) {
+            self.insert(std::iter::once(task));
+        }
+        if let Some(node) = self.nodes.get_mut(&task.id) {
+            node.status = status;
+        }
+    }
+
+    /// Record that a task finished successfully
+    pub fn complete(&mut self, task_id: &TaskId) {
+        let node = self.nodes.entry(task_id.clone()).or_insert_with(|| TaskNode {
+            dependencies: Vec::new(),
+            dependents: Vec::new(),
+            estimate: Duration::ZERO,
+            status: TaskStatus::Completed,
+        });
+        node.status = TaskStatus::Completed;
+    }
+
+    /// Dependencies of `task` that have not completed yet
+    pub fn blocked_by(&self, task: &Task) -> Vec<TaskId> {
+        task.context.dependencies.iter()
+            .filter(|dependency| self.status(dependency) != Some(&TaskStatus::Completed))
+            .cloned()
+            .collect()
+    }
+
+    /// Mark a task failed or cancelled for good, cancelling every unfinished
+    /// task that depends on it, directly or not
+    ///
+    /// Returns the cancelled dependents.
+    pub fn cancel(&mut self, task_id: &TaskId, status: TaskStatus) -> Vec<TaskId> {
+        let Some(node) = self.nodes.get_mut(task_id) else {
+            return Vec::new();
+        };
+        node.status = status;
+
+        let mut cancelled = Vec::new();
+        let mut pending: VecDeque<TaskId> = node.dependents.iter().cloned().collect();
+        while let Some(dependent_id) = pending.pop_front() {
+            let Some(dependent) = self.nodes.get_mut(&dependent_id) else {
+                continue;
+            };
+            if matches!(dependent.status, TaskStatus::Completed | TaskStatus::Cancelled | TaskStatus::Failed) {
+                continue;
+            }
+            dependent.status = TaskStatus::Cancelled;
+            pending.extend(dependent.dependents.iter().cloned());
+            cancelled.push(dependent_id);
+        }
+        cancelled
+    }
+
+    /// The longest chain of unfinished tasks, weighted by their estimates
+    pub fn critical_path(&self) -> CriticalPath {
+        let mut finish: HashMap<&TaskId, (Duration, Option<&TaskId>)> = HashMap::new();
+        let mut ids: Vec<&TaskId> = self.nodes.iter()
+            .filter(|(_, node)| is_unfinished(&node.status))
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+
+        for id in &ids {
+            self.earliest_finish(id, &mut finish, &mut Vec::new());
+        }
+
+        let Some((end, (duration, _))) = ids.iter()
+            .filter_map(|id| finish.get(id).map(|entry| (*id, *entry)))
+            .fold(None::<(&TaskId, (Duration, Option<&TaskId>))>, |best, candidate| match best {
+                Some(best) if best.1 .0 >= candidate.1 .0 => Some(best),
+                _ => Some(candidate),
+            })
+        else {
+            return CriticalPath::default();
+        };
+
+        let mut tasks = vec![end.clone()];
+        let mut current = end;
+        while let Some((_, Some(previous))) = finish.get(current) {
+            tasks.push((*previous).clone());
+            current = previous;
+        }
+        tasks.reverse();
+
+        CriticalPath { tasks, duration }
+    }
+
+    /// Estimated finish of `id` measured from now, and the dependency that gates it
+    fn earliest_finish<'a>(
+        &'a self,
+        id: &'a TaskId,
+        finish: &mut HashMap<&'a TaskId, (Duration, Option<&'a TaskId>)>,
+        visiting: &mut Vec<&'a TaskId>,
+    ) -> Duration {
+        if let Some((duration, _)) = finish.get(id) {
+            return *duration;
+        }
+        let Some(node) = self.nodes.get(id).filter(|node| is_unfinished(&node.status)) else {
+            return Duration::ZERO;
+        };
+        // Restored state is not validated; never recurse around a cycle
+        if visiting.contains(&id) {
+            return Duration::ZERO;
+        }
+        visiting.push(id);
+
+        let mut start = Duration::ZERO;
+        let mut gate = None;
+        for dependency in &node.dependencies {
+            let dependency_finish = self.earliest_finish(dependency, finish, visiting);
+            if dependency_finish > start {
+                start = dependency_finish;
+                gate = Some(dependency);
+            }
+        }
+
+        visiting.pop();
+        let duration = start + node.estimate;
+        finish.insert(id, (duration, gate));
+        duration
+    }
+}
+
+fn is_unfinished(status: &TaskStatus) -> bool {
+    matches!(status, TaskStatus::Pending | TaskStatus::Assigned | TaskStatus::InProgress)
+}
+
+/// A dependency cycle among `tasks`, if there is one
+fn find_cycle(tasks: &HashMap<&TaskId, &Task>) -> Option<Vec<TaskId>> {
+    fn visit<'a>(
+        id: &'a TaskId,
+        tasks: &HashMap<&TaskId, &'a Task>,
+        done: &mut Vec<&'a TaskId>,
+        path: &mut Vec<&'a TaskId>,
+    ) -> Option<Vec<TaskId>> {
+        if let Some(start) = path.iter().position(|on_path| *on_path == id) {
+            return Some(path[start..].iter().map(|id| (*id).clone()).collect());
+        }
+        if done.contains(&id) {
+            return None;
+        }
+        let task = tasks.get(id)?;
+        path.push(id);
+        for dependency in &task.context.dependencies {
+            if let Some(cycle) = visit(dependency, tasks, done, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.push(id);
+        None
+    }
+
+    let mut ids: Vec<_> = tasks.keys().copied().collect();
+    ids.sort();
+    let mut done = Vec::new();
+    ids.into_iter().find_map(|id| visit(id, tasks, &mut done, &mut Vec::new()))
 }
 
 impl WorkshopManager {
     /// Create a new workshop manager with default capacity limits
     pub fn new() -> Self {
-        let mut max_concurrent = HashMap::new();
-        max_concurrent.insert(AgentRole::Scaffolder, 1);    // Only one scaffolder at a time
-        max_concurrent.insert(AgentRole::Implementer, 3);   // Multiple implementers can work in parallel
-        max_concurrent.insert(AgentRole::Debugger, 2);      // A couple debuggers can work simultaneously
-        max_concurrent.insert(AgentRole::Tester, 2);        // Testers can work in parallel
-
         Self {
-            max_concurrent,
+            capacity: CapacityLimits::default(),
             active_agents: HashMap::new(),
             agents: HashMap::new(),
             task_queue: VecDeque::new(),
+            in_flight: HashMap::new(),
             completed_tasks: Vec::new(),
+            task_graph: TaskGraph::new(),
             metrics: WorkshopMetrics::default(),
             retry_executor: RetryExecutor::new(RetryPolicy::default()),
             agent_expertise: HashMap::new(),
@@ -130,15 +502,66 @@ impl WorkshopManager {
         Ok(())
     }
 
-    /// Check if we can assign a task to an agent with the given role
+    /// Remove an agent that is not working on a task
+    pub fn remove_agent(&mut self, agent_id: &AgentId) -> Result<Agent, WorkshopError> {
+        let agent = self.agents.get(agent_id)
+            .ok_or_else(|| WorkshopError::AgentNotFound(agent_id.clone()))?;
+
+        if let Some(task_id) = &agent.current_task {
+            return Err(WorkshopError::AgentBusy(agent_id.clone(), task_id.clone()));
+        }
+
+        self.agent_expertise.remove(agent_id);
+        Ok(self.agents.remove(agent_id).unwrap())
+    }
+
+    /// Check if the workshop and the role have room for another task
     pub fn can_assign(&self, role: AgentRole) -> bool {
-        let active = self.active_agents.get(&role).map(|v| v.len()).unwrap_or(0);
-        let max = self.max_concurrent.get(&role).copied().unwrap_or(1);
-        active < max
+        self.has_room(&CapacityScope::Global) && self.has_room(&CapacityScope::Role(role))
+    }
+
+    /// Check if an agent is idle and every scope it works in has room for another task
+    pub fn can_assign_agent(&self, agent: &Agent) -> bool {
+        agent.status == AgentStatus::Idle
+            && CapacityLimits::scopes_for(agent).iter().all(|scope| self.has_room(scope))
+    }
+
+    /// Number of tasks running in a scope
+    pub fn load(&self, scope: &CapacityScope) -> usize {
+        self.agents.values()
+            .filter(|agent| agent.current_task.is_some())
+            .filter(|agent| match scope {
+                CapacityScope::Global => true,
+                CapacityScope::Role(role) => &agent.role == role,
+                CapacityScope::Pane(pane_id) => &agent.pane_id == pane_id,
+            })
+            .count()
+    }
+
+    fn has_room(&self, scope: &CapacityScope) -> bool {
+        self.capacity.limit(scope).is_none_or(|limit| self.load(scope) < limit)
     }
 
     /// Add a task to the queue
-    pub fn queue_task(&mut self, task: Task) {
+    ///
+    /// Fails if the task depends on a task the workshop has never seen, or on
+    /// one that failed or was cancelled.
+    pub fn queue_task(&mut self, task: Task) -> Result<(), WorkshopError> {
+        self.queue_tasks(vec![task])
+    }
+
+    /// Add tasks that may depend on each other to the queue
+    ///
+    /// Nothing is queued if any task is invalid or the batch contains a cycle.
+    pub fn queue_tasks(&mut self, tasks: Vec<Task>) -> Result<(), WorkshopError> {
+        self.task_graph.add_tasks(&tasks)?;
+        for task in tasks {
+            self.enqueue(task);
+        }
+        Ok(())
+    }
+
+    fn enqueue(&mut self, task: Task) {
         // Insert task in priority order
         let insert_pos = self.task_queue.iter().position(|t| {
             t.context.priority < task.context.priority
@@ -148,6 +571,46 @@ impl WorkshopManager {
         self.metrics.queue_length = self.task_queue.len();
     }
 
+    /// Cancel a task that is still waiting in the queue
+    ///
+    /// Returns the cancelled task followed by every queued task that depended
+    /// on it, which is cancelled as well.
+    pub fn cancel_task(&mut self, task_id: &TaskId) -> Result<Vec<Task>, WorkshopError> {
+        if let Some(agent) = self.agents.values().find(|a| a.current_task.as_ref() == Some(task_id)) {
+            return Err(WorkshopError::TaskAlreadyAssigned(task_id.clone(), agent.id.clone()));
+        }
+
+        let index = self.task_queue.iter().position(|t| &t.id == task_id)
+            .ok_or_else(|| WorkshopError::TaskNotFound(task_id.clone()))?;
+
+        let mut task = self.task_queue.remove(index).unwrap();
+        task.status = TaskStatus::Cancelled;
+
+        let mut cancelled = vec![task];
+        cancelled.extend(self.cancel_dependents(task_id, TaskStatus::Cancelled));
+        self.metrics.queue_length = self.task_queue.len();
+
+        Ok(cancelled)
+    }
+
+    /// Remove queued tasks that can no longer run because `task_id` failed or was cancelled
+    fn cancel_dependents(&mut self, task_id: &TaskId, status: TaskStatus) -> Vec<Task> {
+        let dependents = self.task_graph.cancel(task_id, status);
+        let mut cancelled = Vec::new();
+        self.task_queue.retain(|task| {
+            if dependents.contains(&task.id) {
+                let mut task = task.clone();
+                task.status = TaskStatus::Cancelled;
+                cancelled.push(task);
+                false
+            } else {
+                true
+            }
+        });
+        self.metrics.queue_length = self.task_queue.len();
+        cancelled
+    }
+
     /// Try to assign the next available task
     pub fn try_assign_next_task(&mut self) -> Result<Option<(AgentId, TaskId)>, WorkshopError> {
         // Find a task that can be assigned
@@ -169,7 +632,7 @@ impl WorkshopManager {
         
         // Find the best task-agent match
         for (task_index, task) in self.task_queue.iter().enumerate() {
-            if !task.dependencies_satisfied(&self.completed_tasks) {
+            if !self.task_graph.blocked_by(task).is_empty() {
                 continue;
             }
             
@@ -184,12 +647,17 @@ impl WorkshopManager {
         Ok(None)
     }
 
+    /// Use a different runner for agent processes
+    pub fn set_agent_runner(&mut self, runner: Arc<dyn AgentRunner>) {
+        self.retry_executor.set_runner(runner);
+    }
+
     /// Execute a task with retry logic
     pub async fn execute_task_with_retry(
         &mut self,
         agent_id: &AgentId,
         task: &Task,
-    ) -> Result<TaskResult, WorkshopError> {
+    ) -> Result<TaskAttempt, WorkshopError> {
         let agent = self.agents.get_mut(agent_id)
             .ok_or_else(|| WorkshopError::AgentNotFound(agent_id.clone()))?;
         
@@ -207,7 +675,7 @@ impl WorkshopManager {
     fn find_assignable_task_index(&self) -> Result<Option<usize>, WorkshopError> {
         for (index, task) in self.task_queue.iter().enumerate() {
             // Check if dependencies are satisfied
-            if !task.dependencies_satisfied(&self.completed_tasks) {
+            if !self.task_graph.blocked_by(task).is_empty() {
                 continue;
             }
             
@@ -227,6 +695,10 @@ impl WorkshopManager {
 
     /// Assign a task to an available agent
     pub fn assign_task(&mut self, mut task: Task) -> Result<AgentId, WorkshopError> {
+        if !self.can_assign(task.required_role.clone()) {
+            return Err(WorkshopError::AtCapacity(task.required_role.clone()));
+        }
+
         // Find available agent
         let agent_id = self.find_available_agent(task.required_role.clone())
             .ok_or_else(|| WorkshopError::NoAvailableAgents(task.required_role.clone()))?;
@@ -243,21 +715,23 @@ impl WorkshopManager {
         // Update task
         task.assign_to(agent_id.clone());
         task.start();
+        self.task_graph.set_status(&task, TaskStatus::InProgress);
 
         // Track active agent
         self.active_agents.entry(task.required_role.clone())
             .or_insert_with(Vec::new)
             .push(agent_id.clone());
+        self.in_flight.insert(task.id.clone(), task);
 
         self.metrics.queue_length = self.task_queue.len();
         
         Ok(agent_id)
     }
 
-    /// Find an available agent for the given role
+    /// Find an available agent for the given role whose pane has room
     fn find_available_agent(&self, role: AgentRole) -> Option<AgentId> {
         self.agents.values()
-            .find(|agent| agent.role == role && agent.status == AgentStatus::Idle)
+            .find(|agent| agent.role == role && self.can_assign_agent(agent))
             .map(|agent| agent.id.clone())
     }
 
@@ -280,6 +754,14 @@ impl WorkshopManager {
         }
 
         // Track completion
+        if let Some(task) = self.in_flight.remove(&task_id) {
+            if let Some(started) = task.assigned_at {
+                let expertise = self.agent_expertise.entry(agent_id.clone()).or_default();
+                expertise.record_completion(task_complexity(&task), started.elapsed());
+                expertise.total_tasks += 1;
+            }
+        }
+        self.task_graph.complete(&task_id);
         self.completed_tasks.push(task_id);
         self.metrics.tasks_completed += 1;
         self.metrics.total_tasks_processed += 1;
@@ -290,8 +772,11 @@ impl WorkshopManager {
         Ok(())
     }
 
-    /// Mark a task as failed
-    pub fn fail_task(&mut self, agent_id: AgentId, task_id: TaskId, error: String) -> Result<(), WorkshopError> {
+    /// Mark a task as failed for good
+    ///
+    /// Queued tasks that depend on it can never run and are cancelled; they
+    /// are returned.
+    pub fn fail_task(&mut self, agent_id: AgentId, task_id: TaskId, error: String) -> Result<Vec<Task>, WorkshopError> {
         // Get agent and fail task
         let agent = self.agents.get_mut(&agent_id)
             .ok_or_else(|| WorkshopError::AgentNotFound(agent_id.clone()))?;
@@ -309,17 +794,24 @@ impl WorkshopManager {
         }
 
         // Track failure
+        self.in_flight.remove(&task_id);
+        let cancelled = self.cancel_dependents(&task_id, TaskStatus::Failed);
         self.metrics.tasks_failed += 1;
         self.metrics.total_tasks_processed += 1;
 
         // Update metrics
         self.update_metrics();
 
-        Ok(())
+        Ok(cancelled)
     }
 
     /// Get current workshop status
     pub fn get_status(&mut self) -> WorkshopStatus {
+        self.status_with_activities(&HashMap::new())
+    }
+
+    /// Get current workshop status, estimating running tasks from what each agent is doing
+    pub fn status_with_activities(&mut self, activities: &HashMap<AgentId, TrackedActivity>) -> WorkshopStatus {
         // Update metrics before returning status
         self.update_metrics();
         
@@ -327,18 +819,67 @@ impl WorkshopManager {
             total_agents: self.agents.len(),
             active_agents: self.active_agents.values().map(|v| v.len()).sum(),
             queue_length: self.task_queue.len(),
-            capacity_per_role: self.max_concurrent.clone(),
+            capacity_per_role: self.capacity.per_role.clone(),
+            global_capacity: self.capacity.global,
             active_per_role: self.active_agents.iter()
                 .map(|(role, agents)| (role.clone(), agents.len()))
                 .collect(),
             metrics: self.metrics.clone(),
+            blocked_by: self.task_queue.iter()
+                .map(|task| (task.id.clone(), self.task_graph.blocked_by(task)))
+                .filter(|(_, blockers)| !blockers.is_empty())
+                .collect(),
+            critical_path: self.task_graph.critical_path(),
+            completion_estimates: self.completion_estimates(activities),
         }
     }
 
+    /// Predicted time left on the task an agent is working on
+    pub fn estimate_completion(&self, agent_id: &AgentId, activity: Option<&TrackedActivity>) -> Option<CompletionEstimate> {
+        let task_id = self.agents.get(agent_id)?.current_task.as_ref()?;
+        let task = self.in_flight.get(task_id)?;
+        let elapsed = task.assigned_at.map(|started| started.elapsed()).unwrap_or_default();
+        Some(estimate_completion(task, self.agent_expertise.get(agent_id), activity, elapsed))
+    }
+
+    /// Predicted time left on every running task, given each agent's current activity
+    pub fn completion_estimates(&self, activities: &HashMap<AgentId, TrackedActivity>) -> HashMap<TaskId, CompletionEstimate> {
+        self.agents.values()
+            .filter_map(|agent| {
+                let estimate = self.estimate_completion(&agent.id, activities.get(&agent.id))?;
+                Some((agent.current_task.clone()?, estimate))
+            })
+            .collect()
+    }
+
+    /// Dependencies between the workshop's tasks
+    pub fn task_graph(&self) -> &TaskGraph {
+        &self.task_graph
+    }
+
+    /// The longest chain of unfinished tasks, weighted by their estimates
+    pub fn critical_path(&self) -> CriticalPath {
+        self.task_graph.critical_path()
+    }
+
+    /// Take the latest model usage totals into the metrics
+    pub fn record_model_usage(&mut self, usage: UsageSummary) {
+        self.metrics.model_usage = usage;
+        self.update_cost_per_task();
+    }
+
+    fn update_cost_per_task(&mut self) {
+        self.metrics.cost_per_task = if self.metrics.tasks_completed > 0 {
+            (self.metrics.model_usage.total.cost_usd / self.metrics.tasks_completed as f64) as f32
+        } else {
+            0.0
+        };
+    }
+
     /// Update workshop metrics
     fn update_metrics(&mut self) {
         // Calculate agent utilization per role
-        for (role, max_capacity) in &self.max_concurrent {
+        for (role, max_capacity) in &self.capacity.per_role {
             let active_count = self.active_agents.get(role).map(|v| v.len()).unwrap_or(0);
             let utilization = if *max_capacity > 0 {
                 active_count as f32 / *max_capacity as f32
@@ -354,6 +895,7 @@ impl WorkshopManager {
             .map(|(role, _)| role.clone());
 
         self.metrics.queue_length = self.task_queue.len();
+        self.update_cost_per_task();
     }
 
     /// Get agent by ID
@@ -361,6 +903,11 @@ impl WorkshopManager {
         self.agents.get(agent_id)
     }
 
+    /// Get the agent working in a pane
+    pub fn get_agent_by_pane(&self, pane_id: u32) -> Option<&Agent> {
+        self.agents.values().find(|agent| agent.pane_id == pane_id)
+    }
+
     /// Get mutable agent by ID
     pub fn get_agent_mut(&mut self, agent_id: &AgentId) -> Option<&mut Agent> {
         self.agents.get_mut(agent_id)
@@ -376,6 +923,75 @@ impl WorkshopManager {
         &self.task_queue
     }
 
+    /// Get a task that is assigned to an agent and not yet finished
+    pub fn get_in_flight_task(&self, task_id: &TaskId) -> Option<&Task> {
+        self.in_flight.get(task_id)
+    }
+
+    /// Get all tasks assigned to agents and not yet finished
+    pub fn get_in_flight_tasks(&self) -> Vec<&Task> {
+        self.in_flight.values().collect()
+    }
+
+    /// Capture everything needed to rebuild this workshop
+    pub fn export_state(&self) -> WorkshopState {
+        WorkshopState {
+            capacity: self.capacity.clone(),
+            agents: self.agents.iter().map(|(id, agent)| (id.clone(), agent.clone())).collect(),
+            queue: self.task_queue.iter().cloned().collect(),
+            in_flight: self.in_flight.iter().map(|(id, task)| (id.clone(), task.clone())).collect(),
+            completed_tasks: self.completed_tasks.clone(),
+            expertise: self.agent_expertise.iter().map(|(id, expertise)| (id.clone(), expertise.clone())).collect(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Rebuild a workshop from persisted state
+    ///
+    /// Assignments are kept for agents still working on their task; an
+    /// in-flight task whose agent no longer holds it goes back on the queue.
+    pub fn from_state(state: WorkshopState) -> Self {
+        let mut workshop = Self::new();
+        workshop.capacity = state.capacity;
+        workshop.completed_tasks = state.completed_tasks;
+        workshop.agent_expertise = state.expertise.into_iter().collect();
+        workshop.metrics = state.metrics;
+
+        for agent in state.agents.into_values() {
+            let active = workshop.active_agents.entry(agent.role.clone()).or_default();
+            if agent.current_task.is_some() {
+                active.push(agent.id.clone());
+            }
+            workshop.agents.insert(agent.id.clone(), agent);
+        }
+
+        workshop.task_graph.insert(state.queue.iter().chain(state.in_flight.values()));
+        for task_id in &workshop.completed_tasks {
+            workshop.task_graph.complete(task_id);
+        }
+
+        for task in state.queue {
+            workshop.enqueue(task);
+        }
+        for (task_id, mut task) in state.in_flight {
+            let held = task.assignee.as_ref()
+                .and_then(|agent_id| workshop.agents.get(agent_id))
+                .is_some_and(|agent| agent.current_task.as_ref() == Some(&task_id));
+            if held {
+                workshop.in_flight.insert(task_id, task);
+            } else {
+                task.status = TaskStatus::Pending;
+                task.assignee = None;
+                task.assigned_at = None;
+                workshop.task_graph.set_status(&task, TaskStatus::Pending);
+                workshop.enqueue(task);
+            }
+        }
+
+        workshop.metrics.queue_length = workshop.task_queue.len();
+        workshop
+    }
+
     /// Check for stuck agents and request supervision
     pub fn check_for_stuck_agents(&mut self, stuck_threshold: Duration) -> Vec<AgentId> {
         let mut stuck_agents = Vec::new();
@@ -390,9 +1006,82 @@ impl WorkshopManager {
         stuck_agents
     }
 
-    /// Set capacity for a role
-    pub fn set_capacity(&mut self, role: AgentRole, capacity: usize) {
-        self.max_concurrent.insert(role, capacity);
+    /// Set capacity for a role, letting running work drain if it is over the new limit
+    pub fn set_capacity(&mut self, role: AgentRole, capacity: usize) -> Vec<CapacityEvent> {
+        self.set_limit(CapacityScope::Role(role), Some(capacity), ShrinkPolicy::Drain)
+    }
+
+    /// Current concurrency limits
+    pub fn capacity_limits(&self) -> &CapacityLimits {
+        &self.capacity
+    }
+
+    /// Set or clear the limit for a scope
+    ///
+    /// If the scope is now over its limit, `policy` decides what happens to
+    /// the running tasks: with `Drain` they finish and nothing new starts
+    /// until the load is under the limit; with `Preempt` the most recently
+    /// started ones are put back on the queue straight away.
+    pub fn set_limit(&mut self, scope: CapacityScope, limit: Option<usize>, policy: ShrinkPolicy) -> Vec<CapacityEvent> {
+        let old_limit = self.capacity.set_limit(&scope, limit);
+        let load = self.load(&scope);
+        let mut events = vec![CapacityEvent::LimitChanged { scope: scope.clone(), old_limit, new_limit: limit, load }];
+
+        let Some(effective) = self.capacity.limit(&scope) else {
+            return events;
+        };
+        if load <= effective {
+            return events;
+        }
+
+        match policy {
+            ShrinkPolicy::Drain => events.push(CapacityEvent::Draining { scope, load, limit: effective }),
+            ShrinkPolicy::Preempt => {
+                let mut running: Vec<(AgentId, TaskId, Option<Instant>)> = self.agents.values()
+                    .filter(|agent| match &scope {
+                        CapacityScope::Global => true,
+                        CapacityScope::Role(role) => &agent.role == role,
+                        CapacityScope::Pane(pane_id) => &agent.pane_id == pane_id,
+                    })
+                    .filter_map(|agent| {
+                        let task_id = agent.current_task.clone()?;
+                        let started = self.in_flight.get(&task_id).and_then(|task| task.assigned_at);
+                        Some((agent.id.clone(), task_id, started))
+                    })
+                    .collect();
+                // Newest first; tasks with no recorded start are treated as oldest
+                running.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+
+                for (agent_id, task_id, _) in running.into_iter().take(load - effective) {
+                    self.preempt(&agent_id);
+                    events.push(CapacityEvent::Preempted { scope: scope.clone(), agent_id, task_id });
+                }
+                self.update_metrics();
+            }
+        }
+
+        events
+    }
+
+    /// Take an agent's task away and put it back on the queue
+    fn preempt(&mut self, agent_id: &AgentId) -> Option<TaskId> {
+        let agent = self.agents.get_mut(agent_id)?;
+        let task_id = agent.current_task.take()?;
+        agent.status = AgentStatus::Idle;
+        agent.mark_activity();
+
+        if let Some(active) = self.active_agents.get_mut(&agent.role) {
+            active.retain(|id| id != agent_id);
+        }
+
+        if let Some(mut task) = self.in_flight.remove(&task_id) {
+            task.status = TaskStatus::Pending;
+            task.assignee = None;
+            task.assigned_at = None;
+            self.task_graph.set_status(&task, TaskStatus::Pending);
+            self.enqueue(task);
+        }
+        Some(task_id)
     }
 
     /// Prioritize task queue by priority and complexity
@@ -404,8 +1093,8 @@ impl WorkshopManager {
             match b.context.priority.cmp(&a.context.priority) {
                 std::cmp::Ordering::Equal => {
                     // Then by complexity (simpler tasks first for quick wins)
-                    let a_complexity = self.estimate_task_complexity(a);
-                    let b_complexity = self.estimate_task_complexity(b);
+                    let a_complexity = task_complexity(a);
+                    let b_complexity = task_complexity(b);
                     a_complexity.cmp(&b_complexity)
                 }
                 other => other,
@@ -418,34 +1107,12 @@ impl WorkshopManager {
         }
     }
 
-    /// Estimate task complexity based on description and requirements
-    fn estimate_task_complexity(&self, task: &Task) -> TaskComplexity {
-        let description = task.description.to_lowercase();
-        let title = task.title.to_lowercase();
-        
-        // Simple heuristics for complexity estimation
-        if description.contains("fix") || description.contains("bug") || title.len() < 20 {
-            TaskComplexity::Simple
-        } else if description.contains("implement") || description.contains("feature") {
-            if description.contains("complex") || description.contains("integration") {
-                TaskComplexity::Complex
-            } else {
-                TaskComplexity::Medium
-            }
-        } else if description.contains("architecture") || description.contains("design") {
-            TaskComplexity::Expert
-        } else {
-            TaskComplexity::Medium
-        }
-    }
-
     /// Find the best agent for a given task based on expertise and availability
     fn find_best_agent_for_task(&self, task: &Task) -> Option<AgentId> {
         let available_agents: Vec<_> = self.agents.values()
             .filter(|agent| {
-                agent.role == task.required_role 
-                && agent.status == AgentStatus::Idle
-                && self.can_assign(agent.role.clone())
+                agent.role == task.required_role
+                && self.can_assign_agent(agent)
             })
             .collect();
 
@@ -482,7 +1149,7 @@ impl WorkshopManager {
             }
             
             // Factor in completion time efficiency
-            let complexity = self.estimate_task_complexity(task);
+            let complexity = task_complexity(task);
             if let Some(avg_time) = exp.completion_times.get(&complexity) {
                 // Lower time = higher score
                 let efficiency = 1.0 / (avg_time.as_secs_f32() / 3600.0 + 1.0);
@@ -517,9 +1184,9 @@ impl WorkshopManager {
     }
 
     /// Update agent expertise based on task completion
-    fn update_agent_expertise(&mut self, agent_id: &AgentId, task: &Task, result: &TaskResult) {
+    fn update_agent_expertise(&mut self, agent_id: &AgentId, task: &Task, result: &TaskAttempt) {
         let task_type = self.categorize_task(task);
-        let complexity = self.estimate_task_complexity(task);
+        let complexity = task_complexity(task);
         
         // Get or create expertise entry
         let expertise = self.agent_expertise.entry(agent_id.clone())
@@ -536,12 +1203,7 @@ impl WorkshopManager {
         
         // Update completion time
         if result.success {
-            let current_time = expertise.completion_times.get(&complexity)
-                .copied().unwrap_or(Duration::from_secs(3600));
-            let new_time = Duration::from_secs(
-                ((current_time.as_secs() as f32 * 0.8) + (result.duration.as_secs() as f32 * 0.2)) as u64
-            );
-            expertise.completion_times.insert(complexity, new_time);
+            expertise.record_completion(complexity, result.duration);
         }
         
         // Update total tasks and last updated
@@ -586,8 +1248,17 @@ pub struct WorkshopStatus {
     pub active_agents: usize,
     pub queue_length: usize,
     pub capacity_per_role: HashMap<AgentRole, usize>,
+    /// Cap on tasks running across the workshop, if any
+    pub global_capacity: Option<usize>,
     pub active_per_role: HashMap<AgentRole, usize>,
     pub metrics: WorkshopMetrics,
+    /// Queued tasks mapped to the unfinished tasks they wait on
+    pub blocked_by: HashMap<TaskId, Vec<TaskId>>,
+    /// Longest chain of unfinished work
+    pub critical_path: CriticalPath,
+    /// Predicted time left on each running task
+    #[serde(default)]
+    pub completion_estimates: HashMap<TaskId, CompletionEstimate>,
 }
 
 impl Default for WorkshopMetrics {
@@ -604,15 +1275,33 @@ impl Default for WorkshopMetrics {
             throughput: 0.0,
             success_rate: 0.0,
             cost_per_task: 0.0,
+            model_usage: UsageSummary::default(),
         }
     }
 }
 
+impl AgentExpertise {
+    /// Fold a completed task's duration into the average for its complexity
+    ///
+    /// The first task sets the average; later ones move it a fifth of the way,
+    /// favouring recent performance.
+    pub fn record_completion(&mut self, complexity: TaskComplexity, duration: Duration) {
+        let samples = self.completion_samples.entry(complexity.clone()).or_insert(0);
+        let average = match self.completion_times.get(&complexity) {
+            Some(average) if *samples > 0 => average.mul_f32(0.8) + duration.mul_f32(0.2),
+            _ => duration,
+        };
+        *samples += 1;
+        self.completion_times.insert(complexity, average);
+    }
+}
+
 impl Default for AgentExpertise {
     fn default() -> Self {
         Self {
             task_success_rates: HashMap::new(),
             completion_times: HashMap::new(),
+            completion_samples: HashMap::new(),
             total_tasks: 0,
             specialization_score: 0.0,
             last_updated: Instant::now(),
@@ -657,7 +1346,7 @@ mod tests {
             TaskPriority::Normal,
         );
 
-        workshop.queue_task(task);
+        workshop.queue_task(task).unwrap();
         assert_eq!(workshop.get_queue().len(), 1);
     }
 
@@ -684,6 +1373,69 @@ mod tests {
         assert_eq!(agent.status, AgentStatus::Working);
     }
 
+    #[test]
+    fn test_model_usage_sets_cost_per_task() {
+        let mut workshop = WorkshopManager::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        workshop.register_agent(agent).unwrap();
+        let task = Task::new("Task".to_string(), "Desc".to_string(), AgentRole::Implementer, TaskPriority::Normal);
+        let task_id = task.id.clone();
+        workshop.assign_task(task).unwrap();
+
+        let ledger = dfcoder_baml::UsageLedger::default();
+        let scope = dfcoder_baml::UsageScope::new(Some(agent_id.clone()), Some(task_id.clone()));
+        ledger.record("gpt-4o-mini", &scope, dfcoder_baml::TokenUsage { input_tokens: 2_000_000, output_tokens: 0 });
+        workshop.record_model_usage(ledger.summary());
+        // Nothing completed yet
+        assert_eq!(workshop.get_status().metrics.cost_per_task, 0.0);
+
+        workshop.complete_task(agent_id.clone(), task_id.clone()).unwrap();
+        let metrics = workshop.get_status().metrics;
+        assert!((metrics.cost_per_task - 0.30).abs() < 1e-6);
+        assert_eq!(metrics.model_usage.by_agent[&agent_id].calls, 1);
+        assert_eq!(metrics.model_usage.by_task[&task_id].input_tokens, 2_000_000);
+    }
+
+    #[test]
+    fn test_remove_agent() {
+        let mut workshop = WorkshopManager::new();
+        let idle = Agent::new(AgentRole::Implementer, 1);
+        let idle_id = idle.id.clone();
+        workshop.register_agent(idle).unwrap();
+
+        let busy = Agent::new(AgentRole::Tester, 2);
+        let busy_id = busy.id.clone();
+        workshop.register_agent(busy).unwrap();
+        workshop.assign_task(Task::new("Tests".to_string(), "Desc".to_string(), AgentRole::Tester, TaskPriority::Normal)).unwrap();
+
+        assert_eq!(workshop.remove_agent(&idle_id).unwrap().id, idle_id);
+        assert!(workshop.get_agent(&idle_id).is_none());
+        assert!(matches!(workshop.remove_agent(&busy_id), Err(WorkshopError::AgentBusy(_, _))));
+        assert!(matches!(workshop.remove_agent(&idle_id), Err(WorkshopError::AgentNotFound(_))));
+    }
+
+    #[test]
+    fn test_cancel_task() {
+        let mut workshop = WorkshopManager::new();
+        let queued = Task::new("Queued".to_string(), "Desc".to_string(), AgentRole::Implementer, TaskPriority::Normal);
+        let queued_id = queued.id.clone();
+        workshop.queue_task(queued).unwrap();
+
+        let agent = Agent::new(AgentRole::Tester, 1);
+        workshop.register_agent(agent).unwrap();
+        let running = Task::new("Running".to_string(), "Desc".to_string(), AgentRole::Tester, TaskPriority::Normal);
+        let running_id = running.id.clone();
+        workshop.assign_task(running).unwrap();
+
+        let cancelled = workshop.cancel_task(&queued_id).unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].status, TaskStatus::Cancelled);
+        assert!(workshop.get_queue().is_empty());
+        assert!(matches!(workshop.cancel_task(&queued_id), Err(WorkshopError::TaskNotFound(_))));
+        assert!(matches!(workshop.cancel_task(&running_id), Err(WorkshopError::TaskAlreadyAssigned(_, _))));
+    }
+
     #[test]
     fn test_capacity_limits() {
         let mut workshop = WorkshopManager::new();
@@ -704,4 +1456,264 @@ mod tests {
         // Second assignment should fail due to capacity
         assert!(!workshop.can_assign(AgentRole::Scaffolder));
     }
-}
\ No newline at end of file
+
+    fn implement(title: &str) -> Task {
+        Task::new(title.to_string(), "Desc".to_string(), AgentRole::Implementer, TaskPriority::Normal)
+    }
+
+    #[test]
+    fn test_panes_are_unlimited_by_default() {
+        let mut workshop = WorkshopManager::new();
+        for _ in 0..2 {
+            workshop.register_agent(Agent::new(AgentRole::Implementer, 1)).unwrap();
+        }
+        workshop.assign_task(implement("A")).unwrap();
+        workshop.assign_task(implement("B")).unwrap();
+        assert_eq!(workshop.load(&CapacityScope::Pane(1)), 2);
+    }
+
+    #[test]
+    fn test_global_and_pane_limits() {
+        let mut workshop = WorkshopManager::new();
+        workshop.capacity.per_pane = Some(1);
+        // Two implementers share pane 1, a third has pane 2 to itself
+        for pane_id in [1, 1, 2] {
+            workshop.register_agent(Agent::new(AgentRole::Implementer, pane_id)).unwrap();
+        }
+        workshop.queue_tasks(vec![implement("A"), implement("B"), implement("C")]).unwrap();
+
+        let task = workshop.task_queue.pop_front().unwrap();
+        workshop.assign_task(task).unwrap();
+        assert_eq!(workshop.load(&CapacityScope::Global), 1);
+
+        // Pane 1 or 2 is now full, the other still has room
+        assert!(workshop.find_assignable_task_index().unwrap().is_some());
+        let task = workshop.task_queue.pop_front().unwrap();
+        workshop.assign_task(task).unwrap();
+        assert_eq!(workshop.load(&CapacityScope::Pane(1)), 1);
+        assert_eq!(workshop.load(&CapacityScope::Pane(2)), 1);
+
+        // The role has room but both panes are busy
+        assert!(workshop.can_assign(AgentRole::Implementer));
+        assert_eq!(workshop.find_assignable_task_index().unwrap(), None);
+        assert!(matches!(
+            workshop.assign_task(implement("D")),
+            Err(WorkshopError::NoAvailableAgents(AgentRole::Implementer))
+        ));
+
+        workshop.set_limit(CapacityScope::Pane(1), Some(2), ShrinkPolicy::Drain);
+        assert_eq!(workshop.find_assignable_task_index().unwrap(), Some(0));
+
+        // A workshop cap applies across roles
+        workshop.set_limit(CapacityScope::Global, Some(2), ShrinkPolicy::Drain);
+        assert!(!workshop.can_assign(AgentRole::Implementer));
+        assert!(!workshop.can_assign(AgentRole::Tester));
+        assert_eq!(workshop.find_assignable_task_index().unwrap(), None);
+        assert!(matches!(
+            workshop.assign_task(implement("E")),
+            Err(WorkshopError::AtCapacity(AgentRole::Implementer))
+        ));
+        assert_eq!(workshop.get_status().global_capacity, Some(2));
+    }
+
+    #[test]
+    fn test_shrinking_capacity_drains_or_preempts() {
+        let mut workshop = WorkshopManager::new();
+        for pane_id in 1..=3 {
+            workshop.register_agent(Agent::new(AgentRole::Implementer, pane_id)).unwrap();
+        }
+        workshop.queue_tasks(vec![implement("A"), implement("B"), implement("C"), implement("D")]).unwrap();
+        let mut started = Vec::new();
+        while let Some((_, task)) = workshop.assign_by_priority().unwrap() {
+            started.push(task.id);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(started.len(), 3);
+        let role = CapacityScope::Role(AgentRole::Implementer);
+
+        // Draining leaves running work alone and blocks new work
+        let events = workshop.set_limit(role.clone(), Some(2), ShrinkPolicy::Drain);
+        assert_eq!(events, vec![
+            CapacityEvent::LimitChanged { scope: role.clone(), old_limit: Some(3), new_limit: Some(2), load: 3 },
+            CapacityEvent::Draining { scope: role.clone(), load: 3, limit: 2 },
+        ]);
+        assert_eq!(workshop.load(&role), 3);
+        assert!(!workshop.can_assign(AgentRole::Implementer));
+
+        // Pre-empting requeues the newest tasks until the scope fits
+        let events = workshop.set_limit(role.clone(), Some(1), ShrinkPolicy::Preempt);
+        let preempted: Vec<_> = events.iter()
+            .filter_map(|event| match event {
+                CapacityEvent::Preempted { task_id, .. } => Some(task_id.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(preempted, vec![started[2].clone(), started[1].clone()]);
+        assert_eq!(workshop.load(&role), 1);
+        assert!(workshop.get_in_flight_task(&started[0]).is_some());
+        for task_id in &preempted {
+            let task = workshop.get_queue().iter().find(|task| &task.id == task_id).unwrap();
+            assert_eq!(task.status, TaskStatus::Pending);
+            assert!(task.assignee.is_none());
+        }
+        assert_eq!(workshop.get_queue().len(), 3);
+        assert_eq!(workshop.find_assignable_task_index().unwrap(), None);
+
+        // Growing the limit again lets the requeued work restart
+        let events = workshop.set_capacity(AgentRole::Implementer, 3);
+        assert_eq!(events.len(), 1);
+        assert!(workshop.assign_by_priority().unwrap().is_some());
+        assert!(workshop.assign_by_priority().unwrap().is_some());
+        assert_eq!(workshop.load(&role), 3);
+    }
+
+    fn task_after(title: &str, role: AgentRole, hours: u64, dependencies: &[&Task]) -> Task {
+        let mut task = Task::new(title.to_string(), "Desc".to_string(), role, TaskPriority::Normal);
+        task.context.estimated_duration = Some(Duration::from_secs(hours * 3600));
+        task.context.dependencies = dependencies.iter().map(|dependency| dependency.id.clone()).collect();
+        task
+    }
+
+    #[test]
+    fn test_queue_rejects_unknown_dependencies_and_cycles() {
+        let mut workshop = WorkshopManager::new();
+        let setup = task_after("Setup", AgentRole::Scaffolder, 1, &[]);
+        let mut orphan = task_after("Orphan", AgentRole::Implementer, 1, &[]);
+        orphan.context.dependencies = vec!["missing".to_string()];
+        assert!(matches!(
+            workshop.queue_task(orphan),
+            Err(WorkshopError::UnknownDependencies(ids)) if ids == vec!["missing".to_string()]
+        ));
+        let mut orphan = task_after("Orphan", AgentRole::Implementer, 1, &[]);
+        orphan.context.dependencies = ["b", "a", "b"].iter().map(|id| id.to_string()).collect();
+        assert!(matches!(
+            workshop.queue_task(orphan),
+            Err(WorkshopError::UnknownDependencies(ids)) if ids == vec!["a".to_string(), "b".to_string()]
+        ));
+
+        let mut first = task_after("First", AgentRole::Implementer, 1, &[&setup]);
+        let second = task_after("Second", AgentRole::Implementer, 1, &[&first]);
+        first.context.dependencies.push(second.id.clone());
+        assert!(matches!(
+            workshop.queue_tasks(vec![setup.clone(), first, second]),
+            Err(WorkshopError::DependencyCycle(cycle)) if cycle.len() == 2
+        ));
+        assert!(workshop.get_queue().is_empty());
+
+        // Tasks in one batch may depend on each other in any order
+        let implement = task_after("Implement", AgentRole::Implementer, 1, &[&setup]);
+        workshop.queue_tasks(vec![implement, setup.clone()]).unwrap();
+        assert_eq!(workshop.get_queue().len(), 2);
+        assert!(matches!(workshop.queue_task(setup), Err(WorkshopError::DuplicateTask(_))));
+    }
+
+    #[test]
+    fn test_failure_cascades_to_dependents() {
+        let mut workshop = WorkshopManager::new();
+        workshop.register_agent(Agent::new(AgentRole::Scaffolder, 1)).unwrap();
+
+        let setup = task_after("Setup", AgentRole::Scaffolder, 1, &[]);
+        let implement = task_after("Implement", AgentRole::Implementer, 2, &[&setup]);
+        let test = task_after("Test", AgentRole::Tester, 1, &[&implement]);
+        let docs = task_after("Docs", AgentRole::Implementer, 1, &[]);
+        let (setup_id, test_id) = (setup.id.clone(), test.id.clone());
+        workshop.queue_tasks(vec![setup, implement, test, docs]).unwrap();
+
+        let status = workshop.get_status();
+        assert_eq!(status.blocked_by.len(), 2);
+        assert_eq!(status.blocked_by[&test_id].len(), 1);
+
+        let (agent_id, task) = workshop.assign_by_priority().unwrap().unwrap();
+        assert_eq!(task.id, setup_id);
+
+        let cancelled = workshop.fail_task(agent_id, setup_id.clone(), "broken toolchain".to_string()).unwrap();
+        let mut titles: Vec<_> = cancelled.iter().map(|task| task.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Implement", "Test"]);
+        assert!(cancelled.iter().all(|task| task.status == TaskStatus::Cancelled));
+        assert_eq!(workshop.get_queue().len(), 1);
+        assert_eq!(workshop.task_graph().status(&test_id), Some(&TaskStatus::Cancelled));
+
+        // Nothing new may depend on a task that failed for good
+        let retry = task_after("Retry", AgentRole::Tester, 1, &[&task]);
+        assert!(matches!(workshop.queue_task(retry), Err(WorkshopError::DependenciesNotSatisfied(_))));
+    }
+
+    #[test]
+    fn test_critical_path_follows_longest_chain() {
+        let mut workshop = WorkshopManager::new();
+        let setup = task_after("Setup", AgentRole::Scaffolder, 1, &[]);
+        let api = task_after("API", AgentRole::Implementer, 4, &[&setup]);
+        let ui = task_after("UI", AgentRole::Implementer, 2, &[&setup]);
+        let tests = task_after("Tests", AgentRole::Tester, 1, &[&api, &ui]);
+        let docs = task_after("Docs", AgentRole::Implementer, 3, &[]);
+        let ids: Vec<_> = [&setup, &api, &tests].iter().map(|task| task.id.clone()).collect();
+        workshop.queue_tasks(vec![setup, api, ui, tests, docs]).unwrap();
+
+        let path = workshop.critical_path();
+        assert_eq!(path.tasks, ids);
+        assert_eq!(path.duration, Duration::from_secs(6 * 3600));
+
+        workshop.register_agent(Agent::new(AgentRole::Scaffolder, 1)).unwrap();
+        let (agent_id, task) = workshop.assign_by_priority().unwrap().unwrap();
+        workshop.complete_task(agent_id, task.id).unwrap();
+
+        // Finished work drops off the path
+        let path = workshop.get_status().critical_path;
+        assert_eq!(path.tasks, ids[1..].to_vec());
+        assert_eq!(path.duration, Duration::from_secs(5 * 3600));
+    }
+
+    #[test]
+    fn test_finished_tasks_are_pruned() {
+        let mut workshop = WorkshopManager::new();
+        workshop.register_agent(Agent::new(AgentRole::Scaffolder, 1)).unwrap();
+        let setup = task_after("Setup", AgentRole::Scaffolder, 1, &[]);
+        let implement = task_after("Implement", AgentRole::Implementer, 2, &[&setup]);
+        let lint = task_after("Lint", AgentRole::Tester, 1, &[]);
+        let lint_id = lint.id.clone();
+        workshop.queue_tasks(vec![setup.clone(), implement.clone(), lint]).unwrap();
+
+        let (agent_id, task) = workshop.assign_by_priority().unwrap().unwrap();
+        assert_eq!(task.id, setup.id);
+        workshop.complete_task(agent_id, task.id).unwrap();
+        workshop.cancel_task(&lint_id).unwrap();
+        workshop.queue_task(task_after("Docs", AgentRole::Implementer, 1, &[])).unwrap();
+
+        // Setup still has an unfinished dependent; the cancelled lint has none
+        assert_eq!(workshop.task_graph().len(), 3);
+        assert_eq!(workshop.task_graph().status(&setup.id), Some(&TaskStatus::Completed));
+        assert_eq!(workshop.task_graph().status(&lint_id), None);
+
+        workshop.cancel_task(&implement.id).unwrap();
+        workshop.queue_task(task_after("Changelog", AgentRole::Implementer, 1, &[])).unwrap();
+        assert_eq!(workshop.task_graph().len(), 2);
+        assert_eq!(workshop.task_graph().status(&implement.id), None);
+
+        // Pruned completed work can still be depended on
+        assert_eq!(workshop.task_graph().status(&setup.id), Some(&TaskStatus::Completed));
+        workshop.queue_task(task_after("Release", AgentRole::Implementer, 1, &[&setup])).unwrap();
+        assert!(matches!(workshop.queue_task(setup), Err(WorkshopError::DuplicateTask(_))));
+    }
+
+    #[test]
+    fn test_graph_forgets_the_oldest_completed_tasks() {
+        let mut graph = TaskGraph::new().with_completed_capacity(2);
+        let tasks: Vec<Task> = (0..4).map(|n| task_after(&format!("Step {}", n), AgentRole::Implementer, 1, &[])).collect();
+        // Each task added prunes the one completed before it
+        for task in &tasks {
+            graph.add_tasks(std::slice::from_ref(task)).unwrap();
+            graph.complete(&task.id);
+        }
+        graph.add_tasks(&[task_after("Last", AgentRole::Implementer, 1, &[])]).unwrap();
+
+        assert!(!graph.contains(&tasks[0].id));
+        assert!(!graph.contains(&tasks[1].id));
+        assert_eq!(graph.status(&tasks[3].id), Some(&TaskStatus::Completed));
+        assert!(matches!(
+            graph.add_tasks(&[task_after("Late", AgentRole::Implementer, 1, &[&tasks[0]])]),
+            Err(WorkshopError::UnknownDependencies(_))
+        ));
+        graph.add_tasks(&[task_after("Follow-up", AgentRole::Implementer, 1, &[&tasks[2]])]).unwrap();
+    }
+}
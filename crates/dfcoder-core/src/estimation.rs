@@ -0,0 +1,230 @@
+//! Predicting when running tasks will finish
+//!
+//! An estimate starts from how long the task should take: the agent's own
+//! average for tasks of the same complexity, blended with the task's stated
+//! estimate (or a default for its complexity) until the agent has a history.
+//! Time already spent is taken off, and what the agent is doing right now
+//! stretches or shrinks the rest: being stuck or frustrated slows work down,
+//! testing usually means it is nearly done.
+
+use crate::agents::Task;
+use crate::coordination::{AgentExpertise, TaskComplexity};
+use dfcoder_baml::{ActivityType, EmotionalState, TrackedActivity};
+use dfcoder_types::duration_serde;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Share of outcomes the interval of a `CompletionEstimate` covers
+pub const ESTIMATE_COVERAGE: f32 = 0.8;
+
+/// Standard normal quantile for a central interval covering `ESTIMATE_COVERAGE`
+const INTERVAL_Z: f64 = 1.2816;
+
+/// Predicted time left on a task
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CompletionEstimate {
+    /// Most likely time left
+    #[serde(with = "duration_serde")]
+    pub remaining: Duration,
+    /// Lower end of the interval expected to hold `ESTIMATE_COVERAGE` of outcomes
+    #[serde(with = "duration_serde")]
+    pub low: Duration,
+    /// Upper end of that interval
+    #[serde(with = "duration_serde")]
+    pub high: Duration,
+    /// Completed tasks of the same complexity the agent's history is based on
+    pub samples: u32,
+}
+
+/// How long a task usually takes when nothing better is known
+pub fn typical_duration(complexity: &TaskComplexity) -> Duration {
+    match complexity {
+        TaskComplexity::Simple => Duration::from_secs(30 * 60),
+        TaskComplexity::Medium => Duration::from_secs(2 * 3600),
+        TaskComplexity::Complex => Duration::from_secs(6 * 3600),
+        TaskComplexity::Expert => Duration::from_secs(12 * 3600),
+    }
+}
+
+/// Estimate task complexity based on description and requirements
+pub fn task_complexity(task: &Task) -> TaskComplexity {
+    let description = task.description.to_lowercase();
+    let title = task.title.to_lowercase();
+
+    // Simple heuristics for complexity estimation
+    if description.contains("fix") || description.contains("bug") || title.len() < 20 {
+        TaskComplexity::Simple
+    } else if description.contains("implement") || description.contains("feature") {
+        if description.contains("complex") || description.contains("integration") {
+            TaskComplexity::Complex
+        } else {
+            TaskComplexity::Medium
+        }
+    } else if description.contains("architecture") || description.contains("design") {
+        TaskComplexity::Expert
+    } else {
+        TaskComplexity::Medium
+    }
+}
+
+/// Predict the time left on `task`, which has been running for `elapsed`
+///
+/// `expertise` is the history of the agent working on it and `activity` what
+/// it is currently doing; either may be missing, which widens the interval.
+pub fn estimate_completion(
+    task: &Task,
+    expertise: Option<&AgentExpertise>,
+    activity: Option<&TrackedActivity>,
+    elapsed: Duration,
+) -> CompletionEstimate {
+    let complexity = task_complexity(task);
+    let prior = task.context.estimated_duration
+        .unwrap_or_else(|| typical_duration(&complexity))
+        .as_secs_f64();
+    let samples = expertise
+        .and_then(|expertise| expertise.completion_samples.get(&complexity))
+        .copied()
+        .unwrap_or(0);
+
+    // Trust the agent's own average more the more tasks it is based on
+    let expected = match expertise.and_then(|expertise| expertise.completion_times.get(&complexity)) {
+        Some(average) if samples > 0 => {
+            let weight = samples as f64 / (samples as f64 + 2.0);
+            average.as_secs_f64() * weight + prior * (1.0 - weight)
+        }
+        _ => prior,
+    };
+
+    // Spread in log space, narrowing as history builds up
+    let mut spread = 0.3 + 0.5 / (1.0 + samples as f64);
+    let elapsed = elapsed.as_secs_f64();
+    if elapsed > expected {
+        // Overrunning tasks are harder to call
+        spread += 0.2;
+    }
+
+    // A task past its expected length is assumed to run a quarter as long again
+    let mut remaining = (expected - elapsed).max(elapsed * 0.25);
+    if let Some(activity) = activity {
+        remaining *= activity_slowdown(activity);
+        if let Some(class) = &activity.classification {
+            spread += 0.2 * (1.0 - class.confidence.clamp(0.0, 1.0) as f64);
+        }
+    } else {
+        spread += 0.1;
+    }
+
+    let factor = (INTERVAL_Z * spread).exp();
+    CompletionEstimate {
+        remaining: Duration::from_secs_f64(remaining),
+        low: Duration::from_secs_f64(remaining / factor),
+        high: Duration::from_secs_f64(remaining * factor),
+        samples,
+    }
+}
+
+/// How much the agent's current activity stretches the time left
+fn activity_slowdown(activity: &TrackedActivity) -> f64 {
+    let mut factor = 1.0;
+    if let Some(class) = &activity.classification {
+        factor *= match class.primary {
+            ActivityType::Stuck => 1.5,
+            ActivityType::Waiting => 1.3,
+            ActivityType::Debugging => 1.2,
+            ActivityType::Testing => 0.8,
+            _ => 1.0,
+        };
+        factor *= match class.emotional_state {
+            EmotionalState::Desperate => 1.5,
+            EmotionalState::Frustrated => 1.25,
+            EmotionalState::Confident => 0.9,
+            _ => 1.0,
+        };
+    }
+
+    // Time recently lost to problems tends to keep being lost
+    let recent = &activity.context.recent_activities;
+    if !recent.is_empty() {
+        let troubled = recent.iter()
+            .filter(|activity| matches!(activity, ActivityType::Stuck | ActivityType::Debugging))
+            .count();
+        factor *= 1.0 + 0.5 * troubled as f64 / recent.len() as f64;
+    }
+    factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{AgentRole, TaskPriority};
+    use dfcoder_baml::{ActivityClass, ActivityContext, ActivityOutcome, ClassificationSource};
+
+    fn task(estimate: Option<Duration>) -> Task {
+        let mut task = Task::new(
+            "Add the login endpoint".to_string(),
+            "Implement the login feature".to_string(),
+            AgentRole::Implementer,
+            TaskPriority::Normal,
+        );
+        task.context.estimated_duration = estimate;
+        task
+    }
+
+    fn activity(primary: ActivityType, emotional_state: EmotionalState, recent: Vec<ActivityType>) -> TrackedActivity {
+        TrackedActivity {
+            id: "activity-1".to_string(),
+            agent_id: "agent-1".to_string(),
+            context: ActivityContext { recent_activities: recent, ..ActivityContext::new() },
+            output: String::new(),
+            classification: Some(ActivityClass {
+                primary,
+                confidence: 0.8,
+                needs_help: false,
+                emotional_state,
+                estimated_completion: None,
+                source: ClassificationSource::Rules,
+            }),
+            start_time: chrono::Utc::now(),
+            end_time: None,
+            outcome: ActivityOutcome::InProgress,
+        }
+    }
+
+    #[test]
+    fn test_history_outweighs_the_prior_and_narrows_the_interval() {
+        let task = task(Some(Duration::from_secs(3600)));
+        let fresh = estimate_completion(&task, None, None, Duration::ZERO);
+        assert_eq!(fresh.remaining, Duration::from_secs(3600));
+        assert!(fresh.low < fresh.remaining && fresh.remaining < fresh.high);
+
+        let mut expertise = AgentExpertise::default();
+        for _ in 0..8 {
+            expertise.record_completion(TaskComplexity::Medium, Duration::from_secs(1200));
+        }
+        let seasoned = estimate_completion(&task, Some(&expertise), None, Duration::ZERO);
+        assert_eq!(seasoned.samples, 8);
+        // 8 of 10 parts history, 2 parts the stated hour
+        assert_eq!(seasoned.remaining.as_secs(), 1680);
+        let width = |estimate: &CompletionEstimate| estimate.high.as_secs_f64() / estimate.low.as_secs_f64();
+        assert!(width(&seasoned) < width(&fresh));
+    }
+
+    #[test]
+    fn test_elapsed_time_and_activity_move_the_estimate() {
+        let task = task(None);
+        assert_eq!(task_complexity(&task), TaskComplexity::Medium);
+
+        let halfway = estimate_completion(&task, None, None, Duration::from_secs(3600));
+        assert_eq!(halfway.remaining, Duration::from_secs(3600));
+
+        let overrun = estimate_completion(&task, None, None, Duration::from_secs(4 * 3600));
+        assert_eq!(overrun.remaining, Duration::from_secs(3600));
+
+        let testing = activity(ActivityType::Testing, EmotionalState::Focused, vec![]);
+        let stuck = activity(ActivityType::Stuck, EmotionalState::Frustrated, vec![ActivityType::Debugging, ActivityType::Stuck]);
+        let nearly_done = estimate_completion(&task, None, Some(&testing), Duration::from_secs(3600));
+        let struggling = estimate_completion(&task, None, Some(&stuck), Duration::from_secs(3600));
+        assert!(nearly_done.remaining < halfway.remaining);
+        assert_eq!(struggling.remaining.as_secs(), (3600.0 * 1.5 * 1.25 * 1.5) as u64);
+    }
+}
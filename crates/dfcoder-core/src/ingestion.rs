@@ -0,0 +1,501 @@
+//! Pane output ingestion
+//!
+//! Raw bytes read from an agent's pane go through an [`AnsiStripper`] into a
+//! bounded [`RollingWindow`] per agent. Text that arrived since the last
+//! classification is handed to the `ActivityTracker`, which classifies it with
+//! the agent's running `ActivityContext`.
+
+use crate::agents::{Agent, AgentId};
+use dfcoder_baml::{ActivityClass, ActivityClassifier, ActivityContext, ActivityTracker, ClassificationError};
+use std::collections::HashMap;
+
+/// Settings for the ingestion pipeline
+#[derive(Debug, Clone)]
+pub struct IngestionConfig {
+    /// How much cleaned output is kept per agent
+    pub window_bytes: usize,
+}
+
+impl Default for IngestionConfig {
+    fn default() -> Self {
+        Self { window_bytes: 16 * 1024 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Ground,
+    Escape,
+    Intermediate,
+    Csi,
+    /// OSC, DCS and similar strings terminated by BEL or ST
+    String,
+    StringEscape,
+}
+
+/// Streaming terminal-output cleaner
+///
+/// Removes escape sequences and control characters, collapses carriage-return
+/// redraws (spinners, progress bars) to their final state and only releases
+/// complete lines, so sequences and UTF-8 characters split across reads are
+/// handled.
+#[derive(Debug, Clone)]
+pub struct AnsiStripper {
+    state: EscapeState,
+    line: Vec<u8>,
+    carriage_return: bool,
+}
+
+impl AnsiStripper {
+    pub fn new() -> Self {
+        Self {
+            state: EscapeState::Ground,
+            line: Vec::new(),
+            carriage_return: false,
+        }
+    }
+
+    /// Feed raw bytes, returning the lines they completed
+    pub fn feed(&mut self, bytes: &[u8]) -> String {
+        let mut out = String::new();
+
+        for &byte in bytes {
+            match self.state {
+                EscapeState::Ground => self.ground(byte, &mut out),
+                EscapeState::Escape => {
+                    self.state = match byte {
+                        b'[' => EscapeState::Csi,
+                        b']' | b'P' | b'X' | b'^' | b'_' => EscapeState::String,
+                        0x20..=0x2f => EscapeState::Intermediate,
+                        _ => EscapeState::Ground,
+                    };
+                }
+                EscapeState::Intermediate => {
+                    if !(0x20..=0x2f).contains(&byte) {
+                        self.state = EscapeState::Ground;
+                    }
+                }
+                EscapeState::Csi => {
+                    if (0x40..=0x7e).contains(&byte) {
+                        self.state = EscapeState::Ground;
+                    }
+                }
+                EscapeState::String => match byte {
+                    0x07 => self.state = EscapeState::Ground,
+                    0x1b => self.state = EscapeState::StringEscape,
+                    _ => {}
+                },
+                EscapeState::StringEscape => {
+                    self.state = if byte == b'\\' { EscapeState::Ground } else { EscapeState::String };
+                }
+            }
+        }
+
+        out
+    }
+
+    /// The line currently being written, not yet terminated
+    pub fn partial_line(&self) -> String {
+        match std::str::from_utf8(&self.line) {
+            Ok(line) => line.to_string(),
+            // Drop a character still waiting for its remaining bytes
+            Err(e) => String::from_utf8_lossy(&self.line[..e.valid_up_to()]).into_owned(),
+        }
+    }
+
+    fn ground(&mut self, byte: u8, out: &mut String) {
+        if self.carriage_return {
+            self.carriage_return = false;
+            if byte != b'\n' {
+                // A bare carriage return redraws the line
+                self.line.clear();
+            }
+        }
+
+        match byte {
+            0x1b => self.state = EscapeState::Escape,
+            b'\r' => self.carriage_return = true,
+            b'\n' => {
+                out.push_str(&String::from_utf8_lossy(&self.line));
+                out.push('\n');
+                self.line.clear();
+            }
+            0x08 => {
+                // Backspace removes a whole character
+                while let Some(removed) = self.line.pop() {
+                    if removed & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+            }
+            b'\t' => self.line.push(byte),
+            0x00..=0x1f | 0x7f => {}
+            _ => self.line.push(byte),
+        }
+    }
+}
+
+impl Default for AnsiStripper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounded buffer of recent output that remembers what was already classified
+#[derive(Debug, Clone)]
+pub struct RollingWindow {
+    text: String,
+    max_bytes: usize,
+    unclassified: usize,
+}
+
+impl RollingWindow {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            text: String::new(),
+            max_bytes,
+            unclassified: 0,
+        }
+    }
+
+    /// Append text, dropping the oldest whole lines once the window is full
+    ///
+    /// Only a single line longer than the whole window is cut mid-line.
+    pub fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+        if self.text.len() <= self.max_bytes {
+            return;
+        }
+
+        let len = self.text.len();
+        let mut cut = len - self.max_bytes;
+        while !self.text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        if self.text.as_bytes()[cut - 1] != b'\n' {
+            let next_line = self.text[cut..].find('\n').map(|newline| cut + newline + 1);
+            let last_line = self.text[..len - 1].rfind('\n').map_or(0, |newline| newline + 1);
+            match next_line {
+                Some(next_line) if next_line < len => cut = next_line,
+                // Keep the newest line whole if it fits on its own
+                _ if len - last_line <= self.max_bytes => cut = last_line,
+                _ => {}
+            }
+        }
+        self.text.drain(..cut);
+        self.unclassified = self.unclassified.saturating_sub(cut);
+    }
+
+    /// Everything currently in the window
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Text added since the last call to `take_unclassified`
+    pub fn unclassified(&self) -> &str {
+        &self.text[self.unclassified..]
+    }
+
+    /// Take the text added since the last call, marking it classified
+    pub fn take_unclassified(&mut self) -> Option<String> {
+        if self.unclassified == self.text.len() {
+            return None;
+        }
+        let text = self.text[self.unclassified..].to_string();
+        self.unclassified = self.text.len();
+        Some(text)
+    }
+}
+
+/// A chunk of output that was classified
+#[derive(Debug, Clone)]
+pub struct IngestedChunk {
+    pub agent_id: AgentId,
+    /// Activity the chunk was recorded against
+    pub activity_id: String,
+    /// Cleaned text that was new since the previous chunk
+    pub text: String,
+    /// `None` when the classifier failed; the text is still recorded
+    pub classification: Option<ActivityClass>,
+}
+
+#[derive(Debug, Clone)]
+struct AgentStream {
+    stripper: AnsiStripper,
+    window: RollingWindow,
+}
+
+/// Turns raw pane byte streams into classified activity
+#[derive(Debug, Clone, Default)]
+pub struct OutputIngestor {
+    config: IngestionConfig,
+    streams: HashMap<AgentId, AgentStream>,
+}
+
+impl OutputIngestor {
+    pub fn new(config: IngestionConfig) -> Self {
+        Self {
+            config,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Feed raw bytes read from an agent's pane
+    pub fn ingest(&mut self, agent_id: &AgentId, bytes: &[u8]) {
+        let window_bytes = self.config.window_bytes;
+        let stream = self.streams.entry(agent_id.clone()).or_insert_with(|| AgentStream {
+            stripper: AnsiStripper::new(),
+            window: RollingWindow::new(window_bytes),
+        });
+
+        let lines = stream.stripper.feed(bytes);
+        if !lines.is_empty() {
+            stream.window.push(&lines);
+        }
+    }
+
+    /// Recent cleaned output for an agent, including the line being written
+    pub fn recent_output(&self, agent_id: &AgentId) -> Option<String> {
+        self.streams.get(agent_id).map(|stream| {
+            let mut output = stream.window.as_str().to_string();
+            output.push_str(&stream.stripper.partial_line());
+            output
+        })
+    }
+
+    /// Forget an agent's stream
+    pub fn remove_agent(&mut self, agent_id: &AgentId) {
+        self.streams.remove(agent_id);
+    }
+
+    /// Take the complete lines received since the last classification
+    pub fn take_new(&mut self, agent_id: &AgentId) -> Option<String> {
+        self.streams.get_mut(agent_id).and_then(|stream| stream.window.take_unclassified())
+    }
+
+    /// Like `take_new`, but also includes the unterminated last line
+    ///
+    /// Use this once a burst of output has ended, so prompts that wait on the
+    /// same line ("Continue? [y/n]") are not held back. The line is taken
+    /// again once it is terminated.
+    pub fn take_flush(&mut self, agent_id: &AgentId) -> Option<String> {
+        let stream = self.streams.get_mut(agent_id)?;
+        let mut text = stream.window.take_unclassified().unwrap_or_default();
+        text.push_str(&stream.stripper.partial_line());
+        (!text.trim().is_empty()).then_some(text)
+    }
+
+    /// Classify complete lines received since the last classification
+    pub async fn classify_new(
+        &mut self,
+        agent: &Agent,
+        tracker: &mut ActivityTracker,
+    ) -> Result<Option<IngestedChunk>, ClassificationError> {
+        let Some(text) = self.take_new(&agent.id) else {
+            return Ok(None);
+        };
+        Ok(Some(PendingChunk::new(agent, tracker, text).classify_into(tracker).await))
+    }
+
+    /// Like `classify_new`, but classifies `take_flush` output
+    pub async fn flush(
+        &mut self,
+        agent: &Agent,
+        tracker: &mut ActivityTracker,
+    ) -> Result<Option<IngestedChunk>, ClassificationError> {
+        let Some(text) = self.take_flush(&agent.id) else {
+            return Ok(None);
+        };
+        Ok(Some(PendingChunk::new(agent, tracker, text).classify_into(tracker).await))
+    }
+}
+
+/// Output taken from an agent's window, waiting to be classified
+///
+/// Classifying may call a language model, so it is split in three steps that
+/// callers sharing the tracker can run without holding it across the call:
+/// `new` reads the agent's running context, `classify` needs no tracker, and
+/// `record` files the result.
+#[derive(Debug, Clone)]
+pub struct PendingChunk {
+    agent_id: AgentId,
+    /// Activity the chunk continues; a new one is started when `None`
+    activity_id: Option<String>,
+    context: ActivityContext,
+    text: String,
+    classifier: ActivityClassifier,
+}
+
+impl PendingChunk {
+    /// Prepare `text` for classification with the agent's current activity context
+    pub fn new(agent: &Agent, tracker: &ActivityTracker, text: String) -> Self {
+        let current = tracker.current_activity(&agent.id).map(|activity| {
+            let mut context = activity.context.clone();
+            if let Some(classification) = &activity.classification {
+                context.add_activity(classification.primary.clone());
+            }
+            let elapsed = chrono::Utc::now() - activity.start_time;
+            context.update_working_time(elapsed.to_std().unwrap_or_default());
+            (activity.id.clone(), context)
+        });
+
+        let (activity_id, mut context) = match current {
+            Some((activity_id, context)) => (Some(activity_id), context),
+            None => {
+                let mut context = ActivityContext::new();
+                context.agent_id = Some(agent.id.clone());
+                context.agent_role = Some(agent.role.to_string());
+                (None, context)
+            }
+        };
+        context.current_task = agent.current_task.clone();
+
+        Self {
+            agent_id: agent.id.clone(),
+            activity_id,
+            context,
+            text,
+            classifier: tracker.classifier().clone(),
+        }
+    }
+
+    /// The cleaned text to classify
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Classify the text; `None` when the classifier failed
+    pub async fn classify(&self) -> Option<ActivityClass> {
+        self.classifier.classify_with_context(&self.text, Some(&self.context)).await.ok()
+    }
+
+    /// Record the text and its classification against the agent's activity
+    pub fn record(self, tracker: &mut ActivityTracker, classification: Option<ActivityClass>) -> IngestedChunk {
+        let activity_id = match self.activity_id {
+            Some(activity_id) => {
+                tracker.update_classified_activity(&self.agent_id, &activity_id, &self.text, self.context, classification);
+                activity_id
+            }
+            None => tracker.start_classified_activity(self.agent_id.clone(), &self.text, self.context, classification),
+        };
+
+        let classification = tracker
+            .current_activity(&self.agent_id)
+            .and_then(|activity| activity.classification.clone());
+
+        IngestedChunk {
+            agent_id: self.agent_id,
+            activity_id,
+            text: self.text,
+            classification,
+        }
+    }
+
+    async fn classify_into(self, tracker: &mut ActivityTracker) -> IngestedChunk {
+        let classification = self.classify().await;
+        self.record(tracker, classification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::AgentRole;
+    use dfcoder_baml::ActivityType;
+
+    fn strip(bytes: &[u8]) -> String {
+        let mut stripper = AnsiStripper::new();
+        let mut out = stripper.feed(bytes);
+        out.push_str(&stripper.partial_line());
+        out
+    }
+
+    #[test]
+    fn test_strips_escape_sequences() {
+        assert_eq!(strip(b"\x1b[1;32mCompiling\x1b[0m dfcoder\n"), "Compiling dfcoder\n");
+        assert_eq!(strip(b"\x1b]0;claude\x07title set\n"), "title set\n");
+        assert_eq!(strip(b"\x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\\n"), "link\n");
+        assert_eq!(strip(b"\x1b(Bplain\x1b=\n"), "plain\n");
+        assert_eq!(strip(b"tab\tbell\x07\r\n"), "tab\tbell\n");
+    }
+
+    #[test]
+    fn test_carriage_return_and_backspace_redraw_the_line() {
+        assert_eq!(strip(b"| working\r/ working\r- done\n"), "- done\n");
+        assert_eq!(strip("cafe\u{0301}\x08\x08é!\n".as_bytes()), "café!\n");
+    }
+
+    #[test]
+    fn test_sequences_split_across_reads() {
+        let mut stripper = AnsiStripper::new();
+        let bytes = "\x1b[31merror: naïve\x1b[0m\n".as_bytes();
+        let mut out = String::new();
+        for byte in bytes {
+            out.push_str(&stripper.feed(std::slice::from_ref(byte)));
+        }
+        assert_eq!(out, "error: naïve\n");
+
+        stripper.feed(&"é".as_bytes()[..1]);
+        assert_eq!(stripper.partial_line(), "");
+    }
+
+    #[test]
+    fn test_rolling_window_bounds_and_tracks_unclassified() {
+        let mut window = RollingWindow::new(12);
+        window.push("one\ntwo\n");
+        assert_eq!(window.take_unclassified().as_deref(), Some("one\ntwo\n"));
+        assert_eq!(window.take_unclassified(), None);
+
+        window.push("three\n");
+        assert_eq!(window.as_str(), "two\nthree\n");
+        assert_eq!(window.unclassified(), "three\n");
+
+        window.push("ten chars!\n");
+        assert_eq!(window.as_str(), "ten chars!\n");
+        assert_eq!(window.take_unclassified().as_deref(), Some("ten chars!\n"));
+
+        window.push("far too long for it\n");
+        assert_eq!(window.as_str(), "long for it\n");
+    }
+
+    #[tokio::test]
+    async fn test_classifies_new_chunks_into_one_activity() {
+        let agent = Agent::new(AgentRole::Debugger, 1);
+        let mut tracker = ActivityTracker::new(ActivityClassifier::new(String::new()));
+        let mut ingestor = OutputIngestor::default();
+
+        ingestor.ingest(&agent.id, b"\x1b[33mDebugging the auth");
+        assert!(ingestor.classify_new(&agent, &mut tracker).await.unwrap().is_none());
+
+        ingestor.ingest(&agent.id, b" error\x1b[0m\n");
+        let first = ingestor.classify_new(&agent, &mut tracker).await.unwrap().unwrap();
+        assert_eq!(first.text, "Debugging the auth error\n");
+        assert_eq!(first.classification.unwrap().primary, ActivityType::Debugging);
+
+        ingestor.ingest(&agent.id, b"Running tests to verify\n");
+        let second = ingestor.classify_new(&agent, &mut tracker).await.unwrap().unwrap();
+        assert_eq!(second.activity_id, first.activity_id);
+        assert_eq!(second.text, "Running tests to verify\n");
+
+        let activity = tracker.current_activity(&agent.id).unwrap();
+        assert_eq!(activity.context.recent_activities, vec![ActivityType::Debugging]);
+        assert_eq!(activity.context.agent_role.as_deref(), Some("Debugger"));
+        assert!(ingestor.classify_new(&agent, &mut tracker).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_flush_includes_waiting_prompt() {
+        let agent = Agent::new(AgentRole::Implementer, 2);
+        let mut tracker = ActivityTracker::new(ActivityClassifier::new(String::new()));
+        let mut ingestor = OutputIngestor::default();
+
+        ingestor.ingest(&agent.id, b"Waiting for user input? [y/n] ");
+        let chunk = ingestor.flush(&agent, &mut tracker).await.unwrap().unwrap();
+        assert_eq!(chunk.text, "Waiting for user input? [y/n] ");
+        assert_eq!(chunk.classification.unwrap().primary, ActivityType::Waiting);
+        assert_eq!(ingestor.recent_output(&agent.id).unwrap(), "Waiting for user input? [y/n] ");
+
+        ingestor.remove_agent(&agent.id);
+        assert!(ingestor.flush(&agent, &mut tracker).await.unwrap().is_none());
+    }
+}
@@ -1,13 +1,24 @@
 //! Core functionality for DFCoder
 
 pub mod agents;
+pub mod capacity;
 pub mod coordination;
+pub mod estimation;
+pub mod ingestion;
+pub mod persistence;
+pub mod protocol;
 pub mod retry;
+pub mod runner;
 pub mod supervision;
 
 pub use agents::*;
+pub use capacity::*;
 pub use coordination::*;
+pub use estimation::*;
+pub use ingestion::*;
+pub use persistence::*;
 pub use retry::*;
+pub use runner::*;
 pub use supervision::*;
 
 /// Placeholder trait for Event types
@@ -0,0 +1,507 @@
+//! Crash-safe persistence for the workshop and supervision state
+//!
+//! A state directory holds a snapshot plus an append-only journal:
+//!
+//! - `snapshot.json` is the full [`PersistedState`] as of journal sequence
+//!   number `seq`
+//! - `journal.jsonl` has one [`JournalEntry`] per line for every change made
+//!   after the snapshot
+//!
+//! [`StateStore::record`] diffs the live state against what is already on disk
+//! and appends only the changes, so a crashed daemon loses at most what
+//! changed since the last call. Once the journal grows past a limit it is
+//! folded into a fresh snapshot. Timestamps are stored as wall-clock time, so
+//! after a restart tasks and agents keep their real age.
+
+use crate::agents::*;
+use crate::capacity::CapacityLimits;
+use crate::coordination::*;
+use crate::supervision::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Snapshot format written by this version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// Everything needed to rebuild a `WorkshopManager`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkshopState {
+    pub capacity: CapacityLimits,
+    pub agents: BTreeMap<AgentId, Agent>,
+    /// Tasks waiting for an agent, in queue order
+    pub queue: Vec<Task>,
+    /// Tasks assigned to an agent and not yet finished
+    pub in_flight: BTreeMap<TaskId, Task>,
+    pub completed_tasks: Vec<TaskId>,
+    pub expertise: BTreeMap<AgentId, AgentExpertise>,
+    pub metrics: WorkshopMetrics,
+}
+
+/// Everything needed to rebuild a `SupervisionSystem`'s requests and history
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SupervisionState {
+    pub active_requests: BTreeMap<AgentId, SupervisionRequest>,
+    pub history: Vec<SupervisionEvent>,
+}
+
+/// The state a daemon persists
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub workshop: WorkshopState,
+    pub supervision: SupervisionState,
+}
+
+/// A single change to the persisted state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateChange {
+    CapacityChanged { capacity: CapacityLimits },
+    AgentUpdated { agent: Agent },
+    AgentRemoved { agent_id: AgentId },
+    QueueChanged { queue: Vec<Task> },
+    TaskStarted { task: Task },
+    TaskFinished { task_id: TaskId },
+    TasksCompleted { task_ids: Vec<TaskId> },
+    CompletedReset { task_ids: Vec<TaskId> },
+    ExpertiseUpdated { agent_id: AgentId, expertise: AgentExpertise },
+    MetricsUpdated { metrics: WorkshopMetrics },
+    SupervisionOpened { request: SupervisionRequest },
+    SupervisionClosed { agent_id: AgentId },
+    SupervisionRecorded { events: Vec<SupervisionEvent> },
+    HistoryReset { events: Vec<SupervisionEvent> },
+}
+
+/// One line of the journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub change: StateChange,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    seq: u64,
+    saved_at: DateTime<Utc>,
+    state: PersistedState,
+}
+
+/// Errors raised while loading or saving persisted state
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Corrupt journal entry on line {line}: {message}")]
+    CorruptJournal { line: usize, message: String },
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+}
+
+impl StateChange {
+    /// Apply the change to `state`
+    pub fn apply(self, state: &mut PersistedState) {
+        let workshop = &mut state.workshop;
+        let supervision = &mut state.supervision;
+        match self {
+            StateChange::CapacityChanged { capacity } => workshop.capacity = capacity,
+            StateChange::AgentUpdated { agent } => {
+                workshop.agents.insert(agent.id.clone(), agent);
+            }
+            StateChange::AgentRemoved { agent_id } => {
+                workshop.agents.remove(&agent_id);
+                workshop.expertise.remove(&agent_id);
+            }
+            StateChange::QueueChanged { queue } => workshop.queue = queue,
+            StateChange::TaskStarted { task } => {
+                workshop.in_flight.insert(task.id.clone(), task);
+            }
+            StateChange::TaskFinished { task_id } => {
+                workshop.in_flight.remove(&task_id);
+            }
+            StateChange::TasksCompleted { task_ids } => workshop.completed_tasks.extend(task_ids),
+            StateChange::CompletedReset { task_ids } => workshop.completed_tasks = task_ids,
+            StateChange::ExpertiseUpdated { agent_id, expertise } => {
+                workshop.expertise.insert(agent_id, expertise);
+            }
+            StateChange::MetricsUpdated { metrics } => workshop.metrics = metrics,
+            StateChange::SupervisionOpened { request } => {
+                supervision.active_requests.insert(request.agent_id.clone(), request);
+            }
+            StateChange::SupervisionClosed { agent_id } => {
+                supervision.active_requests.remove(&agent_id);
+            }
+            StateChange::SupervisionRecorded { events } => supervision.history.extend(events),
+            StateChange::HistoryReset { events } => supervision.history = events,
+        }
+    }
+}
+
+/// Changes that turn `old` into `new`
+pub fn diff_states(old: &PersistedState, new: &PersistedState) -> Vec<StateChange> {
+    let mut changes = Vec::new();
+    let (old_workshop, new_workshop) = (&old.workshop, &new.workshop);
+
+    if !same(&old_workshop.capacity, &new_workshop.capacity) {
+        changes.push(StateChange::CapacityChanged { capacity: new_workshop.capacity.clone() });
+    }
+
+    for (agent_id, agent) in &new_workshop.agents {
+        if !old_workshop.agents.get(agent_id).is_some_and(|old_agent| same(old_agent, agent)) {
+            changes.push(StateChange::AgentUpdated { agent: agent.clone() });
+        }
+    }
+    for agent_id in old_workshop.agents.keys().filter(|id| !new_workshop.agents.contains_key(*id)) {
+        changes.push(StateChange::AgentRemoved { agent_id: agent_id.clone() });
+    }
+
+    if !same(&old_workshop.queue, &new_workshop.queue) {
+        changes.push(StateChange::QueueChanged { queue: new_workshop.queue.clone() });
+    }
+
+    for (task_id, task) in &new_workshop.in_flight {
+        if !old_workshop.in_flight.get(task_id).is_some_and(|old_task| same(old_task, task)) {
+            changes.push(StateChange::TaskStarted { task: task.clone() });
+        }
+    }
+    for task_id in old_workshop.in_flight.keys().filter(|id| !new_workshop.in_flight.contains_key(*id)) {
+        changes.push(StateChange::TaskFinished { task_id: task_id.clone() });
+    }
+
+    match appended(&old_workshop.completed_tasks, &new_workshop.completed_tasks) {
+        Some([]) => {}
+        Some(task_ids) => changes.push(StateChange::TasksCompleted { task_ids: task_ids.to_vec() }),
+        None => changes.push(StateChange::CompletedReset { task_ids: new_workshop.completed_tasks.clone() }),
+    }
+
+    for (agent_id, expertise) in &new_workshop.expertise {
+        if !old_workshop.expertise.get(agent_id).is_some_and(|old_expertise| same(old_expertise, expertise)) {
+            changes.push(StateChange::ExpertiseUpdated { agent_id: agent_id.clone(), expertise: expertise.clone() });
+        }
+    }
+
+    if !same(&old_workshop.metrics, &new_workshop.metrics) {
+        changes.push(StateChange::MetricsUpdated { metrics: new_workshop.metrics.clone() });
+    }
+
+    let (old_supervision, new_supervision) = (&old.supervision, &new.supervision);
+    for (agent_id, request) in &new_supervision.active_requests {
+        if !old_supervision.active_requests.get(agent_id).is_some_and(|old_request| same(old_request, request)) {
+            changes.push(StateChange::SupervisionOpened { request: request.clone() });
+        }
+    }
+    for agent_id in old_supervision.active_requests.keys().filter(|id| !new_supervision.active_requests.contains_key(*id)) {
+        changes.push(StateChange::SupervisionClosed { agent_id: agent_id.clone() });
+    }
+
+    let old_history: Vec<_> = old_supervision.history.iter().map(to_value).collect();
+    let new_history: Vec<_> = new_supervision.history.iter().map(to_value).collect();
+    match appended(&old_history, &new_history) {
+        Some([]) => {}
+        Some(_) => changes.push(StateChange::SupervisionRecorded {
+            events: new_supervision.history[old_history.len()..].to_vec(),
+        }),
+        None => changes.push(StateChange::HistoryReset { events: new_supervision.history.clone() }),
+    }
+
+    changes
+}
+
+/// The tail added to `old` to get `new`, or `None` if `new` does not extend it
+fn appended<'a, T: PartialEq>(old: &[T], new: &'a [T]) -> Option<&'a [T]> {
+    new.starts_with(old).then(|| &new[old.len()..])
+}
+
+fn to_value<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+/// Compare through the serialized form, which is what ends up on disk
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    to_value(a) == to_value(b)
+}
+
+/// Snapshot and journal files for one state directory
+#[derive(Debug)]
+pub struct StateStore {
+    dir: PathBuf,
+    journal: File,
+    /// The state the files on disk describe
+    state: PersistedState,
+    seq: u64,
+    journal_entries: usize,
+    compact_after: usize,
+}
+
+impl StateStore {
+    /// Open a state directory, creating it if needed, and replay what it holds
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, PersistenceError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let (mut state, snapshot_seq) = match File::open(dir.join(SNAPSHOT_FILE)) {
+            Ok(file) => {
+                let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+                if snapshot.version != SNAPSHOT_VERSION {
+                    return Err(PersistenceError::UnsupportedVersion(snapshot.version));
+                }
+                (snapshot.state, snapshot.seq)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (PersistedState::default(), 0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        let (seq, journal_entries, torn) = replay_journal(&journal_path, snapshot_seq, &mut state)?;
+
+        let journal = OpenOptions::new().create(true).append(true).open(&journal_path)?;
+        let mut store = Self {
+            dir,
+            journal,
+            state,
+            seq,
+            journal_entries,
+            compact_after: 1000,
+        };
+
+        // A write cut short by a crash leaves a partial last line; fold
+        // everything before it into a snapshot so new entries start clean
+        if torn {
+            tracing::warn!("Discarding a partially written entry at the end of {}", journal_path.display());
+            store.compact()?;
+        }
+
+        Ok(store)
+    }
+
+    /// Compact into a new snapshot once the journal holds this many entries
+    pub fn with_compact_after(mut self, entries: usize) -> Self {
+        self.compact_after = entries.max(1);
+        self
+    }
+
+    /// Directory the store writes to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The state as last recorded
+    pub fn state(&self) -> &PersistedState {
+        &self.state
+    }
+
+    /// Number of journal entries written since the last snapshot
+    pub fn journal_entries(&self) -> usize {
+        self.journal_entries
+    }
+
+    /// Journal whatever changed since the last call, returning how many entries were written
+    pub fn record(&mut self, state: &PersistedState) -> Result<usize, PersistenceError> {
+        let changes = diff_states(&self.state, state);
+        if changes.is_empty() {
+            return Ok(0);
+        }
+
+        let at = Utc::now();
+        let mut lines = String::new();
+        for change in &changes {
+            self.seq += 1;
+            let entry = JournalEntry { seq: self.seq, at, change: change.clone() };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        self.journal.write_all(lines.as_bytes())?;
+        self.journal.sync_data()?;
+
+        self.state = state.clone();
+        self.journal_entries += changes.len();
+        if self.journal_entries >= self.compact_after {
+            self.compact()?;
+        }
+
+        Ok(changes.len())
+    }
+
+    /// Write the current state as a new snapshot and empty the journal
+    pub fn compact(&mut self) -> Result<(), PersistenceError> {
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            seq: self.seq,
+            saved_at: Utc::now(),
+            state: self.state.clone(),
+        };
+
+        // Replace the snapshot atomically; if we crash before truncating the
+        // journal, its entries are skipped on replay by sequence number
+        let temp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut temp = File::create(&temp_path)?;
+        serde_json::to_writer(&mut temp, &snapshot)?;
+        temp.sync_all()?;
+        std::fs::rename(&temp_path, self.dir.join(SNAPSHOT_FILE))?;
+
+        self.journal.set_len(0)?;
+        self.journal.sync_all()?;
+        self.journal_entries = 0;
+        Ok(())
+    }
+}
+
+/// Apply journal entries newer than `after`, returning the last sequence
+/// number, how many entries were applied and whether the last line was torn
+fn replay_journal(path: &Path, after: u64, state: &mut PersistedState) -> Result<(u64, usize, bool), PersistenceError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((after, 0, false)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let lines: Vec<String> = BufReader::new(file).lines().collect::<Result<_, _>>()?;
+    let mut seq = after;
+    let mut applied = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(_) if index + 1 == lines.len() => return Ok((seq, applied, true)),
+            Err(e) => {
+                return Err(PersistenceError::CorruptJournal { line: index + 1, message: e.to_string() });
+            }
+        };
+        if entry.seq <= seq {
+            continue;
+        }
+        seq = entry.seq;
+        entry.change.apply(state);
+        applied += 1;
+    }
+
+    Ok((seq, applied, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn busy_workshop() -> (WorkshopManager, AgentId, TaskId) {
+        let mut workshop = WorkshopManager::new();
+        let mut agent = Agent::new(AgentRole::Tester, 3);
+        agent.created_at = Instant::now() - Duration::from_secs(600);
+        let agent_id = agent.id.clone();
+        workshop.register_agent(agent).unwrap();
+        workshop.register_agent(Agent::new(AgentRole::Implementer, 4)).unwrap();
+
+        let running = Task::new("Cover parser".to_string(), "Write tests".to_string(), AgentRole::Tester, TaskPriority::High);
+        let running_id = running.id.clone();
+        workshop.queue_task(running).unwrap();
+        workshop.queue_task(Task::new("Queued".to_string(), "Desc".to_string(), AgentRole::Scaffolder, TaskPriority::Low)).unwrap();
+        workshop.assign_by_priority().unwrap().unwrap();
+
+        (workshop, agent_id, running_id)
+    }
+
+    fn persisted(workshop: &WorkshopManager, supervision: &SupervisionSystem) -> PersistedState {
+        PersistedState {
+            workshop: workshop.export_state(),
+            supervision: supervision.export_state(),
+        }
+    }
+
+    #[test]
+    fn test_diff_replays_to_the_same_state() {
+        let (mut workshop, agent_id, task_id) = busy_workshop();
+        let mut supervision = SupervisionSystem::new();
+        let before = persisted(&workshop, &supervision);
+
+        workshop.complete_task(agent_id.clone(), task_id.clone()).unwrap();
+        supervision.restore_state(SupervisionState {
+            active_requests: BTreeMap::new(),
+            history: vec![SupervisionEvent { agent_id: agent_id.clone(), ..Default::default() }],
+        });
+        let after = persisted(&workshop, &supervision);
+
+        let changes = diff_states(&before, &after);
+        assert!(changes.iter().any(|change| matches!(change, StateChange::TaskFinished { task_id: id } if id == &task_id)));
+        assert!(changes.iter().any(|change| matches!(change, StateChange::TasksCompleted { .. })));
+
+        let mut replayed = before.clone();
+        for change in changes {
+            change.apply(&mut replayed);
+        }
+        assert!(diff_states(&replayed, &after).is_empty());
+        assert!(diff_states(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_recovers_queue_and_assignments_after_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let (workshop, agent_id, task_id) = busy_workshop();
+        let supervision = SupervisionSystem::new();
+
+        {
+            let mut store = StateStore::open(dir.path()).unwrap();
+            assert!(store.record(&persisted(&workshop, &supervision)).unwrap() > 0);
+            assert_eq!(store.record(&persisted(&workshop, &supervision)).unwrap(), 0);
+            // Dropped without compacting, as if the process died
+        }
+
+        let store = StateStore::open(dir.path()).unwrap();
+        let mut recovered = WorkshopManager::from_state(store.state().workshop.clone());
+
+        let agent = recovered.get_agent(&agent_id).unwrap();
+        assert_eq!(agent.current_task, Some(task_id.clone()));
+        assert_eq!(agent.status, AgentStatus::Working);
+        assert!(agent.created_at.elapsed() >= Duration::from_secs(599));
+        assert_eq!(recovered.get_in_flight_task(&task_id).unwrap().assignee, Some(agent_id.clone()));
+        assert_eq!(recovered.get_queue().len(), 1);
+        assert_eq!(recovered.get_status().active_per_role[&AgentRole::Tester], 1);
+
+        recovered.complete_task(agent_id, task_id.clone()).unwrap();
+        assert!(recovered.get_in_flight_task(&task_id).is_none());
+    }
+
+    #[test]
+    fn test_compaction_and_torn_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut workshop, agent_id, task_id) = busy_workshop();
+        let supervision = SupervisionSystem::new();
+
+        let mut store = StateStore::open(dir.path()).unwrap().with_compact_after(2);
+        store.record(&persisted(&workshop, &supervision)).unwrap();
+        assert_eq!(store.journal_entries(), 0);
+        assert!(dir.path().join(SNAPSHOT_FILE).exists());
+
+        workshop.complete_task(agent_id, task_id.clone()).unwrap();
+        store.record(&persisted(&workshop, &supervision)).unwrap();
+        drop(store);
+
+        // A crash mid-write leaves half a line behind
+        let mut journal = OpenOptions::new().append(true).open(dir.path().join(JOURNAL_FILE)).unwrap();
+        journal.write_all(b"{\"seq\":99,\"at\":\"2024-").unwrap();
+
+        let store = StateStore::open(dir.path()).unwrap();
+        assert_eq!(store.state().workshop.completed_tasks, vec![task_id]);
+        assert!(store.state().workshop.in_flight.is_empty());
+        assert_eq!(store.journal_entries(), 0);
+    }
+
+    #[test]
+    fn test_corrupt_journal_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(JOURNAL_FILE), "not json\n{}\n").unwrap();
+        assert!(matches!(StateStore::open(dir.path()), Err(PersistenceError::CorruptJournal { line: 1, .. })));
+    }
+}
@@ -0,0 +1,210 @@
+//! Conversions from runtime structs to the shared types in dfcoder-types
+
+use crate::agents::*;
+use crate::coordination::*;
+use crate::estimation::*;
+use crate::retry::*;
+use crate::supervision::*;
+use dfcoder_types::{instant_serde, AgentState};
+use dfcoder_types::protocol::*;
+use std::time::Duration;
+
+impl From<&Agent> for AgentSummary {
+    fn from(agent: &Agent) -> Self {
+        Self {
+            id: agent.id.clone(),
+            role: agent.role.clone(),
+            pane_id: agent.pane_id,
+            status: agent.status.clone(),
+            current_task: agent.current_task.clone(),
+            tasks_completed: agent.metrics.tasks_completed,
+            tasks_failed: agent.metrics.tasks_failed,
+            help_requests: agent.metrics.help_requests,
+        }
+    }
+}
+
+impl From<&Agent> for AgentState {
+    fn from(agent: &Agent) -> Self {
+        Self {
+            status: agent.status.clone(),
+            current_task: agent.current_task.clone(),
+            last_activity: instant_serde::to_wall_clock(agent.last_activity),
+            tasks_completed: agent.metrics.tasks_completed,
+            metrics: agent.metrics.clone(),
+        }
+    }
+}
+
+impl From<&TaskAttempt> for TaskResult {
+    fn from(attempt: &TaskAttempt) -> Self {
+        if attempt.success {
+            return TaskResult::Success;
+        }
+        match &attempt.error {
+            Some(error) => TaskResult::Failed(format!("{:?}", error)),
+            None => TaskResult::Failed(attempt.output.clone()),
+        }
+    }
+}
+
+impl From<&Task> for TaskSummary {
+    fn from(task: &Task) -> Self {
+        Self {
+            id: task.id.clone(),
+            title: task.title.clone(),
+            description: task.description.clone(),
+            required_role: task.required_role.clone(),
+            priority: task.context.priority.clone(),
+            status: task.status.clone(),
+            assignee: task.assignee.clone(),
+            dependencies: task.context.dependencies.clone(),
+        }
+    }
+}
+
+impl From<TaskSpec> for Task {
+    fn from(spec: TaskSpec) -> Self {
+        let mut task = Task::new(spec.title, spec.description, spec.required_role, spec.priority);
+        task.context.dependencies = spec.dependencies;
+        task.context.files = spec.files;
+        task.context.estimated_duration = spec.estimated_duration_secs.map(Duration::from_secs);
+        task
+    }
+}
+
+impl From<&SupervisionRequest> for SupervisionPrompt {
+    fn from(request: &SupervisionRequest) -> Self {
+        Self {
+            agent_id: request.agent_id.clone(),
+            context: request.context.clone(),
+            urgency: request.urgency.clone(),
+            options: request.options.iter().map(|option| SupervisionChoice {
+                id: option.id,
+                text: option.text.clone(),
+                icon: option.icon.clone(),
+                estimated_time_secs: option.estimated_time.as_secs(),
+            }).collect(),
+            remaining_secs: request.timeout.saturating_sub(request.created_at.elapsed()).as_secs(),
+        }
+    }
+}
+
+impl From<&WorkshopStatus> for WorkshopSummary {
+    fn from(status: &WorkshopStatus) -> Self {
+        Self {
+            total_agents: status.total_agents,
+            active_agents: status.active_agents,
+            queue_length: status.queue_length,
+            capacity_per_role: status.capacity_per_role.clone(),
+            global_capacity: status.global_capacity,
+            active_per_role: status.active_per_role.clone(),
+            tasks_processed: status.metrics.total_tasks_processed,
+            tasks_completed: status.metrics.tasks_completed,
+            tasks_failed: status.metrics.tasks_failed,
+            tasks_retried: status.metrics.tasks_retried,
+            success_rate: status.metrics.success_rate,
+            throughput: status.metrics.throughput,
+            agent_utilization: status.metrics.agent_utilization.clone(),
+            bottleneck_role: status.metrics.bottleneck_role.clone(),
+            cost_per_task: status.metrics.cost_per_task,
+            blocked_by: status.blocked_by.clone(),
+            critical_path: status.critical_path.tasks.clone(),
+            critical_path_secs: status.critical_path.duration.as_secs(),
+            model_cost_usd: status.metrics.model_usage.total.cost_usd,
+            model_tokens: status.metrics.model_usage.total.tokens(),
+            completion_etas: status.completion_estimates.iter()
+                .map(|(task_id, estimate)| (task_id.clone(), estimate.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<&CompletionEstimate> for CompletionEta {
+    fn from(estimate: &CompletionEstimate) -> Self {
+        Self {
+            remaining_secs: estimate.remaining.as_secs(),
+            low_secs: estimate.low.as_secs(),
+            high_secs: estimate.high.as_secs(),
+            samples: estimate.samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_task_spec_builds_task() {
+        let task: Task = TaskSpec {
+            title: "Parser".to_string(),
+            description: "Implement the parser".to_string(),
+            required_role: AgentRole::Implementer,
+            priority: TaskPriority::High,
+            dependencies: vec!["task-0".to_string()],
+            files: vec!["src/parser.rs".to_string()],
+            estimated_duration_secs: Some(120),
+        }.into();
+
+        assert_eq!(task.status, TaskStatus::Pending);
+        assert_eq!(task.context.priority, TaskPriority::High);
+        assert_eq!(task.context.dependencies, vec!["task-0".to_string()]);
+        assert_eq!(task.context.estimated_duration, Some(Duration::from_secs(120)));
+
+        let summary = TaskSummary::from(&task);
+        assert_eq!(summary.id, task.id);
+        assert_eq!(summary.status, TaskStatus::Pending);
+    }
+
+    #[test]
+    fn test_agent_summary_and_state() {
+        let mut agent = Agent::new(AgentRole::Tester, 4);
+        agent.assign_task("task-1".to_string()).unwrap();
+
+        let summary = AgentSummary::from(&agent);
+        assert_eq!(summary.pane_id, 4);
+        assert_eq!(summary.status, AgentStatus::Working);
+        assert_eq!(summary.current_task.as_deref(), Some("task-1"));
+
+        let state = AgentState::from(&agent);
+        assert_eq!(state.status, AgentStatus::Working);
+        assert!(state.last_activity <= chrono::Utc::now());
+
+        agent.fail_task("linker error".to_string()).unwrap();
+        let state = AgentState::from(&agent);
+        assert_eq!(state.metrics.tasks_failed, 1);
+        assert_eq!(state.metrics.last_error.as_deref(), Some("linker error"));
+    }
+
+    #[test]
+    fn test_attempt_becomes_task_result() {
+        let mut attempt = TaskAttempt {
+            success: true,
+            output: "done".to_string(),
+            error: None,
+            duration: Duration::from_secs(3),
+            attempt_number: 1,
+        };
+        assert!(matches!(TaskResult::from(&attempt), TaskResult::Success));
+
+        attempt.success = false;
+        attempt.error = Some(ErrorType::NetworkError);
+        assert!(matches!(TaskResult::from(&attempt), TaskResult::Failed(reason) if reason == "NetworkError"));
+    }
+
+    #[test]
+    fn test_supervision_prompt_counts_down() {
+        let request = SupervisionRequest {
+            agent_id: "agent-1".to_string(),
+            timeout: Duration::from_secs(30),
+            created_at: Instant::now() - Duration::from_secs(10),
+            ..SupervisionRequest::default()
+        };
+
+        let prompt = SupervisionPrompt::from(&request);
+        assert!(prompt.remaining_secs <= 20);
+        assert!(prompt.remaining_secs >= 19);
+    }
+}
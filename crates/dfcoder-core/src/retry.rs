@@ -3,49 +3,17 @@
 //! Provides exponential backoff, failure pattern tracking, and strategy switching.
 
 use crate::agents::*;
+use crate::runner::{AgentRunner, OutputSink, PtyRunner};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
-/// Policy for retrying failed operations
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RetryPolicy {
-    /// Maximum number of retry attempts
-    pub max_attempts: u32,
-    /// Initial backoff duration
-    pub initial_backoff: Duration,
-    /// Multiplier for exponential backoff
-    pub backoff_multiplier: f32,
-    /// Maximum backoff duration
-    pub max_backoff: Duration,
-    /// Error types that should trigger retries
-    pub retry_on: Vec<ErrorType>,
-}
-
-/// Types of errors that can occur during task execution
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum ErrorType {
-    /// Network/API connection issues
-    NetworkError,
-    /// Rate limiting from external services
-    RateLimitError,
-    /// Authentication/authorization failures
-    AuthError,
-    /// Temporary resource unavailability
-    ResourceUnavailable,
-    /// Parse/format errors in output
-    ParseError,
-    /// Task complexity exceeds agent capability
-    ComplexityError,
-    /// Generic retryable error
-    Retryable,
-    /// Non-retryable logic errors
-    Fatal,
-}
+pub use dfcoder_types::{ErrorType, RetryPolicy, TaskResult};
 
 /// Result of a task execution attempt
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TaskResult {
+pub struct TaskAttempt {
     pub success: bool,
     pub output: String,
     pub error: Option<ErrorType>,
@@ -76,81 +44,27 @@ pub enum RetryError {
     AgentError(String),
 }
 
-impl Default for RetryPolicy {
-    fn default() -> Self {
-        Self {
-            max_attempts: 3,
-            initial_backoff: Duration::from_secs(1),
-            backoff_multiplier: 2.0,
-            max_backoff: Duration::from_secs(30),
-            retry_on: vec![
-                ErrorType::NetworkError,
-                ErrorType::RateLimitError,
-                ErrorType::ResourceUnavailable,
-                ErrorType::Retryable,
-            ],
-        }
-    }
-}
-
-impl RetryPolicy {
-    /// Create a conservative retry policy
-    pub fn conservative() -> Self {
-        Self {
-            max_attempts: 2,
-            initial_backoff: Duration::from_secs(2),
-            backoff_multiplier: 3.0,
-            max_backoff: Duration::from_secs(60),
-            retry_on: vec![ErrorType::NetworkError, ErrorType::RateLimitError],
-        }
-    }
-
-    /// Create an aggressive retry policy
-    pub fn aggressive() -> Self {
-        Self {
-            max_attempts: 5,
-            initial_backoff: Duration::from_millis(500),
-            backoff_multiplier: 1.5,
-            max_backoff: Duration::from_secs(15),
-            retry_on: vec![
-                ErrorType::NetworkError,
-                ErrorType::RateLimitError,
-                ErrorType::ResourceUnavailable,
-                ErrorType::ParseError,
-                ErrorType::Retryable,
-            ],
-        }
-    }
-
-    /// Calculate backoff duration for given attempt
-    pub fn calculate_backoff(&self, attempt: u32) -> Duration {
-        if attempt == 0 {
-            return Duration::from_secs(0);
-        }
-
-        let backoff_secs = self.initial_backoff.as_secs_f32() 
-            * self.backoff_multiplier.powi((attempt - 1) as i32);
-        
-        let backoff = Duration::from_secs_f32(backoff_secs);
-        std::cmp::min(backoff, self.max_backoff)
-    }
-
-    /// Check if an error type should trigger a retry
-    pub fn should_retry(&self, error: &ErrorType) -> bool {
-        self.retry_on.contains(error)
-    }
-}
-
 /// Executor for tasks with retry logic
 #[derive(Debug)]
 pub struct RetryExecutor {
     policy: RetryPolicy,
+    runner: Arc<dyn AgentRunner>,
 }
 
 impl RetryExecutor {
-    /// Create a new retry executor with the given policy
+    /// Create a new retry executor that runs agents as CLI processes
     pub fn new(policy: RetryPolicy) -> Self {
-        Self { policy }
+        Self::with_runner(policy, Arc::new(PtyRunner::default()))
+    }
+
+    /// Create a retry executor with a custom agent runner
+    pub fn with_runner(policy: RetryPolicy, runner: Arc<dyn AgentRunner>) -> Self {
+        Self { policy, runner }
+    }
+
+    /// Replace the agent runner
+    pub fn set_runner(&mut self, runner: Arc<dyn AgentRunner>) {
+        self.runner = runner;
     }
 
     /// Execute a task with retry logic
@@ -158,7 +72,17 @@ impl RetryExecutor {
         &self,
         agent: &mut Agent,
         task: &Task,
-    ) -> Result<TaskResult, RetryError> {
+    ) -> Result<TaskAttempt, RetryError> {
+        self.execute_task_with_output(agent, task, OutputSink::discard()).await
+    }
+
+    /// Execute a task with retry logic, streaming every attempt's output to `sink`
+    pub async fn execute_task_with_output(
+        &self,
+        agent: &mut Agent,
+        task: &Task,
+        sink: OutputSink,
+    ) -> Result<TaskAttempt, RetryError> {
         let mut retry_state = RetryState {
             attempts: 0,
             last_attempt: None,
@@ -180,13 +104,13 @@ impl RetryExecutor {
 
             // Execute the task
             let attempt_start = Instant::now();
-            let result = self.execute_single_attempt(agent, task, attempt).await;
+            let result = self.execute_single_attempt(agent, task, attempt, sink.clone()).await;
             let _attempt_duration = attempt_start.elapsed();
 
             match result {
                 Ok(task_result) => {
                     // Success - return result
-                    return Ok(TaskResult {
+                    return Ok(TaskAttempt {
                         success: true,
                         output: task_result.output,
                         error: None,
@@ -219,33 +143,28 @@ impl RetryExecutor {
     /// Execute a single attempt of the task
     async fn execute_single_attempt(
         &self,
-        _agent: &mut Agent,
+        agent: &mut Agent,
         task: &Task,
         attempt: u32,
-    ) -> Result<TaskResult, ErrorType> {
-        // Simulate task execution - in real implementation this would
-        // call the actual agent execution logic
-        
-        // For demo purposes, simulate different failure scenarios
-        if attempt == 1 && task.description.contains("network") {
-            return Err(ErrorType::NetworkError);
-        }
-        
-        if attempt <= 2 && task.description.contains("rate") {
-            return Err(ErrorType::RateLimitError);
-        }
-        
-        if task.description.contains("fatal") {
-            return Err(ErrorType::Fatal);
+        sink: OutputSink,
+    ) -> Result<TaskAttempt, ErrorType> {
+        let outcome = self.runner.run(agent, task, attempt, sink).await.map_err(|e| {
+            tracing::warn!("Agent {}: attempt {} could not run: {}", agent.id, attempt, e);
+            e.error_type()
+        })?;
+        agent.mark_activity();
+
+        if let Some(error) = outcome.error_type() {
+            tracing::warn!("Agent {}: attempt {} failed with {:?} (exit {:?})",
+                          agent.id, attempt, error, outcome.exit_code);
+            return Err(error);
         }
 
-        // Simulate successful execution
-        Ok(TaskResult {
+        Ok(TaskAttempt {
             success: true,
-            output: format!("Task '{}' completed successfully on attempt {}", 
-                          task.title, attempt),
+            output: outcome.output,
             error: None,
-            duration: Duration::from_secs(1),
+            duration: outcome.duration,
             attempt_number: attempt,
         })
     }
@@ -284,6 +203,7 @@ impl Default for RetryState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::{RunOutcome, RunnerError};
 
     #[test]
     fn test_retry_policy_backoff_calculation() {
@@ -305,43 +225,79 @@ mod tests {
         assert!(!policy.should_retry(&ErrorType::AuthError));
     }
 
-    #[tokio::test]
-    async fn test_retry_executor_success() {
-        let policy = RetryPolicy::default();
-        let executor = RetryExecutor::new(policy);
-        let mut agent = Agent::new(AgentRole::Implementer, 1);
-        let task = Task::new(
+    /// Fails its first attempts with `failure`, then exits cleanly
+    #[derive(Debug)]
+    struct FlakyRunner {
+        failures: u32,
+        failure: &'static str,
+    }
+
+    #[async_trait::async_trait]
+    impl AgentRunner for FlakyRunner {
+        async fn run(&self, _agent: &Agent, task: &Task, attempt: u32, sink: OutputSink) -> Result<RunOutcome, RunnerError> {
+            let (exit_code, output) = if attempt <= self.failures {
+                (Some(1), self.failure.to_string())
+            } else {
+                (Some(0), format!("Finished {}", task.title))
+            };
+            sink.send(output.as_bytes());
+            Ok(RunOutcome { exit_code, output, duration: Duration::from_millis(1) })
+        }
+    }
+
+    fn executor(failures: u32, failure: &'static str) -> RetryExecutor {
+        let policy = RetryPolicy { initial_backoff: Duration::from_millis(10), ..RetryPolicy::default() };
+        RetryExecutor::with_runner(policy, Arc::new(FlakyRunner { failures, failure }))
+    }
+
+    fn task() -> Task {
+        Task::new(
             "Test task".to_string(),
             "Simple test task".to_string(),
             AgentRole::Implementer,
             TaskPriority::Normal,
-        );
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retry_executor_success() {
+        let executor = executor(0, "");
+        let mut agent = Agent::new(AgentRole::Implementer, 1);
 
-        let result = executor.execute_task(&mut agent, &task).await;
+        let result = executor.execute_task(&mut agent, &task()).await;
         assert!(result.is_ok());
-        
+
         let task_result = result.unwrap();
         assert!(task_result.success);
         assert_eq!(task_result.attempt_number, 1);
+        assert_eq!(task_result.output, "Finished Test task");
     }
 
     #[tokio::test]
     async fn test_retry_executor_with_retries() {
-        let policy = RetryPolicy::default();
-        let executor = RetryExecutor::new(policy);
+        let executor = executor(2, "Error: connection reset by peer");
         let mut agent = Agent::new(AgentRole::Implementer, 1);
-        let task = Task::new(
-            "Test task".to_string(),
-            "Task with network issues".to_string(),
-            AgentRole::Implementer,
-            TaskPriority::Normal,
-        );
+        let (sink, mut chunks) = OutputSink::channel();
 
-        let result = executor.execute_task(&mut agent, &task).await;
+        let result = executor.execute_task_with_output(&mut agent, &task(), sink).await;
         assert!(result.is_ok());
-        
+
         let task_result = result.unwrap();
         assert!(task_result.success);
-        assert!(task_result.attempt_number > 1);
+        assert_eq!(task_result.attempt_number, 3);
+        let mut attempts = 0;
+        while chunks.try_recv().is_ok() {
+            attempts += 1;
+        }
+        assert_eq!(attempts, 3);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_retry_executor_stops_on_non_retryable_error() {
+        let executor = executor(1, "Error: invalid API key");
+        let mut agent = Agent::new(AgentRole::Implementer, 1);
+
+        let result = executor.execute_task(&mut agent, &task()).await;
+        assert!(matches!(result, Err(RetryError::NonRetryable(ErrorType::AuthError))));
+    }
+}
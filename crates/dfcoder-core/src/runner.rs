@@ -0,0 +1,438 @@
+//! Running agents as real CLI processes
+//!
+//! An [`AgentRunner`] executes one attempt of a task for an agent. The default
+//! [`PtyRunner`] spawns a CLI agent (Claude Code by default) in a pseudo
+//! terminal, streams its output and reports how it exited; [`classify_failure`]
+//! maps a failed run onto an [`ErrorType`] for the retry policy.
+
+use crate::agents::{Agent, Task};
+use crate::ingestion::AnsiStripper;
+use crate::retry::ErrorType;
+use async_trait::async_trait;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Errors that prevent a run from producing an outcome
+#[derive(Debug, Error)]
+pub enum RunnerError {
+    #[error("Failed to start agent process: {0}")]
+    Spawn(String),
+    #[error("Agent process I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Agent process timed out after {0:?}")]
+    TimedOut(Duration),
+}
+
+impl RunnerError {
+    /// How the retry policy should treat this error
+    pub fn error_type(&self) -> ErrorType {
+        match self {
+            // A missing or unrunnable binary won't fix itself
+            RunnerError::Spawn(_) => ErrorType::Fatal,
+            RunnerError::Io(_) => ErrorType::Retryable,
+            RunnerError::TimedOut(_) => ErrorType::ComplexityError,
+        }
+    }
+}
+
+/// How an agent process finished
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    /// Exit code, or `None` if the process was killed
+    pub exit_code: Option<u32>,
+    /// Everything the process wrote, escape sequences included
+    pub output: String,
+    pub duration: Duration,
+}
+
+impl RunOutcome {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// `None` for a successful run, otherwise the recognised failure
+    pub fn error_type(&self) -> Option<ErrorType> {
+        if self.success() {
+            None
+        } else {
+            Some(classify_failure(self.exit_code, &self.output))
+        }
+    }
+}
+
+/// Where raw output chunks go while an agent runs
+#[derive(Debug, Clone, Default)]
+pub struct OutputSink {
+    sender: Option<mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+impl OutputSink {
+    /// A sink paired with the receiver that gets every chunk
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender: Some(sender) }, receiver)
+    }
+
+    /// A sink that drops everything
+    pub fn discard() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, bytes: &[u8]) {
+        if let Some(sender) = &self.sender {
+            // A receiver that went away just stops listening
+            let _ = sender.send(bytes.to_vec());
+        }
+    }
+}
+
+/// Executes task attempts for agents
+#[async_trait]
+pub trait AgentRunner: Send + Sync + std::fmt::Debug {
+    /// Run one attempt of `task` as `agent`, streaming output to `sink`
+    async fn run(&self, agent: &Agent, task: &Task, attempt: u32, sink: OutputSink) -> Result<RunOutcome, RunnerError>;
+}
+
+/// Prompt given to the agent process: its role prompt followed by the task
+pub fn build_prompt(agent: &Agent, task: &Task) -> String {
+    let mut prompt = format!("{}\n\n# Task: {}\n\n{}\n", agent.system_prompt(), task.title, task.description);
+    if !task.context.files.is_empty() {
+        prompt.push_str("\nRelevant files:\n");
+        for file in &task.context.files {
+            prompt.push_str(&format!("- {}\n", file));
+        }
+    }
+    prompt
+}
+
+/// Output fragments that identify why an agent process failed, checked in order
+const FAILURE_PATTERNS: &[(&str, ErrorType)] = &[
+    ("rate limit", ErrorType::RateLimitError),
+    ("rate_limit", ErrorType::RateLimitError),
+    ("too many requests", ErrorType::RateLimitError),
+    ("invalid api key", ErrorType::AuthError),
+    ("unauthorized", ErrorType::AuthError),
+    ("authentication", ErrorType::AuthError),
+    ("overloaded", ErrorType::ResourceUnavailable),
+    ("service unavailable", ErrorType::ResourceUnavailable),
+    ("connection refused", ErrorType::NetworkError),
+    ("connection reset", ErrorType::NetworkError),
+    ("network", ErrorType::NetworkError),
+    ("timed out", ErrorType::NetworkError),
+    ("dns", ErrorType::NetworkError),
+    ("context length", ErrorType::ComplexityError),
+    ("max turns", ErrorType::ComplexityError),
+    ("parse error", ErrorType::ParseError),
+    ("invalid json", ErrorType::ParseError),
+];
+
+/// HTTP statuses that identify why an agent process failed
+///
+/// Only matched as a number of its own that shortly follows a word such as
+/// "HTTP" or "status", so line numbers, ports and counts are not mistaken for them.
+const STATUS_PATTERNS: &[(&str, ErrorType)] = &[
+    ("429", ErrorType::RateLimitError),
+    ("401", ErrorType::AuthError),
+    ("529", ErrorType::ResourceUnavailable),
+    ("503", ErrorType::ResourceUnavailable),
+];
+
+/// Words that mark a number following within a few words as an HTTP status
+const STATUS_CONTEXT: &[&str] = &["http", "https", "status", "api", "response"];
+
+/// Whether `status` occurs in `line` as an HTTP status rather than part of a
+/// longer number, path or address
+fn contains_status(line: &str, status: &str) -> bool {
+    line.match_indices(status).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let mut after = line[start + status.len()..].chars();
+        let joined_before = before.is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '.' | '/'));
+        let joined_after = match after.next() {
+            Some('.') => after.next().is_some_and(char::is_alphanumeric),
+            Some(c) => c.is_alphanumeric() || c == '_',
+            None => false,
+        };
+        let in_context = line[..start]
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .rev()
+            .take(3)
+            .any(|word| STATUS_CONTEXT.contains(&word));
+        !joined_before && !joined_after && in_context
+    })
+}
+
+/// Map a failed run onto an `ErrorType`
+///
+/// Recognised error lines take precedence, latest first; otherwise a killed
+/// process is retryable and any other exit is treated as fatal.
+pub fn classify_failure(exit_code: Option<u32>, output: &str) -> ErrorType {
+    let mut stripper = AnsiStripper::new();
+    let mut text = stripper.feed(output.as_bytes());
+    text.push_str(&stripper.partial_line());
+
+    for line in text.lines().rev() {
+        let line = line.to_lowercase();
+        if !(line.contains("error") || line.contains("fail") || line.contains("exceeded")) {
+            continue;
+        }
+        if let Some((_, error)) = FAILURE_PATTERNS.iter().find(|(pattern, _)| line.contains(pattern)) {
+            return error.clone();
+        }
+        if let Some((_, error)) = STATUS_PATTERNS.iter().find(|(status, _)| contains_status(&line, status)) {
+            return error.clone();
+        }
+    }
+
+    match exit_code {
+        None => ErrorType::Retryable,
+        Some(_) => ErrorType::Fatal,
+    }
+}
+
+/// Settings for spawning agent processes
+#[derive(Debug, Clone)]
+pub struct PtyRunnerConfig {
+    /// Agent CLI to run
+    pub program: String,
+    /// Arguments placed before the prompt, which is always the last argument
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    pub env: HashMap<String, String>,
+    /// Kill the process if it runs longer than this
+    pub timeout: Option<Duration>,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl Default for PtyRunnerConfig {
+    fn default() -> Self {
+        Self {
+            program: "claude".to_string(),
+            args: vec!["--print".to_string()],
+            working_dir: None,
+            env: HashMap::new(),
+            timeout: Some(Duration::from_secs(30 * 60)),
+            rows: 40,
+            cols: 120,
+        }
+    }
+}
+
+/// Runs agents as CLI processes attached to a pseudo terminal
+#[derive(Debug, Clone, Default)]
+pub struct PtyRunner {
+    config: PtyRunnerConfig,
+}
+
+impl PtyRunner {
+    pub fn new(config: PtyRunnerConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &PtyRunnerConfig {
+        &self.config
+    }
+
+    fn command(&self, prompt: &str) -> CommandBuilder {
+        let mut command = CommandBuilder::new(&self.config.program);
+        command.args(&self.config.args);
+        command.arg(prompt);
+        if let Some(dir) = &self.config.working_dir {
+            command.cwd(dir);
+        }
+        for (key, value) in &self.config.env {
+            command.env(key, value);
+        }
+        command
+    }
+}
+
+#[async_trait]
+impl AgentRunner for PtyRunner {
+    async fn run(&self, agent: &Agent, task: &Task, _attempt: u32, sink: OutputSink) -> Result<RunOutcome, RunnerError> {
+        let start = Instant::now();
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: self.config.rows,
+                cols: self.config.cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| RunnerError::Spawn(e.to_string()))?;
+
+        let mut child = pair
+            .slave
+            .spawn_command(self.command(&build_prompt(agent, task)))
+            .map_err(|e| RunnerError::Spawn(format!("{}: {}", self.config.program, e)))?;
+        // Only the child should hold the slave, so the reader sees EOF when it exits
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| RunnerError::Spawn(e.to_string()))?;
+        let (chunks, mut received) = mpsc::unbounded_channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            // Linux reports EIO rather than EOF once the child side closes
+            while let Ok(read) = reader.read(&mut buffer) {
+                if read == 0 || chunks.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut killer = child.clone_killer();
+        let wait = tokio::task::spawn_blocking(move || child.wait());
+
+        let mut output = Vec::new();
+        let collect = async {
+            while let Some(chunk) = received.recv().await {
+                sink.send(&chunk);
+                output.extend_from_slice(&chunk);
+            }
+        };
+
+        let collected = match self.config.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, collect).await.is_ok(),
+            None => {
+                collect.await;
+                true
+            }
+        };
+        if !collected {
+            let _ = killer.kill();
+            return Err(RunnerError::TimedOut(self.config.timeout.unwrap_or_default()));
+        }
+
+        let status = wait
+            .await
+            .map_err(|e| RunnerError::Io(std::io::Error::other(e.to_string())))??;
+        drop(pair.master);
+
+        Ok(RunOutcome {
+            exit_code: Some(status.exit_code()),
+            output: String::from_utf8_lossy(&output).into_owned(),
+            duration: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{AgentRole, TaskPriority};
+
+    fn task(description: &str) -> Task {
+        Task::new("Parser".to_string(), description.to_string(), AgentRole::Implementer, TaskPriority::Normal)
+    }
+
+    fn shell_runner(script: &str) -> PtyRunner {
+        PtyRunner::new(PtyRunnerConfig {
+            program: "sh".to_string(),
+            // The prompt lands in $0
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout: Some(Duration::from_secs(10)),
+            ..PtyRunnerConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_build_prompt_includes_role_and_task() {
+        let agent = Agent::new(AgentRole::Tester, 1);
+        let mut task = task("Cover the tokenizer");
+        task.context.files.push("src/lexer.rs".to_string());
+
+        let prompt = build_prompt(&agent, &task);
+        assert!(prompt.starts_with(&agent.system_prompt()));
+        assert!(prompt.contains("# Task: Parser"));
+        assert!(prompt.contains("Cover the tokenizer"));
+        assert!(prompt.contains("- src/lexer.rs"));
+    }
+
+    #[test]
+    fn test_classify_failure() {
+        assert_eq!(classify_failure(Some(1), "API Error: 429 Too Many Requests"), ErrorType::RateLimitError);
+        assert_eq!(classify_failure(Some(1), "\x1b[31mError:\x1b[0m Invalid API key"), ErrorType::AuthError);
+        assert_eq!(classify_failure(Some(1), "API Error: 529 Overloaded"), ErrorType::ResourceUnavailable);
+        assert_eq!(classify_failure(Some(1), "error: connection refused"), ErrorType::NetworkError);
+        // Only error lines count, and the latest one wins
+        assert_eq!(
+            classify_failure(Some(1), "Reading the network module\nError: rate limit exceeded\n"),
+            ErrorType::RateLimitError
+        );
+        assert_eq!(classify_failure(Some(2), "Reading the network module\n"), ErrorType::Fatal);
+        assert_eq!(classify_failure(None, ""), ErrorType::Retryable);
+    }
+
+    #[test]
+    fn test_classify_failure_needs_http_context_for_status_codes() {
+        assert_eq!(classify_failure(Some(1), "Error: HTTP 503"), ErrorType::ResourceUnavailable);
+        assert_eq!(classify_failure(Some(1), "request failed with status code 401"), ErrorType::AuthError);
+        assert_eq!(classify_failure(Some(1), "API Error: 429 {\"type\":\"error\"}"), ErrorType::RateLimitError);
+
+        // Line numbers, ports, counts and error codes are not statuses
+        for output in [
+            "error: src/http/client.rs:429:17: mismatched types",
+            "error: could not reach the API at localhost:503",
+            "test result: FAILED. 401 passed; 2 failed",
+            "error[E0401]: can't use generic parameters from outer item",
+            "error: status check failed after 5290 attempts",
+            "error: api handler at line 529 panicked",
+        ] {
+            assert_eq!(classify_failure(Some(1), output), ErrorType::Fatal, "{}", output);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pty_runner_streams_output_and_exit_status() {
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let runner = shell_runner("printf 'Implementing parser\\n'; printf 'Error: rate limit exceeded\\n'; exit 3");
+        let (sink, mut chunks) = OutputSink::channel();
+
+        let outcome = runner.run(&agent, &task("Write it"), 1, sink).await.unwrap();
+
+        assert_eq!(outcome.exit_code, Some(3));
+        assert!(outcome.output.contains("Implementing parser"));
+        assert_eq!(outcome.error_type(), Some(ErrorType::RateLimitError));
+
+        let mut streamed = Vec::new();
+        while let Ok(chunk) = chunks.try_recv() {
+            streamed.extend(chunk);
+        }
+        assert_eq!(String::from_utf8_lossy(&streamed), outcome.output);
+    }
+
+    #[tokio::test]
+    async fn test_pty_runner_passes_prompt_and_runs_in_a_tty() {
+        let agent = Agent::new(AgentRole::Debugger, 1);
+        let runner = shell_runner("test -t 1 && printf '%s' \"$0\" | head -n 1");
+
+        let outcome = runner.run(&agent, &task("Fix it"), 1, OutputSink::discard()).await.unwrap();
+
+        assert!(outcome.success());
+        assert!(outcome.output.contains("Debugger"));
+    }
+
+    #[tokio::test]
+    async fn test_pty_runner_errors() {
+        let agent = Agent::new(AgentRole::Implementer, 1);
+
+        let missing = PtyRunner::new(PtyRunnerConfig {
+            program: "/nonexistent/agent-cli".to_string(),
+            ..PtyRunnerConfig::default()
+        });
+        let error = missing.run(&agent, &task("Write it"), 1, OutputSink::discard()).await.unwrap_err();
+        assert_eq!(error.error_type(), ErrorType::Fatal);
+
+        let slow = PtyRunner::new(PtyRunnerConfig {
+            timeout: Some(Duration::from_millis(200)),
+            ..shell_runner("sleep 5").config().clone()
+        });
+        let error = slow.run(&agent, &task("Write it"), 1, OutputSink::discard()).await.unwrap_err();
+        assert!(matches!(error, RunnerError::TimedOut(_)));
+    }
+}
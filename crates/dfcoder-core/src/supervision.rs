@@ -1,12 +1,17 @@
 //! Context-aware supervision system for agent management
 
 use crate::agents::*;
+use crate::estimation::CompletionEstimate;
+use crate::persistence::SupervisionState;
 use dfcoder_baml::{classify_activity, ActivityClass, ActivityType, EmotionalState};
+use dfcoder_types::instant_serde;
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
 use thiserror::Error;
 
+pub use dfcoder_types::SupervisionUrgency;
+
 /// Supervision request generated when an agent needs help
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -16,7 +21,7 @@ pub struct SupervisionRequest {
     pub options: Vec<SupervisionOption>,
     pub timeout: Duration,
     pub urgency: SupervisionUrgency,
-    #[serde(skip)]
+    #[serde(with = "instant_serde")]
     pub created_at: Instant,
 }
 
@@ -56,15 +61,6 @@ pub enum SupervisionAction {
     RestartAgent,
 }
 
-/// Urgency levels for supervision requests
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub enum SupervisionUrgency {
-    Low,
-    Medium,
-    High,
-    Critical,
-}
-
 /// Errors that can occur in the supervision system
 #[derive(Debug, Error)]
 pub enum SupervisionError {
@@ -94,7 +90,7 @@ pub struct SupervisionEvent {
     pub agent_id: AgentId,
     pub event_type: SupervisionEventType,
     pub context: String,
-    #[serde(skip)]
+    #[serde(with = "instant_serde")]
     pub timestamp: Instant,
     pub resolution: Option<SupervisionAction>,
 }
@@ -142,11 +138,35 @@ impl SupervisionSystem {
         self.stuck_threshold = threshold;
     }
 
+    /// Capture open requests and the supervision history
+    pub fn export_state(&self) -> SupervisionState {
+        SupervisionState {
+            active_requests: self.active_requests.iter().map(|(id, request)| (id.clone(), request.clone())).collect(),
+            history: self.supervision_history.clone(),
+        }
+    }
+
+    /// Replace open requests and history with persisted ones, keeping settings
+    pub fn restore_state(&mut self, state: SupervisionState) {
+        self.active_requests = state.active_requests.into_iter().collect();
+        self.supervision_history = state.history;
+    }
+
     /// Check if an agent needs supervision based on activity classification
     pub async fn check_supervision_need(
         &mut self,
         agent: &Agent,
         recent_output: &str,
+    ) -> Result<Option<SupervisionRequest>, SupervisionError> {
+        self.check_supervision_need_with_estimate(agent, recent_output, None).await
+    }
+
+    /// Like `check_supervision_need`, quoting how long the agent's task has left
+    pub async fn check_supervision_need_with_estimate(
+        &mut self,
+        agent: &Agent,
+        recent_output: &str,
+        estimate: Option<CompletionEstimate>,
     ) -> Result<Option<SupervisionRequest>, SupervisionError> {
         // Skip if already has active supervision request
         if self.active_requests.contains_key(&agent.id) {
@@ -154,11 +174,14 @@ impl SupervisionSystem {
         }
 
         // Classify the agent's activity
-        let activity_class = classify_activity(recent_output).await;
+        let mut activity_class = classify_activity(recent_output).await;
+        if let Some(estimate) = &estimate {
+            activity_class.estimated_completion = Some(estimate.remaining);
+        }
 
         // Generate supervision request if needed
         if activity_class.needs_help {
-            let request = self.generate_supervision_request(agent, &activity_class, recent_output)?;
+            let request = self.generate_supervision_request(agent, &activity_class, recent_output, estimate.as_ref())?;
             self.active_requests.insert(agent.id.clone(), request.clone());
             
             // Record event
@@ -215,12 +238,15 @@ impl SupervisionSystem {
         agent_id: &AgentId,
         option_id: u32,
     ) -> Result<SupervisionAction, SupervisionError> {
-        let request = self.active_requests.remove(agent_id)
+        let request = self.active_requests.get(agent_id)
             .ok_or_else(|| SupervisionError::AgentNotFound(agent_id.clone()))?;
 
+        // Validate the choice before closing the request so a bad id can be retried
         let option = request.options.iter()
             .find(|o| o.id == option_id)
-            .ok_or_else(|| SupervisionError::InvalidOption(option_id))?;
+            .ok_or(SupervisionError::InvalidOption(option_id))?
+            .clone();
+        self.active_requests.remove(agent_id);
 
         let action = option.action.clone();
 
@@ -314,8 +340,9 @@ impl SupervisionSystem {
         agent: &Agent,
         activity: &ActivityClass,
         recent_output: &str,
+        estimate: Option<&CompletionEstimate>,
     ) -> Result<SupervisionRequest, SupervisionError> {
-        let context = format!(
+        let mut context = format!(
             "Agent '{}' (role: {}) needs supervision.\n\
             Current task: {:?}\n\
             Activity type: {:?}\n\
@@ -326,6 +353,12 @@ impl SupervisionSystem {
             activity.primary, activity.emotional_state,
             activity.confidence * 100.0, recent_output
         );
+        if let Some(estimate) = estimate {
+            context.push_str(&format!(
+                "\nEstimated time left: {}m (likely {}-{}m)",
+                minutes(estimate.remaining), minutes(estimate.low), minutes(estimate.high)
+            ));
+        }
 
         let options = generate_dialogue_options(agent, activity, recent_output);
         let urgency = determine_urgency(activity);
@@ -424,13 +457,14 @@ pub fn generate_dialogue_options(
         _ => {}
     }
 
-    // Always include standard options
+    // Always include standard options; letting the agent carry on takes as
+    // long as its task has left
     options.push(SupervisionOption {
         id: option_id,
         text: "Let agent continue for now".to_string(),
         action: SupervisionAction::IgnoreForNow,
         icon: "⏭️".to_string(),
-        estimated_time: Duration::from_secs(0),
+        estimated_time: activity.estimated_completion.unwrap_or_default(),
     });
     option_id += 1;
 
@@ -456,6 +490,11 @@ pub fn determine_urgency(activity: &ActivityClass) -> SupervisionUrgency {
     }
 }
 
+/// Whole minutes, rounded to nearest
+fn minutes(duration: Duration) -> u64 {
+    (duration.as_secs() + 30) / 60
+}
+
 impl Default for SupervisionSystem {
     fn default() -> Self {
         Self::new()
@@ -466,6 +505,7 @@ impl Default for SupervisionSystem {
 mod tests {
     use super::*;
     use crate::AgentRole;
+    use dfcoder_baml::ClassificationSource;
 
     #[tokio::test]
     async fn test_supervision_request_generation() {
@@ -493,6 +533,7 @@ mod tests {
             needs_help: true,
             emotional_state: EmotionalState::Frustrated,
             estimated_completion: None,
+            source: ClassificationSource::Rules,
         };
 
         let options = generate_dialogue_options(&agent, &activity, "I'm stuck on this error");
@@ -511,6 +552,7 @@ mod tests {
             needs_help: true,
             emotional_state: EmotionalState::Desperate,
             estimated_completion: None,
+            source: ClassificationSource::Rules,
         };
         assert_eq!(determine_urgency(&desperate), SupervisionUrgency::Critical);
 
@@ -520,6 +562,7 @@ mod tests {
             needs_help: true,
             emotional_state: EmotionalState::Frustrated,
             estimated_completion: None,
+            source: ClassificationSource::Rules,
         };
         assert_eq!(determine_urgency(&frustrated), SupervisionUrgency::High);
 
@@ -529,6 +572,7 @@ mod tests {
             needs_help: true,
             emotional_state: EmotionalState::Cautious,
             estimated_completion: None,
+            source: ClassificationSource::Rules,
         };
         assert_eq!(determine_urgency(&low_confidence), SupervisionUrgency::Medium);
     }
@@ -549,6 +593,29 @@ mod tests {
         assert!(supervision.get_active_request(&agent.id).is_none());
     }
 
+    #[tokio::test]
+    async fn test_estimate_is_quoted_in_the_request() {
+        let mut supervision = SupervisionSystem::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let estimate = CompletionEstimate {
+            remaining: Duration::from_secs(1200),
+            low: Duration::from_secs(600),
+            high: Duration::from_secs(2400),
+            samples: 3,
+        };
+
+        let request = supervision
+            .check_supervision_need_with_estimate(&agent, "I'm stuck and confused, need help", Some(estimate))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(request.context.ends_with("Estimated time left: 20m (likely 10-40m)"));
+        let carry_on = request.options.iter()
+            .find(|option| matches!(option.action, SupervisionAction::IgnoreForNow))
+            .unwrap();
+        assert_eq!(carry_on.estimated_time, Duration::from_secs(1200));
+    }
+
     #[test]
     fn test_expired_request_cleanup() {
         let mut supervision = SupervisionSystem::new();
@@ -0,0 +1,90 @@
+//! Client side of the daemon socket, for the TUI, plugin and CLI tools
+
+use crate::DaemonError;
+use dfcoder_types::protocol::*;
+use dfcoder_types::SystemEvent;
+use std::collections::VecDeque;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+/// Connection to a running dfcoderd
+#[derive(Debug)]
+pub struct DaemonClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+    /// Events that arrived while waiting for a response
+    pending_events: VecDeque<SystemEvent>,
+}
+
+impl DaemonClient {
+    /// Attach to the daemon listening on `path`
+    pub async fn connect(path: &Path) -> Result<Self, DaemonError> {
+        let stream = UnixStream::connect(path).await?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+            pending_events: VecDeque::new(),
+        })
+    }
+
+    /// Attach to the daemon and check it speaks our protocol version
+    pub async fn connect_with_handshake(path: &Path, client: &str) -> Result<Self, DaemonError> {
+        let mut connection = Self::connect(path).await?;
+        let response = connection.request(DaemonRequest::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            client: client.to_string(),
+        }).await?;
+
+        match response {
+            DaemonResponse::Hello { .. } => Ok(connection),
+            DaemonResponse::Error { message, .. } => Err(DaemonError::Protocol(message)),
+            other => Err(DaemonError::Protocol(format!("unexpected handshake response: {:?}", other))),
+        }
+    }
+
+    /// Send a request and wait for its response, buffering any events received meanwhile
+    pub async fn request(&mut self, request: DaemonRequest) -> Result<DaemonResponse, DaemonError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut encoded = serde_json::to_string(&ClientMessage { id, request })?;
+        encoded.push('\n');
+        self.writer.write_all(encoded.as_bytes()).await?;
+
+        loop {
+            match self.read_message().await? {
+                ServerMessage::Response { id: response_id, response } if response_id == id => return Ok(response),
+                ServerMessage::Response { id: response_id, .. } => {
+                    tracing::warn!("Discarding response to unknown request {}", response_id);
+                }
+                ServerMessage::Event { event } => self.pending_events.push_back(event),
+            }
+        }
+    }
+
+    /// Wait for the next event on a subscribed connection
+    pub async fn next_event(&mut self) -> Result<SystemEvent, DaemonError> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(event);
+        }
+
+        loop {
+            match self.read_message().await? {
+                ServerMessage::Event { event } => return Ok(event),
+                ServerMessage::Response { id, .. } => {
+                    tracing::warn!("Discarding response to request {} while waiting for events", id);
+                }
+            }
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<ServerMessage, DaemonError> {
+        let line = self.lines.next_line().await?.ok_or(DaemonError::ConnectionClosed)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
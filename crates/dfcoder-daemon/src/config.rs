@@ -0,0 +1,213 @@
+//! Daemon configuration
+
+use dfcoder_baml::{ActivityClassifier, BamlConfig, CacheConfig, ProviderKind, UsageBudget};
+use dfcoder_mcp::McpConfig;
+use dfcoder_types::duration_serde;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{DaemonError, SPOOL_MAX_BYTES};
+
+/// Runtime configuration for dfcoderd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Path of the Unix socket clients attach to
+    pub socket_path: PathBuf,
+    /// How often queued tasks are matched against idle agents
+    #[serde(with = "duration_serde")]
+    pub assign_interval: Duration,
+    /// How often working agents are checked for lack of progress
+    #[serde(with = "duration_serde")]
+    pub stuck_check_interval: Duration,
+    /// How long an agent may work without activity before it counts as stuck
+    #[serde(with = "duration_serde")]
+    pub stuck_threshold: Duration,
+    /// How often expired supervision requests are cleaned up and auto-resolved
+    #[serde(with = "duration_serde")]
+    pub cleanup_interval: Duration,
+    /// Resolve safe supervision requests without waiting for a human
+    pub auto_supervision: bool,
+    /// Spool file the Zellij plugin appends requests to, if it is in use
+    pub spool_path: Option<PathBuf>,
+    /// How often the spool file is checked for new requests
+    #[serde(with = "duration_serde")]
+    pub spool_interval: Duration,
+    /// Size past which the spool file is rotated
+    pub spool_max_bytes: u64,
+    /// Directory the workshop is persisted to; state is only kept in memory when unset
+    pub state_dir: Option<PathBuf>,
+    /// How often changes are written to the state journal
+    #[serde(with = "duration_serde")]
+    pub persist_interval: Duration,
+    /// Rule files layered over the built-in classification rules, reloaded when they change
+    pub rule_files: Vec<PathBuf>,
+    /// MCP server sharing the daemon's workshop; none is served when unset
+    pub mcp: Option<McpConfig>,
+    /// Language model pane output is classified with; only keyword rules are used when unset
+    pub classifier: Option<ClassifierConfig>,
+}
+
+impl DaemonConfig {
+    /// Load configuration from a TOML file, falling back to defaults for missing keys
+    pub fn load(path: &Path) -> Result<Self, DaemonError> {
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|e| DaemonError::Config(e.to_string()))
+    }
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            socket_path: default_socket_path(),
+            assign_interval: Duration::from_secs(1),
+            stuck_check_interval: Duration::from_secs(30),
+            stuck_threshold: Duration::from_secs(300),
+            cleanup_interval: Duration::from_secs(5),
+            auto_supervision: false,
+            spool_path: None,
+            spool_interval: Duration::from_secs(1),
+            spool_max_bytes: SPOOL_MAX_BYTES,
+            state_dir: None,
+            persist_interval: Duration::from_secs(1),
+            rule_files: Vec::new(),
+            mcp: None,
+            classifier: None,
+        }
+    }
+}
+
+/// Model settings for activity classification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassifierConfig {
+    pub provider: ProviderKind,
+    /// Defaults to the provider's small model
+    pub model: Option<String>,
+    /// Defaults to the provider's usual endpoint
+    pub endpoint: Option<String>,
+    /// Environment variable holding the API key; defaults to the provider's usual one
+    pub api_key_var: Option<String>,
+    /// How long a single classification may take before rules are used instead
+    #[serde(with = "duration_serde")]
+    pub timeout: Duration,
+    /// Reuse classifications of repeated output; disabled when unset
+    pub cache: Option<CacheConfig>,
+    /// Limits past which the model is no longer called
+    pub budget: UsageBudget,
+}
+
+impl ClassifierConfig {
+    /// Client settings, with the API key read from the environment
+    pub fn baml_config(&self) -> BamlConfig {
+        let mut config = BamlConfig::for_provider(self.provider);
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        if let Some(endpoint) = &self.endpoint {
+            config.endpoint = endpoint.clone();
+        }
+        let api_key_var = self.api_key_var.as_deref().or(self.provider.api_key_var());
+        config.api_key = api_key_var
+            .and_then(|var| std::env::var(var).ok())
+            .filter(|key| !key.is_empty());
+        config.timeout = self.timeout;
+        config.cache = self.cache.clone();
+        config.budget = self.budget;
+        config
+    }
+
+    /// Build the classifier, falling back to keyword rules if the client cannot be set up
+    pub fn classifier(&self) -> ActivityClassifier {
+        ActivityClassifier::from_config(self.baml_config())
+    }
+}
+
+impl Default for ClassifierConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            model: None,
+            endpoint: None,
+            api_key_var: None,
+            timeout: BamlConfig::default().timeout,
+            cache: None,
+            budget: UsageBudget::default(),
+        }
+    }
+}
+
+/// Default socket location, preferring `$XDG_RUNTIME_DIR` over the temp directory
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dfcoderd.sock")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: DaemonConfig = toml::from_str(
+            r#"
+            socket_path = "/tmp/custom.sock"
+            stuck_threshold = 60
+            auto_supervision = true
+            spool_path = ".dfcoder/spool.jsonl"
+            state_dir = ".dfcoder/state"
+            rule_files = [".dfcoder/rules.toml"]
+
+            [mcp.transport]
+            transport_type = "Http"
+            port = 7878
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.socket_path, PathBuf::from("/tmp/custom.sock"));
+        assert_eq!(config.stuck_threshold, Duration::from_secs(60));
+        assert!(config.auto_supervision);
+        assert_eq!(config.spool_path, Some(PathBuf::from(".dfcoder/spool.jsonl")));
+        assert_eq!(config.state_dir, Some(PathBuf::from(".dfcoder/state")));
+        assert_eq!(config.rule_files, vec![PathBuf::from(".dfcoder/rules.toml")]);
+        assert_eq!(config.assign_interval, DaemonConfig::default().assign_interval);
+
+        let mcp = config.mcp.unwrap();
+        assert!(matches!(mcp.transport.transport_type, dfcoder_mcp::TransportType::Http));
+        assert_eq!(mcp.transport.port, Some(7878));
+        assert_eq!(mcp.server_name, "dfcoder");
+        assert!(config.classifier.is_none());
+    }
+
+    #[test]
+    fn test_classifier_config() {
+        let config: DaemonConfig = toml::from_str(
+            r#"
+            [classifier]
+            provider = "ollama"
+            model = "qwen2.5"
+            timeout = 3
+
+            [classifier.cache]
+            max_entries = 64
+
+            [classifier.budget]
+            daily_cost_usd = 2.5
+            "#,
+        )
+        .unwrap();
+
+        let baml = config.classifier.unwrap().baml_config();
+        assert_eq!(baml.provider, ProviderKind::Ollama);
+        assert_eq!(baml.model, "qwen2.5");
+        assert_eq!(baml.endpoint, ProviderKind::Ollama.default_endpoint());
+        assert_eq!(baml.api_key, None);
+        assert_eq!(baml.timeout, Duration::from_secs(3));
+        assert_eq!(baml.cache.unwrap().max_entries, 64);
+        assert_eq!(baml.budget.daily_cost_usd, Some(2.5));
+    }
+}
@@ -0,0 +1,593 @@
+//! The daemon's main loop
+
+use crate::server;
+use crate::spool::SpoolReader;
+use crate::{DaemonConfig, DaemonError, DaemonState};
+use dfcoder_baml::RuleSource;
+use dfcoder_core::StateStore;
+use dfcoder_mcp::{DFCoderMCPServer, McpConfig, McpService, TransportType};
+use dfcoder_types::protocol::DaemonResponse;
+use std::future::Future;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// dfcoderd: owns the workshop and serves it to every attached client
+#[derive(Debug)]
+pub struct Daemon {
+    config: DaemonConfig,
+    state: DaemonState,
+}
+
+impl Daemon {
+    /// Create a daemon around a fresh, empty workshop
+    pub async fn new(config: DaemonConfig) -> Self {
+        let state = match &config.classifier {
+            Some(classifier) => DaemonState::with_classifier(classifier.classifier()),
+            None => DaemonState::new(),
+        };
+        Self::with_state(config, state).await
+    }
+
+    /// Create a daemon around existing workshop state
+    pub async fn with_state(config: DaemonConfig, state: DaemonState) -> Self {
+        {
+            let mut supervision = state.supervision.lock().await;
+            supervision.set_stuck_threshold(config.stuck_threshold);
+            if config.auto_supervision {
+                supervision.enable_auto_supervision();
+            }
+        }
+
+        if !config.rule_files.is_empty() {
+            match RuleSource::load(config.rule_files.clone()) {
+                Ok(rules) => state.activities.lock().await.classifier_mut().set_rules(rules),
+                Err(e) => tracing::warn!("Using built-in classification rules: {}", e),
+            }
+        }
+
+        Self { config, state }
+    }
+
+    /// Shared state, for embedding the daemon in-process
+    pub fn state(&self) -> &DaemonState {
+        &self.state
+    }
+
+    /// Serve clients and run the scheduling loops until `shutdown` resolves
+    ///
+    /// With a `state_dir` configured, the persisted workshop replaces the
+    /// initial state before any client is served.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<(), DaemonError> {
+        let listener = server::bind(&self.config.socket_path).await?;
+        let mut store = match &self.config.state_dir {
+            Some(dir) => Some(self.recover(dir).await?),
+            None => None,
+        };
+        tracing::info!("dfcoderd listening on {}", self.config.socket_path.display());
+        let mcp = match &self.config.mcp {
+            Some(config) => Some(self.serve_mcp(config.clone())?),
+            None => None,
+        };
+
+        let mut assign = interval(self.config.assign_interval);
+        let mut stuck_check = interval(self.config.stuck_check_interval);
+        let mut cleanup = interval(self.config.cleanup_interval);
+        let mut spool_poll = interval(self.config.spool_interval);
+        let mut persist = interval(self.config.persist_interval);
+        for timer in [&mut assign, &mut stuck_check, &mut cleanup, &mut spool_poll, &mut persist] {
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        }
+
+        let mut spool = self.config.spool_path.clone().map(|path| {
+            tracing::info!("Ingesting requests from spool {}", path.display());
+            SpoolReader::new(path).with_max_bytes(self.config.spool_max_bytes)
+        });
+
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let state = self.state.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = server::serve_connection(stream, state).await {
+                                tracing::warn!("Client connection ended with error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::error!("Failed to accept client: {}", e),
+                },
+                _ = assign.tick() => {
+                    self.state.assign_pending().await;
+                }
+                _ = stuck_check.tick() => {
+                    self.state.check_stuck(self.config.stuck_threshold).await;
+                }
+                _ = cleanup.tick() => {
+                    self.state.cleanup_supervision().await;
+                }
+                _ = spool_poll.tick(), if spool.is_some() => {
+                    if let Some(reader) = spool.as_mut() {
+                        self.ingest_spool(reader).await;
+                    }
+                }
+                _ = persist.tick(), if store.is_some() => {
+                    if let Some(store) = store.as_mut() {
+                        self.persist(store).await;
+                    }
+                }
+            }
+        }
+
+        tracing::info!("dfcoderd shutting down");
+        if let Some((stop, served)) = mcp {
+            drop(stop);
+            let _ = served.await;
+        }
+        if let Some(store) = store.as_mut() {
+            self.persist(store).await;
+        }
+        let _ = std::fs::remove_file(&self.config.socket_path);
+        Ok(())
+    }
+
+    /// Serve MCP clients from the daemon's workshop until the returned sender is dropped
+    fn serve_mcp(&self, config: McpConfig) -> Result<(oneshot::Sender<()>, JoinHandle<()>), DaemonError> {
+        if matches!(config.transport.transport_type, TransportType::Stdio) {
+            return Err(DaemonError::Config(
+                "dfcoderd cannot serve MCP over stdio; serve a socket and relay it with dfcoder-mcp".to_string(),
+            ));
+        }
+
+        let server = DFCoderMCPServer::new(self.state.workshop.clone())
+            .with_pane_output(self.state.ingestion.clone());
+        let service = McpService::with_server(config, server)
+            .map_err(|e| DaemonError::Config(e.to_string()))?;
+        let (stop, stopped) = oneshot::channel::<()>();
+        let served = tokio::spawn(async move {
+            if let Err(e) = service.run(async { let _ = stopped.await; }).await {
+                tracing::error!("MCP server stopped: {}", e);
+            }
+        });
+        Ok((stop, served))
+    }
+
+    async fn recover(&self, dir: &std::path::Path) -> Result<StateStore, DaemonError> {
+        let store = StateStore::open(dir)?;
+        let state = store.state().clone();
+        tracing::info!(
+            "Recovered {} agents, {} queued and {} in-flight tasks from {}",
+            state.workshop.agents.len(),
+            state.workshop.queue.len(),
+            state.workshop.in_flight.len(),
+            dir.display(),
+        );
+        self.state.restore(state).await;
+        Ok(store)
+    }
+
+    async fn persist(&self, store: &mut StateStore) {
+        let state = self.state.persisted_state().await;
+        if let Err(e) = store.record(&state) {
+            tracing::error!("Failed to persist workshop state to {}: {}", store.dir().display(), e);
+        }
+    }
+
+    async fn ingest_spool(&self, reader: &mut SpoolReader) {
+        let messages = match reader.poll() {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::warn!("Failed to read spool {}: {}", reader.path().display(), e);
+                return;
+            }
+        };
+
+        for message in messages {
+            if let DaemonResponse::Error { code, message: error } = self.state.handle_request(message.request).await {
+                tracing::warn!("Spool request {} failed ({:?}): {}", message.id, code, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DaemonClient;
+    use dfcoder_core::*;
+    use dfcoder_types::protocol::*;
+    use dfcoder_types::SystemEvent;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::oneshot;
+
+    fn test_config(dir: &tempfile::TempDir) -> DaemonConfig {
+        DaemonConfig {
+            socket_path: dir.path().join("dfcoderd.sock"),
+            assign_interval: Duration::from_millis(10),
+            ..DaemonConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_one_workshop() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+
+        let mut first = connect_with_retry(&socket_path).await;
+        let mut second = DaemonClient::connect_with_handshake(&socket_path, "test").await.unwrap();
+        assert!(matches!(second.request(DaemonRequest::Subscribe).await.unwrap(), DaemonResponse::Subscribed));
+
+        let agent_id = match first.request(DaemonRequest::RegisterAgent { role: AgentRole::Tester, pane_id: 4 }).await.unwrap() {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        first.request(DaemonRequest::QueueTask {
+            task: TaskSpec {
+                title: "Cover the parser".to_string(),
+                description: "Write tests for the parser".to_string(),
+                required_role: AgentRole::Tester,
+                priority: TaskPriority::High,
+                dependencies: Vec::new(),
+                files: Vec::new(),
+                estimated_duration_secs: None,
+            },
+        }).await.unwrap();
+
+        // The assign loop picks the task up without any client asking, and subscribers hear about it
+        match second.next_event().await.unwrap() {
+            SystemEvent::AgentStateChanged { agent_id: changed, new_state, .. } => {
+                assert_eq!(changed, agent_id);
+                assert_eq!(new_state.status, dfcoder_types::AgentStatus::Working);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let response = first.request(DaemonRequest::ReportOutput {
+            agent_id: agent_id.clone(),
+            output: "I'm stuck and confused, need help".to_string(),
+        }).await.unwrap();
+        assert!(matches!(response, DaemonResponse::OutputAccepted { supervision: Some(_) }));
+
+        match second.request(DaemonRequest::ListSupervision).await.unwrap() {
+            DaemonResponse::Supervision { requests } => {
+                assert_eq!(requests.len(), 1);
+                assert_eq!(requests[0].agent_id, agent_id);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        // Whether it arrived before or after that response, the event is not lost
+        assert!(matches!(second.next_event().await.unwrap(), SystemEvent::SupervisionRequested { .. }));
+
+        match second.request(DaemonRequest::GetStatus).await.unwrap() {
+            DaemonResponse::Status { status } => {
+                assert_eq!(status.total_agents, 1);
+                assert_eq!(status.queue_length, 0);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        assert!(!socket_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_completed_task_frees_the_agent_for_the_next() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+        let mut client = connect_with_retry(&socket_path).await;
+        assert!(matches!(client.request(DaemonRequest::Subscribe).await.unwrap(), DaemonResponse::Subscribed));
+
+        let agent_id = match client.request(DaemonRequest::RegisterAgent { role: AgentRole::Tester, pane_id: 4 }).await.unwrap() {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        for title in ["Unit tests", "Integration tests"] {
+            client.request(DaemonRequest::QueueTask {
+                task: TaskSpec {
+                    title: title.to_string(),
+                    description: title.to_string(),
+                    required_role: AgentRole::Tester,
+                    priority: TaskPriority::Normal,
+                    dependencies: Vec::new(),
+                    files: Vec::new(),
+                    estimated_duration_secs: None,
+                },
+            }).await.unwrap();
+        }
+
+        let first = next_assignment(&mut client).await;
+        match client.request(DaemonRequest::CompleteTask { agent_id: agent_id.clone(), task_id: first.clone() }).await.unwrap() {
+            DaemonResponse::TaskCompleted { task_id } => assert_eq!(task_id, first),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // The assign loop hands the freed agent the other task
+        let second = next_assignment(&mut client).await;
+        assert_ne!(second, first);
+        match client.request(DaemonRequest::GetStatus).await.unwrap() {
+            DaemonResponse::Status { status } => {
+                assert_eq!(status.tasks_completed, 1);
+                assert_eq!(status.queue_length, 0);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    /// The task the next agent state change shows an agent taking on
+    async fn next_assignment(client: &mut DaemonClient) -> String {
+        loop {
+            if let SystemEvent::AgentStateChanged { new_state, .. } = client.next_event().await.unwrap() {
+                if let Some(task_id) = new_state.current_task {
+                    return task_id;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_request_gets_error_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+
+        let stream = connect_stream_with_retry(&socket_path).await;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        writer.write_all(b"{\"id\":3,\"request\":{\"type\":\"launch_missiles\"}}\n").await.unwrap();
+        let message: ServerMessage = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert!(matches!(
+            message,
+            ServerMessage::Response { response: DaemonResponse::Error { code: ErrorCode::InvalidRequest, .. }, .. }
+        ));
+
+        // The connection stays usable after a bad line
+        writer.write_all(b"{\"id\":4,\"request\":{\"type\":\"ping\"}}\n").await.unwrap();
+        let message: ServerMessage = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert!(matches!(message, ServerMessage::Response { id: 4, response: DaemonResponse::Pong }));
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_second_daemon_refuses_live_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(&dir);
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config.clone()).await.run(async { let _ = stopped.await; }));
+        connect_with_retry(&socket_path).await;
+
+        let result = Daemon::new(config).await.run(std::future::pending()).await;
+        assert!(matches!(result, Err(DaemonError::AlreadyRunning(_))));
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spool_requests_reach_the_workshop() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool_path = dir.path().join("spool.jsonl");
+        let config = DaemonConfig {
+            spool_path: Some(spool_path.clone()),
+            spool_interval: Duration::from_millis(10),
+            ..test_config(&dir)
+        };
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+        let mut client = connect_with_retry(&socket_path).await;
+
+        let lines: Vec<String> = [
+            DaemonRequest::RegisterAgent { role: AgentRole::Debugger, pane_id: 12 },
+            DaemonRequest::RegisterAgent { role: AgentRole::Debugger, pane_id: 12 },
+            DaemonRequest::ReportPaneOutput { pane_id: 12, output: "I'm stuck and confused, need help".to_string() },
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, request)| serde_json::to_string(&ClientMessage { id: id as u64, request }).unwrap() + "\n")
+        .collect();
+        std::fs::write(&spool_path, lines.concat()).unwrap();
+
+        let mut requests = Vec::new();
+        for _ in 0..100 {
+            if let DaemonResponse::Supervision { requests: open } = client.request(DaemonRequest::ListSupervision).await.unwrap() {
+                requests = open;
+            }
+            if !requests.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(requests.len(), 1);
+
+        match client.request(DaemonRequest::ListAgents).await.unwrap() {
+            DaemonResponse::Agents { agents } => {
+                assert_eq!(agents.len(), 1);
+                assert_eq!(agents[0].pane_id, 12);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restarted_daemon_recovers_queue_and_assignments() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DaemonConfig {
+            state_dir: Some(dir.path().join("state")),
+            persist_interval: Duration::from_millis(10),
+            ..test_config(&dir)
+        };
+        let socket_path = config.socket_path.clone();
+
+        let handle = tokio::spawn(Daemon::new(config.clone()).await.run(std::future::pending()));
+        let mut client = connect_with_retry(&socket_path).await;
+        client.request(DaemonRequest::RegisterAgent { role: AgentRole::Debugger, pane_id: 7 }).await.unwrap();
+        for (title, role) in [("Fix the login crash", AgentRole::Debugger), ("Scaffold the CLI", AgentRole::Scaffolder)] {
+            client.request(DaemonRequest::QueueTask {
+                task: TaskSpec {
+                    title: title.to_string(),
+                    description: title.to_string(),
+                    required_role: role,
+                    priority: TaskPriority::Normal,
+                    dependencies: Vec::new(),
+                    files: Vec::new(),
+                    estimated_duration_secs: None,
+                },
+            }).await.unwrap();
+        }
+
+        // Wait for the assignment to reach the journal, then crash without shutting down
+        let state_dir = dir.path().join("state");
+        for _ in 0..100 {
+            let store = StateStore::open(&state_dir).unwrap();
+            if store.state().workshop.in_flight.len() == 1 && store.state().workshop.queue.len() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.abort();
+        let _ = handle.await;
+        std::fs::remove_file(&socket_path).unwrap();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+        let mut client = connect_with_retry(&socket_path).await;
+
+        match client.request(DaemonRequest::ListAgents).await.unwrap() {
+            DaemonResponse::Agents { agents } => {
+                assert_eq!(agents.len(), 1);
+                assert_eq!(agents[0].status, dfcoder_types::AgentStatus::Working);
+                assert!(agents[0].current_task.is_some());
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match client.request(DaemonRequest::ListQueue).await.unwrap() {
+            DaemonResponse::Queue { tasks } => {
+                assert_eq!(tasks.len(), 1);
+                assert_eq!(tasks[0].title, "Scaffold the CLI");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mcp_clients_share_the_daemon_workshop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mcp_path = dir.path().join("dfcoder-mcp.sock");
+        let config = DaemonConfig {
+            mcp: Some(McpConfig {
+                transport: dfcoder_mcp::TransportConfig {
+                    transport_type: TransportType::Unix,
+                    address: Some(mcp_path.display().to_string()),
+                    ..Default::default()
+                },
+                ..McpConfig::default()
+            }),
+            ..test_config(&dir)
+        };
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+
+        let mut client = connect_with_retry(&socket_path).await;
+        let agent_id = match client.request(DaemonRequest::RegisterAgent { role: AgentRole::Debugger, pane_id: 7 }).await.unwrap() {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let (reader, mut writer) = connect_stream_with_retry(&mcp_path).await.into_split();
+        let mut replies = BufReader::new(reader).lines();
+        let script = [
+            serde_json::json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {
+                "protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": { "name": "test", "version": "0.1" }
+            }}),
+            serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {
+                "name": "get_agent_status", "arguments": { "agent_id": agent_id }
+            }}),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+                "name": "create_task", "arguments": { "title": "Trace the crash", "description": "Find the panic", "role": "Debugger" }
+            }}),
+        ];
+        for message in &script {
+            writer.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+        }
+        assert_eq!(next_json(&mut replies).await["id"], 0);
+
+        // An agent registered over the daemon socket is visible to MCP clients
+        let status = next_json(&mut replies).await;
+        assert_eq!(status["result"]["isError"], false);
+        assert!(status["result"]["content"][0]["text"].as_str().unwrap().contains(&agent_id));
+
+        // And work created over MCP lands in the daemon's workshop
+        assert_eq!(next_json(&mut replies).await["result"]["isError"], false);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match client.request(DaemonRequest::ListAgents).await.unwrap() {
+                    DaemonResponse::Agents { agents } if agents[0].current_task.is_some() => break,
+                    _ => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        }).await.unwrap();
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        assert!(!mcp_path.exists());
+    }
+
+    async fn next_json(lines: &mut tokio::io::Lines<BufReader<tokio::net::unix::OwnedReadHalf>>) -> serde_json::Value {
+        let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn connect_with_retry(path: &std::path::Path) -> DaemonClient {
+        for _ in 0..100 {
+            if let Ok(client) = DaemonClient::connect(path).await {
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("daemon never started listening");
+    }
+
+    async fn connect_stream_with_retry(path: &std::path::Path) -> tokio::net::UnixStream {
+        for _ in 0..100 {
+            if let Ok(stream) = tokio::net::UnixStream::connect(path).await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("daemon never started listening");
+    }
+}
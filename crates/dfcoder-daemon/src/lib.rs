@@ -0,0 +1,41 @@
+//! dfcoderd: the long-running daemon that owns the workshop
+//!
+//! A single `WorkshopManager`, `SupervisionSystem` and `ActivityTracker` live
+//! here; the TUI, the Zellij plugin and CLI tools attach over a Unix socket
+//! instead of each building their own. The wire format is defined in
+//! `dfcoder_types::protocol`.
+
+pub mod client;
+pub mod config;
+pub mod daemon;
+pub mod server;
+pub mod spool;
+pub mod state;
+
+pub use client::*;
+pub use config::*;
+pub use daemon::*;
+pub use spool::*;
+pub use state::*;
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors raised by the daemon and its clients
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Persistence error: {0}")]
+    Persistence(#[from] dfcoder_core::PersistenceError),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error("Another dfcoderd is already listening on {0}")]
+    AlreadyRunning(PathBuf),
+    #[error("Daemon closed the connection")]
+    ConnectionClosed,
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+}
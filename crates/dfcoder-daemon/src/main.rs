@@ -1,12 +1,73 @@
-use anyhow::Result;
-use tracing_subscriber;
+use anyhow::{bail, Context, Result};
+use dfcoder_daemon::{Daemon, DaemonConfig};
+use dfcoder_mcp::{default_mcp_socket_path, McpConfig, TransportConfig, TransportType};
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: dfcoderd [--config <file.toml>] [--socket <path>] [--state-dir <dir>] [--auto-supervision] [--mcp]
+
+--mcp serves MCP from the daemon's workshop on the Unix socket dfcoder-mcp
+relays to, unless the config file's [mcp] section picks another transport.";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
-    
+
+    let config = parse_args(std::env::args().skip(1))?;
+
     tracing::info!("Starting dfcoderd daemon...");
-    println!("DFCoder Daemon (dfcoderd) - Implementation in progress!");
-    
+    Daemon::new(config).await
+        .run(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
     Ok(())
 }
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<DaemonConfig> {
+    let mut config_path = None;
+    let mut socket_path = None;
+    let mut state_dir = None;
+    let mut auto_supervision = false;
+    let mut mcp = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--socket" => socket_path = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--state-dir" => state_dir = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--auto-supervision" => auto_supervision = true,
+            "--mcp" => mcp = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => bail!("unknown argument '{}'\n{}", other, USAGE),
+        }
+    }
+
+    let mut config = match config_path {
+        Some(path) => DaemonConfig::load(&path)
+            .with_context(|| format!("failed to load {}", path.display()))?,
+        None => DaemonConfig::default(),
+    };
+    if let Some(path) = socket_path {
+        config.socket_path = path;
+    }
+    if let Some(dir) = state_dir {
+        config.state_dir = Some(dir);
+    }
+    config.auto_supervision |= auto_supervision;
+    if mcp && config.mcp.is_none() {
+        config.mcp = Some(McpConfig {
+            transport: TransportConfig {
+                transport_type: TransportType::Unix,
+                address: Some(default_mcp_socket_path().display().to_string()),
+                ..TransportConfig::default()
+            },
+            ..McpConfig::default()
+        });
+    }
+
+    Ok(config)
+}
@@ -0,0 +1,110 @@
+//! Unix socket listener serving the control protocol
+
+use crate::{DaemonError, DaemonState};
+use dfcoder_types::protocol::*;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+/// Bind the daemon socket, replacing a stale socket file left by a crashed daemon
+pub async fn bind(path: &Path) -> Result<UnixListener, DaemonError> {
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(DaemonError::AlreadyRunning(path.to_path_buf()));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+/// Serve requests from one client until it disconnects
+///
+/// Responses and subscribed events share the connection, so everything is
+/// funnelled through a single writer task.
+pub async fn serve_connection(stream: UnixStream, state: DaemonState) -> Result<(), DaemonError> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<ServerMessage>();
+    let writer_task: JoinHandle<Result<(), DaemonError>> = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let mut encoded = serde_json::to_string(&message)?;
+            encoded.push('\n');
+            writer.write_all(encoded.as_bytes()).await?;
+        }
+        Ok(())
+    });
+
+    let mut subscription: Option<JoinHandle<()>> = None;
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message = match serde_json::from_str::<ClientMessage>(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                // Without a parseable id the best we can do is answer id 0
+                let response = DaemonResponse::error(ErrorCode::InvalidRequest, format!("Invalid request: {}", e));
+                let _ = outgoing.send(ServerMessage::Response { id: 0, response });
+                continue;
+            }
+        };
+
+        let response = match message.request {
+            DaemonRequest::Subscribe => {
+                if subscription.is_none() {
+                    subscription = Some(forward_events(state.subscribe(), outgoing.clone()));
+                }
+                DaemonResponse::Subscribed
+            }
+            DaemonRequest::Unsubscribe => {
+                if let Some(task) = subscription.take() {
+                    task.abort();
+                }
+                DaemonResponse::Unsubscribed
+            }
+            request => state.handle_request(request).await,
+        };
+
+        if outgoing.send(ServerMessage::Response { id: message.id, response }).is_err() {
+            break;
+        }
+    }
+
+    if let Some(task) = subscription {
+        task.abort();
+    }
+    drop(outgoing);
+    writer_task.await.map_err(|e| DaemonError::Io(std::io::Error::other(e)))?
+}
+
+/// Copy published events onto a connection until it closes
+fn forward_events(
+    mut events: broadcast::Receiver<dfcoder_types::SystemEvent>,
+    outgoing: mpsc::UnboundedSender<ServerMessage>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if outgoing.send(ServerMessage::Event { event }).is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Subscriber fell behind and missed {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    })
+}
@@ -0,0 +1,212 @@
+//! Ingesting requests from a spool file
+//!
+//! Zellij plugins run under WASI and cannot open the daemon's socket, so the
+//! plugin appends `ClientMessage` lines to a file instead. Requests read from
+//! the spool are handled like socket requests, but nobody reads the responses.
+//!
+//! The daemon keeps the spool from growing without bound by renaming it aside
+//! once it has read past `max_bytes`. The plugin opens the spool for every
+//! batch, so its next write starts a fresh file; the renamed one is drained
+//! once more for writes that raced the rename, then removed.
+
+use dfcoder_types::protocol::ClientMessage;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Size past which the spool is rotated, unless configured otherwise
+pub const SPOOL_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Follows a JSON-lines spool file from where it last stopped reading
+#[derive(Debug)]
+pub struct SpoolReader {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+    max_bytes: u64,
+    /// Renamed spool still to be drained, read up to `offset`
+    rotated: Option<PathBuf>,
+}
+
+impl SpoolReader {
+    /// Start following `path` from its current end, skipping anything already in it
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let offset = std::fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+        Self { path, offset, partial: String::new(), max_bytes: SPOOL_MAX_BYTES, rotated: None }
+    }
+
+    /// Rotate the spool once this many bytes have been read from it
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the messages appended since the last poll
+    ///
+    /// A missing file yields nothing; a truncated one is read again from the start.
+    /// Lines that fail to parse are logged and skipped. A spool read past
+    /// `max_bytes` is renamed aside, drained on the next poll and removed.
+    pub fn poll(&mut self) -> std::io::Result<Vec<ClientMessage>> {
+        let mut messages = Vec::new();
+
+        if let Some(rotated) = self.rotated.take() {
+            messages.extend(self.read_from(&rotated)?);
+            if !self.partial.is_empty() {
+                tracing::warn!("Dropping unterminated line at the end of {}", rotated.display());
+                self.partial.clear();
+            }
+            if let Err(e) = std::fs::remove_file(&rotated) {
+                tracing::warn!("Failed to remove rotated spool {}: {}", rotated.display(), e);
+            }
+            self.offset = 0;
+        }
+
+        let path = self.path.clone();
+        messages.extend(self.read_from(&path)?);
+
+        // Failing to rotate only delays it; the messages already read must still be returned
+        if self.offset >= self.max_bytes {
+            let rotated = self.rotated_path();
+            match std::fs::rename(&self.path, &rotated) {
+                Ok(()) => {
+                    tracing::info!("Rotated spool {} after {} bytes", self.path.display(), self.offset);
+                    self.rotated = Some(rotated);
+                }
+                Err(e) => tracing::warn!("Failed to rotate spool {}: {}", self.path.display(), e),
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Where the spool is moved while it is drained
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".rotated");
+        self.path.with_file_name(name)
+    }
+
+    /// Read the complete lines appended to `path` past `offset`
+    fn read_from(&mut self, path: &Path) -> std::io::Result<Vec<ClientMessage>> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            tracing::info!("Spool {} was truncated, reading from the start", path.display());
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Ok(Vec::new());
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        self.offset += bytes.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+
+        // Keep a trailing line without its newline for the next poll
+        let complete = match self.partial.rfind('\n') {
+            Some(end) => self.partial.drain(..=end).collect::<String>(),
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(complete
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(message) => Some(message),
+                Err(e) => {
+                    tracing::warn!("Skipping malformed spool line in {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfcoder_types::protocol::DaemonRequest;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_poll_follows_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spool.jsonl");
+        append(&path, "{\"id\":1,\"request\":{\"type\":\"ping\"}}\n");
+
+        let mut reader = SpoolReader::new(&path);
+        assert!(reader.poll().unwrap().is_empty());
+
+        append(&path, "{\"id\":2,\"request\":{\"type\":\"ping\"}}\nnot json\n{\"id\":3,\"request\"");
+        let messages = reader.poll().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, 2);
+
+        append(&path, ":{\"type\":\"list_agents\"}}\n");
+        let messages = reader.poll().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].request, DaemonRequest::ListAgents));
+    }
+
+    #[test]
+    fn test_poll_rotates_and_drains_large_spools() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spool.jsonl");
+        let rotated = dir.path().join("spool.jsonl.rotated");
+
+        let mut reader = SpoolReader::new(&path).with_max_bytes(64);
+        append(&path, "{\"id\":1,\"request\":{\"type\":\"ping\"}}\n");
+        assert_eq!(reader.poll().unwrap().len(), 1);
+        assert!(path.exists());
+
+        append(&path, "{\"id\":2,\"request\":{\"type\":\"ping\"}}\n");
+        assert_eq!(reader.poll().unwrap().len(), 1);
+        assert!(!path.exists());
+        assert!(rotated.exists());
+
+        // A write that raced the rename lands in the old file, the next one starts a new spool
+        append(&rotated, "{\"id\":3,\"request\":{\"type\":\"ping\"}}\n");
+        append(&path, "{\"id\":4,\"request\":{\"type\":\"ping\"}}\n");
+        let ids: Vec<u64> = reader.poll().unwrap().iter().map(|message| message.id).collect();
+        assert_eq!(ids, vec![3, 4]);
+        assert!(!rotated.exists());
+
+        append(&path, "{\"id\":5,\"request\":{\"type\":\"ping\"}}\n");
+        assert_eq!(reader.poll().unwrap()[0].id, 5);
+    }
+
+    #[test]
+    fn test_poll_handles_missing_and_truncated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spool.jsonl");
+
+        let mut reader = SpoolReader::new(&path);
+        assert!(reader.poll().unwrap().is_empty());
+
+        append(&path, "{\"id\":1,\"request\":{\"type\":\"ping\"}}\n{\"id\":2,\"request\":{\"type\":\"ping\"}}\n");
+        assert_eq!(reader.poll().unwrap().len(), 2);
+
+        std::fs::write(&path, "{\"id\":9,\"request\":{\"type\":\"ping\"}}\n").unwrap();
+        let messages = reader.poll().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, 9);
+    }
+}
@@ -0,0 +1,872 @@
+//! Workshop state owned by the daemon and the periodic work run against it
+
+use dfcoder_baml::{ActivityClassifier, ActivityTracker, TrackedActivity};
+use dfcoder_core::*;
+use dfcoder_types::protocol::*;
+use dfcoder_types::SystemEvent;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+
+/// How many events a slow subscriber may fall behind before it starts missing them
+const EVENT_BUFFER: usize = 256;
+
+/// The single workshop every attached client shares
+///
+/// Locks are taken one at a time and never held across a model call, so
+/// handlers and the scheduling loops cannot deadlock or stall each other.
+#[derive(Debug, Clone)]
+pub struct DaemonState {
+    pub workshop: Arc<Mutex<WorkshopManager>>,
+    pub supervision: Arc<Mutex<SupervisionSystem>>,
+    pub activities: Arc<Mutex<ActivityTracker>>,
+    pub ingestion: Arc<Mutex<OutputIngestor>>,
+    events: broadcast::Sender<SystemEvent>,
+}
+
+impl DaemonState {
+    /// Create an empty workshop with a rule-based activity tracker
+    pub fn new() -> Self {
+        Self::with_classifier(ActivityClassifier::rule_based())
+    }
+
+    /// Create an empty workshop whose pane output is classified by `classifier`
+    pub fn with_classifier(classifier: ActivityClassifier) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            workshop: Arc::new(Mutex::new(WorkshopManager::new())),
+            supervision: Arc::new(Mutex::new(SupervisionSystem::new())),
+            activities: Arc::new(Mutex::new(ActivityTracker::new(classifier))),
+            ingestion: Arc::new(Mutex::new(OutputIngestor::default())),
+            events,
+        }
+    }
+
+    /// Receive every `SystemEvent` published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<SystemEvent> {
+        self.events.subscribe()
+    }
+
+    /// Publish an event to subscribers, if there are any
+    pub fn publish(&self, event: SystemEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Copy the classifier's model usage into the workshop metrics
+    pub async fn sync_model_usage(&self) {
+        let usage = self.activities.lock().await.classifier().usage();
+        if let Some(usage) = usage {
+            self.workshop.lock().await.record_model_usage(usage);
+        }
+    }
+
+    /// Capture the workshop and supervision state for persisting
+    pub async fn persisted_state(&self) -> PersistedState {
+        self.sync_model_usage().await;
+        let workshop = self.workshop.lock().await.export_state();
+        let supervision = self.supervision.lock().await.export_state();
+        PersistedState { workshop, supervision }
+    }
+
+    /// Replace the workshop and supervision state with persisted state
+    ///
+    /// Supervision settings are kept; pane output windows and activity
+    /// history start empty. Model usage carries over so budgets still apply.
+    pub async fn restore(&self, state: PersistedState) {
+        let usage = state.workshop.metrics.model_usage.clone();
+        self.activities.lock().await.classifier().restore_usage(usage);
+        *self.workshop.lock().await = WorkshopManager::from_state(state.workshop);
+        self.supervision.lock().await.restore_state(state.supervision);
+    }
+
+    /// Hand queued tasks to idle agents until nothing more can be assigned
+    pub async fn assign_pending(&self) -> Vec<(AgentId, TaskId)> {
+        let mut workshop = self.workshop.lock().await;
+        let before = agent_states(&workshop);
+        let mut assigned = Vec::new();
+
+        loop {
+            match workshop.assign_by_priority() {
+                Ok(Some((agent_id, task))) => {
+                    tracing::info!("Assigned task '{}' to agent {}", task.title, agent_id);
+                    assigned.push((agent_id, task.id));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!("Task assignment failed: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let changed: Vec<_> = assigned.iter().map(|(agent_id, _)| agent_id.clone()).collect();
+        self.publish_state_changes(&workshop, &before, &changed);
+        assigned
+    }
+
+    /// Flag agents that have made no progress and raise supervision from their last output
+    pub async fn check_stuck(&self, threshold: Duration) -> Vec<AgentId> {
+        let stuck: Vec<Agent> = {
+            let mut workshop = self.workshop.lock().await;
+            let before = agent_states(&workshop);
+            let stuck_ids = workshop.check_for_stuck_agents(threshold);
+            self.publish_state_changes(&workshop, &before, &stuck_ids);
+            stuck_ids.iter()
+                .filter_map(|agent_id| workshop.get_agent(agent_id).cloned())
+                .collect()
+        };
+
+        for agent in &stuck {
+            tracing::warn!("Agent {} has made no progress for {:?}", agent.id, threshold);
+
+            let activity = self.activities.lock().await.current_activity(&agent.id).cloned();
+
+            if let Some(activity) = activity {
+                let estimate = self.estimate_completion(&agent.id, &activity).await;
+                let check = self.supervision.lock().await
+                    .check_supervision_need_with_estimate(agent, &activity.output, estimate)
+                    .await;
+                match check {
+                    Ok(Some(request)) => self.publish_supervision_requested(&request),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Supervision check for {} failed: {}", agent.id, e),
+                }
+            }
+        }
+
+        stuck.into_iter().map(|agent| agent.id).collect()
+    }
+
+    /// Drop expired supervision requests and auto-resolve the rest when enabled
+    pub async fn cleanup_supervision(&self) -> Vec<(AgentId, SupervisionAction)> {
+        let mut supervision = self.supervision.lock().await;
+        supervision.cleanup_expired_requests();
+
+        let resolutions = supervision.auto_resolve_requests().await;
+        for (agent_id, action) in &resolutions {
+            tracing::info!("Auto-resolved supervision for {}: {:?}", agent_id, action);
+        }
+        resolutions
+    }
+
+    /// Classify new pane output for an agent and check whether it needs supervision
+    pub async fn report_output(
+        &self,
+        agent_id: &AgentId,
+        output: &str,
+    ) -> Result<Option<SupervisionRequest>, SupervisionError> {
+        let agent = {
+            let mut workshop = self.workshop.lock().await;
+            let agent = workshop.get_agent_mut(agent_id)
+                .ok_or_else(|| SupervisionError::AgentNotFound(agent_id.clone()))?;
+            agent.mark_activity();
+            agent.clone()
+        };
+
+        let text = {
+            let mut ingestion = self.ingestion.lock().await;
+            ingestion.ingest(agent_id, output.as_bytes());
+            // Each report ends a line, so a trailing prompt is not held back or
+            // glued onto the next report
+            if !output.ends_with('\n') {
+                ingestion.ingest(agent_id, b"\n");
+            }
+            ingestion.take_new(agent_id)
+        };
+        let Some(text) = text else {
+            return Ok(None);
+        };
+
+        // The classifier may call a model, so no lock is held while it runs
+        let pending = PendingChunk::new(&agent, &*self.activities.lock().await, text);
+        let classification = pending.classify().await;
+        let chunk = pending.record(&mut *self.activities.lock().await, classification);
+
+        let activity = self.activities.lock().await.current_activity(agent_id).cloned();
+        let estimate = match &activity {
+            Some(activity) => self.estimate_completion(agent_id, activity).await,
+            None => None,
+        };
+        let request = self.supervision.lock().await
+            .check_supervision_need_with_estimate(&agent, &chunk.text, estimate)
+            .await?;
+        if let Some(request) = &request {
+            self.publish_supervision_requested(request);
+        }
+        Ok(request)
+    }
+
+    /// Predict the time left on an agent's task and note it on its current activity
+    async fn estimate_completion(&self, agent_id: &AgentId, activity: &TrackedActivity) -> Option<CompletionEstimate> {
+        let estimate = self.workshop.lock().await.estimate_completion(agent_id, Some(activity))?;
+        self.activities.lock().await.set_estimated_completion(agent_id, estimate.remaining);
+        Some(estimate)
+    }
+
+    /// Answer a single client request
+    ///
+    /// `Hello`, `Subscribe` and `Unsubscribe` concern the connection rather
+    /// than the workshop and are handled by the server.
+    pub async fn handle_request(&self, request: DaemonRequest) -> DaemonResponse {
+        match request {
+            DaemonRequest::Hello { protocol_version, .. } => {
+                if protocol_version == PROTOCOL_VERSION {
+                    DaemonResponse::Hello {
+                        protocol_version: PROTOCOL_VERSION,
+                        server: format!("dfcoderd {}", env!("CARGO_PKG_VERSION")),
+                    }
+                } else {
+                    DaemonResponse::error(
+                        ErrorCode::UnsupportedVersion,
+                        format!("dfcoderd speaks protocol version {}, client sent {}", PROTOCOL_VERSION, protocol_version),
+                    )
+                }
+            }
+            DaemonRequest::Ping => DaemonResponse::Pong,
+            DaemonRequest::GetStatus => {
+                self.sync_model_usage().await;
+                let activities: HashMap<AgentId, TrackedActivity> = self.activities.lock().await
+                    .current_activities()
+                    .map(|activity| (activity.agent_id.clone(), activity.clone()))
+                    .collect();
+                DaemonResponse::Status {
+                    status: Box::new((&self.workshop.lock().await.status_with_activities(&activities)).into()),
+                }
+            }
+            DaemonRequest::ListAgents => DaemonResponse::Agents {
+                agents: self.workshop.lock().await.get_all_agents().into_iter().map(AgentSummary::from).collect(),
+            },
+            DaemonRequest::ListQueue => DaemonResponse::Queue {
+                tasks: self.workshop.lock().await.get_queue().iter().map(TaskSummary::from).collect(),
+            },
+            DaemonRequest::ListSupervision => {
+                let supervision = self.supervision.lock().await;
+                let mut requests: Vec<_> = supervision.get_all_active_requests().into_iter().collect();
+                requests.sort_by(|a, b| b.urgency.cmp(&a.urgency));
+                DaemonResponse::Supervision {
+                    requests: requests.into_iter().map(SupervisionPrompt::from).collect(),
+                }
+            }
+            DaemonRequest::RegisterAgent { role, pane_id } => {
+                let mut workshop = self.workshop.lock().await;
+                if let Some(existing) = workshop.get_agent_by_pane(pane_id) {
+                    return DaemonResponse::AgentRegistered { agent_id: existing.id.clone() };
+                }
+
+                let agent = Agent::new(role, pane_id);
+                let agent_id = agent.id.clone();
+                match workshop.register_agent(agent) {
+                    Ok(()) => DaemonResponse::AgentRegistered { agent_id },
+                    Err(e) => workshop_error(e),
+                }
+            }
+            DaemonRequest::RemoveAgent { agent_id } => {
+                let removed = self.workshop.lock().await.remove_agent(&agent_id);
+                match removed {
+                    Ok(_) => {
+                        self.ingestion.lock().await.remove_agent(&agent_id);
+                        DaemonResponse::AgentRemoved { agent_id }
+                    }
+                    Err(e) => workshop_error(e),
+                }
+            }
+            DaemonRequest::QueueTask { task } => {
+                let task = Task::from(task);
+                let task_id = task.id.clone();
+                match self.workshop.lock().await.queue_task(task) {
+                    Ok(()) => DaemonResponse::TaskQueued { task_id },
+                    Err(e) => workshop_error(e),
+                }
+            }
+            DaemonRequest::CancelTask { task_id } => match self.workshop.lock().await.cancel_task(&task_id) {
+                Ok(cancelled) => DaemonResponse::TaskCancelled {
+                    task_id,
+                    cascaded: cancelled.into_iter().skip(1).map(|task| task.id).collect(),
+                },
+                Err(e) => workshop_error(e),
+            },
+            DaemonRequest::CompleteTask { agent_id, task_id } => self.finish_task(agent_id, task_id, None).await,
+            DaemonRequest::FailTask { agent_id, task_id, error } => self.finish_task(agent_id, task_id, Some(error)).await,
+            DaemonRequest::SetCapacity { scope, limit, policy } => self.set_capacity(scope, limit, policy).await,
+            DaemonRequest::AnswerSupervision { agent_id, option_id } => {
+                let result = self.supervision.lock().await.handle_supervision_response(&agent_id, option_id).await;
+                match result {
+                    Ok(action) => DaemonResponse::SupervisionAnswered {
+                        agent_id,
+                        option_id,
+                        action: format!("{:?}", action),
+                    },
+                    Err(SupervisionError::AgentNotFound(agent_id)) => DaemonResponse::error(
+                        ErrorCode::NoSupervisionRequest,
+                        format!("No open supervision request for agent {}", agent_id),
+                    ),
+                    Err(e) => supervision_error(e),
+                }
+            }
+            DaemonRequest::ReportOutput { agent_id, output } => match self.report_output(&agent_id, &output).await {
+                Ok(request) => DaemonResponse::OutputAccepted {
+                    supervision: request.as_ref().map(SupervisionPrompt::from),
+                },
+                Err(e) => supervision_error(e),
+            },
+            DaemonRequest::ReportPaneOutput { pane_id, output } => {
+                let agent_id = self.workshop.lock().await.get_agent_by_pane(pane_id).map(|agent| agent.id.clone());
+                match agent_id {
+                    Some(agent_id) => match self.report_output(&agent_id, &output).await {
+                        Ok(request) => DaemonResponse::OutputAccepted {
+                            supervision: request.as_ref().map(SupervisionPrompt::from),
+                        },
+                        Err(e) => supervision_error(e),
+                    },
+                    None => DaemonResponse::error(
+                        ErrorCode::AgentNotFound,
+                        format!("No agent registered for pane {}", pane_id),
+                    ),
+                }
+            }
+            DaemonRequest::Subscribe | DaemonRequest::Unsubscribe => DaemonResponse::error(
+                ErrorCode::InvalidRequest,
+                "Subscriptions are only available over a connection",
+            ),
+        }
+    }
+
+    /// Record that an agent finished its task, successfully unless there is
+    /// an `error`, freeing the agent for the assign loop
+    async fn finish_task(&self, agent_id: AgentId, task_id: TaskId, error: Option<String>) -> DaemonResponse {
+        let mut workshop = self.workshop.lock().await;
+        let current_task = match workshop.get_agent(&agent_id) {
+            Some(agent) => agent.current_task.clone(),
+            None => return workshop_error(WorkshopError::AgentNotFound(agent_id)),
+        };
+        // Checked up front, as the workshop frees the agent before comparing ids
+        if current_task.as_ref() != Some(&task_id) {
+            return DaemonResponse::error(
+                ErrorCode::TaskNotFound,
+                format!("Agent {} is not working on task {}", agent_id, task_id),
+            );
+        }
+
+        let before = agent_states(&workshop);
+        let (response, result) = match error {
+            None => match workshop.complete_task(agent_id.clone(), task_id.clone()) {
+                Ok(()) => {
+                    tracing::info!("Agent {} completed task {}", agent_id, task_id);
+                    (DaemonResponse::TaskCompleted { task_id: task_id.clone() }, TaskResult::Success)
+                }
+                Err(e) => return workshop_error(e),
+            },
+            Some(error) => match workshop.fail_task(agent_id.clone(), task_id.clone(), error.clone()) {
+                Ok(cancelled) => {
+                    tracing::warn!("Agent {} failed task {}: {}", agent_id, task_id, error);
+                    // The failure stays in the agent's metrics; the agent itself can take other work
+                    if let Some(agent) = workshop.get_agent_mut(&agent_id) {
+                        agent.status = AgentStatus::Idle;
+                    }
+                    let cascaded = cancelled.into_iter().map(|task| task.id).collect();
+                    (DaemonResponse::TaskFailed { task_id: task_id.clone(), cascaded }, TaskResult::Failed(error))
+                }
+                Err(e) => return workshop_error(e),
+            },
+        };
+
+        self.publish(SystemEvent::TaskCompleted { agent_id: agent_id.clone(), task_id, result });
+        self.publish_state_changes(&workshop, &before, &[agent_id]);
+        response
+    }
+
+    /// Change a concurrency limit and publish what it did to running work
+    async fn set_capacity(&self, scope: CapacityScope, limit: Option<usize>, policy: ShrinkPolicy) -> DaemonResponse {
+        let mut workshop = self.workshop.lock().await;
+        let before = agent_states(&workshop);
+        let events = workshop.set_limit(scope.clone(), limit, policy);
+
+        let mut preempted_agents = Vec::new();
+        let mut preempted = Vec::new();
+        for event in events {
+            match event {
+                CapacityEvent::LimitChanged { scope, old_limit, new_limit, load } => {
+                    tracing::info!("Capacity for {} changed from {:?} to {:?} with {} running", scope, old_limit, new_limit, load);
+                    self.publish(SystemEvent::CapacityChanged { scope, old_limit, new_limit, load });
+                }
+                CapacityEvent::Draining { scope, load, limit } => {
+                    tracing::info!("Draining {}: {} running, limit {}", scope, load, limit);
+                }
+                CapacityEvent::Preempted { scope, agent_id, task_id } => {
+                    tracing::warn!("Pre-empted task {} on agent {} to honour the {} limit", task_id, agent_id, scope);
+                    self.publish(SystemEvent::TaskPreempted {
+                        agent_id: agent_id.clone(),
+                        task_id: task_id.clone(),
+                        scope,
+                    });
+                    preempted_agents.push(agent_id);
+                    preempted.push(task_id);
+                }
+            }
+        }
+        self.publish_state_changes(&workshop, &before, &preempted_agents);
+
+        DaemonResponse::CapacitySet {
+            load: workshop.load(&scope),
+            scope,
+            limit,
+            preempted,
+        }
+    }
+
+    fn publish_state_changes(&self, workshop: &WorkshopManager, before: &HashMap<AgentId, dfcoder_types::AgentState>, changed: &[AgentId]) {
+        for agent_id in changed {
+            if let (Some(old_state), Some(agent)) = (before.get(agent_id), workshop.get_agent(agent_id)) {
+                self.publish(SystemEvent::AgentStateChanged {
+                    agent_id: agent_id.clone(),
+                    old_state: old_state.clone(),
+                    new_state: agent.into(),
+                });
+            }
+        }
+    }
+
+    fn publish_supervision_requested(&self, request: &SupervisionRequest) {
+        self.publish(SystemEvent::SupervisionRequested {
+            agent_id: request.agent_id.clone(),
+            message: format!("{:?} urgency supervision requested", request.urgency),
+            context: request.context.clone(),
+        });
+    }
+}
+
+impl Default for DaemonState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn agent_states(workshop: &WorkshopManager) -> HashMap<AgentId, dfcoder_types::AgentState> {
+    workshop.get_all_agents().into_iter()
+        .map(|agent| (agent.id.clone(), agent.into()))
+        .collect()
+}
+
+fn workshop_error(error: WorkshopError) -> DaemonResponse {
+    let code = match &error {
+        WorkshopError::AgentNotFound(_) => ErrorCode::AgentNotFound,
+        WorkshopError::AgentBusy(_, _) => ErrorCode::AgentBusy,
+        WorkshopError::TaskNotFound(_) => ErrorCode::TaskNotFound,
+        WorkshopError::TaskAlreadyAssigned(_, _) => ErrorCode::TaskAlreadyAssigned,
+        WorkshopError::DuplicateTask(_)
+        | WorkshopError::UnknownDependencies(_)
+        | WorkshopError::DependencyCycle(_)
+        | WorkshopError::DependenciesNotSatisfied(_) => ErrorCode::InvalidDependencies,
+        _ => ErrorCode::Internal,
+    };
+    DaemonResponse::error(code, error.to_string())
+}
+
+fn supervision_error(error: SupervisionError) -> DaemonResponse {
+    let code = match &error {
+        SupervisionError::AgentNotFound(_) => ErrorCode::AgentNotFound,
+        SupervisionError::InvalidOption(_) => ErrorCode::InvalidOption,
+        _ => ErrorCode::Internal,
+    };
+    DaemonResponse::error(code, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfcoder_baml::ActivityContext;
+
+    #[tokio::test]
+    async fn test_assign_pending_drains_assignable_tasks() {
+        let state = DaemonState::new();
+        let mut events = state.subscribe();
+        {
+            let mut workshop = state.workshop.lock().await;
+            workshop.register_agent(Agent::new(AgentRole::Implementer, 1)).unwrap();
+            workshop.register_agent(Agent::new(AgentRole::Implementer, 2)).unwrap();
+            for title in ["First", "Second", "Third"] {
+                workshop.queue_task(Task::new(
+                    title.to_string(),
+                    "Implement the feature".to_string(),
+                    AgentRole::Implementer,
+                    TaskPriority::Normal,
+                )).unwrap();
+            }
+        }
+
+        let assigned = state.assign_pending().await;
+        assert_eq!(assigned.len(), 2);
+        assert_eq!(state.workshop.lock().await.get_queue().len(), 1);
+
+        for _ in 0..2 {
+            match events.try_recv().unwrap() {
+                SystemEvent::AgentStateChanged { old_state, new_state, .. } => {
+                    assert_eq!(old_state.status, dfcoder_types::AgentStatus::Idle);
+                    assert_eq!(new_state.status, dfcoder_types::AgentStatus::Working);
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_output_releases_locks_while_the_model_is_called() {
+        // A model endpoint that accepts requests but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (called, call_started) = tokio::sync::oneshot::channel();
+        let _model = tokio::spawn(async move {
+            let (connection, _) = listener.accept().await.unwrap();
+            let _ = called.send(());
+            std::future::pending::<()>().await;
+            drop(connection);
+        });
+
+        let classifier = ActivityClassifier::with_config("key".to_string(), endpoint, "model".to_string());
+        let state = DaemonState::with_classifier(classifier);
+        let agent = Agent::new(AgentRole::Debugger, 4);
+        let agent_id = agent.id.clone();
+        state.workshop.lock().await.register_agent(agent).unwrap();
+
+        let report = tokio::spawn({
+            let state = state.clone();
+            let agent_id = agent_id.clone();
+            async move { state.report_output(&agent_id, "Debugging the auth error").await }
+        });
+        call_started.await.unwrap();
+
+        assert!(state.ingestion.try_lock().is_ok());
+        assert!(state.activities.try_lock().is_ok());
+        report.abort();
+    }
+
+    #[tokio::test]
+    async fn test_report_output_tracks_activity_and_raises_supervision() {
+        let state = DaemonState::new();
+        let mut events = state.subscribe();
+        let agent = Agent::new(AgentRole::Debugger, 3);
+        let agent_id = agent.id.clone();
+        state.workshop.lock().await.register_agent(agent).unwrap();
+
+        let request = state.report_output(&agent_id, "Running tests to verify the fix").await.unwrap();
+        assert!(request.is_none());
+
+        let request = state.report_output(&agent_id, "I'm stuck and confused, need help").await.unwrap();
+        assert!(request.is_some());
+        assert!(matches!(events.try_recv().unwrap(), SystemEvent::SupervisionRequested { .. }));
+
+        let activities = state.activities.lock().await;
+        let current = activities.current_activity(&agent_id).unwrap();
+        assert_eq!(current.output, "I'm stuck and confused, need help\n");
+        assert_eq!(current.context.recent_activities.len(), 1);
+        assert_eq!(activities.get_agent_activities(&agent_id).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_report_output_for_unknown_agent() {
+        let state = DaemonState::new();
+        let response = state.handle_request(DaemonRequest::ReportOutput {
+            agent_id: "ghost".to_string(),
+            output: "hello".to_string(),
+        }).await;
+
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::AgentNotFound, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_stuck_agent_gets_supervision_from_last_output() {
+        let state = DaemonState::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        {
+            let mut workshop = state.workshop.lock().await;
+            workshop.register_agent(agent).unwrap();
+            workshop.get_agent_mut(&agent_id).unwrap().assign_task("task-1".to_string()).unwrap();
+        }
+        state.activities.lock().await
+            .start_activity(agent_id.clone(), "Stuck on the linker, need help", ActivityContext::new())
+            .await
+            .unwrap();
+
+        let stuck = state.check_stuck(Duration::ZERO).await;
+        assert_eq!(stuck, vec![agent_id.clone()]);
+
+        let workshop = state.workshop.lock().await;
+        assert_eq!(workshop.get_agent(&agent_id).unwrap().status, AgentStatus::NeedsSupervision);
+        assert!(state.supervision.lock().await.get_active_request(&agent_id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_register_queue_cancel_and_remove() {
+        let state = DaemonState::new();
+
+        let agent_id = match state.handle_request(DaemonRequest::RegisterAgent { role: AgentRole::Tester, pane_id: 5 }).await {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let task_id = match state.handle_request(DaemonRequest::QueueTask {
+            task: TaskSpec {
+                title: "Cover the parser".to_string(),
+                description: "Write tests".to_string(),
+                required_role: AgentRole::Implementer,
+                priority: TaskPriority::High,
+                dependencies: Vec::new(),
+                files: Vec::new(),
+                estimated_duration_secs: None,
+            },
+        }).await {
+            DaemonResponse::TaskQueued { task_id } => task_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        match state.handle_request(DaemonRequest::ListQueue).await {
+            DaemonResponse::Queue { tasks } => assert_eq!(tasks[0].id, task_id),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response = state.handle_request(DaemonRequest::CancelTask { task_id: task_id.clone() }).await;
+        assert!(matches!(response, DaemonResponse::TaskCancelled { .. }));
+        let response = state.handle_request(DaemonRequest::CancelTask { task_id }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::TaskNotFound, .. }));
+
+        let response = state.handle_request(DaemonRequest::RemoveAgent { agent_id }).await;
+        assert!(matches!(response, DaemonResponse::AgentRemoved { .. }));
+        match state.handle_request(DaemonRequest::GetStatus).await {
+            DaemonResponse::Status { status } => assert_eq!(status.total_agents, 0),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_are_validated_and_cancellation_cascades() {
+        let state = DaemonState::new();
+        let spec = |title: &str, dependencies: Vec<String>| TaskSpec {
+            title: title.to_string(),
+            description: title.to_string(),
+            required_role: AgentRole::Implementer,
+            priority: TaskPriority::Normal,
+            dependencies,
+            files: Vec::new(),
+            estimated_duration_secs: Some(600),
+        };
+        let queue = |task: TaskSpec| async {
+            match state.handle_request(DaemonRequest::QueueTask { task }).await {
+                DaemonResponse::TaskQueued { task_id } => task_id,
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        let response = state.handle_request(DaemonRequest::QueueTask { task: spec("Orphan", vec!["nope".to_string()]) }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::InvalidDependencies, .. }));
+
+        let schema = queue(spec("Schema", Vec::new())).await;
+        let api = queue(spec("API", vec![schema.clone()])).await;
+
+        match state.handle_request(DaemonRequest::GetStatus).await {
+            DaemonResponse::Status { status } => {
+                assert_eq!(status.blocked_by[&api], vec![schema.clone()]);
+                assert_eq!(status.critical_path, vec![schema.clone(), api.clone()]);
+                assert_eq!(status.critical_path_secs, 1200);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        match state.handle_request(DaemonRequest::CancelTask { task_id: schema }).await {
+            DaemonResponse::TaskCancelled { cascaded, .. } => assert_eq!(cascaded, vec![api]),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match state.handle_request(DaemonRequest::ListQueue).await {
+            DaemonResponse::Queue { tasks } => assert!(tasks.is_empty()),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_predicts_completion_of_running_tasks() {
+        let state = DaemonState::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        state.workshop.lock().await.register_agent(agent).unwrap();
+        let task_id = match state.handle_request(DaemonRequest::QueueTask {
+            task: TaskSpec {
+                title: "Add the login endpoint".to_string(),
+                description: "Implement the login feature".to_string(),
+                required_role: AgentRole::Implementer,
+                priority: TaskPriority::Normal,
+                dependencies: Vec::new(),
+                files: Vec::new(),
+                estimated_duration_secs: Some(600),
+            },
+        }).await {
+            DaemonResponse::TaskQueued { task_id } => task_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert_eq!(state.assign_pending().await.len(), 1);
+
+        state.report_output(&agent_id, "Running tests to verify the endpoint").await.unwrap();
+        let tracked = state.activities.lock().await.current_activity(&agent_id).unwrap().clone();
+        let estimated = tracked.classification.unwrap().estimated_completion.unwrap();
+
+        match state.handle_request(DaemonRequest::GetStatus).await {
+            DaemonResponse::Status { status } => {
+                let eta = status.completion_etas[&task_id];
+                assert!(eta.remaining_secs < 600);
+                assert!(eta.low_secs <= eta.remaining_secs && eta.remaining_secs <= eta.high_secs);
+                assert!(estimated.as_secs().abs_diff(eta.remaining_secs) <= 1);
+                assert_eq!(eta.samples, 0);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_task_cancels_dependents_and_frees_the_agent() {
+        let state = DaemonState::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        let schema = Task::new("Schema".to_string(), "Design the schema".to_string(), AgentRole::Implementer, TaskPriority::High);
+        let mut api = Task::new("API".to_string(), "Build the API".to_string(), AgentRole::Implementer, TaskPriority::Normal);
+        api.context.dependencies = vec![schema.id.clone()];
+        let (schema_id, api_id) = (schema.id.clone(), api.id.clone());
+        {
+            let mut workshop = state.workshop.lock().await;
+            workshop.register_agent(agent).unwrap();
+            workshop.queue_tasks(vec![schema, api]).unwrap();
+        }
+        assert_eq!(state.assign_pending().await, vec![(agent_id.clone(), schema_id.clone())]);
+        let mut events = state.subscribe();
+
+        let response = state.handle_request(DaemonRequest::CompleteTask { agent_id: agent_id.clone(), task_id: api_id.clone() }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::TaskNotFound, .. }));
+        assert_eq!(state.workshop.lock().await.get_agent(&agent_id).unwrap().current_task, Some(schema_id.clone()));
+
+        let response = state.handle_request(DaemonRequest::FailTask {
+            agent_id: agent_id.clone(),
+            task_id: schema_id.clone(),
+            error: "the database is unreachable".to_string(),
+        }).await;
+        match response {
+            DaemonResponse::TaskFailed { task_id, cascaded } => {
+                assert_eq!(task_id, schema_id);
+                assert_eq!(cascaded, vec![api_id]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SystemEvent::TaskCompleted { result: TaskResult::Failed(_), .. }
+        ));
+        match events.try_recv().unwrap() {
+            SystemEvent::AgentStateChanged { new_state, .. } => assert_eq!(new_state.status, dfcoder_types::AgentStatus::Idle),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(state.workshop.lock().await.get_queue().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_capacity_preempts_and_publishes_events() {
+        let state = DaemonState::new();
+        {
+            let mut workshop = state.workshop.lock().await;
+            workshop.register_agent(Agent::new(AgentRole::Tester, 1)).unwrap();
+            workshop.register_agent(Agent::new(AgentRole::Tester, 2)).unwrap();
+            for title in ["Unit tests", "Integration tests"] {
+                workshop.queue_task(Task::new(
+                    title.to_string(),
+                    "Write tests".to_string(),
+                    AgentRole::Tester,
+                    TaskPriority::Normal,
+                )).unwrap();
+            }
+        }
+        assert_eq!(state.assign_pending().await.len(), 2);
+        let mut events = state.subscribe();
+
+        let response = state.handle_request(DaemonRequest::SetCapacity {
+            scope: CapacityScope::Global,
+            limit: Some(1),
+            policy: ShrinkPolicy::Preempt,
+        }).await;
+        let preempted = match response {
+            DaemonResponse::CapacitySet { scope: CapacityScope::Global, limit: Some(1), load: 1, preempted } => preempted,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert_eq!(preempted.len(), 1);
+
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SystemEvent::CapacityChanged { scope: CapacityScope::Global, old_limit: None, new_limit: Some(1), load: 2 }
+        ));
+        match events.try_recv().unwrap() {
+            SystemEvent::TaskPreempted { task_id, .. } => assert_eq!(task_id, preempted[0]),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        match events.try_recv().unwrap() {
+            SystemEvent::AgentStateChanged { new_state, .. } => {
+                assert_eq!(new_state.status, dfcoder_types::AgentStatus::Idle);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // Nothing restarts while the workshop is at its cap
+        assert!(state.assign_pending().await.is_empty());
+        assert_eq!(state.workshop.lock().await.get_queue().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pane_registration_is_idempotent_and_routes_output() {
+        let state = DaemonState::new();
+
+        let register = || state.handle_request(DaemonRequest::RegisterAgent { role: AgentRole::Implementer, pane_id: 7 });
+        let first = match register().await {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        match register().await {
+            DaemonResponse::AgentRegistered { agent_id } => assert_eq!(agent_id, first),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert_eq!(state.workshop.lock().await.get_all_agents().len(), 1);
+
+        let response = state.handle_request(DaemonRequest::ReportPaneOutput {
+            pane_id: 7,
+            output: "I'm stuck and confused, need help".to_string(),
+        }).await;
+        assert!(matches!(response, DaemonResponse::OutputAccepted { supervision: Some(_) }));
+
+        let response = state.handle_request(DaemonRequest::ReportPaneOutput { pane_id: 8, output: String::new() }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::AgentNotFound, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_answer_supervision() {
+        let state = DaemonState::new();
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        state.workshop.lock().await.register_agent(agent).unwrap();
+        state.report_output(&agent_id, "I'm stuck and confused, need help").await.unwrap();
+
+        let response = state.handle_request(DaemonRequest::AnswerSupervision { agent_id: agent_id.clone(), option_id: 99 }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::InvalidOption, .. }));
+
+        match state.handle_request(DaemonRequest::AnswerSupervision { agent_id: agent_id.clone(), option_id: 1 }).await {
+            DaemonResponse::SupervisionAnswered { action, .. } => assert!(action.starts_with("ProvideGuidance")),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response = state.handle_request(DaemonRequest::AnswerSupervision { agent_id, option_id: 1 }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::NoSupervisionRequest, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_hello_rejects_other_versions() {
+        let state = DaemonState::new();
+        let response = state.handle_request(DaemonRequest::Hello {
+            protocol_version: PROTOCOL_VERSION + 1,
+            client: "future".to_string(),
+        }).await;
+        assert!(matches!(response, DaemonResponse::Error { code: ErrorCode::UnsupportedVersion, .. }));
+    }
+}
@@ -247,10 +247,11 @@ impl AgentBuilder {
     }
     
     pub fn responds_to(mut self, pattern: impl Into<String>, action: AgentAction) -> Self {
+        let pattern = pattern.into();
         self.behaviors.push(BehaviorRule {
-            trigger: TriggerCondition::RespondsTo(pattern.into()),
+            description: format!("Responds to '{}'", pattern),
+            trigger: TriggerCondition::RespondsTo(pattern),
             action,
-            description: format!("Responds to '{}'", pattern.into()),
         });
         self
     }
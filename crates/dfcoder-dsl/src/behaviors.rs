@@ -1,6 +1,6 @@
 use crate::*;
+use crate::traits::AgentBehavior;
 use std::time::Duration;
-use async_trait::async_trait;
 
 /// Behavior execution engine
 #[derive(Debug)]
@@ -63,7 +63,11 @@ impl BehaviorEngine {
                     .publish(SystemEvent::AgentStateChanged {
                         agent_id: agent_name.to_string(),
                         old_state: agent.current_state().clone(),
-                        new_state: AgentState::Working,
+                        new_state: AgentState {
+                            status: AgentStatus::Working,
+                            last_activity: chrono::Utc::now(),
+                            ..agent.current_state().clone()
+                        },
                     })
                     .await
                     .map_err(|e| DslError::EventHandlingFailed(e.to_string()))?;
@@ -240,7 +244,7 @@ pub enum Schedule {
     /// Execute repeatedly with an interval
     Interval(Duration),
     /// Execute when a condition is met
-    WhenCondition(Box<dyn traits::Condition>),
+    WhenCondition(std::sync::Arc<dyn traits::Condition>),
 }
 
 impl BehaviorScheduler {
@@ -1,4 +1,5 @@
 use crate::*;
+use crate::traits::Condition;
 use std::time::Duration;
 
 /// Condition evaluation implementations
@@ -49,14 +50,14 @@ impl traits::Condition for ConditionType {
             }
             ConditionType::AgentStatus(agent_id, expected_status) => {
                 context.agents.get(agent_id)
-                    .map(|state| matches!(state.status, expected_status))
+                    .map(|state| state.status == *expected_status)
                     .unwrap_or(false)
             }
             ConditionType::AgentIdle(agent_id, duration) => {
                 context.agents.get(agent_id)
                     .map(|state| {
-                        matches!(state.status, AgentStatus::Idle) &&
-                        state.last_activity.elapsed() >= *duration
+                        let idle_for = (context.current_time - state.last_activity).to_std().unwrap_or_default();
+                        state.status == AgentStatus::Idle && idle_for >= *duration
                     })
                     .unwrap_or(false)
             }
@@ -385,4 +386,32 @@ impl Default for ConditionMonitor {
     fn default() -> Self {
         Self::new(Duration::from_secs(1))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_conditions_evaluate_core_agents() {
+        let mut agent = Agent::new(AgentRole::Implementer, 1);
+        agent.assign_task("task-1".to_string()).unwrap();
+        agent.complete_task().unwrap();
+
+        let now = chrono::Utc::now();
+        let mut state = AgentState::from(&agent);
+        state.last_activity = now - chrono::Duration::minutes(10);
+        let context = EvaluationContext {
+            agents: HashMap::from([(agent.id.clone(), state)]),
+            panes: HashMap::new(),
+            current_time: now,
+            events: Vec::new(),
+        };
+
+        assert!(ConditionType::AgentStatus(agent.id.clone(), AgentStatus::Idle).evaluate(&context));
+        assert!(!ConditionType::AgentStatus(agent.id.clone(), AgentStatus::Working).evaluate(&context));
+        assert!(ConditionType::AgentIdle(agent.id.clone(), Duration::from_secs(300)).evaluate(&context));
+        assert!(!ConditionType::AgentIdle(agent.id.clone(), Duration::from_secs(3600)).evaluate(&context));
+        assert!(ConditionType::AgentTaskCount(agent.id.clone(), 1).evaluate(&context));
+    }
+}
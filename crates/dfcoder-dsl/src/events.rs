@@ -1,30 +1,44 @@
 use crate::*;
 use std::collections::VecDeque;
 use async_trait::async_trait;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast;
 
 /// Event bus for managing system-wide events
-#[derive(Debug)]
 pub struct EventBus {
     sender: broadcast::Sender<SystemEvent>,
     receiver: broadcast::Receiver<SystemEvent>,
     handlers: std::collections::HashMap<String, Box<dyn EventHandlerWrapper>>,
 }
 
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.sender.receiver_count())
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
 /// Wrapper trait for type-erased event handlers
 #[async_trait]
 trait EventHandlerWrapper: Send + Sync {
     async fn handle_event(&self, event: &SystemEvent) -> Result<(), EventError>;
 }
 
+/// A typed handler along with the event type it handles
+struct TypedHandler<T, H> {
+    handler: H,
+    _event: std::marker::PhantomData<fn(T)>,
+}
+
 /// Implementation of EventHandlerWrapper for typed handlers
 #[async_trait]
-impl<T, H> EventHandlerWrapper for H
+impl<T, H> EventHandlerWrapper for TypedHandler<T, H>
 where
     T: Event + Clone + Send + Sync + 'static,
     H: traits::EventHandler<T> + Send + Sync + 'static,
 {
-    async fn handle_event(&self, event: &SystemEvent) -> Result<(), EventError> {
+    async fn handle_event(&self, _event: &SystemEvent) -> Result<(), EventError> {
         // Type-safe event handling would require more sophisticated dispatch
         // For now, we'll use a simplified approach
         Ok(())
@@ -72,7 +86,7 @@ impl EventBus {
         T: Event + Clone + Send + Sync + 'static,
         H: traits::EventHandler<T> + Send + Sync + 'static,
     {
-        self.handlers.insert(name, Box::new(handler));
+        self.handlers.insert(name, Box::new(TypedHandler { handler, _event: std::marker::PhantomData }));
     }
 }
 
@@ -91,8 +105,8 @@ impl EventFlows {
         vec![
             SystemEvent::AgentStateChanged {
                 agent_id: "example_agent".to_string(),
-                old_state: AgentState::Working,
-                new_state: AgentState::Stuck,
+                old_state: AgentState::with_status(AgentStatus::Working),
+                new_state: AgentState::with_status(AgentStatus::Stuck),
             },
             SystemEvent::SupervisionRequested {
                 agent_id: "example_agent".to_string(),
@@ -107,8 +121,8 @@ impl EventFlows {
         vec![
             SystemEvent::AgentStateChanged {
                 agent_id: "example_agent".to_string(),
-                old_state: AgentState::Working,
-                new_state: AgentState::Idle,
+                old_state: AgentState::with_status(AgentStatus::Working),
+                new_state: AgentState::with_status(AgentStatus::Idle),
             },
             SystemEvent::TaskCompleted {
                 agent_id: "example_agent".to_string(),
@@ -160,6 +174,8 @@ impl EventPattern {
                     SystemEvent::SupervisionRequested { agent_id: id, .. } => id == agent_id,
                     SystemEvent::TaskCompleted { agent_id: id, .. } => id == agent_id,
                     SystemEvent::ErrorOccurred { agent_id: id, .. } => id == agent_id,
+                    SystemEvent::TaskPreempted { agent_id: id, .. } => id == agent_id,
+                    SystemEvent::CapacityChanged { .. } => false,
                 }
             }
             EventPattern::OfType(event_type) => {
@@ -168,6 +184,8 @@ impl EventPattern {
                     SystemEvent::SupervisionRequested { .. } => "SupervisionRequested",
                     SystemEvent::TaskCompleted { .. } => "TaskCompleted",
                     SystemEvent::ErrorOccurred { .. } => "ErrorOccurred",
+                    SystemEvent::CapacityChanged { .. } => "CapacityChanged",
+                    SystemEvent::TaskPreempted { .. } => "TaskPreempted",
                 };
                 actual_type == event_type
             }
@@ -308,6 +326,8 @@ impl EventAggregator {
             SystemEvent::SupervisionRequested { agent_id, .. } => Some(agent_id.clone()),
             SystemEvent::TaskCompleted { agent_id, .. } => Some(agent_id.clone()),
             SystemEvent::ErrorOccurred { agent_id, .. } => Some(agent_id.clone()),
+            SystemEvent::TaskPreempted { agent_id, .. } => Some(agent_id.clone()),
+            SystemEvent::CapacityChanged { .. } => None,
         }
     }
     
@@ -317,6 +337,8 @@ impl EventAggregator {
             SystemEvent::SupervisionRequested { .. } => "SupervisionRequested".to_string(),
             SystemEvent::TaskCompleted { .. } => "TaskCompleted".to_string(),
             SystemEvent::ErrorOccurred { .. } => "ErrorOccurred".to_string(),
+            SystemEvent::CapacityChanged { .. } => "CapacityChanged".to_string(),
+            SystemEvent::TaskPreempted { .. } => "TaskPreempted".to_string(),
         }
     }
 }
\ No newline at end of file
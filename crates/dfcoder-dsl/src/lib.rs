@@ -45,7 +45,7 @@ pub mod traits {
     }
     
     /// Trait for condition evaluation
-    pub trait Condition: Send + Sync {
+    pub trait Condition: std::fmt::Debug + Send + Sync {
         /// Evaluate the condition
         fn evaluate(&self, context: &EvaluationContext) -> bool;
         
@@ -73,7 +73,7 @@ pub enum TriggerCondition {
     /// Triggered during supervision
     DuringSupervision,
     /// Custom condition
-    Custom(Box<dyn traits::Condition>),
+    Custom(std::sync::Arc<dyn traits::Condition>),
 }
 
 /// Actions that agents can take
@@ -93,43 +93,6 @@ pub enum AgentAction {
     None,
 }
 
-/// System events that can occur
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub enum SystemEvent {
-    /// Agent state changed
-    AgentStateChanged {
-        agent_id: String,
-        old_state: AgentState,
-        new_state: AgentState,
-    },
-    /// Supervision requested
-    SupervisionRequested {
-        agent_id: String,
-        message: String,
-        context: String,
-    },
-    /// Task completed
-    TaskCompleted {
-        agent_id: String,
-        task_id: String,
-        result: TaskResult,
-    },
-    /// Error occurred
-    ErrorOccurred {
-        agent_id: String,
-        error_message: String,
-        context: String,
-    },
-}
-
-/// Result of a task execution
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub enum TaskResult {
-    Success,
-    Failed(String),
-    Cancelled,
-}
-
 /// Error types for DSL operations
 #[derive(Debug, thiserror::Error)]
 pub enum DslError {
@@ -1,14 +1,31 @@
 use crate::*;
-use tokio::process::Command;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 
 /// MCP client implementation
+///
+/// Requests may be made concurrently; a background task reads the connection
+/// and hands each response to the request with the same id.
 #[derive(Debug)]
 pub struct McpClient {
     protocol: McpProtocol,
     session: ProtocolSession,
     connection: ClientConnection,
+    pending: Arc<std::sync::Mutex<PendingRequests>>,
+    router: JoinHandle<()>,
+}
+
+/// Requests waiting for their response, by serialized JSON-RPC id
+#[derive(Debug, Default)]
+struct PendingRequests {
+    waiting: HashMap<String, oneshot::Sender<McpMessage>>,
+    /// Set once the connection is gone, so new requests fail instead of waiting forever
+    closed: bool,
 }
 
 /// Client connection types
@@ -16,18 +33,39 @@ pub struct McpClient {
 pub enum ClientConnection {
     Stdio {
         process: tokio::process::Child,
-        stdin: tokio::process::ChildStdin,
-        stdout: BufReader<tokio::process::ChildStdout>,
+        transport: Arc<StreamTransport>,
     },
     WebSocket {
         url: String,
+        transport: Arc<WebSocketTransport>,
+    },
+    Http {
+        url: String,
+        transport: Arc<HttpClientTransport>,
     },
     Tcp {
         address: String,
-        port: u16,
+        transport: Arc<StreamTransport>,
+    },
+    Unix {
+        path: PathBuf,
+        transport: Arc<StreamTransport>,
     },
 }
 
+impl ClientConnection {
+    /// The transport messages are exchanged over
+    pub fn transport(&self) -> Arc<dyn Transport> {
+        match self {
+            ClientConnection::Stdio { transport, .. }
+            | ClientConnection::Tcp { transport, .. }
+            | ClientConnection::Unix { transport, .. } => transport.clone(),
+            ClientConnection::WebSocket { transport, .. } => transport.clone(),
+            ClientConnection::Http { transport, .. } => transport.clone(),
+        }
+    }
+}
+
 impl McpClient {
     /// Create a new MCP client connected to a server
     pub async fn new(server_uri: &str) -> Result<Self, McpError> {
@@ -38,16 +76,27 @@ impl McpClient {
             Self::create_stdio_connection(server_uri).await?
         } else if server_uri.starts_with("ws://") || server_uri.starts_with("wss://") {
             Self::create_websocket_connection(server_uri).await?
+        } else if server_uri.starts_with("http://") || server_uri.starts_with("https://") {
+            ClientConnection::Http {
+                url: server_uri.to_string(),
+                transport: Arc::new(HttpClientTransport::new(server_uri)),
+            }
         } else if server_uri.starts_with("tcp://") {
             Self::create_tcp_connection(server_uri).await?
+        } else if let Some(path) = server_uri.strip_prefix("unix://") {
+            Self::create_unix_connection(Path::new(path)).await?
         } else {
             return Err(McpError::TransportError(format!("Unsupported URI scheme: {}", server_uri)));
         };
         
+        let pending = Arc::new(std::sync::Mutex::new(PendingRequests::default()));
+        let router = tokio::spawn(route_responses(connection.transport(), protocol.clone(), pending.clone()));
         let mut client = Self {
             protocol,
             session,
             connection,
+            pending,
+            router,
         };
         
         // Initialize the connection
@@ -68,6 +117,9 @@ impl McpClient {
                 let capabilities: ServerCapabilities = serde_json::from_value(server_caps.clone())?;
                 self.session.set_capabilities(capabilities);
             }
+            // The server holds back everything but pings until it hears this
+            let initialized = self.protocol.create_notification("notifications/initialized", None);
+            self.connection.transport().send(&self.protocol.serialize_message(&initialized)?).await?;
             self.session.set_state(ProtocolState::Initialized);
             tracing::info!("MCP client initialized successfully");
         } else if let Some(error) = response.error {
@@ -151,35 +203,26 @@ impl McpClient {
         Err(McpError::ResourceError("No prompt returned".to_string()))
     }
     
-    /// Send a request and wait for response
+    /// Send a request and wait for its response
     async fn send_request(&self, request: McpMessage) -> Result<McpMessage, McpError> {
-        match &self.connection {
-            ClientConnection::Stdio { stdin, stdout, .. } => {
-                // This is a simplified implementation
-                // In a real implementation, we'd need proper async handling
-                let request_str = self.protocol.serialize_message(&request)?;
-                // stdin.write_all(request_str.as_bytes()).await?;
-                // stdin.write_all(b"\n").await?;
-                
-                // For now, return a mock response
-                Ok(McpMessage {
-                    jsonrpc: "2.0".to_string(),
-                    id: request.id,
-                    method: None,
-                    params: None,
-                    result: Some(serde_json::json!({})),
-                    error: None,
-                })
-            }
-            ClientConnection::WebSocket { .. } => {
-                // WebSocket implementation would go here
-                Err(McpError::TransportError("WebSocket not implemented".to_string()))
-            }
-            ClientConnection::Tcp { .. } => {
-                // TCP implementation would go here
-                Err(McpError::TransportError("TCP not implemented".to_string()))
+        let id = request.id.as_ref()
+            .ok_or_else(|| McpError::ProtocolError("Request has no id".to_string()))?
+            .to_string();
+        let (sender, response) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(McpError::ConnectionClosed);
             }
+            pending.waiting.insert(id.clone(), sender);
         }
+        
+        let sent = async { self.connection.transport().send(&self.protocol.serialize_message(&request)?).await };
+        if let Err(e) = sent.await {
+            self.pending.lock().unwrap().waiting.remove(&id);
+            return Err(e);
+        }
+        response.await.map_err(|_| McpError::ConnectionClosed)
     }
     
     async fn create_stdio_connection(uri: &str) -> Result<ClientConnection, McpError> {
@@ -209,18 +252,16 @@ impl McpClient {
         let stdout = process.stdout.take()
             .ok_or_else(|| McpError::TransportError("Failed to get stdout".to_string()))?;
         
-        let stdout = BufReader::new(stdout);
-        
         Ok(ClientConnection::Stdio {
+            transport: Arc::new(StreamTransport::from_child(stdin, stdout, command_part.to_string())),
             process,
-            stdin,
-            stdout,
         })
     }
     
     async fn create_websocket_connection(uri: &str) -> Result<ClientConnection, McpError> {
         Ok(ClientConnection::WebSocket {
             url: uri.to_string(),
+            transport: Arc::new(WebSocketTransport::connect(uri).await?),
         })
     }
     
@@ -234,9 +275,17 @@ impl McpClient {
         let port = url.port()
             .ok_or_else(|| McpError::TransportError("Missing port in TCP URI".to_string()))?;
         
+        let address = format!("{}:{}", host, port);
         Ok(ClientConnection::Tcp {
-            address: host.to_string(),
-            port,
+            transport: Arc::new(StreamTransport::connect_tcp(&address).await?),
+            address,
+        })
+    }
+    
+    async fn create_unix_connection(path: &Path) -> Result<ClientConnection, McpError> {
+        Ok(ClientConnection::Unix {
+            path: path.to_path_buf(),
+            transport: Arc::new(StreamTransport::connect_unix(path).await?),
         })
     }
     
@@ -248,6 +297,49 @@ impl McpClient {
     }
 }
 
+/// Hand each response to the request waiting for it, until the connection closes
+///
+/// Requests still waiting then fail with `ConnectionClosed`, as do any made later.
+async fn route_responses(
+    transport: Arc<dyn Transport>,
+    protocol: McpProtocol,
+    pending: Arc<std::sync::Mutex<PendingRequests>>,
+) {
+    loop {
+        let data = match transport.receive().await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::debug!("MCP connection ended: {}", e);
+                break;
+            }
+        };
+        let message = match protocol.parse_message(&data) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::warn!("Ignoring MCP message: {}", e);
+                continue;
+            }
+        };
+        if message.method.is_some() {
+            tracing::debug!("Ignoring {:?} from the server", message.method);
+            continue;
+        }
+
+        let waiting = message.id.as_ref()
+            .and_then(|id| pending.lock().unwrap().waiting.remove(&id.to_string()));
+        match waiting {
+            Some(sender) => {
+                let _ = sender.send(message);
+            }
+            None => tracing::debug!("Dropping response to unknown request {:?}", message.id),
+        }
+    }
+
+    let mut pending = pending.lock().unwrap();
+    pending.closed = true;
+    pending.waiting.clear();
+}
+
 /// Resource content
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceContent {
@@ -263,8 +355,9 @@ pub struct ResourceContent {
 pub struct Resource {
     pub uri: String,
     pub name: String,
+    #[serde(default)]
     pub description: String,
-    #[serde(rename = "mimeType")]
+    #[serde(rename = "mimeType", default)]
     pub mime_type: String,
 }
 
@@ -316,8 +409,102 @@ impl McpClientBuilder {
 
 impl Drop for McpClient {
     fn drop(&mut self) {
+        self.router.abort();
         if let ClientConnection::Stdio { process, .. } = &mut self.connection {
-            let _ = process.kill();
+            let _ = process.start_kill();
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    fn start(listener: TransportListener) -> (oneshot::Sender<()>, JoinHandle<Result<(), McpError>>) {
+        let service = McpService::new(McpConfig::default()).unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            service.serve_listener(listener, async { let _ = stopped.await; }).await
+        });
+        (stop, handle)
+    }
+
+    async fn create_and_read_back(client: &McpClient, title: &str) {
+        let created = client.call_tool("create_task", serde_json::json!({
+            "title": title, "description": "Write it", "role": "Implementer"
+        })).await.unwrap();
+        assert!(!created.is_error);
+        assert_eq!(created.content[0].type_, "text");
+
+        let resources = client.list_resources().await.unwrap();
+        assert!(resources.iter().any(|resource| resource.uri == "tasks"));
+        let tasks = client.read_resource("tasks").await.unwrap();
+        assert!(tasks.text.unwrap().contains(title));
+    }
+
+    async fn next_message(transport: &dyn Transport) -> McpMessage {
+        serde_json::from_str(&transport.receive().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_get_their_own_responses() {
+        let listener = TransportListener::bind_tcp("127.0.0.1:0").await.unwrap();
+        let uri = format!("tcp://{}", listener.local_addr());
+        let protocol = McpProtocol::new("2024-11-05".to_string());
+
+        // A server that answers the two reads in the opposite order to how they arrived
+        let server = tokio::spawn(async move {
+            let transport = listener.accept().await.unwrap().establish().await.unwrap();
+            let initialize = next_message(transport.as_ref()).await;
+            let reply = protocol.create_success_response(initialize.id.unwrap(), serde_json::json!({}));
+            transport.send(&protocol.serialize_message(&reply).unwrap()).await.unwrap();
+
+            let initialized = next_message(transport.as_ref()).await;
+            assert_eq!(initialized.method.as_deref(), Some("notifications/initialized"));
+            assert!(initialized.id.is_none());
+
+            let first = next_message(transport.as_ref()).await;
+            let second = next_message(transport.as_ref()).await;
+            for request in [second, first] {
+                let uri = request.params.unwrap()["uri"].clone();
+                let reply = protocol.create_success_response(request.id.unwrap(), serde_json::json!({
+                    "contents": [{ "uri": uri, "mimeType": "text/plain", "text": uri }]
+                }));
+                transport.send(&protocol.serialize_message(&reply).unwrap()).await.unwrap();
+            }
+            transport
+        });
+
+        let client = McpClient::new(&uri).await.unwrap();
+        let (agents, tasks) = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            async { tokio::join!(client.read_resource("agents"), client.read_resource("tasks")) },
+        ).await.unwrap();
+        assert_eq!(agents.unwrap().text.as_deref(), Some("agents"));
+        assert_eq!(tasks.unwrap().text.as_deref(), Some("tasks"));
+
+        // Once the server hangs up, requests fail rather than wait
+        server.await.unwrap().close().await.unwrap();
+        assert!(matches!(client.read_resource("agents").await, Err(McpError::ConnectionClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_client_speaks_mcp_over_tcp_and_unix_sockets() {
+        let listener = TransportListener::bind_tcp("127.0.0.1:0").await.unwrap();
+        let uri = format!("tcp://{}", listener.local_addr());
+        let (stop, handle) = start(listener);
+        let client = McpClient::new(&uri).await.unwrap();
+        create_and_read_back(&client, "Lexer").await;
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dfcoder-mcp.sock");
+        let (stop, handle) = start(TransportListener::bind_unix(&path).await.unwrap());
+        let client = McpClient::new(&format!("unix://{}", path.display())).await.unwrap();
+        create_and_read_back(&client, "Parser").await;
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+}
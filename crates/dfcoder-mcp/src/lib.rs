@@ -4,23 +4,30 @@
 //! enabling seamless integration with external tools and services
 //! through a standardized protocol.
 
-use dfcoder_macros::mcp_resources;
 use dfcoder_types::*;
 use dfcoder_core::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
 
 pub use client::*;
 pub use server::*;
 pub use resources::*;
 pub use protocol::*;
 pub use transport::*;
+pub use session::*;
+pub use streamable_http::*;
 
 mod client;
 mod server;
 mod resources;
 mod protocol;
 mod transport;
+mod session;
+mod streamable_http;
 
 // Define resource exposures using the DSL
 // mcp_resources! {
@@ -33,6 +40,7 @@ mod transport;
 
 /// MCP configuration for DFCoder
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct McpConfig {
     /// Server name for identification
     pub server_name: String,
@@ -49,8 +57,9 @@ pub struct McpConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TransportConfig {
-    /// Transport type (stdio, websocket, tcp)
+    /// Transport type (stdio, websocket, tcp, unix, http)
     pub transport_type: TransportType,
     /// Address for network transports
     pub address: Option<String>,
@@ -66,6 +75,8 @@ pub enum TransportType {
     WebSocket,
     Tcp,
     Unix,
+    /// Streamable HTTP, with server messages on a server-sent event stream
+    Http,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -104,12 +115,7 @@ impl Default for McpConfig {
             server_name: "dfcoder".to_string(),
             server_version: "1.0.0".to_string(),
             protocol_version: "2024-11-05".to_string(),
-            transport: TransportConfig {
-                transport_type: TransportType::Stdio,
-                address: None,
-                port: None,
-                timeout_ms: 30000,
-            },
+            transport: TransportConfig::default(),
             security: SecurityConfig {
                 require_auth: false,
                 api_keys: Vec::new(),
@@ -125,38 +131,168 @@ impl Default for McpConfig {
     }
 }
 
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            transport_type: TransportType::Stdio,
+            address: None,
+            port: None,
+            timeout_ms: 30000,
+        }
+    }
+}
+
+/// Default socket dfcoderd serves MCP on, preferring `$XDG_RUNTIME_DIR` over the temp directory
+pub fn default_mcp_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dfcoder-mcp.sock")
+}
+
+/// How often a running service mirrors the workshop into its resources
+pub const RESOURCE_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Main MCP service for DFCoder
 #[derive(Debug)]
 pub struct McpService {
     config: McpConfig,
-    // server: McpServer,
+    server: Arc<DFCoderMCPServer>,
     client: Option<McpClient>,
-    resource_manager: ResourceManager,
+    resource_manager: Arc<ResourceManager>,
+    sync_interval: Duration,
 }
 
 impl McpService {
-    /// Create a new MCP service
+    /// Create a new MCP service backed by its own workshop
     pub fn new(config: McpConfig) -> Result<Self, McpError> {
-        // let server = McpServer::new(config.clone())?;
-        let resource_manager = ResourceManager::new(config.resources.clone());
+        Self::with_workshop(config, Arc::new(Mutex::new(WorkshopManager::new())))
+    }
+    
+    /// Create a new MCP service serving an existing workshop
+    pub fn with_workshop(config: McpConfig, workshop: Arc<Mutex<WorkshopManager>>) -> Result<Self, McpError> {
+        Self::with_server(config, DFCoderMCPServer::new(workshop))
+    }
+    
+    /// Create a new MCP service around a configured server
+    pub fn with_server(config: McpConfig, server: DFCoderMCPServer) -> Result<Self, McpError> {
+        let resource_manager = Arc::new(ResourceManager::new(config.resources.clone()));
         
         Ok(Self {
             config,
-            // server,
+            server: Arc::new(server),
             client: None,
             resource_manager,
+            sync_interval: RESOURCE_SYNC_INTERVAL,
         })
     }
     
-    /// Start the MCP server
+    /// Mirror the workshop into the resources this often while running
+    pub fn with_sync_interval(mut self, sync_interval: Duration) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+    
+    /// The server answering MCP requests
+    pub fn server(&self) -> &Arc<DFCoderMCPServer> {
+        &self.server
+    }
+    
+    /// The agent, pane and task resources that sessions publish and watch
+    pub fn resources(&self) -> &Arc<ResourceManager> {
+        &self.resource_manager
+    }
+    
+    /// Start a session for one client of the server
+    pub fn session(&self) -> ServerSession {
+        ServerSession::new(self.server.clone(), self.resource_manager.clone(), &self.config)
+    }
+    
+    /// Serve the configured transport until the client disconnects
     pub async fn start_server(&mut self) -> Result<(), McpError> {
-        // Register resources with the server
-        self.register_resources().await?;
+        self.run(std::future::pending()).await
+    }
+    
+    /// Serve the configured transport until `shutdown` resolves
+    ///
+    /// Socket transports accept any number of clients, each in its own
+    /// session; stdio serves its single client until it disconnects. The
+    /// resources are kept in step with the workshop meanwhile, so subscribers
+    /// hear of changes that no session made.
+    pub async fn run(&self, shutdown: impl Future<Output = ()>) -> Result<(), McpError> {
+        tokio::select! {
+            served = self.serve(shutdown) => served,
+            _ = self.sync_resources() => Ok(()),
+        }
+    }
+    
+    /// Mirror the workshop into the resources every `sync_interval`, forever
+    async fn sync_resources(&self) {
+        let mut ticks = tokio::time::interval(self.sync_interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            self.server.publish_resources(&self.resource_manager).await;
+        }
+    }
+    
+    async fn serve(&self, shutdown: impl Future<Output = ()>) -> Result<(), McpError> {
+        match self.config.transport.transport_type {
+            TransportType::Tcp | TransportType::WebSocket | TransportType::Unix => {
+                let listener = TransportListener::bind(&self.config.transport).await?;
+                self.serve_listener(listener, shutdown).await
+            }
+            TransportType::Http => {
+                let address = self.config.transport.address.as_deref().unwrap_or("127.0.0.1");
+                let port = self.config.transport.port
+                    .ok_or_else(|| McpError::TransportError("HTTP port required".to_string()))?;
+                let server = HttpServer::bind(&format!("{}:{}", address, port)).await?;
+                let (mcp, resources, config) = (self.server.clone(), self.resource_manager.clone(), self.config.clone());
+                server.serve(move || ServerSession::new(mcp.clone(), resources.clone(), &config), shutdown).await
+            }
+            TransportType::Stdio => {
+                let transport = TransportFactory::create_transport(&self.config.transport).await?;
+                let session = self.session();
+                tracing::info!("MCP server started on {:?}", self.config.transport.transport_type);
+                tokio::select! {
+                    _ = shutdown => transport.close().await,
+                    served = session.serve(transport.as_ref()) => served,
+                }
+            }
+        }
+    }
+    
+    /// Give every client of `listener` its own session until `shutdown` resolves
+    pub async fn serve_listener(&self, listener: TransportListener, shutdown: impl Future<Output = ()>) -> Result<(), McpError> {
+        tracing::info!("MCP server listening on {}", listener.local_addr());
+        tokio::pin!(shutdown);
         
-        // Start the server
-        self.server.start().await?;
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(incoming) => {
+                        let session = self.session();
+                        tokio::spawn(async move {
+                            let peer = incoming.peer().to_string();
+                            let served = match incoming.establish().await {
+                                Ok(transport) => {
+                                    tracing::info!("MCP client connected from {}", peer);
+                                    session.serve(transport.as_ref()).await
+                                }
+                                Err(e) => Err(e),
+                            };
+                            if let Err(e) = served {
+                                tracing::warn!("MCP session with {} ended with error: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::error!("Failed to accept MCP client: {}", e),
+                },
+            }
+        }
         
-        tracing::info!("MCP server started on {:?}", self.config.transport.transport_type);
+        tracing::info!("MCP server shutting down");
         Ok(())
     }
     
@@ -184,11 +320,6 @@ impl McpService {
         self.resource_manager.send_command(agent_id, command).await
     }
     
-    /// Register a new tool via MCP
-    pub async fn register_tool(&mut self, tool: ToolDefinition) -> Result<(), McpError> {
-        self.server.register_tool(tool).await
-    }
-    
     /// Call a tool via MCP
     pub async fn call_tool(&self, tool_name: &str, arguments: serde_json::Value) -> Result<ToolResult, McpError> {
         if let Some(ref client) = self.client {
@@ -198,18 +329,26 @@ impl McpService {
         }
     }
     
-    /// Register prompt templates
-    pub async fn register_prompt(&mut self, prompt: PromptDefinition) -> Result<(), McpError> {
-        self.server.register_prompt(prompt).await
-    }
-    
     /// Get prompt template
     pub async fn get_prompt(&self, prompt_name: &str, arguments: Option<serde_json::Value>) -> Result<PromptResult, McpError> {
         if let Some(ref client) = self.client {
-            client.get_prompt(prompt_name, arguments).await
-        } else {
-            self.server.get_prompt(prompt_name, arguments).await
+            return client.get_prompt(prompt_name, arguments).await;
         }
+        
+        let prompt = self.server
+            .get_prompt(prompt_name, arguments.unwrap_or_else(|| serde_json::json!({})))
+            .await
+            .map_err(|e| McpError::ResourceError(e.to_string()))?;
+        Ok(PromptResult {
+            description: prompt.description,
+            messages: prompt.messages.into_iter().map(|message| PromptMessage {
+                role: message.role,
+                content: PromptContent {
+                    type_: "text".to_string(),
+                    text: message.content,
+                },
+            }).collect(),
+        })
     }
     
     /// Subscribe to resource changes
@@ -219,55 +358,7 @@ impl McpService {
     
     /// Get server capabilities
     pub fn get_capabilities(&self) -> ServerCapabilities {
-        self.server.get_capabilities()
-    }
-    
-    async fn register_resources(&mut self) -> Result<(), McpError> {
-        if self.config.resources.expose_agents {
-            let agent_resource = ResourceDefinition {
-                uri: "dfcoder://agents".to_string(),
-                name: "DFCoder Agents".to_string(),
-                description: "Active AI agents in DFCoder".to_string(),
-                mime_type: "application/json".to_string(),
-                capabilities: vec![
-                    ResourceCapability::Read,
-                    ResourceCapability::List,
-                    ResourceCapability::Write,
-                ],
-            };
-            self.server.register_resource(agent_resource).await?;
-        }
-        
-        if self.config.resources.expose_panes {
-            let pane_resource = ResourceDefinition {
-                uri: "dfcoder://panes".to_string(),
-                name: "DFCoder Panes".to_string(),
-                description: "Terminal panes managed by DFCoder".to_string(),
-                mime_type: "application/json".to_string(),
-                capabilities: vec![
-                    ResourceCapability::Read,
-                    ResourceCapability::List,
-                ],
-            };
-            self.server.register_resource(pane_resource).await?;
-        }
-        
-        if self.config.resources.expose_tasks {
-            let task_resource = ResourceDefinition {
-                uri: "dfcoder://tasks".to_string(),
-                name: "DFCoder Tasks".to_string(),
-                description: "Active and completed tasks".to_string(),
-                mime_type: "application/json".to_string(),
-                capabilities: vec![
-                    ResourceCapability::Read,
-                    ResourceCapability::List,
-                    ResourceCapability::Write,
-                ],
-            };
-            self.server.register_resource(task_resource).await?;
-        }
-        
-        Ok(())
+        ServerCapabilities::default()
     }
 }
 
@@ -307,13 +398,17 @@ pub struct ToolDefinition {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     pub content: Vec<ToolContent>,
+    #[serde(rename = "isError", default)]
     pub is_error: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolContent {
+    #[serde(rename = "type")]
     pub type_: String,
+    #[serde(default)]
     pub text: Option<String>,
+    #[serde(default)]
     pub data: Option<serde_json::Value>,
 }
 
@@ -347,6 +442,7 @@ pub struct PromptMessage {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptContent {
+    #[serde(rename = "type")]
     pub type_: String,
     pub text: String,
 }
@@ -362,28 +458,51 @@ pub struct ResourceSubscription {
 /// Server capabilities
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub logging: Option<LoggingCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub prompts: Option<PromptsCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub resources: Option<ResourcesCapability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tools: Option<ToolsCapability>,
 }
 
+impl Default for ServerCapabilities {
+    /// What `ServerSession` offers: watchable resources, tools and prompts
+    fn default() -> Self {
+        Self {
+            logging: None,
+            prompts: Some(PromptsCapability { list_changed: false }),
+            resources: Some(ResourcesCapability { subscribe: true, list_changed: true }),
+            tools: Some(ToolsCapability { list_changed: false }),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingCapability {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PromptsCapability {
+    #[serde(default)]
     pub list_changed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ResourcesCapability {
+    #[serde(default)]
     pub subscribe: bool,
+    #[serde(default)]
     pub list_changed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ToolsCapability {
+    #[serde(default)]
     pub list_changed: bool,
 }
 
@@ -404,6 +523,10 @@ pub enum McpError {
     ClientNotConnected,
     #[error("Server not started")]
     ServerNotStarted,
+    #[error("Connection closed")]
+    ConnectionClosed,
+    #[error("RPC error {}: {}", .0.code, .0.message)]
+    Rpc(McpRpcError),
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
     #[error("IO error: {0}")]
@@ -461,4 +584,81 @@ impl Default for McpServiceBuilder {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    async fn next_matching(transport: &StreamTransport, matches: impl Fn(&Value) -> bool) -> Value {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let message: Value = serde_json::from_str(&transport.receive().await.unwrap()).unwrap();
+                if matches(&message) {
+                    return message;
+                }
+            }
+        }).await.unwrap()
+    }
+
+    async fn call(transport: &StreamTransport, id: u64, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        transport.send(&request.to_string()).await.unwrap();
+        next_matching(transport, |message| message["id"] == id).await
+    }
+
+    #[tokio::test]
+    async fn test_run_publishes_changes_nobody_asked_about() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.sock");
+        let mut config = McpConfig::default();
+        config.transport.transport_type = TransportType::Unix;
+        config.transport.address = Some(path.display().to_string());
+
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let output = Arc::new(Mutex::new(OutputIngestor::default()));
+        let agent = Agent::new(AgentRole::Implementer, 3);
+        let agent_id = agent.id.clone();
+        workshop.lock().await.register_agent(agent).unwrap();
+
+        let server = DFCoderMCPServer::new(workshop.clone()).with_pane_output(output.clone());
+        let service = McpService::with_server(config, server).unwrap()
+            .with_sync_interval(Duration::from_millis(10));
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(async move { service.run(async { let _ = stopped.await; }).await });
+
+        let mut client = None;
+        for _ in 0..100 {
+            if let Ok(connected) = StreamTransport::connect_unix(&path).await {
+                client = Some(connected);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let client = client.expect("service never started listening");
+        call(&client, 0, "initialize", json!({ "protocolVersion": "2024-11-05" })).await;
+        client.send(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string()).await.unwrap();
+        call(&client, 1, "resources/subscribe", json!({ "uri": "dfcoder://panes/*" })).await;
+        call(&client, 2, "resources/subscribe", json!({ "uri": "dfcoder://agents/*" })).await;
+
+        // Output reaches the pane without the client making another request
+        output.lock().await.ingest(&agent_id, b"Compiling parser v0.1.0\n");
+        let updated = next_matching(&client, |message| {
+            message["method"] == "notifications/resources/updated" && message["params"]["uri"] == "dfcoder://panes/3"
+        }).await;
+        assert_eq!(updated["params"]["uri"], "dfcoder://panes/3");
+        let read = call(&client, 3, "resources/read", json!({ "uri": "dfcoder://panes/3" })).await;
+        assert!(read["result"]["contents"][0]["text"].as_str().unwrap().contains("Compiling parser"));
+
+        // So does a change made to the workshop directly
+        let agent_uri = format!("dfcoder://agents/{}", agent_id);
+        workshop.lock().await.get_agent_mut(&agent_id).unwrap().status = AgentStatus::Working;
+        next_matching(&client, |message| {
+            message["method"] == "notifications/resources/updated" && message["params"]["uri"] == agent_uri.as_str()
+        }).await;
+
+        stop.send(()).unwrap();
+        running.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::{bail, Context, Result};
+use dfcoder_mcp::default_mcp_socket_path;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixStream;
+
+const USAGE: &str = "usage: dfcoder-mcp [--socket <path>]
+
+Relays MCP between stdin/stdout and the MCP server dfcoderd hosts on a Unix
+socket, so clients that launch MCP servers as subprocesses see the daemon's
+agents, tasks and workshop status. Start dfcoderd with --mcp to serve the
+default socket; the [mcp] section of dfcoderd's config can serve TCP,
+WebSocket and Streamable HTTP clients directly instead. Logs go to stderr.";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Stdout carries the protocol, so logging must stay off it
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    let socket_path = parse_args(std::env::args().skip(1))?;
+    let stream = UnixStream::connect(&socket_path).await.with_context(|| {
+        format!("no MCP server at {}; is dfcoderd running with --mcp?", socket_path.display())
+    })?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    // Closing stdin ends the session; the daemon then closes its side, which ends the relay
+    let upstream = tokio::spawn(async move {
+        tokio::io::copy(&mut tokio::io::stdin(), &mut writer).await?;
+        writer.shutdown().await
+    });
+
+    let mut stdout = tokio::io::stdout();
+    tokio::io::copy(&mut reader, &mut stdout).await?;
+    stdout.flush().await?;
+    upstream.abort();
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<PathBuf> {
+    let mut socket_path = default_mcp_socket_path();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => socket_path = PathBuf::from(args.next().context(USAGE)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => bail!("unknown argument '{}'\n{}", other, USAGE),
+        }
+    }
+
+    Ok(socket_path)
+}
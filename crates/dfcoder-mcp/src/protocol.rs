@@ -1,6 +1,9 @@
 use crate::*;
 use serde_json::Value;
 
+/// Protocol revisions the server can speak, newest first
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
 /// MCP protocol implementation
 #[derive(Debug, Clone)]
 pub struct McpProtocol {
@@ -12,6 +15,11 @@ impl McpProtocol {
         Self { version }
     }
     
+    /// Protocol version this side prefers
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+    
     /// Create initialization request
     pub fn create_initialize_request(&self, capabilities: ClientCapabilities) -> McpMessage {
         McpMessage {
@@ -189,6 +197,14 @@ pub struct McpRpcError {
 }
 
 impl McpRpcError {
+    pub fn parse_error(message: &str) -> Self {
+        Self {
+            code: -32700,
+            message: format!("Parse error: {}", message),
+            data: None,
+        }
+    }
+    
     pub fn invalid_request(message: &str) -> Self {
         Self {
             code: -32600,
@@ -221,6 +237,15 @@ impl McpRpcError {
         }
     }
     
+    /// MCP's error for a `resources/read` of an unknown URI
+    pub fn resource_not_found(uri: &str) -> Self {
+        Self {
+            code: -32002,
+            message: format!("Resource not found: {}", uri),
+            data: Some(serde_json::json!({ "uri": uri })),
+        }
+    }
+    
     pub fn custom_error(code: i32, message: &str) -> Self {
         Self {
             code,
@@ -231,7 +256,7 @@ impl McpRpcError {
 }
 
 /// Client capabilities
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub experimental: Option<Value>,
@@ -239,15 +264,6 @@ pub struct ClientCapabilities {
     pub sampling: Option<Value>,
 }
 
-impl Default for ClientCapabilities {
-    fn default() -> Self {
-        Self {
-            experimental: None,
-            sampling: None,
-        }
-    }
-}
-
 /// Message handler trait
 #[async_trait::async_trait]
 pub trait MessageHandler: Send + Sync {
@@ -263,9 +279,15 @@ pub trait MessageHandler: Send + Sync {
     /// Handle resource read request
     async fn handle_read_resource(&self, uri: &str) -> Result<Value, McpError>;
     
+    /// Handle tool list request
+    async fn handle_list_tools(&self) -> Result<Value, McpError>;
+    
     /// Handle tool call request
     async fn handle_tool_call(&self, name: &str, arguments: Value) -> Result<Value, McpError>;
     
+    /// Handle prompt list request
+    async fn handle_list_prompts(&self) -> Result<Value, McpError>;
+    
     /// Handle prompt get request
     async fn handle_get_prompt(&self, name: &str, arguments: Option<Value>) -> Result<Value, McpError>;
 }
@@ -19,7 +19,7 @@ pub struct ResourceManager {
 pub struct AgentResource {
     pub id: String,
     pub name: String,
-    pub status: dfcoder_core::AgentStatus,
+    pub status: AgentStatus,
     pub current_task: Option<String>,
     pub last_activity: chrono::DateTime<chrono::Utc>,
     pub metrics: AgentMetrics,
@@ -61,6 +61,84 @@ pub enum ResourceChange {
     TaskAdded(String),
     TaskUpdated(String),
     TaskCompleted(String),
+    TaskRemoved(String),
+}
+
+impl ResourceChange {
+    /// URI of the resource that changed
+    pub fn uri(&self) -> String {
+        match self {
+            ResourceChange::AgentAdded(id)
+            | ResourceChange::AgentUpdated(id)
+            | ResourceChange::AgentRemoved(id) => format!("dfcoder://agents/{}", id),
+            ResourceChange::PaneAdded(id)
+            | ResourceChange::PaneUpdated(id)
+            | ResourceChange::PaneRemoved(id) => format!("dfcoder://panes/{}", id),
+            ResourceChange::TaskAdded(id)
+            | ResourceChange::TaskUpdated(id)
+            | ResourceChange::TaskCompleted(id)
+            | ResourceChange::TaskRemoved(id) => format!("dfcoder://tasks/{}", id),
+        }
+    }
+    
+    /// Whether the change adds or removes a resource, altering `resources/list`
+    pub fn alters_listing(&self) -> bool {
+        matches!(
+            self,
+            ResourceChange::AgentAdded(_)
+                | ResourceChange::AgentRemoved(_)
+                | ResourceChange::PaneAdded(_)
+                | ResourceChange::PaneRemoved(_)
+                | ResourceChange::TaskAdded(_)
+                | ResourceChange::TaskRemoved(_)
+        )
+    }
+}
+
+impl ResourceSubscription {
+    /// Whether `uri` is covered by this subscription's pattern
+    pub fn matches(&self, uri: &str) -> bool {
+        self.active && glob_match(&self.pattern, uri)
+    }
+}
+
+/// Match a URI against a glob where `*` spans part of one path segment,
+/// `**` spans any number of segments and `?` is one character
+fn glob_match(pattern: &str, uri: &str) -> bool {
+    let uri: Vec<char> = uri.chars().collect();
+    // matched[j]: the pattern so far matches the first j characters of the URI
+    let mut matched = vec![false; uri.len() + 1];
+    matched[0] = true;
+    
+    let mut pattern = pattern.chars().peekable();
+    while let Some(token) = pattern.next() {
+        let mut next = vec![false; uri.len() + 1];
+        match token {
+            '*' if pattern.next_if_eq(&'*').is_some() => {
+                for j in 0..=uri.len() {
+                    next[j] = matched[j] || (j > 0 && next[j - 1]);
+                }
+            }
+            '*' => {
+                for j in 0..=uri.len() {
+                    next[j] = matched[j] || (j > 0 && next[j - 1] && uri[j - 1] != '/');
+                }
+            }
+            '?' => {
+                for j in 1..=uri.len() {
+                    next[j] = matched[j - 1] && uri[j - 1] != '/';
+                }
+            }
+            literal => {
+                for j in 1..=uri.len() {
+                    next[j] = matched[j - 1] && uri[j - 1] == literal;
+                }
+            }
+        }
+        matched = next;
+    }
+    
+    matched[uri.len()]
 }
 
 /// Resource definition for MCP
@@ -301,6 +379,38 @@ impl ResourceManager {
         let _ = self.change_sender.send(change);
     }
     
+    /// Remove a pane resource
+    pub async fn remove_pane(&self, pane_id: &str) {
+        if !self.config.expose_panes {
+            return;
+        }
+        
+        let mut panes = self.panes.write().await;
+        if panes.remove(pane_id).is_some() {
+            let _ = self.change_sender.send(ResourceChange::PaneRemoved(pane_id.to_string()));
+        }
+    }
+    
+    /// Mirror the given panes, announcing only what changed
+    pub async fn sync_panes(&self, panes: Vec<PaneResource>) {
+        let known: Vec<String> = self.panes.read().await.keys().cloned().collect();
+        for id in known.iter().filter(|id| !panes.iter().any(|pane| &pane.id == *id)) {
+            self.remove_pane(id).await;
+        }
+        for mut pane in panes {
+            let existing = self.panes.read().await.get(&pane.id).cloned();
+            if let Some(known) = existing {
+                if known.content == pane.content && known.is_active == pane.is_active && known.title == pane.title {
+                    continue;
+                }
+                if known.content == pane.content {
+                    pane.last_update = known.last_update;
+                }
+            }
+            self.update_pane(pane).await;
+        }
+    }
+    
     /// List all tasks as resources
     pub async fn list_tasks(&self) -> Result<Vec<Resource>, McpError> {
         if !self.config.expose_tasks {
@@ -346,6 +456,80 @@ impl ResourceManager {
         let _ = self.change_sender.send(change);
     }
     
+    /// Remove a task resource
+    pub async fn remove_task(&self, task_id: &str) {
+        if !self.config.expose_tasks {
+            return;
+        }
+        
+        let mut tasks = self.tasks.write().await;
+        if tasks.remove(task_id).is_some() {
+            let _ = self.change_sender.send(ResourceChange::TaskRemoved(task_id.to_string()));
+        }
+    }
+    
+    /// Mirror the workshop's agents and tasks, announcing only what changed
+    pub async fn sync_workshop(&self, workshop: &WorkshopManager) {
+        let agents: Vec<AgentResource> = workshop.get_all_agents().into_iter()
+            .map(|agent| ResourceFactory::create_agent_resource(agent.id.clone(), &AgentState::from(agent)))
+            .collect();
+        let known: Vec<String> = self.agents.read().await.keys().cloned().collect();
+        for id in known.iter().filter(|id| !agents.iter().any(|agent| &agent.id == *id)) {
+            self.remove_agent(id).await;
+        }
+        for agent in agents {
+            let changed = match self.agents.read().await.get(&agent.id) {
+                Some(known) => {
+                    known.status != agent.status
+                        || known.current_task != agent.current_task
+                        || serde_json::to_value(&known.metrics).ok() != serde_json::to_value(&agent.metrics).ok()
+                }
+                None => true,
+            };
+            if changed {
+                self.update_agent(agent).await;
+            }
+        }
+        
+        let live: Vec<&Task> = workshop.get_queue().iter().chain(workshop.get_in_flight_tasks()).collect();
+        for task in &live {
+            let existing = self.tasks.read().await.get(&task.id).cloned();
+            if existing.as_ref().is_some_and(|known| known.status == task.status && known.assigned_agent == task.assignee) {
+                continue;
+            }
+            let mut resource = ResourceFactory::create_task_resource(
+                task.id.clone(),
+                task.description.clone(),
+                task.status.clone(),
+                task.assignee.clone(),
+            );
+            if let Some(known) = existing {
+                resource.created_at = known.created_at;
+            }
+            self.update_task(resource).await;
+        }
+        
+        // Tasks that left the workshop keep their final status while the task graph remembers it
+        let finished: Vec<TaskResource> = self.tasks.read().await.values()
+            .filter(|known| !live.iter().any(|task| task.id == known.id))
+            .cloned()
+            .collect();
+        for mut known in finished {
+            match workshop.task_graph().status(&known.id) {
+                Some(status) if *status == known.status => {}
+                Some(status) => {
+                    known.status = status.clone();
+                    if known.status == TaskStatus::Completed {
+                        known.completed_at = Some(chrono::Utc::now());
+                        known.progress = 1.0;
+                    }
+                    self.update_task(known).await;
+                }
+                None => self.remove_task(&known.id).await,
+            }
+        }
+    }
+    
     /// Subscribe to resource changes
     pub async fn subscribe(&self, pattern: &str) -> Result<ResourceSubscription, McpError> {
         let subscription_id = uuid::Uuid::new_v4().to_string();
@@ -368,6 +552,15 @@ impl ResourceManager {
         Ok(())
     }
     
+    /// Active subscriptions whose pattern covers `uri`
+    pub async fn subscriptions_for(&self, uri: &str) -> Vec<ResourceSubscription> {
+        self.subscriptions.read().await
+            .values()
+            .filter(|subscription| subscription.matches(uri))
+            .cloned()
+            .collect()
+    }
+    
     /// Get resource change stream
     pub fn get_change_stream(&self) -> broadcast::Receiver<ResourceChange> {
         self.change_sender.subscribe()
@@ -460,4 +653,70 @@ impl ResourceFactory {
             progress: 0.0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_glob_patterns() {
+        assert!(glob_match("dfcoder://agents/*", "dfcoder://agents/agent-1"));
+        assert!(!glob_match("dfcoder://agents/*", "dfcoder://tasks/task-1"));
+        assert!(!glob_match("dfcoder://agents/*", "dfcoder://agents/agent-1/log"));
+        assert!(glob_match("dfcoder://**", "dfcoder://agents/agent-1/log"));
+        assert!(glob_match("dfcoder://panes/?", "dfcoder://panes/3"));
+        assert!(!glob_match("dfcoder://panes/?", "dfcoder://panes/12"));
+        assert!(glob_match("dfcoder://tasks/task-1", "dfcoder://tasks/task-1"));
+        assert!(!glob_match("dfcoder://tasks/task-1", "dfcoder://tasks/task-10"));
+    }
+    
+    #[tokio::test]
+    async fn test_sync_workshop_announces_only_changes() {
+        let manager = ResourceManager::new(McpConfig::default().resources);
+        let mut workshop = WorkshopManager::new();
+        workshop.register_agent(Agent::new(AgentRole::Implementer, 1)).unwrap();
+        let task = Task::new("Parser".to_string(), "Write the parser".to_string(), AgentRole::Implementer, TaskPriority::Normal);
+        let task_id = task.id.clone();
+        workshop.queue_task(task).unwrap();
+        let mut changes = manager.get_change_stream();
+        
+        manager.sync_workshop(&workshop).await;
+        let mut announced = [changes.recv().await.unwrap(), changes.recv().await.unwrap()];
+        announced.sort_by_key(ResourceChange::uri);
+        assert!(matches!(&announced[0], ResourceChange::AgentAdded(_)));
+        assert!(matches!(&announced[1], ResourceChange::TaskAdded(id) if *id == task_id));
+        
+        // Nothing changed, so nothing is announced
+        manager.sync_workshop(&workshop).await;
+        assert!(changes.try_recv().is_err());
+        
+        let (agent_id, assigned) = workshop.assign_by_priority().unwrap().unwrap();
+        workshop.complete_task(agent_id, assigned.id).unwrap();
+        manager.sync_workshop(&workshop).await;
+        let announced: Vec<ResourceChange> = std::iter::from_fn(|| changes.try_recv().ok()).collect();
+        assert!(announced.iter().any(|change| matches!(change, ResourceChange::TaskCompleted(id) if *id == task_id)));
+        assert!(announced.iter().any(|change| matches!(change, ResourceChange::AgentUpdated(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_subscriptions_follow_changes() {
+        let manager = ResourceManager::new(McpConfig::default().resources);
+        let agents = manager.subscribe("dfcoder://agents/*").await.unwrap();
+        manager.subscribe("dfcoder://tasks/task-1").await.unwrap();
+        let mut changes = manager.get_change_stream();
+        
+        let agent = ResourceFactory::create_agent_resource("agent-1".to_string(), &AgentState::with_status(AgentStatus::Idle));
+        manager.update_agent(agent).await;
+        let change = changes.recv().await.unwrap();
+        assert!(change.alters_listing());
+        
+        let subscribed = manager.subscriptions_for(&change.uri()).await;
+        assert_eq!(subscribed.len(), 1);
+        assert_eq!(subscribed[0].id, agents.id);
+        
+        manager.unsubscribe(&agents.id).await.unwrap();
+        assert!(manager.subscriptions_for("dfcoder://agents/agent-1").await.is_empty());
+        assert_eq!(manager.subscriptions_for("dfcoder://tasks/task-1").await.len(), 1);
+    }
+}
@@ -1,21 +1,30 @@
 //! MCP server implementation for DFCoder agent monitoring
 
-use crate::protocol::*;
-use dfcoder_core::{Agent, Task, WorkshopManager, AgentId, TaskId};
+use crate::PaneResource;
+use dfcoder_core::{Agent, AgentStatus, OutputIngestor, Task, WorkshopManager, AgentId, TaskId};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::Mutex;
 
 /// MCP server for DFCoder agent management
 pub struct DFCoderMCPServer {
-    agents: Arc<RwLock<HashMap<AgentId, Agent>>>,
     workshop: Arc<Mutex<WorkshopManager>>,
+    /// Cleaned output of the agents' panes, when shared with whoever ingests it
+    pane_output: Option<Arc<Mutex<OutputIngestor>>>,
     event_handlers: Vec<Box<dyn Fn(McpEvent) + Send + Sync>>,
 }
 
+impl std::fmt::Debug for DFCoderMCPServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DFCoderMCPServer")
+            .field("workshop", &self.workshop)
+            .field("event_handlers", &self.event_handlers.len())
+            .finish_non_exhaustive()
+    }
+}
+
 /// Events that can be emitted by the MCP server
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum McpEvent {
@@ -59,27 +68,50 @@ impl DFCoderMCPServer {
     /// Create a new MCP server
     pub fn new(workshop: Arc<Mutex<WorkshopManager>>) -> Self {
         Self {
-            agents: Arc::new(RwLock::new(HashMap::new())),
             workshop,
+            pane_output: None,
             event_handlers: Vec::new(),
         }
     }
 
+    /// Serve pane contents from `output`, such as the daemon's output ingestor
+    pub fn with_pane_output(mut self, output: Arc<Mutex<OutputIngestor>>) -> Self {
+        self.pane_output = Some(output);
+        self
+    }
+
     /// Register an agent with the MCP server
     pub async fn register_agent(&self, agent: Agent) -> Result<(), McpServerError> {
-        let agent_id = agent.id.clone();
-        {
-            let mut agents = self.agents.write().unwrap();
-            agents.insert(agent_id.clone(), agent.clone());
-        }
-
-        // Register with workshop
         let mut workshop = self.workshop.lock().await;
         workshop.register_agent(agent).map_err(|e| McpServerError::WorkshopError(e.to_string()))?;
 
         Ok(())
     }
 
+    /// Mirror the workshop and its agents' panes into `resources`, so their subscribers hear of changes
+    pub async fn publish_resources(&self, resources: &crate::ResourceManager) {
+        let agents: Vec<Agent> = {
+            let workshop = self.workshop.lock().await;
+            resources.sync_workshop(&workshop).await;
+            workshop.get_all_agents().into_iter().cloned().collect()
+        };
+
+        let output = match &self.pane_output {
+            Some(output) => Some(output.lock().await),
+            None => None,
+        };
+        let panes = agents.iter().map(|agent| PaneResource {
+            id: agent.pane_id.to_string(),
+            title: format!("Pane {} ({})", agent.pane_id, agent.role),
+            content: output.as_ref().and_then(|output| output.recent_output(&agent.id)).unwrap_or_default(),
+            is_active: agent.status == AgentStatus::Working,
+            last_update: chrono::Utc::now(),
+            command_history: Vec::new(),
+        }).collect();
+        drop(output);
+        resources.sync_panes(panes).await;
+    }
+
     /// List available resources
     pub async fn list_resources(&self) -> Vec<McpResource> {
         vec![
@@ -237,12 +269,12 @@ impl DFCoderMCPServer {
 
     // Internal resource readers
     async fn read_agents_resource(&self, params: Option<Value>) -> Result<Value, McpServerError> {
-        let agents = self.agents.read().unwrap();
+        let workshop = self.workshop.lock().await;
         
         if let Some(params) = params {
             if let Some(agent_id) = params.get("agent_id").and_then(|v| v.as_str()) {
                 // Return specific agent
-                if let Some(agent) = agents.get(agent_id) {
+                if let Some(agent) = workshop.get_agent(&agent_id.to_string()) {
                     return Ok(serde_json::to_value(agent)?);
                 } else {
                     return Err(McpServerError::AgentNotFound(agent_id.to_string()));
@@ -251,7 +283,7 @@ impl DFCoderMCPServer {
         }
         
         // Return all agents
-        let agent_list: Vec<_> = agents.values().collect();
+        let agent_list = workshop.get_all_agents();
         Ok(serde_json::to_value(agent_list)?)
     }
 
@@ -262,7 +294,7 @@ impl DFCoderMCPServer {
     }
 
     async fn read_workshop_resource(&self) -> Result<Value, McpServerError> {
-        let workshop = self.workshop.lock().await;
+        let mut workshop = self.workshop.lock().await;
         let status = workshop.get_status();
         Ok(serde_json::to_value(status)?)
     }
@@ -281,8 +313,8 @@ impl DFCoderMCPServer {
                     .ok_or_else(|| McpServerError::InvalidRequest("Missing status".to_string()))?;
 
                 // Update agent status (simplified for demo)
-                let mut agents = self.agents.write().unwrap();
-                if let Some(agent) = agents.get_mut(agent_id) {
+                let workshop = self.workshop.lock().await;
+                if let Some(agent) = workshop.get_agent(&agent_id.to_string()) {
                     // In a real implementation, this would properly update the agent status
                     // For now, just emit an event
                     self.emit_event(McpEvent::AgentStateChanged {
@@ -304,44 +336,47 @@ impl DFCoderMCPServer {
             .ok_or_else(|| McpServerError::InvalidRequest("Missing action field".to_string()))?;
 
         match action {
-            "create" => {
-                // Parse task creation request
-                let title = content.get("title").and_then(|v| v.as_str())
-                    .ok_or_else(|| McpServerError::InvalidRequest("Missing title".to_string()))?;
-                let description = content.get("description").and_then(|v| v.as_str())
-                    .ok_or_else(|| McpServerError::InvalidRequest("Missing description".to_string()))?;
-                let role_str = content.get("role").and_then(|v| v.as_str())
-                    .ok_or_else(|| McpServerError::InvalidRequest("Missing role".to_string()))?;
-                
-                // Parse role and priority
-                let role = match role_str {
-                    "Scaffolder" => dfcoder_core::AgentRole::Scaffolder,
-                    "Implementer" => dfcoder_core::AgentRole::Implementer,
-                    "Debugger" => dfcoder_core::AgentRole::Debugger,
-                    "Tester" => dfcoder_core::AgentRole::Tester,
-                    _ => return Err(McpServerError::InvalidRequest("Invalid role".to_string())),
-                };
-
-                let priority = content.get("priority").and_then(|v| v.as_str())
-                    .map(|p| match p {
-                        "Low" => dfcoder_core::TaskPriority::Low,
-                        "High" => dfcoder_core::TaskPriority::High,
-                        "Critical" => dfcoder_core::TaskPriority::Critical,
-                        _ => dfcoder_core::TaskPriority::Normal,
-                    })
-                    .unwrap_or(dfcoder_core::TaskPriority::Normal);
-
-                // Create and queue task
-                let task = Task::new(title.to_string(), description.to_string(), role, priority);
-                let mut workshop = self.workshop.lock().await;
-                workshop.queue_task(task);
-                
-                Ok(())
-            },
+            "create" => self.create_task(&content).await,
             _ => Err(McpServerError::InvalidRequest(format!("Unknown action: {}", action))),
         }
     }
 
+    async fn create_task(&self, content: &Value) -> Result<(), McpServerError> {
+        // Parse task creation request
+        let title = content.get("title").and_then(|v| v.as_str())
+            .ok_or_else(|| McpServerError::InvalidRequest("Missing title".to_string()))?;
+        let description = content.get("description").and_then(|v| v.as_str())
+            .ok_or_else(|| McpServerError::InvalidRequest("Missing description".to_string()))?;
+        let role_str = content.get("role").and_then(|v| v.as_str())
+            .ok_or_else(|| McpServerError::InvalidRequest("Missing role".to_string()))?;
+        
+        // Parse role and priority
+        let role = match role_str {
+            "Scaffolder" => dfcoder_core::AgentRole::Scaffolder,
+            "Implementer" => dfcoder_core::AgentRole::Implementer,
+            "Debugger" => dfcoder_core::AgentRole::Debugger,
+            "Tester" => dfcoder_core::AgentRole::Tester,
+            _ => return Err(McpServerError::InvalidRequest("Invalid role".to_string())),
+        };
+
+        let priority = content.get("priority").and_then(|v| v.as_str())
+            .map(|p| match p {
+                "Low" => dfcoder_core::TaskPriority::Low,
+                "High" => dfcoder_core::TaskPriority::High,
+                "Critical" => dfcoder_core::TaskPriority::Critical,
+                _ => dfcoder_core::TaskPriority::Normal,
+            })
+            .unwrap_or(dfcoder_core::TaskPriority::Normal);
+
+        // Create and queue task
+        let task = Task::new(title.to_string(), description.to_string(), role, priority);
+        let mut workshop = self.workshop.lock().await;
+        workshop.queue_task(task)
+            .map_err(|e| McpServerError::InvalidRequest(e.to_string()))?;
+        
+        Ok(())
+    }
+
     // Tool implementations
     async fn execute_assign_task(&self, arguments: Value) -> Result<Value, McpServerError> {
         let _task_id = arguments.get("task_id").and_then(|v| v.as_str())
@@ -374,8 +409,8 @@ impl DFCoderMCPServer {
         let agent_id = arguments.get("agent_id").and_then(|v| v.as_str())
             .ok_or_else(|| McpServerError::InvalidRequest("Missing agent_id".to_string()))?;
 
-        let agents = self.agents.read().unwrap();
-        if let Some(agent) = agents.get(agent_id) {
+        let workshop = self.workshop.lock().await;
+        if let Some(agent) = workshop.get_agent(&agent_id.to_string()) {
             Ok(serde_json::to_value(agent)?)
         } else {
             Err(McpServerError::AgentNotFound(agent_id.to_string()))
@@ -383,7 +418,7 @@ impl DFCoderMCPServer {
     }
 
     async fn execute_create_task(&self, arguments: Value) -> Result<Value, McpServerError> {
-        self.write_tasks_resource(arguments).await?;
+        self.create_task(&arguments).await?;
         Ok(json!({"success": true}))
     }
 
@@ -394,8 +429,8 @@ impl DFCoderMCPServer {
         
         let context = arguments.get("context").and_then(|v| v.as_str()).unwrap_or("");
 
-        let agents = self.agents.read().unwrap();
-        if let Some(agent) = agents.get(agent_id) {
+        let workshop = self.workshop.lock().await;
+        if let Some(agent) = workshop.get_agent(&agent_id.to_string()) {
             let prompt = format!(
                 "The agent '{}' (role: {:?}) needs supervision. Current status: {:?}\n\
                 Current task: {:?}\n\
@@ -471,15 +506,19 @@ impl DFCoderMCPServer {
 
 /// MCP resource definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct McpResource {
     pub uri: String,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
 }
 
 /// MCP tool definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct McpTool {
     pub name: String,
     pub description: String,
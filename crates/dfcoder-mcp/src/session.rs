@@ -0,0 +1,690 @@
+//! JSON-RPC session loop that serves `DFCoderMCPServer` over a transport
+
+use crate::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+/// One client's MCP session against a [`DFCoderMCPServer`]
+///
+/// Drives the `initialize` handshake and dispatches requests through
+/// [`MessageHandler`]; each connection gets its own session. Changes to
+/// resources the client subscribed to are pushed as notifications.
+#[derive(Debug)]
+pub struct ServerSession {
+    server: Arc<DFCoderMCPServer>,
+    resources: Arc<ResourceManager>,
+    /// Subscription ids in `resources`, by the pattern the client asked for
+    subscriptions: std::sync::Mutex<HashMap<String, String>>,
+    protocol: McpProtocol,
+    session: std::sync::Mutex<ProtocolSession>,
+    server_info: Value,
+    capabilities: ServerCapabilities,
+}
+
+impl ServerSession {
+    /// Create a session for a new client
+    pub fn new(server: Arc<DFCoderMCPServer>, resources: Arc<ResourceManager>, config: &McpConfig) -> Self {
+        Self {
+            server,
+            resources,
+            subscriptions: std::sync::Mutex::new(HashMap::new()),
+            protocol: McpProtocol::new(config.protocol_version.clone()),
+            session: std::sync::Mutex::new(ProtocolSession::new()),
+            server_info: json!({
+                "name": config.server_name,
+                "version": config.server_version,
+            }),
+            capabilities: ServerCapabilities::default(),
+        }
+    }
+
+    /// Current handshake state
+    pub fn state(&self) -> ProtocolState {
+        self.session.lock().unwrap().state().clone()
+    }
+
+    /// Answer messages from the transport until the client disconnects
+    pub async fn serve(&self, transport: &dyn Transport) -> Result<(), McpError> {
+        let served = tokio::select! {
+            served = self.answer(transport) => served,
+            notified = self.notify(transport) => notified,
+        };
+        self.end_subscriptions().await;
+        served?;
+
+        tracing::info!("MCP client disconnected");
+        transport.close().await
+    }
+
+    async fn answer(&self, transport: &dyn Transport) -> Result<(), McpError> {
+        loop {
+            let data = match transport.receive().await {
+                Ok(data) => data,
+                Err(McpError::ConnectionClosed) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if data.trim().is_empty() {
+                continue;
+            }
+
+            if let Some(reply) = self.handle_raw(&data).await? {
+                transport.send(&self.protocol.serialize_message(&reply)?).await?;
+            }
+        }
+    }
+
+    /// Push resource changes to the client for as long as the session lasts
+    async fn notify(&self, transport: &dyn Transport) -> Result<(), McpError> {
+        let mut changes = self.resources.get_change_stream();
+        loop {
+            match changes.recv().await {
+                Ok(change) => {
+                    for notification in self.notifications_for(&change).await {
+                        transport.send(&self.protocol.serialize_message(&notification)?).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("MCP session fell behind by {} resource changes", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+            }
+        }
+    }
+
+    /// What this client should hear about a change
+    async fn notifications_for(&self, change: &ResourceChange) -> Vec<McpMessage> {
+        if *self.session.lock().unwrap().state() == ProtocolState::Uninitialized {
+            return Vec::new();
+        }
+
+        let mut notifications = Vec::new();
+        if change.alters_listing() {
+            notifications.push(self.protocol.create_notification("notifications/resources/list_changed", None));
+        }
+
+        let uri = change.uri();
+        let ours: Vec<String> = self.subscriptions.lock().unwrap().values().cloned().collect();
+        if !ours.is_empty() && self.resources.subscriptions_for(&uri).await.iter().any(|subscription| ours.contains(&subscription.id)) {
+            notifications.push(self.protocol.create_notification(
+                "notifications/resources/updated",
+                Some(json!({ "uri": uri })),
+            ));
+        }
+        notifications
+    }
+
+    /// Watch resources matching `uri`, which may be a glob such as `dfcoder://agents/*`
+    async fn subscribe(&self, uri: &str) -> Result<Value, McpError> {
+        if !self.subscriptions.lock().unwrap().contains_key(uri) {
+            let subscription = self.resources.subscribe(uri).await?;
+            self.subscriptions.lock().unwrap().insert(uri.to_string(), subscription.id);
+        }
+        Ok(json!({}))
+    }
+
+    async fn unsubscribe(&self, uri: &str) -> Result<Value, McpError> {
+        let subscription = self.subscriptions.lock().unwrap().remove(uri);
+        if let Some(id) = subscription {
+            self.resources.unsubscribe(&id).await?;
+        }
+        Ok(json!({}))
+    }
+
+    /// Drop the client's subscriptions once it has gone
+    async fn end_subscriptions(&self) {
+        let ids: Vec<String> = self.subscriptions.lock().unwrap().drain().map(|(_, id)| id).collect();
+        for id in ids {
+            if let Err(e) = self.resources.unsubscribe(&id).await {
+                tracing::warn!("Failed to drop resource subscription {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Handle one framed message, answering malformed input with a JSON-RPC error
+    pub async fn handle_raw(&self, data: &str) -> Result<Option<McpMessage>, McpError> {
+        let value: Value = match serde_json::from_str(data) {
+            Ok(value) => value,
+            Err(e) => {
+                return Ok(Some(self.protocol.create_error_response(
+                    Some(Value::Null),
+                    McpRpcError::parse_error(&e.to_string()),
+                )));
+            }
+        };
+
+        let id = value.get("id").cloned().unwrap_or(Value::Null);
+        match serde_json::from_value::<McpMessage>(value) {
+            Ok(message) => self.handle_message(message).await,
+            Err(e) => Ok(Some(self.protocol.create_error_response(
+                Some(id),
+                McpRpcError::invalid_request(&e.to_string()),
+            ))),
+        }
+    }
+
+    /// Route a request to its handler
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        match method {
+            "initialize" => return self.handle_initialize(params).await,
+            "ping" => return Ok(json!({})),
+            _ => {}
+        }
+
+        if *self.session.lock().unwrap().state() == ProtocolState::Uninitialized {
+            return Err(McpError::Rpc(McpRpcError::invalid_request("server not initialized")));
+        }
+
+        match method {
+            "resources/list" => self.handle_list_resources().await,
+            "resources/read" => self.handle_read_resource(required_str(&params, "uri")?).await,
+            "resources/subscribe" => self.subscribe(required_str(&params, "uri")?).await,
+            "resources/unsubscribe" => self.unsubscribe(required_str(&params, "uri")?).await,
+            "tools/list" => self.handle_list_tools().await,
+            "tools/call" => {
+                let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                self.handle_tool_call(required_str(&params, "name")?, arguments).await
+            }
+            "prompts/list" => self.handle_list_prompts().await,
+            "prompts/get" => {
+                self.handle_get_prompt(required_str(&params, "name")?, params.get("arguments").cloned()).await
+            }
+            other => Err(McpError::Rpc(McpRpcError::method_not_found(other))),
+        }
+    }
+
+    /// Apply a notification; notifications never get a reply
+    fn handle_notification(&self, method: &str) {
+        match method {
+            "notifications/initialized" => {
+                let mut session = self.session.lock().unwrap();
+                if *session.state() == ProtocolState::Initializing {
+                    session.set_state(ProtocolState::Initialized);
+                }
+            }
+            other => tracing::debug!("Ignoring MCP notification {}", other),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for ServerSession {
+    async fn handle_message(&self, message: McpMessage) -> Result<Option<McpMessage>, McpError> {
+        if let Err(e) = self.protocol.validate_message(&message) {
+            return Ok(Some(self.protocol.create_error_response(
+                Some(message.id.unwrap_or(Value::Null)),
+                McpRpcError::invalid_request(&e.to_string()),
+            )));
+        }
+
+        let (method, id) = match (message.method, message.id) {
+            (Some(method), Some(id)) => (method, id),
+            (Some(method), None) => {
+                self.handle_notification(&method);
+                return Ok(None);
+            }
+            // A response; the server never sends requests of its own
+            (None, _) => return Ok(None),
+        };
+
+        let reply = match self.dispatch(&method, message.params.unwrap_or(Value::Null)).await {
+            Ok(result) => self.protocol.create_success_response(id, result),
+            Err(McpError::Rpc(error)) => self.protocol.create_error_response(Some(id), error),
+            Err(e) => self.protocol.create_error_response(Some(id), McpRpcError::internal_error(&e.to_string())),
+        };
+        Ok(Some(reply))
+    }
+
+    async fn handle_initialize(&self, params: Value) -> Result<Value, McpError> {
+        let requested = required_str(&params, "protocolVersion")?;
+        self.server.publish_resources(&self.resources).await;
+        let version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+            requested
+        } else {
+            self.protocol.version()
+        };
+
+        let mut session = self.session.lock().unwrap();
+        if *session.state() != ProtocolState::Uninitialized {
+            return Err(McpError::Rpc(McpRpcError::invalid_request("session already initialized")));
+        }
+        if let Some(client_info) = params.get("clientInfo") {
+            session.set_client_info(client_info.clone());
+        }
+        session.set_capabilities(self.capabilities.clone());
+        session.set_state(ProtocolState::Initializing);
+
+        Ok(json!({
+            "protocolVersion": version,
+            "capabilities": self.capabilities,
+            "serverInfo": self.server_info,
+        }))
+    }
+
+    async fn handle_list_resources(&self) -> Result<Value, McpError> {
+        self.server.publish_resources(&self.resources).await;
+        let mut resources = Vec::new();
+        for resource in self.server.list_resources().await {
+            resources.push(serde_json::to_value(resource)?);
+        }
+        let published = [
+            self.resources.list_agents().await?,
+            self.resources.list_panes().await?,
+            self.resources.list_tasks().await?,
+        ];
+        for resource in published.into_iter().flatten() {
+            resources.push(serde_json::to_value(resource)?);
+        }
+        Ok(json!({ "resources": resources }))
+    }
+
+    async fn handle_read_resource(&self, uri: &str) -> Result<Value, McpError> {
+        if uri.starts_with("dfcoder://") {
+            self.server.publish_resources(&self.resources).await;
+            let text = self.resources.get_resource_content(uri).await
+                .map_err(|_| McpError::Rpc(McpRpcError::resource_not_found(uri)))?;
+            let mime_type = if uri.starts_with("dfcoder://panes/") { "text/plain" } else { "application/json" };
+            return Ok(json!({
+                "contents": [{ "uri": uri, "mimeType": mime_type, "text": text }]
+            }));
+        }
+
+        let resources = self.server.list_resources().await;
+        let resource = resources.iter()
+            .find(|resource| resource.uri == uri)
+            .ok_or_else(|| McpError::Rpc(McpRpcError::resource_not_found(uri)))?;
+
+        let content = self.server.read_resource(uri, None).await
+            .map_err(|e| McpError::Rpc(e.into()))?;
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": resource.mime_type.as_deref().unwrap_or("application/json"),
+                "text": serde_json::to_string_pretty(&content)?,
+            }]
+        }))
+    }
+
+    async fn handle_list_tools(&self) -> Result<Value, McpError> {
+        Ok(json!({ "tools": self.server.list_tools().await }))
+    }
+
+    async fn handle_tool_call(&self, name: &str, arguments: Value) -> Result<Value, McpError> {
+        if !self.server.list_tools().await.iter().any(|tool| tool.name == name) {
+            return Err(McpError::Rpc(McpRpcError::invalid_params(&format!("Unknown tool: {}", name))));
+        }
+
+        // Failures of a known tool are results the model should see, not protocol errors
+        let (text, is_error) = match self.server.execute_tool(name, arguments).await {
+            Ok(output) => (serde_json::to_string_pretty(&output)?, false),
+            Err(e) => (e.to_string(), true),
+        };
+        // Tools change the workshop, which subscribers should hear about
+        self.server.publish_resources(&self.resources).await;
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    async fn handle_list_prompts(&self) -> Result<Value, McpError> {
+        Ok(json!({ "prompts": self.server.list_prompts().await }))
+    }
+
+    async fn handle_get_prompt(&self, name: &str, arguments: Option<Value>) -> Result<Value, McpError> {
+        if !self.server.list_prompts().await.iter().any(|prompt| prompt.name == name) {
+            return Err(McpError::Rpc(McpRpcError::invalid_params(&format!("Unknown prompt: {}", name))));
+        }
+
+        let prompt = self.server.get_prompt(name, arguments.unwrap_or_else(|| json!({}))).await
+            .map_err(|e| McpError::Rpc(e.into()))?;
+        let messages: Vec<Value> = prompt.messages.into_iter()
+            .map(|message| json!({
+                "role": message.role,
+                "content": { "type": "text", "text": message.content },
+            }))
+            .collect();
+        Ok(json!({
+            "description": prompt.description,
+            "messages": messages,
+        }))
+    }
+}
+
+impl From<McpServerError> for McpRpcError {
+    fn from(error: McpServerError) -> Self {
+        match error {
+            McpServerError::AgentNotFound(_)
+            | McpServerError::TaskNotFound(_)
+            | McpServerError::InvalidRequest(_) => McpRpcError::invalid_params(&error.to_string()),
+            McpServerError::PermissionDenied(_)
+            | McpServerError::WorkshopError(_)
+            | McpServerError::SerializationError(_) => McpRpcError::internal_error(&error.to_string()),
+        }
+    }
+}
+
+/// A string parameter the request cannot do without
+fn required_str<'a>(params: &'a Value, name: &str) -> Result<&'a str, McpError> {
+    params.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| McpError::Rpc(McpRpcError::invalid_params(&format!("missing '{}'", name))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Transport fed from a script, recording what the server sends back
+    #[derive(Default)]
+    struct ScriptedTransport {
+        incoming: std::sync::Mutex<VecDeque<String>>,
+        outgoing: std::sync::Mutex<Vec<Value>>,
+    }
+
+    impl ScriptedTransport {
+        fn new(messages: &[Value]) -> Self {
+            Self {
+                incoming: std::sync::Mutex::new(messages.iter().map(Value::to_string).collect()),
+                outgoing: std::sync::Mutex::default(),
+            }
+        }
+
+        fn replies(&self) -> Vec<Value> {
+            self.outgoing.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for ScriptedTransport {
+        async fn send(&self, message: &str) -> Result<(), McpError> {
+            self.outgoing.lock().unwrap().push(serde_json::from_str(message)?);
+            Ok(())
+        }
+
+        async fn receive(&self) -> Result<String, McpError> {
+            self.incoming.lock().unwrap().pop_front().ok_or(McpError::ConnectionClosed)
+        }
+
+        async fn close(&self) -> Result<(), McpError> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            !self.incoming.lock().unwrap().is_empty()
+        }
+    }
+
+    fn session() -> ServerSession {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let resources = Arc::new(ResourceManager::new(McpConfig::default().resources));
+        ServerSession::new(Arc::new(DFCoderMCPServer::new(workshop)), resources, &McpConfig::default())
+    }
+
+    /// A client talking to a session served in the background
+    struct Peer {
+        to_session: tokio::sync::mpsc::UnboundedSender<String>,
+        from_session: tokio::sync::mpsc::UnboundedReceiver<String>,
+    }
+
+    /// Transport between a [`Peer`] and its session
+    struct ChannelTransport {
+        incoming: Mutex<tokio::sync::mpsc::UnboundedReceiver<String>>,
+        outgoing: tokio::sync::mpsc::UnboundedSender<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for ChannelTransport {
+        async fn send(&self, message: &str) -> Result<(), McpError> {
+            self.outgoing.send(message.to_string()).map_err(|_| McpError::ConnectionClosed)
+        }
+
+        async fn receive(&self) -> Result<String, McpError> {
+            self.incoming.lock().await.recv().await.ok_or(McpError::ConnectionClosed)
+        }
+
+        async fn close(&self) -> Result<(), McpError> {
+            Ok(())
+        }
+
+        fn is_connected(&self) -> bool {
+            !self.outgoing.is_closed()
+        }
+    }
+
+    impl Peer {
+        async fn start(session: ServerSession) -> Self {
+            let (to_session, incoming) = tokio::sync::mpsc::unbounded_channel();
+            let (outgoing, from_session) = tokio::sync::mpsc::unbounded_channel();
+            let transport = ChannelTransport { incoming: Mutex::new(incoming), outgoing };
+            tokio::spawn(async move { session.serve(&transport).await });
+
+            let mut peer = Self { to_session, from_session };
+            for message in handshake() {
+                peer.to_session.send(message.to_string()).unwrap();
+            }
+            assert_eq!(peer.next().await["id"], 0);
+            peer
+        }
+
+        async fn next(&mut self) -> Value {
+            let message = tokio::time::timeout(std::time::Duration::from_secs(5), self.from_session.recv())
+                .await.unwrap().unwrap();
+            serde_json::from_str(&message).unwrap()
+        }
+
+        async fn call(&mut self, id: u64, method: &str, params: Value) -> Value {
+            self.to_session.send(request(id, method, params).to_string()).unwrap();
+            self.next().await
+        }
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn handshake() -> Vec<Value> {
+        vec![
+            request(0, "initialize", json!({
+                "protocolVersion": "2025-06-18",
+                "capabilities": {},
+                "clientInfo": { "name": "test-client", "version": "0.1" }
+            })),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_serve_handshake_and_listings() {
+        let session = session();
+        let mut script = handshake();
+        script.push(request(1, "tools/list", json!({})));
+        script.push(request(2, "prompts/list", json!({})));
+        script.push(request(3, "resources/list", json!({})));
+        let transport = ScriptedTransport::new(&script);
+
+        session.serve(&transport).await.unwrap();
+        assert_eq!(session.state(), ProtocolState::Initialized);
+
+        // The notification is not answered
+        let replies = transport.replies();
+        assert_eq!(replies.len(), 4);
+
+        let init = &replies[0]["result"];
+        assert_eq!(init["protocolVersion"], "2025-06-18");
+        assert_eq!(init["serverInfo"]["name"], "dfcoder");
+        assert_eq!(init["capabilities"]["tools"]["listChanged"], false);
+        assert!(init["capabilities"].get("logging").is_none());
+
+        let tools = replies[1]["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "create_task" && tool["inputSchema"]["type"] == "object"));
+        assert_eq!(replies[2]["result"]["prompts"][0]["name"], "agent_supervision");
+        assert_eq!(replies[3]["id"], 3);
+        assert_eq!(replies[3]["result"]["resources"][0]["mimeType"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_protocol_version_gets_ours() {
+        let session = session();
+        let reply = session.handle_raw(&request(0, "initialize", json!({ "protocolVersion": "1999-01-01" })).to_string())
+            .await.unwrap().unwrap();
+        assert_eq!(reply.result.unwrap()["protocolVersion"], "2024-11-05");
+
+        let again = session.handle_raw(&request(1, "initialize", json!({ "protocolVersion": "2024-11-05" })).to_string())
+            .await.unwrap().unwrap();
+        assert_eq!(again.error.unwrap().code, -32600);
+    }
+
+    #[tokio::test]
+    async fn test_requests_before_initialize_are_rejected() {
+        let session = session();
+        let reply = session.handle_raw(&request(7, "tools/list", json!({})).to_string()).await.unwrap().unwrap();
+
+        assert_eq!(reply.id, Some(json!(7)));
+        assert_eq!(reply.error.unwrap().code, -32600);
+
+        let ping = session.handle_raw(&request(8, "ping", json!({})).to_string()).await.unwrap().unwrap();
+        assert_eq!(ping.result, Some(json!({})));
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_and_resource_read() {
+        let session = session();
+        let mut script = handshake();
+        script.push(request(1, "tools/call", json!({
+            "name": "create_task",
+            "arguments": { "title": "Parser", "description": "Write the parser", "role": "Implementer" }
+        })));
+        script.push(request(2, "tools/call", json!({
+            "name": "get_agent_status",
+            "arguments": { "agent_id": "missing" }
+        })));
+        script.push(request(3, "resources/read", json!({ "uri": "tasks" })));
+        script.push(request(4, "prompts/get", json!({
+            "name": "task_breakdown",
+            "arguments": { "task_description": "Ship the MCP server" }
+        })));
+        let transport = ScriptedTransport::new(&script);
+
+        session.serve(&transport).await.unwrap();
+        let replies = transport.replies();
+
+        assert_eq!(replies[1]["result"]["isError"], false);
+        assert_eq!(replies[1]["result"]["content"][0]["type"], "text");
+
+        // A failing tool reports the failure in its result
+        assert_eq!(replies[2]["result"]["isError"], true);
+        assert!(replies[2]["result"]["content"][0]["text"].as_str().unwrap().contains("missing"));
+
+        let contents = &replies[3]["result"]["contents"][0];
+        assert_eq!(contents["uri"], "tasks");
+        assert!(contents["text"].as_str().unwrap().contains("Parser"));
+
+        let prompt = &replies[4]["result"]["messages"][0];
+        assert_eq!(prompt["role"], "user");
+        assert!(prompt["content"]["text"].as_str().unwrap().contains("Ship the MCP server"));
+    }
+
+    #[tokio::test]
+    async fn test_errors_use_json_rpc_codes() {
+        let session = session();
+        let mut script = handshake();
+        script.push(request(1, "tools/call", json!({ "name": "launch_rockets" })));
+        script.push(request(2, "resources/read", json!({ "uri": "dfcoder://nowhere" })));
+        script.push(request(3, "prompts/get", json!({ "name": "agent_supervision", "arguments": {} })));
+        script.push(request(4, "resources/read", json!({})));
+        script.push(request(5, "sampling/createMessage", json!({})));
+        script.push(json!({ "jsonrpc": "1.0", "id": 6, "method": "ping" }));
+        let transport = ScriptedTransport::new(&script);
+        transport.incoming.lock().unwrap().push_back("{not json".to_string());
+
+        session.serve(&transport).await.unwrap();
+        let codes: Vec<(Value, i64)> = transport.replies()[1..].iter()
+            .map(|reply| (reply["id"].clone(), reply["error"]["code"].as_i64().unwrap()))
+            .collect();
+
+        assert_eq!(codes, vec![
+            (json!(1), -32602),
+            (json!(2), -32002),
+            (json!(3), -32602),
+            (json!(4), -32602),
+            (json!(5), -32601),
+            (json!(6), -32600),
+            (Value::Null, -32700),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_notify_only_the_owning_session() {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        let agent_id = agent.id.clone();
+        let uri = format!("dfcoder://agents/{}", agent_id);
+        workshop.lock().await.register_agent(agent).unwrap();
+        let server = Arc::new(DFCoderMCPServer::new(workshop.clone()));
+        let resources = Arc::new(ResourceManager::new(McpConfig::default().resources));
+        // Mirrored up front, so the sessions' handshakes have nothing to announce
+        server.publish_resources(&resources).await;
+
+        let mut watcher = Peer::start(ServerSession::new(server.clone(), resources.clone(), &McpConfig::default())).await;
+        let mut bystander = Peer::start(ServerSession::new(server.clone(), resources.clone(), &McpConfig::default())).await;
+
+        let listed = watcher.call(1, "resources/list", json!({})).await;
+        assert!(listed["result"]["resources"].as_array().unwrap().iter().any(|resource| resource["uri"] == uri));
+        let subscribed = watcher.call(2, "resources/subscribe", json!({ "uri": "dfcoder://agents/*" })).await;
+        assert_eq!(subscribed["result"], json!({}));
+
+        workshop.lock().await.get_agent_mut(&agent_id).unwrap().status = AgentStatus::Working;
+        server.publish_resources(&resources).await;
+        let updated = watcher.next().await;
+        assert_eq!(updated["method"], "notifications/resources/updated");
+        assert_eq!(updated["params"]["uri"], uri);
+        let read = watcher.call(3, "resources/read", json!({ "uri": uri })).await;
+        assert!(read["result"]["contents"][0]["text"].as_str().unwrap().contains("Working"));
+
+        // Nothing reaches a session that did not subscribe
+        assert_eq!(bystander.call(1, "ping", json!({})).await["id"], 1);
+
+        // New resources change everyone's listing
+        let task = Task::new("Parser".to_string(), "Write the parser".to_string(), AgentRole::Tester, TaskPriority::Normal);
+        workshop.lock().await.queue_task(task).unwrap();
+        server.publish_resources(&resources).await;
+        assert_eq!(watcher.next().await["method"], "notifications/resources/list_changed");
+        assert_eq!(bystander.next().await["method"], "notifications/resources/list_changed");
+
+        watcher.call(4, "resources/unsubscribe", json!({ "uri": "dfcoder://agents/*" })).await;
+        workshop.lock().await.get_agent_mut(&agent_id).unwrap().status = AgentStatus::Idle;
+        server.publish_resources(&resources).await;
+        assert_eq!(watcher.call(5, "ping", json!({})).await["id"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_publish_workshop_changes() {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let server = Arc::new(DFCoderMCPServer::new(workshop));
+        let resources = Arc::new(ResourceManager::new(McpConfig::default().resources));
+        let mut peer = Peer::start(ServerSession::new(server, resources, &McpConfig::default())).await;
+        peer.call(1, "resources/subscribe", json!({ "uri": "dfcoder://tasks/*" })).await;
+
+        peer.to_session.send(request(2, "tools/call", json!({
+            "name": "create_task",
+            "arguments": { "title": "Parser", "description": "Write the parser", "role": "Implementer" }
+        })).to_string()).unwrap();
+
+        // The reply and the notifications race each other
+        let mut messages = Vec::new();
+        for _ in 0..3 {
+            messages.push(peer.next().await);
+        }
+        let reply = messages.iter().find(|message| message["id"] == 2).unwrap();
+        assert_eq!(reply["result"]["isError"], false);
+        assert!(messages.iter().any(|message| message["method"] == "notifications/resources/list_changed"));
+        let updated = messages.iter().find(|message| message["method"] == "notifications/resources/updated").unwrap();
+        let uri = updated["params"]["uri"].as_str().unwrap();
+        assert!(uri.starts_with("dfcoder://tasks/"));
+
+        let read = peer.call(3, "resources/read", json!({ "uri": uri })).await;
+        assert!(read["result"]["contents"][0]["text"].as_str().unwrap().contains("Write the parser"));
+    }
+}
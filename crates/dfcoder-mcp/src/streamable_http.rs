@@ -0,0 +1,804 @@
+//! MCP's Streamable HTTP transport
+//!
+//! Clients POST each JSON-RPC message to one endpoint and get the reply to a
+//! request as the response body. A GET on the same endpoint opens a
+//! server-sent event stream carrying whatever the server sends unprompted.
+//! Sessions are named by the `Mcp-Session-Id` header handed out alongside
+//! the `initialize` response.
+
+use crate::*;
+use dfcoder_types::framing::{FrameDecoder, StreamFraming};
+use futures::StreamExt;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderValue, ALLOW, CACHE_CONTROL, CONTENT_TYPE, ORIGIN};
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+/// Header naming the session a message belongs to
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Path of the MCP endpoint on an HTTP server
+pub const HTTP_ENDPOINT: &str = "/mcp";
+
+/// How long a session may go unused before the server ends it
+pub const HTTP_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Interval between comments on an otherwise quiet event stream, so a client
+/// that went away is noticed
+const EVENT_STREAM_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// Server side of one HTTP session
+///
+/// POSTed messages are queued for the session's `ServerSession`; replies to
+/// requests go back to the POST that asked, anything else to the session's
+/// event stream.
+#[derive(Debug)]
+pub struct HttpSessionTransport {
+    incoming_sender: std::sync::Mutex<Option<mpsc::UnboundedSender<String>>>,
+    incoming: Mutex<mpsc::UnboundedReceiver<String>>,
+    replies: std::sync::Mutex<HashMap<String, oneshot::Sender<String>>>,
+    events: std::sync::Mutex<Option<mpsc::UnboundedSender<String>>>,
+    last_active: std::sync::Mutex<Instant>,
+}
+
+impl HttpSessionTransport {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            incoming_sender: std::sync::Mutex::new(Some(sender)),
+            incoming: Mutex::new(receiver),
+            replies: std::sync::Mutex::new(HashMap::new()),
+            events: std::sync::Mutex::new(None),
+            last_active: std::sync::Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// How long the session has gone without a message, an open event stream
+    /// or a request awaiting its reply
+    fn idle_for(&self) -> Option<Duration> {
+        let streaming = self.events.lock().unwrap().as_ref().is_some_and(|events| !events.is_closed());
+        if streaming || !self.replies.lock().unwrap().is_empty() {
+            return None;
+        }
+        Some(self.last_active.lock().unwrap().elapsed())
+    }
+
+    /// Queue a POSTed message, failing once the session has ended
+    fn deliver(&self, message: String) -> Result<(), McpError> {
+        self.touch();
+        match &*self.incoming_sender.lock().unwrap() {
+            Some(sender) => sender.send(message).map_err(|_| McpError::ConnectionClosed),
+            None => Err(McpError::ConnectionClosed),
+        }
+    }
+
+    /// Wait for the reply to the request with this id
+    fn expect_reply(&self, id: &Value) -> oneshot::Receiver<String> {
+        let (sender, receiver) = oneshot::channel();
+        self.replies.lock().unwrap().insert(id.to_string(), sender);
+        receiver
+    }
+
+    /// Open the event stream, replacing any stream already open
+    fn open_event_stream(&self) -> mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.events.lock().unwrap() = Some(sender);
+        receiver
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for HttpSessionTransport {
+    async fn send(&self, message: &str) -> Result<(), McpError> {
+        let value: Value = serde_json::from_str(message)?;
+        if value.get("method").is_none() {
+            if let Some(id) = value.get("id") {
+                if let Some(reply) = self.replies.lock().unwrap().remove(&id.to_string()) {
+                    let _ = reply.send(message.to_string());
+                    return Ok(());
+                }
+            }
+        }
+
+        match &*self.events.lock().unwrap() {
+            Some(events) if events.send(message.to_string()).is_ok() => {}
+            _ => tracing::debug!("No MCP event stream open, dropping {}", message),
+        }
+        Ok(())
+    }
+
+    async fn receive(&self) -> Result<String, McpError> {
+        self.incoming.lock().await.recv().await.ok_or(McpError::ConnectionClosed)
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        self.incoming_sender.lock().unwrap().take();
+        self.events.lock().unwrap().take();
+        self.replies.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.incoming_sender.lock().unwrap().is_some()
+    }
+}
+
+/// Serves MCP over Streamable HTTP, with a `ServerSession` per `Mcp-Session-Id`
+pub struct HttpServer {
+    listener: TcpListener,
+    sessions: Arc<HttpSessions>,
+    idle_timeout: Duration,
+}
+
+/// Sessions of an HTTP server, by id
+#[derive(Default)]
+struct HttpSessions {
+    sessions: std::sync::Mutex<HashMap<String, Arc<HttpSessionTransport>>>,
+}
+
+impl HttpServer {
+    /// Listen for HTTP clients on `host:port`
+    pub async fn bind(address: &str) -> Result<Self, McpError> {
+        let listener = TcpListener::bind(address).await
+            .map_err(|e| McpError::TransportError(format!("Failed to bind HTTP listener on {}: {}", address, e)))?;
+        Ok(Self {
+            listener,
+            sessions: Arc::new(HttpSessions::default()),
+            idle_timeout: HTTP_SESSION_IDLE_TIMEOUT,
+        })
+    }
+
+    /// End sessions left unused for `idle_timeout` instead of the default
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// URL of the MCP endpoint
+    pub fn local_addr(&self) -> String {
+        match self.listener.local_addr() {
+            Ok(address) => format!("http://{}{}", address, HTTP_ENDPOINT),
+            Err(_) => "http://<unknown>".to_string(),
+        }
+    }
+
+    /// Answer HTTP clients until `shutdown` resolves, starting each session with `new_session`
+    pub async fn serve<F>(self, new_session: F, shutdown: impl Future<Output = ()>) -> Result<(), McpError>
+    where
+        F: Fn() -> ServerSession + Send + Sync + 'static,
+    {
+        tracing::info!("MCP server listening on {}", self.local_addr());
+        let new_session = Arc::new(new_session);
+        let mut sweep = tokio::time::interval(self.idle_timeout);
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = sweep.tick() => self.sessions.end_idle(self.idle_timeout).await,
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, peer)) => {
+                        let sessions = self.sessions.clone();
+                        let new_session = new_session.clone();
+                        let service = service_fn(move |request| {
+                            let sessions = sessions.clone();
+                            let new_session = new_session.clone();
+                            async move { Ok::<_, Infallible>(sessions.handle(request, new_session.as_ref()).await) }
+                        });
+                        tokio::spawn(async move {
+                            if let Err(e) = hyper::server::conn::Http::new().http1_only(true).serve_connection(stream, service).await {
+                                tracing::debug!("HTTP connection from {} ended with error: {}", peer, e);
+                            }
+                        });
+                    }
+                    Err(e) => tracing::error!("Failed to accept HTTP client: {}", e),
+                },
+            }
+        }
+
+        tracing::info!("MCP server shutting down");
+        let sessions: Vec<_> = self.sessions.sessions.lock().unwrap().drain().map(|(_, session)| session).collect();
+        for session in sessions {
+            session.close().await?;
+        }
+        Ok(())
+    }
+}
+
+impl HttpSessions {
+    async fn handle(&self, request: Request<Body>, new_session: &(dyn Fn() -> ServerSession + Send + Sync)) -> Response<Body> {
+        if !origin_allowed(&request) {
+            return error_response(StatusCode::FORBIDDEN, McpRpcError::invalid_request("origin not allowed"));
+        }
+        if request.uri().path() != HTTP_ENDPOINT {
+            return empty_response(StatusCode::NOT_FOUND);
+        }
+
+        match *request.method() {
+            Method::POST => self.post(request, new_session).await,
+            Method::GET => self.open_event_stream(&request),
+            Method::DELETE => self.end_session(&request).await,
+            _ => {
+                let mut response = empty_response(StatusCode::METHOD_NOT_ALLOWED);
+                response.headers_mut().insert(ALLOW, HeaderValue::from_static("GET, POST, DELETE"));
+                response
+            }
+        }
+    }
+
+    async fn post(&self, request: Request<Body>, new_session: &(dyn Fn() -> ServerSession + Send + Sync)) -> Response<Body> {
+        let session_id = session_id(&request);
+        let body = match read_body(request.into_body(), MAX_MESSAGE_SIZE).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, McpRpcError::parse_error(&e.to_string())),
+        };
+        if message.is_array() {
+            return error_response(StatusCode::BAD_REQUEST, McpRpcError::invalid_request("batches are not supported"));
+        }
+
+        let initializing = message.get("method").and_then(Value::as_str) == Some("initialize");
+        let (session_id, transport) = match session_id {
+            Some(id) => match self.find(&id) {
+                Some(transport) => (id, transport),
+                None => return session_not_found(),
+            },
+            None if initializing => self.start(new_session),
+            None => return error_response(StatusCode::BAD_REQUEST, McpRpcError::invalid_request("missing Mcp-Session-Id header")),
+        };
+
+        let reply = match (message.get("id"), message.get("method")) {
+            (Some(id), Some(_)) => Some(transport.expect_reply(id)),
+            _ => None,
+        };
+        if transport.deliver(message.to_string()).is_err() {
+            return session_not_found();
+        }
+
+        let Some(reply) = reply else {
+            return with_session(empty_response(StatusCode::ACCEPTED), &session_id);
+        };
+        let Ok(reply) = reply.await else {
+            return session_not_found();
+        };
+
+        // A session whose handshake failed would never be usable
+        if initializing && serde_json::from_str::<McpMessage>(&reply).is_ok_and(|reply| reply.error.is_some()) {
+            self.remove(&session_id).await;
+        }
+
+        let mut response = Response::new(Body::from(reply));
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        with_session(response, &session_id)
+    }
+
+    fn open_event_stream(&self, request: &Request<Body>) -> Response<Body> {
+        let Some(id) = session_id(request) else {
+            return error_response(StatusCode::BAD_REQUEST, McpRpcError::invalid_request("missing Mcp-Session-Id header"));
+        };
+        let Some(transport) = self.find(&id) else {
+            return session_not_found();
+        };
+
+        let mut events = transport.open_event_stream();
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + EVENT_STREAM_KEEPALIVE, EVENT_STREAM_KEEPALIVE);
+            loop {
+                let frame = tokio::select! {
+                    message = events.recv() => match message {
+                        Some(message) => format!("data: {}\n\n", message),
+                        None => break,
+                    },
+                    _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+                };
+                if sender.send_data(Bytes::from(frame)).await.is_err() {
+                    break;
+                }
+            }
+            // The session's idle time counts from here
+            transport.touch();
+        });
+
+        let mut response = Response::new(body);
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        with_session(response, &id)
+    }
+
+    async fn end_session(&self, request: &Request<Body>) -> Response<Body> {
+        let Some(id) = session_id(request) else {
+            return error_response(StatusCode::BAD_REQUEST, McpRpcError::invalid_request("missing Mcp-Session-Id header"));
+        };
+        if self.remove(&id).await {
+            empty_response(StatusCode::OK)
+        } else {
+            session_not_found()
+        }
+    }
+
+    /// Start a session, serving it until it is removed
+    fn start(&self, new_session: &(dyn Fn() -> ServerSession + Send + Sync)) -> (String, Arc<HttpSessionTransport>) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let transport = Arc::new(HttpSessionTransport::new());
+        self.sessions.lock().unwrap().insert(id.clone(), transport.clone());
+
+        let session = new_session();
+        let serving = transport.clone();
+        let session_id = id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session.serve(serving.as_ref()).await {
+                tracing::warn!("MCP session {} ended with error: {}", session_id, e);
+            }
+        });
+
+        tracing::info!("MCP HTTP session {} started", id);
+        (id, transport)
+    }
+
+    /// End every session that has been idle for `idle_timeout`
+    async fn end_idle(&self, idle_timeout: Duration) {
+        let idle: Vec<String> = self.sessions.lock().unwrap().iter()
+            .filter(|(_, transport)| transport.idle_for().is_some_and(|idle| idle >= idle_timeout))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in idle {
+            tracing::info!("MCP HTTP session {} idle for {:?}", id, idle_timeout);
+            self.remove(&id).await;
+        }
+    }
+
+    fn find(&self, id: &str) -> Option<Arc<HttpSessionTransport>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    async fn remove(&self, id: &str) -> bool {
+        let removed = self.sessions.lock().unwrap().remove(id);
+        match removed {
+            Some(transport) => {
+                let _ = transport.close().await;
+                tracing::info!("MCP HTTP session {} ended", id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Read a request body, refusing it once it grows past `limit` bytes
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, Response<Body>> {
+    let too_large = || error_response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        McpRpcError::invalid_request(&format!("message exceeds {} bytes", limit)),
+    );
+    // A declared length settles it before anything is read
+    if body.size_hint().lower() > limit as u64 {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| error_response(StatusCode::BAD_REQUEST, McpRpcError::invalid_request(&e.to_string())))?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Browsers may only reach the server from a local page, guarding against DNS rebinding
+fn origin_allowed(request: &Request<Body>) -> bool {
+    let Some(origin) = request.headers().get(ORIGIN) else {
+        return true;
+    };
+    origin.to_str().ok()
+        .and_then(|origin| url::Url::parse(origin).ok())
+        .and_then(|origin| origin.host_str().map(str::to_string))
+        .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+}
+
+fn session_id(request: &Request<Body>) -> Option<String> {
+    request.headers().get(SESSION_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string)
+}
+
+fn with_session(mut response: Response<Body>, id: &str) -> Response<Body> {
+    if let Ok(id) = HeaderValue::from_str(id) {
+        response.headers_mut().insert(SESSION_HEADER, id);
+    }
+    response
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn error_response(status: StatusCode, error: McpRpcError) -> Response<Body> {
+    let message = McpMessage {
+        jsonrpc: "2.0".to_string(),
+        id: Some(Value::Null),
+        method: None,
+        params: None,
+        result: None,
+        error: Some(error),
+    };
+    let mut response = Response::new(Body::from(serde_json::to_string(&message).unwrap_or_default()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn session_not_found() -> Response<Body> {
+    error_response(StatusCode::NOT_FOUND, McpRpcError::custom_error(-32001, "Session not found"))
+}
+
+/// Client side of the Streamable HTTP transport
+///
+/// Replies and server messages, whether returned from a POST or pushed over
+/// the session's event stream, are all handed out by `receive`.
+pub struct HttpClientTransport {
+    url: String,
+    http: reqwest::Client,
+    session_id: std::sync::Mutex<Option<String>>,
+    incoming_sender: std::sync::Mutex<Option<mpsc::UnboundedSender<String>>>,
+    incoming: Mutex<mpsc::UnboundedReceiver<String>>,
+    event_stream: std::sync::Mutex<Option<JoinHandle<()>>>,
+    connected: AtomicBool,
+}
+
+impl std::fmt::Debug for HttpClientTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpClientTransport")
+            .field("url", &self.url)
+            .field("session_id", &self.session_id())
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl HttpClientTransport {
+    /// Talk to the MCP endpoint at `url`; nothing is sent until the first message
+    pub fn new(url: &str) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            url: url.to_string(),
+            http: reqwest::Client::new(),
+            session_id: std::sync::Mutex::new(None),
+            incoming_sender: std::sync::Mutex::new(Some(sender)),
+            incoming: Mutex::new(receiver),
+            event_stream: std::sync::Mutex::new(None),
+            connected: AtomicBool::new(true),
+        }
+    }
+
+    /// The session the server assigned, once initialized
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    fn incoming_sender(&self) -> Result<mpsc::UnboundedSender<String>, McpError> {
+        self.incoming_sender.lock().unwrap().clone().ok_or(McpError::ConnectionClosed)
+    }
+
+    /// Listen for server messages on the session's event stream
+    fn open_event_stream(&self, session_id: &str) -> Result<(), McpError> {
+        let request = self.http.get(&self.url)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, session_id);
+        let incoming = self.incoming_sender()?;
+
+        let listening = tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if response.status().is_success() => forward_events(response, incoming).await,
+                Ok(response) => tracing::debug!("MCP server offers no event stream: {}", response.status()),
+                Err(e) => tracing::warn!("Failed to open MCP event stream: {}", e),
+            }
+        });
+        if let Some(previous) = self.event_stream.lock().unwrap().replace(listening) {
+            previous.abort();
+        }
+        Ok(())
+    }
+}
+
+/// Pass each event of a server-sent event stream on to `incoming`
+async fn forward_events(response: reqwest::Response, incoming: mpsc::UnboundedSender<String>) {
+    let mut decoder = FrameDecoder::new(StreamFraming::ServerSentEvents);
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) => {
+                for payload in decoder.feed(&chunk) {
+                    if incoming.send(payload).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::debug!("MCP event stream ended: {}", e);
+                return;
+            }
+        }
+    }
+    if let Some(payload) = decoder.finish() {
+        let _ = incoming.send(payload);
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for HttpClientTransport {
+    async fn send(&self, message: &str) -> Result<(), McpError> {
+        if !self.is_connected() {
+            return Err(McpError::ConnectionClosed);
+        }
+
+        let session_id = self.session_id();
+        let mut request = self.http.post(&self.url)
+            .header(reqwest::header::ACCEPT, "application/json, text/event-stream")
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(message.to_string());
+        if let Some(id) = &session_id {
+            request = request.header(SESSION_HEADER, id.as_str());
+        }
+
+        let response = request.send().await
+            .map_err(|e| McpError::TransportError(format!("HTTP request to {} failed: {}", self.url, e)))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && session_id.is_some() {
+            // The server has forgotten our session
+            self.connected.store(false, Ordering::Relaxed);
+            return Err(McpError::ConnectionClosed);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(McpError::TransportError(format!("HTTP {} from {}: {}", status, self.url, body)));
+        }
+
+        if session_id.is_none() {
+            let assigned = response.headers().get(SESSION_HEADER)
+                .and_then(|id| id.to_str().ok())
+                .map(str::to_string);
+            if let Some(id) = assigned {
+                *self.session_id.lock().unwrap() = Some(id.clone());
+                self.open_event_stream(&id)?;
+            }
+        }
+
+        let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let incoming = self.incoming_sender()?;
+        if content_type.starts_with("text/event-stream") {
+            tokio::spawn(forward_events(response, incoming));
+        } else if content_type.starts_with("application/json") {
+            let body = response.text().await
+                .map_err(|e| McpError::TransportError(format!("Failed to read HTTP response: {}", e)))?;
+            let _ = incoming.send(body);
+        }
+        Ok(())
+    }
+
+    async fn receive(&self) -> Result<String, McpError> {
+        if !self.is_connected() {
+            return Err(McpError::ConnectionClosed);
+        }
+        self.incoming.lock().await.recv().await.ok_or(McpError::ConnectionClosed)
+    }
+
+    async fn close(&self) -> Result<(), McpError> {
+        if !self.connected.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        if let Some(listening) = self.event_stream.lock().unwrap().take() {
+            listening.abort();
+        }
+        self.incoming_sender.lock().unwrap().take();
+
+        let session_id = self.session_id.lock().unwrap().take();
+        if let Some(id) = session_id {
+            // The session is gone from our side either way
+            let _ = self.http.delete(&self.url).header(SESSION_HEADER, id).send().await;
+        }
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn start_server() -> (String, Arc<HttpSessions>, oneshot::Sender<()>) {
+        start_server_with_idle_timeout(HTTP_SESSION_IDLE_TIMEOUT).await
+    }
+
+    async fn start_server_with_idle_timeout(idle_timeout: Duration) -> (String, Arc<HttpSessions>, oneshot::Sender<()>) {
+        let server = HttpServer::bind("127.0.0.1:0").await.unwrap().with_idle_timeout(idle_timeout);
+        let url = server.local_addr();
+        let sessions = server.sessions.clone();
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let mcp = Arc::new(DFCoderMCPServer::new(Arc::new(Mutex::new(WorkshopManager::new()))));
+        let config = McpConfig::default();
+        let resources = Arc::new(ResourceManager::new(config.resources.clone()));
+        tokio::spawn(server.serve(move || ServerSession::new(mcp.clone(), resources.clone(), &config), async {
+            let _ = stopped.await;
+        }));
+        (url, sessions, stop)
+    }
+
+    fn rpc(id: u64, method: &str, params: Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string()
+    }
+
+    fn initialize_request() -> String {
+        rpc(1, "initialize", json!({
+            "protocolVersion": "2025-03-26",
+            "capabilities": {},
+            "clientInfo": { "name": "test", "version": "0" },
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_named_by_header() {
+        let (url, _sessions, _stop) = start_server().await;
+        let http = reqwest::Client::new();
+
+        let response = http.post(&url).body(initialize_request()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let session = response.headers()[SESSION_HEADER].to_str().unwrap().to_string();
+        let reply: Value = response.json().await.unwrap();
+        assert_eq!(reply["result"]["protocolVersion"], "2025-03-26");
+
+        let tools = rpc(2, "tools/list", json!({}));
+        let response = http.post(&url).body(tools.clone()).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        let response = http.post(&url).header(SESSION_HEADER, "nonsense").body(tools.clone()).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = http.post(&url).header(SESSION_HEADER, &session).body(tools.clone()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let reply: Value = response.json().await.unwrap();
+        assert_eq!(reply["id"], 2);
+        assert!(reply["result"]["tools"].as_array().unwrap().iter().any(|tool| tool["name"] == "create_task"));
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string();
+        let response = http.post(&url).header(SESSION_HEADER, &session).body(initialized).send().await.unwrap();
+        assert_eq!(response.status(), 202);
+
+        let response = http.delete(&url).header(SESSION_HEADER, &session).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let response = http.post(&url).header(SESSION_HEADER, &session).body(tools).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_posts_and_foreign_origins_are_refused() {
+        let (url, _sessions, _stop) = start_server().await;
+        let http = reqwest::Client::new();
+
+        let response = http.post(&url).body("{not json").send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        let reply: Value = response.json().await.unwrap();
+        assert_eq!(reply["error"]["code"], -32700);
+
+        let response = http.post(&url).header(ORIGIN.as_str(), "http://evil.example").body(initialize_request()).send().await.unwrap();
+        assert_eq!(response.status(), 403);
+        let response = http.post(&url).header(ORIGIN.as_str(), "http://localhost:5173").body(initialize_request()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let response = http.put(&url).send().await.unwrap();
+        assert_eq!(response.status(), 405);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_posts_are_refused() {
+        let (url, _sessions, _stop) = start_server().await;
+        let http = reqwest::Client::new();
+
+        let padding = " ".repeat(MAX_MESSAGE_SIZE);
+        let response = http.post(&url).body(format!("{}{}", initialize_request(), padding)).send().await.unwrap();
+        assert_eq!(response.status(), 413);
+
+        // Without a declared length the limit applies as the body arrives
+        let chunks = vec![Ok::<_, std::io::Error>(initialize_request()), Ok(padding)];
+        let response = http.post(&url).body(reqwest::Body::wrap_stream(futures::stream::iter(chunks))).send().await.unwrap();
+        assert_eq!(response.status(), 413);
+
+        let response = http.post(&url).body(initialize_request()).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_server_messages_arrive_over_event_stream() {
+        let (url, sessions, _stop) = start_server().await;
+        let client = HttpClientTransport::new(&url);
+
+        client.send(&initialize_request()).await.unwrap();
+        let reply: Value = serde_json::from_str(&client.receive().await.unwrap()).unwrap();
+        assert_eq!(reply["id"], 1);
+        let session_id = client.session_id().unwrap();
+
+        let session = sessions.find(&session_id).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while session.events.lock().unwrap().is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/resources/list_changed" }).to_string();
+        session.send(&notification).await.unwrap();
+        let pushed: Value = serde_json::from_str(&client.receive().await.unwrap()).unwrap();
+        assert_eq!(pushed["method"], "notifications/resources/list_changed");
+
+        client.close().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sessions.find(&session_id).is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        assert!(!session.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_are_ended() {
+        let (url, sessions, _stop) = start_server_with_idle_timeout(Duration::from_millis(100)).await;
+        let http = reqwest::Client::new();
+        let response = http.post(&url).body(initialize_request()).send().await.unwrap();
+        let abandoned = response.headers()[SESSION_HEADER].to_str().unwrap().to_string();
+
+        // A session listening on its event stream is in use, however quiet
+        let listening = HttpClientTransport::new(&url);
+        listening.send(&initialize_request()).await.unwrap();
+        listening.receive().await.unwrap();
+        let listening_id = listening.session_id().unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sessions.find(&abandoned).is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        let response = http.post(&url).header(SESSION_HEADER, &abandoned).body(rpc(2, "ping", json!({}))).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        assert!(sessions.find(&listening_id).is_some());
+        listening.send(&rpc(2, "ping", json!({}))).await.unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&listening.receive().await.unwrap()).unwrap()["id"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_over_http() {
+        let (url, _sessions, _stop) = start_server().await;
+        let client = McpClient::new(&url).await.unwrap();
+
+        let resources = client.list_resources().await.unwrap();
+        assert!(resources.iter().any(|resource| resource.uri == "workshop"));
+
+        let workshop = client.read_resource("workshop").await.unwrap();
+        assert!(workshop.text.unwrap().contains("total_agents"));
+    }
+}
@@ -1,6 +1,21 @@
 use crate::*;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
 use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::WebSocketStream;
+
+/// Largest message a framed transport accepts (1MB)
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// How often an idle WebSocket peer is pinged
+pub const WEBSOCKET_KEEPALIVE: Duration = Duration::from_secs(30);
 
 /// Transport layer abstraction for MCP
 #[async_trait::async_trait]
@@ -21,8 +36,9 @@ pub trait Transport: Send + Sync {
 /// Stdio transport implementation
 #[derive(Debug)]
 pub struct StdioTransport {
-    stdin_sender: Arc<Mutex<mpsc::UnboundedSender<String>>>,
+    stdin_sender: Arc<Mutex<Option<mpsc::UnboundedSender<String>>>>,
     stdout_receiver: Arc<Mutex<mpsc::UnboundedReceiver<String>>>,
+    writer: Arc<Mutex<Option<JoinHandle<()>>>>,
     connected: Arc<std::sync::atomic::AtomicBool>,
 }
 
@@ -33,18 +49,19 @@ impl StdioTransport {
         let (stdout_sender, stdout_receiver) = mpsc::unbounded_channel();
         
         // Start background tasks for stdio handling
-        tokio::spawn(Self::handle_stdin(stdin_receiver));
+        let writer = tokio::spawn(Self::handle_stdin(stdin_receiver));
         tokio::spawn(Self::handle_stdout(stdout_sender));
         
         Self {
-            stdin_sender: Arc::new(Mutex::new(stdin_sender)),
+            stdin_sender: Arc::new(Mutex::new(Some(stdin_sender))),
             stdout_receiver: Arc::new(Mutex::new(stdout_receiver)),
+            writer: Arc::new(Mutex::new(Some(writer))),
             connected: Arc::new(std::sync::atomic::AtomicBool::new(true)),
         }
     }
     
     async fn handle_stdin(mut receiver: mpsc::UnboundedReceiver<String>) {
-        use tokio::io::{AsyncWriteExt, stdout};
+        use tokio::io::stdout;
         
         let mut stdout = stdout();
         while let Some(message) = receiver.recv().await {
@@ -100,19 +117,27 @@ impl StdioTransport {
 impl Transport for StdioTransport {
     async fn send(&self, message: &str) -> Result<(), McpError> {
         let sender = self.stdin_sender.lock().await;
-        sender.send(message.to_string())
+        sender.as_ref()
+            .ok_or(McpError::ConnectionClosed)?
+            .send(message.to_string())
             .map_err(|e| McpError::TransportError(format!("Failed to send message: {}", e)))?;
         Ok(())
     }
     
     async fn receive(&self) -> Result<String, McpError> {
         let mut receiver = self.stdout_receiver.lock().await;
-        receiver.recv().await
-            .ok_or_else(|| McpError::TransportError("Channel closed".to_string()))
+        receiver.recv().await.ok_or(McpError::ConnectionClosed)
     }
     
     async fn close(&self) -> Result<(), McpError> {
         self.connected.store(false, std::sync::atomic::Ordering::Relaxed);
+        
+        // Dropping the sender ends the writer once it has flushed what was queued
+        self.stdin_sender.lock().await.take();
+        if let Some(writer) = self.writer.lock().await.take() {
+            writer.await
+                .map_err(|e| McpError::TransportError(format!("Stdout writer failed: {}", e)))?;
+        }
         Ok(())
     }
     
@@ -127,26 +152,78 @@ impl Default for StdioTransport {
     }
 }
 
-/// WebSocket transport implementation
-#[derive(Debug)]
+type WebSocketSink = Box<dyn Sink<Message, Error = WsError> + Send + Unpin>;
+type WebSocketSource = Box<dyn Stream<Item = Result<Message, WsError>> + Send + Unpin>;
+
+/// WebSocket transport carrying one JSON-RPC message per text frame
+///
+/// While waiting for a message it pings the peer every keepalive interval
+/// and gives up on a peer that lets a whole interval pass without a frame.
 pub struct WebSocketTransport {
-    url: String,
+    peer: String,
+    sink: Mutex<WebSocketSink>,
+    source: Mutex<WebSocketSource>,
+    keepalive: Duration,
     connected: Arc<std::sync::atomic::AtomicBool>,
 }
 
+impl std::fmt::Debug for WebSocketTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocketTransport")
+            .field("peer", &self.peer)
+            .field("keepalive", &self.keepalive)
+            .field("connected", &self.is_connected())
+            .finish_non_exhaustive()
+    }
+}
+
 impl WebSocketTransport {
-    pub fn new(url: String) -> Self {
+    /// Connect to an MCP server at a `ws://` URL
+    pub async fn connect(url: &str) -> Result<Self, McpError> {
+        let (socket, _) = tokio_tungstenite::connect_async(url).await
+            .map_err(|e| McpError::TransportError(format!("Failed to connect to {}: {}", url, e)))?;
+        tracing::info!("Connected to WebSocket: {}", url);
+        Ok(Self::new(url.to_string(), socket))
+    }
+    
+    /// Complete the server side of the WebSocket handshake on an accepted stream
+    pub async fn accept(stream: TcpStream) -> Result<Self, McpError> {
+        let peer = stream.peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "websocket".to_string());
+        let socket = tokio_tungstenite::accept_async(stream).await
+            .map_err(|e| McpError::TransportError(format!("WebSocket handshake with {} failed: {}", peer, e)))?;
+        Ok(Self::new(peer, socket))
+    }
+    
+    fn new<S>(peer: String, socket: WebSocketStream<S>) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (sink, source) = socket.split();
         Self {
-            url,
-            connected: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            peer,
+            sink: Mutex::new(Box::new(sink)),
+            source: Mutex::new(Box::new(source)),
+            keepalive: WEBSOCKET_KEEPALIVE,
+            connected: Arc::new(std::sync::atomic::AtomicBool::new(true)),
         }
     }
     
-    pub async fn connect(&self) -> Result<(), McpError> {
-        // WebSocket connection logic would go here
-        tracing::info!("Connecting to WebSocket: {}", self.url);
-        self.connected.store(true, std::sync::atomic::Ordering::Relaxed);
-        Ok(())
+    /// Set how often an idle peer is pinged
+    pub fn with_keepalive(mut self, keepalive: Duration) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+    
+    /// Address of the other end, for logging
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+    
+    fn disconnected(&self) -> McpError {
+        self.connected.store(false, std::sync::atomic::Ordering::Relaxed);
+        McpError::ConnectionClosed
     }
 }
 
@@ -154,28 +231,56 @@ impl WebSocketTransport {
 impl Transport for WebSocketTransport {
     async fn send(&self, message: &str) -> Result<(), McpError> {
         if !self.is_connected() {
-            return Err(McpError::TransportError("WebSocket not connected".to_string()));
+            return Err(McpError::ConnectionClosed);
         }
         
-        // WebSocket send logic would go here
-        tracing::debug!("Sending WebSocket message: {}", message);
-        Ok(())
+        self.sink.lock().await.send(Message::Text(message.to_string())).await
+            .map_err(|e| McpError::TransportError(format!("Failed to send WebSocket message: {}", e)))
     }
     
     async fn receive(&self) -> Result<String, McpError> {
-        if !self.is_connected() {
-            return Err(McpError::TransportError("WebSocket not connected".to_string()));
-        }
+        let mut source = self.source.lock().await;
+        let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + self.keepalive, self.keepalive);
+        let mut awaiting_pong = false;
         
-        // WebSocket receive logic would go here
-        // For now, return a placeholder
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        Ok("{}".to_string())
+        loop {
+            tokio::select! {
+                frame = source.next() => {
+                    awaiting_pong = false;
+                    match frame {
+                        Some(Ok(Message::Text(text))) => return Ok(text),
+                        Some(Ok(Message::Binary(data))) => {
+                            return String::from_utf8(data)
+                                .map_err(|e| McpError::TransportError(format!("Invalid UTF-8 in message: {}", e)));
+                        }
+                        // Pings are answered by tungstenite itself
+                        Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => {}
+                        Some(Ok(Message::Close(_)))
+                        | Some(Err(WsError::ConnectionClosed | WsError::AlreadyClosed))
+                        | None => return Err(self.disconnected()),
+                        Some(Err(e)) => {
+                            return Err(McpError::TransportError(format!("WebSocket error: {}", e)));
+                        }
+                    }
+                }
+                _ = keepalive.tick() => {
+                    if awaiting_pong {
+                        tracing::warn!("WebSocket peer {} stopped answering pings", self.peer);
+                        return Err(self.disconnected());
+                    }
+                    self.sink.lock().await.send(Message::Ping(Vec::new())).await
+                        .map_err(|e| McpError::TransportError(format!("Failed to ping {}: {}", self.peer, e)))?;
+                    awaiting_pong = true;
+                }
+            }
+        }
     }
     
     async fn close(&self) -> Result<(), McpError> {
         self.connected.store(false, std::sync::atomic::Ordering::Relaxed);
-        tracing::info!("WebSocket connection closed");
+        // Sends our close frame, or flushes the reply to the peer's; either may find the socket gone
+        let _ = self.sink.lock().await.close().await;
+        tracing::info!("WebSocket connection to {} closed", self.peer);
         Ok(())
     }
     
@@ -184,57 +289,121 @@ impl Transport for WebSocketTransport {
     }
 }
 
-/// TCP transport implementation
-#[derive(Debug)]
-pub struct TcpTransport {
-    address: String,
-    port: u16,
+/// Newline-framed transport over a TCP or Unix-domain socket
+pub struct StreamTransport {
+    peer: String,
+    reader: Mutex<StreamReader>,
+    writer: Mutex<Option<Box<dyn AsyncWrite + Send + Unpin>>>,
     connected: Arc<std::sync::atomic::AtomicBool>,
 }
 
-impl TcpTransport {
-    pub fn new(address: String, port: u16) -> Self {
+/// Read half of a [`StreamTransport`] and the messages framed from it so far
+struct StreamReader {
+    stream: Box<dyn AsyncRead + Send + Unpin>,
+    framer: MessageFramer,
+    ready: VecDeque<String>,
+}
+
+impl std::fmt::Debug for StreamTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamTransport")
+            .field("peer", &self.peer)
+            .field("connected", &self.is_connected())
+            .finish_non_exhaustive()
+    }
+}
+
+impl StreamTransport {
+    /// Connect to an MCP server listening on TCP
+    pub async fn connect_tcp(address: &str) -> Result<Self, McpError> {
+        let stream = TcpStream::connect(address).await
+            .map_err(|e| McpError::TransportError(format!("Failed to connect to {}: {}", address, e)))?;
+        Ok(Self::from_tcp(stream))
+    }
+    
+    /// Connect to an MCP server listening on a Unix-domain socket
+    pub async fn connect_unix(path: &Path) -> Result<Self, McpError> {
+        let stream = UnixStream::connect(path).await
+            .map_err(|e| McpError::TransportError(format!("Failed to connect to {}: {}", path.display(), e)))?;
+        Ok(Self::from_unix(stream, path.display().to_string()))
+    }
+    
+    /// Wrap an accepted or connected TCP stream
+    pub fn from_tcp(stream: TcpStream) -> Self {
+        let peer = stream.peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "tcp".to_string());
+        let (reader, writer) = stream.into_split();
+        Self::new(peer, Box::new(reader), Box::new(writer))
+    }
+    
+    /// Wrap an accepted or connected Unix-domain stream
+    pub fn from_unix(stream: UnixStream, peer: String) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self::new(peer, Box::new(reader), Box::new(writer))
+    }
+    
+    /// Talk to a server process over its stdin and stdout
+    pub fn from_child(stdin: tokio::process::ChildStdin, stdout: tokio::process::ChildStdout, peer: String) -> Self {
+        Self::new(peer, Box::new(stdout), Box::new(stdin))
+    }
+    
+    fn new(peer: String, reader: Box<dyn AsyncRead + Send + Unpin>, writer: Box<dyn AsyncWrite + Send + Unpin>) -> Self {
         Self {
-            address,
-            port,
-            connected: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            peer,
+            reader: Mutex::new(StreamReader {
+                stream: reader,
+                framer: MessageFramer::new(MAX_MESSAGE_SIZE),
+                ready: VecDeque::new(),
+            }),
+            writer: Mutex::new(Some(writer)),
+            connected: Arc::new(std::sync::atomic::AtomicBool::new(true)),
         }
     }
     
-    pub async fn connect(&self) -> Result<(), McpError> {
-        // TCP connection logic would go here
-        tracing::info!("Connecting to TCP: {}:{}", self.address, self.port);
-        self.connected.store(true, std::sync::atomic::Ordering::Relaxed);
-        Ok(())
+    /// Address of the other end, for logging
+    pub fn peer(&self) -> &str {
+        &self.peer
     }
 }
 
 #[async_trait::async_trait]
-impl Transport for TcpTransport {
+impl Transport for StreamTransport {
     async fn send(&self, message: &str) -> Result<(), McpError> {
-        if !self.is_connected() {
-            return Err(McpError::TransportError("TCP not connected".to_string()));
-        }
+        let mut writer = self.writer.lock().await;
+        let writer = writer.as_mut().ok_or(McpError::ConnectionClosed)?;
         
-        // TCP send logic would go here
-        tracing::debug!("Sending TCP message: {}", message);
+        let framed = format!("{}\n", message);
+        writer.write_all(framed.as_bytes()).await?;
+        writer.flush().await?;
         Ok(())
     }
     
     async fn receive(&self) -> Result<String, McpError> {
-        if !self.is_connected() {
-            return Err(McpError::TransportError("TCP not connected".to_string()));
-        }
+        let mut reader = self.reader.lock().await;
+        let mut buffer = [0u8; 8192];
         
-        // TCP receive logic would go here
-        // For now, return a placeholder
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        Ok("{}".to_string())
+        loop {
+            if let Some(message) = reader.ready.pop_front() {
+                return Ok(message);
+            }
+            
+            let read = reader.stream.read(&mut buffer).await?;
+            if read == 0 {
+                self.connected.store(false, std::sync::atomic::Ordering::Relaxed);
+                return Err(McpError::ConnectionClosed);
+            }
+            let messages = reader.framer.add_bytes(&buffer[..read])?;
+            reader.ready.extend(messages);
+        }
     }
     
     async fn close(&self) -> Result<(), McpError> {
         self.connected.store(false, std::sync::atomic::Ordering::Relaxed);
-        tracing::info!("TCP connection closed");
+        if let Some(mut writer) = self.writer.lock().await.take() {
+            // The peer may already be gone, which is what closing wants anyway
+            let _ = writer.shutdown().await;
+        }
         Ok(())
     }
     
@@ -243,12 +412,162 @@ impl Transport for TcpTransport {
     }
 }
 
+/// Accepts MCP clients on a TCP, WebSocket or Unix-domain socket
+#[derive(Debug)]
+pub enum TransportListener {
+    Tcp(TcpListener),
+    WebSocket(TcpListener),
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+    },
+}
+
+/// A client accepted by a [`TransportListener`] that may still owe a handshake
+#[derive(Debug)]
+pub struct IncomingConnection {
+    peer: String,
+    kind: IncomingKind,
+}
+
+#[derive(Debug)]
+enum IncomingKind {
+    Stream(StreamTransport),
+    WebSocket(TcpStream),
+}
+
+impl IncomingConnection {
+    /// Address of the client, for logging
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+    
+    /// Finish any handshake and hand over the transport
+    ///
+    /// Kept apart from `accept` so a slow WebSocket handshake only holds up
+    /// its own client's task.
+    pub async fn establish(self) -> Result<Box<dyn Transport>, McpError> {
+        match self.kind {
+            IncomingKind::Stream(transport) => Ok(Box::new(transport)),
+            IncomingKind::WebSocket(stream) => Ok(Box::new(WebSocketTransport::accept(stream).await?)),
+        }
+    }
+}
+
+impl TransportListener {
+    /// Bind the socket described by a TCP, WebSocket or Unix transport configuration
+    pub async fn bind(config: &TransportConfig) -> Result<Self, McpError> {
+        match config.transport_type {
+            TransportType::Tcp => {
+                let address = config.address.as_deref().unwrap_or("127.0.0.1");
+                let port = config.port
+                    .ok_or_else(|| McpError::TransportError("TCP port required".to_string()))?;
+                Self::bind_tcp(&format!("{}:{}", address, port)).await
+            }
+            TransportType::WebSocket => {
+                let address = config.address.as_deref().unwrap_or("127.0.0.1");
+                let port = config.port
+                    .ok_or_else(|| McpError::TransportError("WebSocket port required".to_string()))?;
+                Self::bind_websocket(&format!("{}:{}", address, port)).await
+            }
+            TransportType::Unix => {
+                let path = config.address.as_ref()
+                    .ok_or_else(|| McpError::TransportError("Unix socket path required".to_string()))?;
+                Self::bind_unix(Path::new(path)).await
+            }
+            ref other => Err(McpError::TransportError(format!("{:?} transport has no listener", other))),
+        }
+    }
+    
+    /// Listen on a TCP address; port 0 picks a free port
+    pub async fn bind_tcp(address: &str) -> Result<Self, McpError> {
+        let listener = TcpListener::bind(address).await
+            .map_err(|e| McpError::TransportError(format!("Failed to bind {}: {}", address, e)))?;
+        Ok(Self::Tcp(listener))
+    }
+    
+    /// Listen for WebSocket clients on a TCP address; port 0 picks a free port
+    pub async fn bind_websocket(address: &str) -> Result<Self, McpError> {
+        let listener = TcpListener::bind(address).await
+            .map_err(|e| McpError::TransportError(format!("Failed to bind {}: {}", address, e)))?;
+        Ok(Self::WebSocket(listener))
+    }
+    
+    /// Listen on a Unix-domain socket, replacing a stale socket file
+    pub async fn bind_unix(path: &Path) -> Result<Self, McpError> {
+        if path.exists() {
+            if UnixStream::connect(path).await.is_ok() {
+                return Err(McpError::TransportError(format!("{} is already being served", path.display())));
+            }
+            std::fs::remove_file(path)?;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        Ok(Self::Unix {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+    
+    /// Where clients should connect
+    pub fn local_addr(&self) -> String {
+        match self {
+            Self::Tcp(listener) => listener.local_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+            Self::WebSocket(listener) => listener.local_addr()
+                .map(|addr| format!("ws://{}", addr))
+                .unwrap_or_default(),
+            Self::Unix { path, .. } => path.display().to_string(),
+        }
+    }
+    
+    /// Wait for the next client
+    pub async fn accept(&self) -> Result<IncomingConnection, McpError> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept().await?;
+                let transport = StreamTransport::from_tcp(stream);
+                Ok(IncomingConnection {
+                    peer: transport.peer().to_string(),
+                    kind: IncomingKind::Stream(transport),
+                })
+            }
+            Self::WebSocket(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok(IncomingConnection {
+                    peer: peer.to_string(),
+                    kind: IncomingKind::WebSocket(stream),
+                })
+            }
+            Self::Unix { listener, path } => {
+                let (stream, _) = listener.accept().await?;
+                let transport = StreamTransport::from_unix(stream, path.display().to_string());
+                Ok(IncomingConnection {
+                    peer: transport.peer().to_string(),
+                    kind: IncomingKind::Stream(transport),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for TransportListener {
+    fn drop(&mut self) {
+        if let Self::Unix { path, .. } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
 /// Transport factory for creating transports based on configuration
 pub struct TransportFactory;
 
 impl TransportFactory {
-    /// Create a transport from configuration
-    pub fn create_transport(config: &TransportConfig) -> Result<Box<dyn Transport>, McpError> {
+    /// Create a transport from configuration, connecting to network servers
+    pub async fn create_transport(config: &TransportConfig) -> Result<Box<dyn Transport>, McpError> {
         match config.transport_type {
             TransportType::Stdio => Ok(Box::new(StdioTransport::new())),
             TransportType::WebSocket => {
@@ -260,26 +579,39 @@ impl TransportFactory {
                 } else {
                     format!("ws://{}:{}", url, port)
                 };
-                Ok(Box::new(WebSocketTransport::new(full_url)))
+                Ok(Box::new(WebSocketTransport::connect(&full_url).await?))
             }
             TransportType::Tcp => {
                 let address = config.address.as_ref()
                     .ok_or_else(|| McpError::TransportError("TCP address required".to_string()))?;
                 let port = config.port
                     .ok_or_else(|| McpError::TransportError("TCP port required".to_string()))?;
-                Ok(Box::new(TcpTransport::new(address.clone(), port)))
+                Ok(Box::new(StreamTransport::connect_tcp(&format!("{}:{}", address, port)).await?))
             }
             TransportType::Unix => {
-                // Unix socket implementation would go here
-                Err(McpError::TransportError("Unix socket transport not implemented".to_string()))
+                let path = config.address.as_ref()
+                    .ok_or_else(|| McpError::TransportError("Unix socket path required".to_string()))?;
+                Ok(Box::new(StreamTransport::connect_unix(Path::new(path)).await?))
+            }
+            TransportType::Http => {
+                let url = config.address.as_ref()
+                    .ok_or_else(|| McpError::TransportError("HTTP address required".to_string()))?;
+                let full_url = if url.contains("://") {
+                    url.clone()
+                } else {
+                    format!("http://{}:{}{}", url, config.port.unwrap_or(80), HTTP_ENDPOINT)
+                };
+                Ok(Box::new(HttpClientTransport::new(&full_url)))
             }
         }
     }
 }
 
 /// Transport message framing for protocols that need it
+#[derive(Debug)]
 pub struct MessageFramer {
     buffer: String,
+    partial_utf8: Vec<u8>,
     max_message_size: usize,
 }
 
@@ -287,10 +619,26 @@ impl MessageFramer {
     pub fn new(max_message_size: usize) -> Self {
         Self {
             buffer: String::new(),
+            partial_utf8: Vec::new(),
             max_message_size,
         }
     }
     
+    /// Add raw bytes from a socket, holding back a character split across reads
+    pub fn add_bytes(&mut self, data: &[u8]) -> Result<Vec<String>, McpError> {
+        self.partial_utf8.extend_from_slice(data);
+        let complete = match std::str::from_utf8(&self.partial_utf8) {
+            Ok(_) => self.partial_utf8.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(McpError::TransportError(format!("Invalid UTF-8 in message: {}", e))),
+        };
+        
+        let text: Vec<u8> = self.partial_utf8.drain(..complete).collect();
+        let text = String::from_utf8(text)
+            .map_err(|e| McpError::TransportError(format!("Invalid UTF-8 in message: {}", e)))?;
+        self.add_data(&text)
+    }
+    
     /// Add data to the buffer and extract complete messages
     pub fn add_data(&mut self, data: &str) -> Result<Vec<String>, McpError> {
         self.buffer.push_str(data);
@@ -322,6 +670,7 @@ impl MessageFramer {
     /// Clear the buffer
     pub fn clear(&mut self) {
         self.buffer.clear();
+        self.partial_utf8.clear();
     }
 }
 
@@ -336,7 +685,7 @@ impl ConnectionManager {
     pub fn new(transport: Box<dyn Transport>) -> Self {
         Self {
             transport,
-            framer: MessageFramer::new(1024 * 1024), // 1MB max message size
+            framer: MessageFramer::new(MAX_MESSAGE_SIZE),
             is_running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         }
     }
@@ -370,4 +719,167 @@ impl ConnectionManager {
         self.is_running.load(std::sync::atomic::Ordering::Relaxed) && 
         self.transport.is_connected()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use tokio::sync::oneshot;
+
+    async fn call(client: &StreamTransport, id: u64, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        client.send(&request.to_string()).await.unwrap();
+        serde_json::from_str(&client.receive().await.unwrap()).unwrap()
+    }
+
+    async fn initialize(client: &StreamTransport) {
+        let reply = call(client, 0, "initialize", json!({ "protocolVersion": "2024-11-05" })).await;
+        assert_eq!(reply["result"]["protocolVersion"], "2024-11-05");
+        client.send(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string()).await.unwrap();
+    }
+
+    fn start(listener: TransportListener) -> (oneshot::Sender<()>, JoinHandle<Result<(), McpError>>) {
+        let service = McpService::new(McpConfig::default()).unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            service.serve_listener(listener, async { let _ = stopped.await; }).await
+        });
+        (stop, handle)
+    }
+
+    #[test]
+    fn test_framer_holds_back_split_characters() {
+        let mut framer = MessageFramer::new(MAX_MESSAGE_SIZE);
+        let data = "{\"text\":\"café\"}\n{\"n\":1}".as_bytes();
+        let split = data.iter().position(|&b| b == 0xC3).unwrap() + 1;
+
+        assert!(framer.add_bytes(&data[..split]).unwrap().is_empty());
+        assert_eq!(framer.add_bytes(&data[split..]).unwrap(), vec!["{\"text\":\"café\"}".to_string()]);
+        assert_eq!(framer.add_bytes(b"\n").unwrap(), vec!["{\"n\":1}".to_string()]);
+        assert!(framer.add_bytes(&[0xFF, b'\n']).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_clients_get_separate_sessions_on_one_server() {
+        let listener = TransportListener::bind_tcp("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr();
+        let (stop, handle) = start(listener);
+
+        let first = StreamTransport::connect_tcp(&address).await.unwrap();
+        let second = StreamTransport::connect_tcp(&address).await.unwrap();
+
+        initialize(&first).await;
+        let created = call(&first, 1, "tools/call", json!({
+            "name": "create_task",
+            "arguments": { "title": "Lexer", "description": "Write the lexer", "role": "Implementer" }
+        })).await;
+        assert_eq!(created["result"]["isError"], false);
+
+        // The first client's handshake does not initialize the second
+        let early = call(&second, 1, "resources/read", json!({ "uri": "tasks" })).await;
+        assert_eq!(early["error"]["code"], -32600);
+
+        initialize(&second).await;
+        let tasks = call(&second, 2, "resources/read", json!({ "uri": "tasks" })).await;
+        assert!(tasks["result"]["contents"][0]["text"].as_str().unwrap().contains("Lexer"));
+
+        first.close().await.unwrap();
+        assert_eq!(call(&second, 3, "ping", json!({})).await["result"], json!({}));
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    async fn websocket_pair(keepalive: Duration) -> (WebSocketTransport, WebSocketTransport) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let accepted = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            WebSocketTransport::accept(stream).await.unwrap()
+        });
+        let client = WebSocketTransport::connect(&url).await.unwrap();
+        (accepted.await.unwrap().with_keepalive(keepalive), client)
+    }
+
+    #[tokio::test]
+    async fn test_websocket_client_speaks_mcp() {
+        let listener = TransportListener::bind_websocket("127.0.0.1:0").await.unwrap();
+        let url = listener.local_addr();
+        let (stop, handle) = start(listener);
+
+        let client = McpClient::new(&url).await.unwrap();
+        let resources = client.list_resources().await.unwrap();
+        assert!(resources.iter().any(|resource| resource.uri == "workshop"));
+
+        let workshop = client.read_resource("workshop").await.unwrap();
+        assert_eq!(workshop.mime_type, "application/json");
+        assert!(workshop.text.unwrap().contains("total_agents"));
+
+        drop(client);
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_websocket_keepalive_is_answered_by_a_listening_peer() {
+        let (server, client) = websocket_pair(Duration::from_millis(20)).await;
+        let (server, client) = (Arc::new(server), Arc::new(client));
+
+        // Only a peer that is reading answers pings
+        let listening = client.clone();
+        tokio::spawn(async move { listening.receive().await });
+        let waiting = server.clone();
+        let received = tokio::spawn(async move { waiting.receive().await });
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        client.send("{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":1}").await.unwrap();
+        assert!(received.await.unwrap().unwrap().contains("ping"));
+        assert!(server.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_keepalive_drops_a_silent_peer() {
+        let (server, _client) = websocket_pair(Duration::from_millis(20)).await;
+
+        let received = tokio::time::timeout(Duration::from_secs(2), server.receive()).await.unwrap();
+        assert!(matches!(received, Err(McpError::ConnectionClosed)));
+        assert!(!server.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_close_ends_the_peer_session() {
+        let (server, client) = websocket_pair(WEBSOCKET_KEEPALIVE).await;
+
+        client.close().await.unwrap();
+        assert!(matches!(server.receive().await, Err(McpError::ConnectionClosed)));
+        assert!(matches!(client.send("{}").await, Err(McpError::ConnectionClosed)));
+        server.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_serves_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp").join("dfcoder.sock");
+        let listener = TransportListener::bind_unix(&path).await.unwrap();
+        assert!(TransportListener::bind_unix(&path).await.is_err());
+        let (stop, handle) = start(listener);
+
+        let config = TransportConfig {
+            transport_type: TransportType::Unix,
+            address: Some(path.display().to_string()),
+            port: None,
+            timeout_ms: 1000,
+        };
+        let client = TransportFactory::create_transport(&config).await.unwrap();
+        client.send(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "2025-03-26" }
+        }).to_string()).await.unwrap();
+        let reply: Value = serde_json::from_str(&client.receive().await.unwrap()).unwrap();
+        assert_eq!(reply["result"]["serverInfo"]["name"], "dfcoder");
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+}
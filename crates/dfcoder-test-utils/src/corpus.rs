@@ -0,0 +1,263 @@
+//! Labelled pane transcripts for measuring classification
+//!
+//! A corpus is a directory of TOML files, each holding any number of
+//! transcripts annotated with how a person watching the pane would classify
+//! them:
+//!
+//! ```toml
+//! [[transcript]]
+//! name = "fixing-type-error"
+//! activity = "Debugging"
+//! emotional_state = "Frustrated"
+//! needs_help = false
+//! current_task = "Fix the build"   # optional context for the classifier
+//! output = """
+//! error[E0308]: mismatched types
+//! Fixing the conversion in parse_port.
+//! """
+//! ```
+//!
+//! Labels describe what the agent is doing, not what the current rules say.
+//! The corpus shipped with this crate lives in `fixtures/transcripts` and is
+//! embedded in the binary, so it is available outside the source tree.
+
+use dfcoder_baml::{
+    ActivityClassifier, ActivityContext, ActivityType, CancellationToken, ClassificationSource, ConfusionMatrix,
+    EmotionalState,
+};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors loading a corpus
+#[derive(Debug, Error)]
+pub enum CorpusError {
+    #[error("Failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid transcript file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Transcript name {0} is used more than once")]
+    DuplicateName(String),
+}
+
+/// Pane output with its expected classification
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelledTranscript {
+    pub name: String,
+    pub output: String,
+    pub activity: ActivityType,
+    pub emotional_state: EmotionalState,
+    pub needs_help: bool,
+    /// Task the agent was given, passed to the classifier as context
+    #[serde(default)]
+    pub current_task: Option<String>,
+}
+
+impl LabelledTranscript {
+    /// Context the classifier is given along with the output
+    pub fn context(&self) -> ActivityContext {
+        ActivityContext {
+            current_task: self.current_task.clone(),
+            ..ActivityContext::new()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TranscriptFile {
+    #[serde(default)]
+    transcript: Vec<LabelledTranscript>,
+}
+
+/// Files of the shipped corpus, in name order
+const FIXTURES: &[(&str, &str)] = &[
+    ("building.toml", include_str!("../fixtures/transcripts/building.toml")),
+    ("debugging.toml", include_str!("../fixtures/transcripts/debugging.toml")),
+    ("other.toml", include_str!("../fixtures/transcripts/other.toml")),
+    ("testing.toml", include_str!("../fixtures/transcripts/testing.toml")),
+];
+
+/// A set of labelled transcripts
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptCorpus {
+    pub transcripts: Vec<LabelledTranscript>,
+}
+
+impl TranscriptCorpus {
+    /// The corpus shipped with this crate
+    pub fn fixtures() -> Result<Self, CorpusError> {
+        let mut corpus = Self::default();
+        for (name, text) in FIXTURES {
+            corpus.add_file(Path::new(name), text)?;
+        }
+        Ok(corpus)
+    }
+
+    /// Load one transcript file, or every `.toml` file in a directory in name order
+    pub fn load(path: &Path) -> Result<Self, CorpusError> {
+        let io_error = |source| CorpusError::Io { path: path.to_path_buf(), source };
+        let files = if path.is_dir() {
+            let mut files = Vec::new();
+            for entry in std::fs::read_dir(path).map_err(io_error)? {
+                let file = entry.map_err(io_error)?.path();
+                if file.extension().is_some_and(|extension| extension == "toml") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut corpus = Self::default();
+        for file in files {
+            let text = std::fs::read_to_string(&file).map_err(|source| CorpusError::Io { path: file.clone(), source })?;
+            corpus.add_file(&file, &text)?;
+        }
+        Ok(corpus)
+    }
+
+    fn add_file(&mut self, path: &Path, text: &str) -> Result<(), CorpusError> {
+        let parsed: TranscriptFile = toml::from_str(text).map_err(|source| CorpusError::Parse { path: path.to_path_buf(), source })?;
+        for transcript in parsed.transcript {
+            if self.transcripts.iter().any(|known| known.name == transcript.name) {
+                return Err(CorpusError::DuplicateName(transcript.name));
+            }
+            self.transcripts.push(transcript);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.transcripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transcripts.is_empty()
+    }
+
+    /// Output and expected activity of each transcript, e.g. for `RuleSet::train`
+    pub fn activity_samples(&self) -> Vec<(&str, ActivityType)> {
+        self.transcripts.iter().map(|transcript| (transcript.output.as_str(), transcript.activity.clone())).collect()
+    }
+
+    /// Classify every transcript and compare the results with the labels
+    pub async fn evaluate(&self, classifier: &ActivityClassifier) -> CorpusReport {
+        let contexts: Vec<ActivityContext> = self.transcripts.iter().map(LabelledTranscript::context).collect();
+        let requests: Vec<(&str, Option<&ActivityContext>)> = self.transcripts.iter()
+            .zip(&contexts)
+            .map(|(transcript, context)| (transcript.output.as_str(), Some(context)))
+            .collect();
+        let results = classifier.classify_batch(&requests, &CancellationToken::new()).await;
+
+        let mut report = CorpusReport::default();
+        for (transcript, result) in self.transcripts.iter().zip(results) {
+            let class = match result {
+                Ok(class) => class,
+                Err(e) => {
+                    report.errors.push((transcript.name.clone(), e.to_string()));
+                    continue;
+                }
+            };
+            if matches!(class.source, ClassificationSource::Fallback { .. }) {
+                report.fallbacks += 1;
+            }
+            if class.primary != transcript.activity {
+                report.misclassified.push((transcript.name.clone(), class.primary.clone()));
+            }
+            report.activity.record(&transcript.activity, &class.primary);
+            report.emotional_state.record(&transcript.emotional_state, &class.emotional_state);
+            report.needs_help.record(&transcript.needs_help, &class.needs_help);
+        }
+        report
+    }
+}
+
+/// How a classifier did against a corpus
+#[derive(Debug, Clone)]
+pub struct CorpusReport {
+    pub activity: ConfusionMatrix<ActivityType>,
+    pub emotional_state: ConfusionMatrix<EmotionalState>,
+    pub needs_help: ConfusionMatrix<bool>,
+    /// Transcripts the model failed on, so the rules answered instead
+    pub fallbacks: usize,
+    /// Transcripts that could not be classified at all, with the error
+    pub errors: Vec<(String, String)>,
+    /// Transcripts given the wrong activity, with the activity predicted
+    pub misclassified: Vec<(String, ActivityType)>,
+}
+
+impl Default for CorpusReport {
+    fn default() -> Self {
+        Self {
+            activity: ConfusionMatrix::new(ActivityType::ALL),
+            emotional_state: ConfusionMatrix::new(EmotionalState::ALL),
+            needs_help: ConfusionMatrix::new([false, true]),
+            fallbacks: 0,
+            errors: Vec::new(),
+            misclassified: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Activity\n{}\n", self.activity)?;
+        writeln!(f, "Emotional state\n{}\n", self.emotional_state)?;
+        write!(f, "Needs help\n{}", self.needs_help)?;
+        if self.fallbacks > 0 {
+            write!(f, "\n\n{} transcripts fell back to rules", self.fallbacks)?;
+        }
+        if !self.misclassified.is_empty() {
+            write!(f, "\n\nMisclassified activity:")?;
+            for (name, predicted) in &self.misclassified {
+                write!(f, "\n  {} -> {:?}", name, predicted)?;
+            }
+        }
+        if !self.errors.is_empty() {
+            write!(f, "\n\nNot classified:")?;
+            for (name, error) in &self.errors {
+                write!(f, "\n  {}: {}", name, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fixtures_load_and_score_the_rules() {
+        let corpus = TranscriptCorpus::fixtures().unwrap();
+        assert!(corpus.len() >= 20);
+        for activity in ActivityType::ALL {
+            assert!(corpus.transcripts.iter().any(|transcript| transcript.activity == activity), "no {:?} transcript", activity);
+        }
+
+        let report = corpus.evaluate(&ActivityClassifier::rule_based()).await;
+        assert_eq!(report.activity.total() as usize, corpus.len());
+        assert!(report.errors.is_empty());
+        assert_eq!(report.fallbacks, 0);
+        assert_eq!(report.misclassified.len(), corpus.len() - (report.activity.accuracy() * corpus.len() as f64).round() as usize);
+        assert!(report.activity.accuracy() > 0.5, "{}", report);
+    }
+
+    #[test]
+    fn test_embedded_fixtures_match_the_directory() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("transcripts");
+        let on_disk = TranscriptCorpus::load(&dir).unwrap();
+        assert_eq!(TranscriptCorpus::fixtures().unwrap().transcripts, on_disk.transcripts);
+    }
+}
@@ -3,6 +3,12 @@
 //! This crate provides a TestSystem for writing integration tests
 //! that validate agent behaviors and system interactions.
 
+pub use corpus::*;
+pub use pane_mock::*;
+pub use runner_mock::*;
 pub use test_system::*;
 
+mod corpus;
+mod pane_mock;
+mod runner_mock;
 mod test_system;
\ No newline at end of file
@@ -152,6 +152,12 @@ impl MockPane {
         self.command_history.iter().collect()
     }
     
+    /// Replay the pane's content as raw reads of at most `chunk_size` bytes, the
+    /// way a PTY delivers it, splitting escape sequences and characters
+    pub fn transcript_chunks(&self, chunk_size: usize) -> impl Iterator<Item = &[u8]> {
+        self.content.as_bytes().chunks(chunk_size.max(1))
+    }
+
     /// Check if the pane has been idle for a duration
     pub fn is_idle_for(&self, duration: std::time::Duration) -> bool {
         self.last_update.elapsed() >= duration
@@ -223,6 +229,11 @@ impl MockPane {
         pane
     }
     
+    /// Create a pane from a recorded terminal transcript, escape sequences included
+    pub fn from_transcript(id: u32, transcript: impl Into<String>) -> Self {
+        Self::new(id).active().with_content(transcript)
+    }
+
     /// Create a pane that's been idle
     pub fn idle_pane(id: u32) -> Self {
         let mut pane = Self::new(id);
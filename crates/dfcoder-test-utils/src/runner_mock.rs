@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use dfcoder_core::{build_prompt, Agent, AgentId, AgentRunner, OutputSink, RunOutcome, RunnerError, Task, TaskId};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// One scripted agent run
+#[derive(Debug, Clone)]
+pub enum ScriptedRun {
+    /// The process writes `chunks` and exits with `exit_code` (`None` = killed)
+    Exit { chunks: Vec<String>, exit_code: Option<u32> },
+    /// The process could not be started
+    SpawnError(String),
+    /// The process ran past its timeout
+    TimedOut(Duration),
+}
+
+/// A run the runner was asked to perform
+#[derive(Debug, Clone)]
+pub struct RecordedRun {
+    pub agent_id: AgentId,
+    pub task_id: TaskId,
+    pub attempt: u32,
+    pub prompt: String,
+}
+
+/// Agent runner that replays scripted runs instead of spawning processes
+///
+/// Runs are consumed in order; once the script is exhausted every run
+/// succeeds. Failures are expressed as process output and exit codes, so the
+/// executor's real failure classification is exercised.
+#[derive(Debug, Default)]
+pub struct ScriptedRunner {
+    script: Mutex<VecDeque<ScriptedRun>>,
+    runs: Mutex<Vec<RecordedRun>>,
+}
+
+impl ScriptedRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a run
+    pub fn then(self, run: ScriptedRun) -> Self {
+        self.script.lock().unwrap().push_back(run);
+        self
+    }
+
+    /// Queue a successful run writing `output`
+    pub fn succeed(self, output: impl Into<String>) -> Self {
+        self.then(ScriptedRun::Exit { chunks: vec![output.into()], exit_code: Some(0) })
+    }
+
+    /// Queue a failed run writing `output` and exiting with `exit_code`
+    pub fn fail(self, exit_code: u32, output: impl Into<String>) -> Self {
+        self.then(ScriptedRun::Exit { chunks: vec![output.into()], exit_code: Some(exit_code) })
+    }
+
+    /// Queue a run that loses its connection to the API
+    pub fn network_error(self) -> Self {
+        self.fail(1, "Working on it...\nAPI Error: Connection refused (network unreachable)\n")
+    }
+
+    /// Queue a run that hits the API rate limit
+    pub fn rate_limited(self) -> Self {
+        self.fail(1, "API Error: 429 rate limit exceeded, please retry later\n")
+    }
+
+    /// Queue a run that fails for a reason retrying won't fix
+    pub fn fatal(self) -> Self {
+        self.fail(2, "Refusing to continue: the repository is in an inconsistent state\n")
+    }
+
+    /// Every run performed so far
+    pub fn runs(&self) -> Vec<RecordedRun> {
+        self.runs.lock().unwrap().clone()
+    }
+
+    /// Number of runs still scripted
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl AgentRunner for ScriptedRunner {
+    async fn run(&self, agent: &Agent, task: &Task, attempt: u32, sink: OutputSink) -> Result<RunOutcome, RunnerError> {
+        self.runs.lock().unwrap().push(RecordedRun {
+            agent_id: agent.id.clone(),
+            task_id: task.id.clone(),
+            attempt,
+            prompt: build_prompt(agent, task),
+        });
+
+        let run = self.script.lock().unwrap().pop_front().unwrap_or_else(|| ScriptedRun::Exit {
+            chunks: vec![format!("Task '{}' completed successfully\n", task.title)],
+            exit_code: Some(0),
+        });
+
+        match run {
+            ScriptedRun::Exit { chunks, exit_code } => {
+                for chunk in &chunks {
+                    sink.send(chunk.as_bytes());
+                }
+                Ok(RunOutcome {
+                    exit_code,
+                    output: chunks.concat(),
+                    duration: Duration::from_millis(1),
+                })
+            }
+            ScriptedRun::SpawnError(message) => Err(RunnerError::Spawn(message)),
+            ScriptedRun::TimedOut(timeout) => Err(RunnerError::TimedOut(timeout)),
+        }
+    }
+}
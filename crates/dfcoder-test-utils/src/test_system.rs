@@ -3,7 +3,6 @@
 //! Provides a high-level API for testing agent behaviors and system interactions.
 
 use dfcoder_core::*;
-use dfcoder_baml::*;
 use std::collections::HashMap;
 use std::time::Duration;
 
@@ -66,7 +65,7 @@ impl TestSystem {
         let task_id = task.id.clone();
         
         // Queue and assign task
-        self.workshop.queue_task(task);
+        self.workshop.queue_task(task).unwrap();
         let assignment = self.workshop.try_assign_next_task().unwrap();
         
         if assignment.is_none() {
@@ -145,7 +144,7 @@ impl TestSystem {
     }
 
     /// Fail a task for an agent
-    pub fn fail_task(&mut self, agent_id: AgentId, task_id: TaskId, error: String) -> Result<(), WorkshopError> {
+    pub fn fail_task(&mut self, agent_id: AgentId, task_id: TaskId, error: String) -> Result<Vec<Task>, WorkshopError> {
         self.workshop.fail_task(agent_id, task_id, error)
     }
 
@@ -0,0 +1,523 @@
+//! Dashboard state derived from the workshop roster
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use dfcoder_core::*;
+use dfcoder_daemon::{DaemonClient, DaemonError};
+use dfcoder_types::protocol::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Workshop shared between the dashboard and whoever drives it
+pub type SharedWorkshop = Arc<Mutex<WorkshopManager>>;
+
+/// Supervision system shared between the dashboard and whoever drives it
+pub type SharedSupervision = Arc<Mutex<SupervisionSystem>>;
+
+/// Roles in the order their panels are drawn
+pub const ROLE_ORDER: [AgentRole; 4] = [
+    AgentRole::Scaffolder,
+    AgentRole::Implementer,
+    AgentRole::Debugger,
+    AgentRole::Tester,
+];
+
+/// Configuration for the dashboard
+#[derive(Debug, Clone)]
+pub struct TuiConfig {
+    /// How often the roster is refreshed from the workshop
+    pub tick_rate: Duration,
+    /// Title shown in the header bar
+    pub title: String,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: Duration::from_millis(250),
+            title: "DFCoder Workshop".to_string(),
+        }
+    }
+}
+
+/// Point-in-time copy of the workshop used for rendering
+///
+/// Held as protocol summaries so a local workshop and one attached over
+/// the dfcoderd socket render the same way.
+#[derive(Debug, Clone)]
+pub struct WorkshopSnapshot {
+    /// Agents grouped by role, in `ROLE_ORDER`, sorted by pane
+    pub agents_by_role: Vec<(AgentRole, Vec<AgentSummary>)>,
+    /// Queued tasks in priority order
+    pub queue: Vec<TaskSummary>,
+    /// Capacity, load and metrics
+    pub status: WorkshopSummary,
+    /// Open supervision requests, most urgent first
+    pub supervision: Vec<PendingSupervision>,
+}
+
+/// An open supervision request and how long it has left
+#[derive(Debug, Clone)]
+pub struct PendingSupervision {
+    pub request: SupervisionPrompt,
+    pub remaining: Duration,
+}
+
+impl PendingSupervision {
+    fn new(request: SupervisionPrompt) -> Self {
+        Self {
+            remaining: Duration::from_secs(request.remaining_secs),
+            request,
+        }
+    }
+
+    /// Whether the request has outlived its timeout
+    pub fn is_expired(&self) -> bool {
+        self.remaining.is_zero()
+    }
+}
+
+impl WorkshopSnapshot {
+    /// Capture the current roster, queue, status and open supervision requests
+    pub fn capture(workshop: &mut WorkshopManager, supervision: &SupervisionSystem) -> Self {
+        Self::from_parts(
+            workshop.get_all_agents().into_iter().map(AgentSummary::from).collect(),
+            workshop.get_queue().iter().map(TaskSummary::from).collect(),
+            WorkshopSummary::from(&workshop.get_status()),
+            supervision.get_all_active_requests().into_iter().map(SupervisionPrompt::from).collect(),
+        )
+    }
+
+    /// Ask a running dfcoderd for its roster, queue, status and open supervision requests
+    pub async fn fetch(client: &mut DaemonClient) -> Result<Self, DaemonError> {
+        let status = match client.request(DaemonRequest::GetStatus).await? {
+            DaemonResponse::Status { status } => *status,
+            other => return Err(unexpected_response(other)),
+        };
+        let agents = match client.request(DaemonRequest::ListAgents).await? {
+            DaemonResponse::Agents { agents } => agents,
+            other => return Err(unexpected_response(other)),
+        };
+        let queue = match client.request(DaemonRequest::ListQueue).await? {
+            DaemonResponse::Queue { tasks } => tasks,
+            other => return Err(unexpected_response(other)),
+        };
+        let supervision = match client.request(DaemonRequest::ListSupervision).await? {
+            DaemonResponse::Supervision { requests } => requests,
+            other => return Err(unexpected_response(other)),
+        };
+
+        Ok(Self::from_parts(agents, queue, status, supervision))
+    }
+
+    /// Group agents by role and order supervision requests by urgency
+    pub fn from_parts(
+        agents: Vec<AgentSummary>,
+        queue: Vec<TaskSummary>,
+        status: WorkshopSummary,
+        supervision: Vec<SupervisionPrompt>,
+    ) -> Self {
+        let mut agents_by_role: Vec<(AgentRole, Vec<AgentSummary>)> = ROLE_ORDER.iter()
+            .map(|role| (role.clone(), Vec::new()))
+            .collect();
+
+        for agent in agents {
+            if let Some((_, agents)) = agents_by_role.iter_mut().find(|(role, _)| *role == agent.role) {
+                agents.push(agent);
+            }
+        }
+
+        for (_, agents) in agents_by_role.iter_mut() {
+            agents.sort_by_key(|agent| agent.pane_id);
+        }
+
+        let mut pending: Vec<PendingSupervision> = supervision.into_iter()
+            .map(PendingSupervision::new)
+            .collect();
+        pending.sort_by(|a, b| {
+            b.request.urgency.cmp(&a.request.urgency)
+                .then(a.remaining.cmp(&b.remaining))
+        });
+
+        Self {
+            agents_by_role,
+            queue,
+            status,
+            supervision: pending,
+        }
+    }
+
+    /// Agents registered for a role
+    pub fn agents_for(&self, role: &AgentRole) -> &[AgentSummary] {
+        self.agents_by_role.iter()
+            .find(|(r, _)| r == role)
+            .map(|(_, agents)| agents.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// Where the dashboard reads the workshop from
+enum Source {
+    /// Workshop and supervision system owned by this process
+    Local {
+        workshop: SharedWorkshop,
+        supervision: SharedSupervision,
+    },
+    /// A running dfcoderd
+    Daemon(DaemonClient),
+}
+
+/// Dashboard application state
+pub struct App {
+    source: Source,
+    config: TuiConfig,
+    snapshot: WorkshopSnapshot,
+    last_refresh: Instant,
+    paused: bool,
+    should_quit: bool,
+    /// Agent whose supervision request is selected
+    selected_request: Option<AgentId>,
+    /// Whether the supervision dialogue is shown while requests are open
+    show_supervision: bool,
+    /// Outcome of the last supervision answer
+    status_message: Option<String>,
+}
+
+impl App {
+    /// Create the dashboard and take an initial snapshot
+    pub async fn new(workshop: SharedWorkshop, supervision: SharedSupervision, config: TuiConfig) -> Self {
+        let snapshot = WorkshopSnapshot::capture(
+            &mut *workshop.lock().await,
+            &*supervision.lock().await,
+        );
+        Self::with_source(Source::Local { workshop, supervision }, snapshot, config)
+    }
+
+    /// Create the dashboard over a connection to dfcoderd and take an initial snapshot
+    pub async fn attach(mut client: DaemonClient, config: TuiConfig) -> Result<Self, DaemonError> {
+        let snapshot = WorkshopSnapshot::fetch(&mut client).await?;
+        Ok(Self::with_source(Source::Daemon(client), snapshot, config))
+    }
+
+    fn with_source(source: Source, snapshot: WorkshopSnapshot, config: TuiConfig) -> Self {
+        let mut app = Self {
+            source,
+            config,
+            snapshot,
+            last_refresh: Instant::now(),
+            paused: false,
+            should_quit: false,
+            selected_request: None,
+            show_supervision: true,
+            status_message: None,
+        };
+        app.keep_selection_valid();
+        app
+    }
+
+    /// Refresh the snapshot unless auto-refresh is paused
+    pub async fn on_tick(&mut self) {
+        if !self.paused {
+            self.refresh().await;
+        }
+    }
+
+    /// Re-read the workshop into a fresh snapshot, keeping the old one if dfcoderd is unreachable
+    pub async fn refresh(&mut self) {
+        let snapshot = match &mut self.source {
+            Source::Local { workshop, supervision } => Ok(WorkshopSnapshot::capture(
+                &mut *workshop.lock().await,
+                &*supervision.lock().await,
+            )),
+            Source::Daemon(client) => WorkshopSnapshot::fetch(client).await,
+        };
+
+        match snapshot {
+            Ok(snapshot) => {
+                self.snapshot = snapshot;
+                self.last_refresh = Instant::now();
+                self.keep_selection_valid();
+            }
+            Err(e) => self.status_message = Some(format!("dfcoderd: {}", e)),
+        }
+    }
+
+    /// Handle a key press
+    pub async fn on_key(&mut self, key: KeyEvent) {
+        if self.is_supervision_visible() {
+            match key.code {
+                KeyCode::Char(c @ '1'..='9') => {
+                    self.answer_selected(c.to_digit(10).unwrap_or(0)).await;
+                    return;
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.move_selection(-1);
+                    return;
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.move_selection(1);
+                    return;
+                }
+                KeyCode::Esc => {
+                    self.show_supervision = false;
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.should_quit = true;
+            }
+            KeyCode::Char('r') => self.refresh().await,
+            KeyCode::Char('p') => self.paused = !self.paused,
+            KeyCode::Char('s') => self.show_supervision = !self.show_supervision,
+            _ => {}
+        }
+    }
+
+    /// Answer the selected supervision request with the given option id
+    pub async fn answer_selected(&mut self, option_id: u32) {
+        let Some(agent_id) = self.selected_request.clone() else {
+            return;
+        };
+
+        let outcome = match &mut self.source {
+            Source::Local { supervision, .. } => {
+                match supervision.lock().await.handle_supervision_response(&agent_id, option_id).await {
+                    Ok(action) => format!("{:?}", action),
+                    Err(e) => e.to_string(),
+                }
+            }
+            Source::Daemon(client) => {
+                let request = DaemonRequest::AnswerSupervision { agent_id: agent_id.clone(), option_id };
+                match client.request(request).await {
+                    Ok(DaemonResponse::SupervisionAnswered { action, .. }) => action,
+                    Ok(other) => unexpected_response(other).to_string(),
+                    Err(e) => e.to_string(),
+                }
+            }
+        };
+
+        self.status_message = Some(format!("{}: {}", short_agent_id(&agent_id), outcome));
+        self.refresh().await;
+    }
+
+    /// Selected supervision request, if any are open
+    pub fn selected_supervision(&self) -> Option<&PendingSupervision> {
+        let agent_id = self.selected_request.as_ref()?;
+        self.snapshot.supervision.iter().find(|p| &p.request.agent_id == agent_id)
+    }
+
+    /// Position of the selected request in the urgency-ordered list
+    pub fn selected_index(&self) -> Option<usize> {
+        let agent_id = self.selected_request.as_ref()?;
+        self.snapshot.supervision.iter().position(|p| &p.request.agent_id == agent_id)
+    }
+
+    /// Whether the supervision dialogue should be drawn
+    pub fn is_supervision_visible(&self) -> bool {
+        self.show_supervision && !self.snapshot.supervision.is_empty()
+    }
+
+    /// Outcome of the last supervision answer
+    pub fn status_message(&self) -> Option<&str> {
+        self.status_message.as_deref()
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let count = self.snapshot.supervision.len();
+        if count == 0 {
+            return;
+        }
+        let current = self.selected_index().unwrap_or(0) as isize;
+        let next = (current + delta).rem_euclid(count as isize) as usize;
+        self.selected_request = Some(self.snapshot.supervision[next].request.agent_id.clone());
+    }
+
+    /// Keep the selection on an open request, falling back to the most urgent
+    fn keep_selection_valid(&mut self) {
+        if self.selected_index().is_none() {
+            self.selected_request = self.snapshot.supervision.first()
+                .map(|p| p.request.agent_id.clone());
+        }
+    }
+
+    /// Latest snapshot of the workshop
+    pub fn snapshot(&self) -> &WorkshopSnapshot {
+        &self.snapshot
+    }
+
+    /// Dashboard configuration
+    pub fn config(&self) -> &TuiConfig {
+        &self.config
+    }
+
+    /// Time since the snapshot was last refreshed
+    pub fn since_refresh(&self) -> Duration {
+        self.last_refresh.elapsed()
+    }
+
+    /// Whether auto-refresh is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether the user asked to quit
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+}
+
+fn short_agent_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+/// Error for a response that doesn't answer the request, passing daemon errors through
+fn unexpected_response(response: DaemonResponse) -> DaemonError {
+    match response {
+        DaemonResponse::Error { message, .. } => DaemonError::Protocol(message),
+        other => DaemonError::Protocol(format!("unexpected response: {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfcoder_daemon::{Daemon, DaemonConfig};
+    use tokio::sync::oneshot;
+
+    #[test]
+    fn test_snapshot_groups_agents_by_role() {
+        let mut workshop = WorkshopManager::new();
+        workshop.register_agent(Agent::new(AgentRole::Implementer, 3)).unwrap();
+        workshop.register_agent(Agent::new(AgentRole::Implementer, 1)).unwrap();
+        workshop.register_agent(Agent::new(AgentRole::Tester, 2)).unwrap();
+
+        let snapshot = WorkshopSnapshot::capture(&mut workshop, &SupervisionSystem::new());
+
+        let implementers = snapshot.agents_for(&AgentRole::Implementer);
+        assert_eq!(implementers.len(), 2);
+        assert_eq!(implementers[0].pane_id, 1);
+        assert_eq!(snapshot.agents_for(&AgentRole::Tester).len(), 1);
+        assert!(snapshot.agents_for(&AgentRole::Scaffolder).is_empty());
+        assert_eq!(snapshot.status.total_agents, 3);
+    }
+
+    #[tokio::test]
+    async fn test_key_handling() {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let supervision = Arc::new(Mutex::new(SupervisionSystem::new()));
+        let mut app = App::new(workshop.clone(), supervision, TuiConfig::default()).await;
+
+        app.on_key(KeyEvent::from(KeyCode::Char('p'))).await;
+        assert!(app.is_paused());
+
+        // Paused ticks leave the snapshot alone, manual refresh still works
+        workshop.lock().await.register_agent(Agent::new(AgentRole::Debugger, 1)).unwrap();
+        app.on_tick().await;
+        assert_eq!(app.snapshot().status.total_agents, 0);
+        app.on_key(KeyEvent::from(KeyCode::Char('r'))).await;
+        assert_eq!(app.snapshot().status.total_agents, 1);
+
+        app.on_key(KeyEvent::from(KeyCode::Char('q'))).await;
+        assert!(app.should_quit());
+    }
+
+    #[tokio::test]
+    async fn test_supervision_answered_by_number_key() {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let supervision = Arc::new(Mutex::new(SupervisionSystem::new()));
+
+        let calm = Agent::new(AgentRole::Implementer, 1);
+        let desperate = Agent::new(AgentRole::Debugger, 2);
+        {
+            let mut system = supervision.lock().await;
+            system.check_supervision_need(&calm, "Stuck on the borrow checker").await.unwrap();
+            system.check_supervision_need(&desperate, "I'm stuck and confused, need help").await.unwrap();
+        }
+
+        let mut app = App::new(workshop, supervision.clone(), TuiConfig::default()).await;
+        assert!(app.is_supervision_visible());
+
+        // Most urgent request is listed and selected first
+        let first = app.selected_supervision().unwrap();
+        assert_eq!(first.request.agent_id, desperate.id);
+        assert_eq!(first.request.urgency, SupervisionUrgency::Critical);
+        assert!(!first.is_expired());
+
+        app.on_key(KeyEvent::from(KeyCode::Char('1'))).await;
+        assert!(supervision.lock().await.get_active_request(&desperate.id).is_none());
+        assert!(app.status_message().unwrap().contains("ProvideGuidance"));
+
+        // Selection falls through to the remaining request
+        assert_eq!(app.selected_supervision().unwrap().request.agent_id, calm.id);
+
+        // Unknown options are reported and leave the request open
+        app.on_key(KeyEvent::from(KeyCode::Char('9'))).await;
+        assert!(app.status_message().unwrap().contains("Invalid supervision option"));
+        assert!(supervision.lock().await.get_active_request(&calm.id).is_some());
+
+        // Esc hides the dialogue instead of quitting
+        app.on_key(KeyEvent::from(KeyCode::Esc)).await;
+        assert!(!app.should_quit());
+    }
+
+    #[tokio::test]
+    async fn test_attached_dashboard_answers_through_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = DaemonConfig {
+            socket_path: dir.path().join("dfcoderd.sock"),
+            ..DaemonConfig::default()
+        };
+        let socket_path = config.socket_path.clone();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(Daemon::new(config).await.run(async { let _ = stopped.await; }));
+
+        let mut client = connect_with_retry(&socket_path).await;
+        let agent_id = match client.request(DaemonRequest::RegisterAgent { role: AgentRole::Debugger, pane_id: 2 }).await.unwrap() {
+            DaemonResponse::AgentRegistered { agent_id } => agent_id,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        client.request(DaemonRequest::ReportOutput {
+            agent_id: agent_id.clone(),
+            output: "I'm stuck and confused, need help".to_string(),
+        }).await.unwrap();
+
+        let dashboard = DaemonClient::connect_with_handshake(&socket_path, "test").await.unwrap();
+        let mut app = App::attach(dashboard, TuiConfig::default()).await.unwrap();
+        assert_eq!(app.snapshot().status.total_agents, 1);
+        assert_eq!(app.snapshot().agents_for(&AgentRole::Debugger)[0].id, agent_id);
+        assert_eq!(app.selected_supervision().unwrap().request.agent_id, agent_id);
+
+        // The answer goes to the daemon, not to a workshop inside the dashboard
+        app.on_key(KeyEvent::from(KeyCode::Char('1'))).await;
+        assert!(app.status_message().unwrap().contains("ProvideGuidance"));
+        assert!(!app.is_supervision_visible());
+        match client.request(DaemonRequest::ListSupervision).await.unwrap() {
+            DaemonResponse::Supervision { requests } => assert!(requests.is_empty()),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // Changes made by other clients show up on the next refresh
+        client.request(DaemonRequest::RegisterAgent { role: AgentRole::Tester, pane_id: 3 }).await.unwrap();
+        app.refresh().await;
+        assert_eq!(app.snapshot().agents_for(&AgentRole::Tester).len(), 1);
+
+        stop.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    async fn connect_with_retry(path: &std::path::Path) -> DaemonClient {
+        for _ in 0..100 {
+            if let Ok(client) = DaemonClient::connect_with_handshake(path, "test").await {
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("daemon never started listening");
+    }
+}
@@ -0,0 +1,75 @@
+//! Terminal input and tick events
+
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Events consumed by the dashboard loop
+#[derive(Debug, Clone)]
+pub enum AppEvent {
+    /// Periodic refresh tick
+    Tick,
+    /// Key pressed
+    Key(KeyEvent),
+    /// Terminal resized
+    Resize(u16, u16),
+    /// dfcoderd reported a change to the workshop
+    WorkshopChanged,
+}
+
+/// Reads crossterm events on a background thread and interleaves ticks
+pub struct EventHandler {
+    receiver: mpsc::UnboundedReceiver<AppEvent>,
+}
+
+impl EventHandler {
+    /// Start polling the terminal, emitting a tick every `tick_rate`
+    pub fn new(tick_rate: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        std::thread::spawn(move || {
+            let mut last_tick = Instant::now();
+            loop {
+                let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+
+                match event::poll(timeout) {
+                    Ok(true) => {
+                        let app_event = match event::read() {
+                            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => Some(AppEvent::Key(key)),
+                            Ok(Event::Resize(width, height)) => Some(AppEvent::Resize(width, height)),
+                            Ok(_) => None,
+                            Err(e) => {
+                                tracing::error!("Failed to read terminal event: {}", e);
+                                break;
+                            }
+                        };
+                        if let Some(app_event) = app_event {
+                            if sender.send(app_event).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::error!("Failed to poll terminal events: {}", e);
+                        break;
+                    }
+                }
+
+                if last_tick.elapsed() >= tick_rate {
+                    if sender.send(AppEvent::Tick).is_err() {
+                        break;
+                    }
+                    last_tick = Instant::now();
+                }
+            }
+        });
+
+        Self { receiver }
+    }
+
+    /// Wait for the next event
+    pub async fn next(&mut self) -> Option<AppEvent> {
+        self.receiver.recv().await
+    }
+}
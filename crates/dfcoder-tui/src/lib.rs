@@ -1,6 +1,151 @@
+//! Terminal user interface for DFCoder
+//!
+//! Renders the workshop roster, task queue and metrics as a full-screen
+//! dashboard that refreshes on a tick, with a supervision dialogue for
+//! answering agents that need help. When dfcoderd is running the dashboard
+//! attaches to it over its socket; otherwise it shows a workshop of its own.
+
+pub mod app;
+pub mod event;
+pub mod ui;
+
+pub use app::*;
+pub use event::*;
+
 use anyhow::Result;
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use dfcoder_core::{SupervisionSystem, WorkshopManager};
+use dfcoder_daemon::{default_socket_path, DaemonClient, DaemonError};
+use dfcoder_types::protocol::{DaemonRequest, DaemonResponse};
+use ratatui::backend::CrosstermBackend;
+use ratatui::Terminal;
+use std::io::Stdout;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
 
+/// Name the dashboard gives dfcoderd in its handshake
+const CLIENT_NAME: &str = "dfcoder-tui";
+
+/// Attach to dfcoderd if its socket exists, otherwise run against a fresh, empty workshop
 pub async fn run() -> Result<()> {
-    println!("DFCoder TUI - Implementation in progress!");
+    let socket_path = default_socket_path();
+    if socket_path.exists() {
+        match connect(&socket_path).await {
+            Ok((client, changes)) => {
+                let app = App::attach(client, TuiConfig::default()).await?;
+                return drive(app, Some(changes)).await;
+            }
+            Err(e) => tracing::warn!("Not attaching to {}: {}", socket_path.display(), e),
+        }
+    }
+    run_local().await
+}
+
+/// Run the dashboard against a fresh, empty workshop of its own
+pub async fn run_local() -> Result<()> {
+    let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+    let supervision = Arc::new(Mutex::new(SupervisionSystem::new()));
+    run_with(workshop, supervision, TuiConfig::default()).await
+}
+
+/// Run the dashboard against the dfcoderd listening on `socket_path`
+pub async fn attach(socket_path: &Path, config: TuiConfig) -> Result<()> {
+    let (client, changes) = connect(socket_path).await?;
+    let app = App::attach(client, config).await?;
+    drive(app, Some(changes)).await
+}
+
+/// Run the dashboard against a workshop and supervision system owned by the caller
+pub async fn run_with(
+    workshop: SharedWorkshop,
+    supervision: SharedSupervision,
+    config: TuiConfig,
+) -> Result<()> {
+    let app = App::new(workshop, supervision, config).await;
+    drive(app, None).await
+}
+
+/// Open a request connection to dfcoderd and a subscribed one that signals each change
+async fn connect(socket_path: &Path) -> Result<(DaemonClient, mpsc::UnboundedReceiver<()>), DaemonError> {
+    let client = DaemonClient::connect_with_handshake(socket_path, CLIENT_NAME).await?;
+    let mut subscription = DaemonClient::connect_with_handshake(socket_path, CLIENT_NAME).await?;
+    match subscription.request(DaemonRequest::Subscribe).await? {
+        DaemonResponse::Subscribed => {}
+        other => return Err(DaemonError::Protocol(format!("unexpected subscribe response: {:?}", other))),
+    }
+
+    let (sender, changes) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            match subscription.next_event().await {
+                Ok(_) => {
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Lost the dfcoderd event stream: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok((client, changes))
+}
+
+/// Draw and handle events until the user quits
+async fn drive(mut app: App, mut changes: Option<mpsc::UnboundedReceiver<()>>) -> Result<()> {
+    let mut terminal = TerminalGuard::enter()?;
+    let mut events = EventHandler::new(app.config().tick_rate);
+
+    while !app.should_quit() {
+        terminal.terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        let event = match changes.as_mut() {
+            Some(changes) => tokio::select! {
+                event = events.next() => event,
+                Some(()) = changes.recv() => {
+                    // One refresh covers a burst of events
+                    while changes.try_recv().is_ok() {}
+                    Some(AppEvent::WorkshopChanged)
+                }
+            },
+            None => events.next().await,
+        };
+
+        match event {
+            Some(AppEvent::Tick | AppEvent::WorkshopChanged) => app.on_tick().await,
+            Some(AppEvent::Key(key)) => app.on_key(key).await,
+            Some(AppEvent::Resize(_, _)) => {}
+            None => break,
+        }
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Puts the terminal into raw/alternate-screen mode and restores it on drop
+struct TerminalGuard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        Ok(Self { terminal })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
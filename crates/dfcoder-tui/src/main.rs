@@ -2,11 +2,5 @@ use anyhow::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("DFCoder TUI - Implementation in progress!");
-    Ok(())
-}
-
-pub async fn run() -> Result<()> {
-    println!("TUI implementation placeholder");
-    Ok(())
+    dfcoder_tui::run().await
 }
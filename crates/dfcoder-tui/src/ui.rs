@@ -0,0 +1,418 @@
+//! Dwarf-Fortress-style rendering of the workshop
+
+use crate::app::*;
+use dfcoder_core::*;
+use dfcoder_types::protocol::*;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, List, ListItem, Paragraph, Wrap};
+use ratatui::Frame;
+use std::time::Duration;
+
+/// Glyph and colour used for an agent status
+pub fn status_glyph(status: &AgentStatus) -> (&'static str, Color) {
+    match status {
+        AgentStatus::Idle => ("z", Color::DarkGray),
+        AgentStatus::Working => ("☼", Color::Green),
+        AgentStatus::Stuck => ("?", Color::Yellow),
+        AgentStatus::NeedsSupervision => ("!", Color::LightMagenta),
+        AgentStatus::Error => ("X", Color::Red),
+    }
+}
+
+/// Glyph and colour used for a task priority
+pub fn priority_glyph(priority: &TaskPriority) -> (&'static str, Color) {
+    match priority {
+        TaskPriority::Low => ("·", Color::DarkGray),
+        TaskPriority::Normal => ("○", Color::White),
+        TaskPriority::High => ("◘", Color::Yellow),
+        TaskPriority::Critical => ("‼", Color::Red),
+    }
+}
+
+/// Glyph and colour used for a supervision urgency
+pub fn urgency_glyph(urgency: &SupervisionUrgency) -> (&'static str, Color) {
+    match urgency {
+        SupervisionUrgency::Low => ("·", Color::DarkGray),
+        SupervisionUrgency::Medium => ("○", Color::White),
+        SupervisionUrgency::High => ("◘", Color::Yellow),
+        SupervisionUrgency::Critical => ("‼", Color::Red),
+    }
+}
+
+/// Draw the whole dashboard
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(8),
+        Constraint::Length(7),
+        Constraint::Length(1),
+    ])
+    .split(frame.area());
+
+    draw_header(frame, app, rows[0]);
+
+    let columns = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[1]);
+    draw_roster(frame, app.snapshot(), columns[0]);
+    draw_queue(frame, app.snapshot(), columns[1]);
+
+    draw_metrics(frame, app.snapshot(), rows[2]);
+    draw_footer(frame, app, rows[3]);
+
+    if app.is_supervision_visible() {
+        draw_supervision(frame, app, rows[1]);
+    }
+}
+
+fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
+    let status = &app.snapshot().status;
+    let header = Line::from(vec![
+        Span::styled(
+            format!(" {} ", app.config().title),
+            Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!(
+            "  dwarves: {}  working: {}  queued: {}",
+            status.total_agents, status.active_agents, status.queue_length
+        )),
+    ]);
+    frame.render_widget(Paragraph::new(header), area);
+}
+
+fn draw_roster(frame: &mut Frame, snapshot: &WorkshopSnapshot, area: Rect) {
+    let rows = Layout::vertical([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).split(area);
+    let mut cells = Vec::new();
+    for row in rows.iter() {
+        let halves = Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).split(*row);
+        cells.extend(halves.iter().copied());
+    }
+
+    for (role, cell) in ROLE_ORDER.iter().zip(cells) {
+        draw_role_panel(frame, snapshot, role, cell);
+    }
+}
+
+fn draw_role_panel(frame: &mut Frame, snapshot: &WorkshopSnapshot, role: &AgentRole, area: Rect) {
+    let agents = snapshot.agents_for(role);
+    let active = snapshot.status.active_per_role.get(role).copied().unwrap_or(0);
+    let capacity = snapshot.status.capacity_per_role.get(role).copied().unwrap_or(0);
+
+    let items: Vec<ListItem> = if agents.is_empty() {
+        vec![ListItem::new(Span::styled("  no dwarves", Style::default().fg(Color::DarkGray)))]
+    } else {
+        agents.iter().map(agent_item).collect()
+    };
+
+    let block = Block::bordered().title(format!(" {}s {}/{} ", role, active, capacity));
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn agent_item(agent: &AgentSummary) -> ListItem<'static> {
+    let (glyph, color) = status_glyph(&agent.status);
+    let task = agent.current_task.as_deref().map(short_id).unwrap_or("-");
+
+    ListItem::new(Line::from(vec![
+        Span::styled(format!("{} ", glyph), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        Span::raw(format!("pane {:<3} {:<8} ", agent.pane_id, short_id(&agent.id))),
+        Span::styled(format!("task {}", task), Style::default().fg(Color::Gray)),
+    ]))
+}
+
+fn draw_queue(frame: &mut Frame, snapshot: &WorkshopSnapshot, area: Rect) {
+    let items: Vec<ListItem> = if snapshot.queue.is_empty() {
+        vec![ListItem::new(Span::styled("  nothing queued", Style::default().fg(Color::DarkGray)))]
+    } else {
+        snapshot.queue.iter().map(|task| {
+            let (glyph, color) = priority_glyph(&task.priority);
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", glyph), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{:<11} ", task.required_role.to_string()), Style::default().fg(Color::Cyan)),
+                Span::raw(task.title.clone()),
+            ]))
+        }).collect()
+    };
+
+    let block = Block::bordered().title(format!(" Task Queue ({}) ", snapshot.queue.len()));
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn draw_metrics(frame: &mut Frame, snapshot: &WorkshopSnapshot, area: Rect) {
+    let metrics = &snapshot.status;
+    let columns = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area);
+
+    let totals = vec![
+        Line::from(format!(
+            "processed {}  completed {}  failed {}  retried {}",
+            metrics.tasks_processed, metrics.tasks_completed, metrics.tasks_failed, metrics.tasks_retried
+        )),
+        Line::from(format!(
+            "success {:.0}%  throughput {:.1}/h  cost/task {:.2}",
+            metrics.success_rate * 100.0, metrics.throughput, metrics.cost_per_task
+        )),
+        Line::from(match &metrics.bottleneck_role {
+            Some(role) => Span::styled(format!("bottleneck: {}", role), Style::default().fg(Color::Yellow)),
+            None => Span::styled("bottleneck: none", Style::default().fg(Color::DarkGray)),
+        }),
+    ];
+    frame.render_widget(
+        Paragraph::new(totals).block(Block::bordered().title(" Workshop Metrics ")),
+        columns[0],
+    );
+
+    let utilization: Vec<Line> = ROLE_ORDER.iter().map(|role| {
+        let value = metrics.agent_utilization.get(role).copied().unwrap_or(0.0);
+        Line::from(vec![
+            Span::raw(format!("{:<11} ", role.to_string())),
+            Span::styled(utilization_bar(value, 10), Style::default().fg(utilization_color(value))),
+            Span::raw(format!(" {:>3.0}%", value * 100.0)),
+        ])
+    }).collect();
+    frame.render_widget(
+        Paragraph::new(utilization).block(Block::bordered().title(" Utilization ")),
+        columns[1],
+    );
+}
+
+/// Supervision dialogue drawn over the roster while requests are open
+fn draw_supervision(frame: &mut Frame, app: &App, area: Rect) {
+    let snapshot = app.snapshot();
+    let Some(selected) = app.selected_supervision() else {
+        return;
+    };
+
+    let popup = centered(area, 90, 90);
+    frame.render_widget(Clear, popup);
+
+    let (_, title_color) = urgency_glyph(&selected.request.urgency);
+    let block = Block::bordered()
+        .title(format!(
+            " Supervision {}/{} ",
+            app.selected_index().map(|i| i + 1).unwrap_or(0),
+            snapshot.supervision.len()
+        ))
+        .border_style(Style::default().fg(title_color));
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    let columns = Layout::horizontal([Constraint::Length(28), Constraint::Min(20)]).split(inner);
+
+    let requests: Vec<ListItem> = snapshot.supervision.iter().enumerate().map(|(index, pending)| {
+        let (glyph, color) = urgency_glyph(&pending.request.urgency);
+        let mut style = Style::default();
+        if Some(index) == app.selected_index() {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{} ", glyph), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{:<8} ", short_id(&pending.request.agent_id))),
+            countdown_span(pending),
+        ])).style(style)
+    }).collect();
+    frame.render_widget(List::new(requests).block(Block::bordered().title(" Requests ")), columns[0]);
+
+    let details = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(selected.request.options.len() as u16 + 2),
+        Constraint::Length(1),
+    ])
+    .split(columns[1]);
+
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled(
+                format!("{:?} ", selected.request.urgency),
+                Style::default().fg(title_color).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("agent {}  ", short_id(&selected.request.agent_id))),
+            countdown_span(selected),
+        ])),
+        details[0],
+    );
+
+    frame.render_widget(
+        Paragraph::new(selected.request.context.clone())
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(" Context ")),
+        details[1],
+    );
+
+    let options: Vec<ListItem> = selected.request.options.iter().map(|option| {
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("[{}] ", option.id), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{} {}", option.icon, option.text)),
+            Span::styled(format!("  ~{}", format_duration(Duration::from_secs(option.estimated_time_secs))), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+    frame.render_widget(List::new(options).block(Block::bordered().title(" Options ")), details[2]);
+
+    frame.render_widget(
+        Paragraph::new(Span::styled(
+            "1-9 choose  ↑/↓ select request  Esc hide",
+            Style::default().fg(Color::DarkGray),
+        )),
+        details[3],
+    );
+}
+
+fn countdown_span(pending: &PendingSupervision) -> Span<'static> {
+    if pending.is_expired() {
+        Span::styled("expired", Style::default().fg(Color::Red))
+    } else {
+        let color = if pending.remaining < Duration::from_secs(10) { Color::Red } else { Color::Gray };
+        Span::styled(format!("{}s left", pending.remaining.as_secs()), Style::default().fg(color))
+    }
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let refresh = if app.is_paused() {
+        "paused".to_string()
+    } else {
+        format!("refreshed {}ms ago", app.since_refresh().as_millis())
+    };
+    let pending = app.snapshot().supervision.len();
+    let mut footer = vec![
+        Span::styled(" q", Style::default().fg(Color::Yellow)),
+        Span::raw(" quit  "),
+        Span::styled("r", Style::default().fg(Color::Yellow)),
+        Span::raw(" refresh  "),
+        Span::styled("p", Style::default().fg(Color::Yellow)),
+        Span::raw(" pause  "),
+        Span::styled("s", Style::default().fg(Color::Yellow)),
+        Span::raw(format!(" supervision ({})  ", pending)),
+        Span::styled(refresh, Style::default().fg(Color::DarkGray)),
+    ];
+    if let Some(message) = app.status_message() {
+        footer.push(Span::styled(format!("  {}", message), Style::default().fg(Color::Cyan)));
+    }
+    frame.render_widget(Paragraph::new(Line::from(footer)), area);
+}
+
+/// Rectangle covering the given percentages of `area`, centred
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Compact human-readable duration such as `5m` or `45s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Text bar filled in proportion to a 0.0-1.0 value
+fn utilization_bar(value: f32, width: usize) -> String {
+    let filled = ((value.clamp(0.0, 1.0) * width as f32).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+fn utilization_color(value: f32) -> Color {
+    if value >= 1.0 {
+        Color::Red
+    } else if value >= 0.5 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+/// First eight characters of an id, enough to tell agents apart
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn buffer_text(terminal: &Terminal<TestBackend>) -> String {
+        let buffer = terminal.backend().buffer();
+        buffer.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_renders_roster_and_queue() {
+        let mut workshop = WorkshopManager::new();
+        workshop.register_agent(Agent::new(AgentRole::Implementer, 1)).unwrap();
+        workshop.register_agent(Agent::new(AgentRole::Debugger, 2)).unwrap();
+        workshop.assign_task(Task::new(
+            "Fix login".to_string(),
+            "Fix the login bug".to_string(),
+            AgentRole::Debugger,
+            TaskPriority::High,
+        )).unwrap();
+        workshop.queue_task(Task::new(
+            "Write parser".to_string(),
+            "Implement the parser".to_string(),
+            AgentRole::Implementer,
+            TaskPriority::Critical,
+        )).unwrap();
+
+        let supervision = Arc::new(Mutex::new(SupervisionSystem::new()));
+        let app = App::new(Arc::new(Mutex::new(workshop)), supervision, TuiConfig::default()).await;
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+
+        let text = buffer_text(&terminal);
+        assert!(text.contains("DFCoder Workshop"));
+        assert!(text.contains("Implementers 0/3"));
+        assert!(text.contains("Debuggers 1/2"));
+        assert!(text.contains("Task Queue (1)"));
+        assert!(text.contains("Write parser"));
+        assert!(text.contains("‼"));
+        assert!(text.contains("☼"));
+        assert!(text.contains("no dwarves"));
+    }
+
+    #[tokio::test]
+    async fn test_supervision_dialogue_renders_options_and_countdown() {
+        let workshop = Arc::new(Mutex::new(WorkshopManager::new()));
+        let supervision = Arc::new(Mutex::new(SupervisionSystem::new()));
+        let agent = Agent::new(AgentRole::Implementer, 1);
+        supervision.lock().await
+            .check_supervision_need(&agent, "I'm stuck and confused, need help")
+            .await
+            .unwrap();
+
+        let app = App::new(workshop, supervision, TuiConfig::default()).await;
+        let mut terminal = Terminal::new(TestBackend::new(140, 40)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+
+        let text = buffer_text(&terminal);
+        assert!(text.contains("Supervision 1/1"));
+        assert!(text.contains("Critical"));
+        assert!(text.contains("[1]"));
+        assert!(text.contains("Provide step-by-step guidance"));
+        assert!(text.contains("~5m"));
+        assert!(text.contains("s left"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(300)), "5m");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+    }
+
+    #[test]
+    fn test_utilization_bar() {
+        assert_eq!(utilization_bar(0.0, 4), "░░░░");
+        assert_eq!(utilization_bar(0.5, 4), "██░░");
+        assert_eq!(utilization_bar(1.5, 4), "████");
+    }
+}
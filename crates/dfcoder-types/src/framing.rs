@@ -0,0 +1,122 @@
+//! Splitting streamed HTTP bodies into event payloads
+//!
+//! Model providers stream replies, and MCP servers push messages, as
+//! server-sent events or JSON lines. The body may be chunked anywhere, even
+//! inside a character, so payloads are only decoded once their lines are whole.
+
+/// How a stream frames its events on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFraming {
+    /// Server-sent events with a JSON payload in each `data` field
+    ServerSentEvents,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Splits a response body into event payloads, however it was chunked
+#[derive(Debug)]
+pub struct FrameDecoder {
+    framing: StreamFraming,
+    /// Bytes of the line being received
+    line: Vec<u8>,
+    /// `data` lines of the server-sent event being received
+    data: Option<String>,
+}
+
+impl FrameDecoder {
+    pub fn new(framing: StreamFraming) -> Self {
+        Self {
+            framing,
+            line: Vec::new(),
+            data: None,
+        }
+    }
+
+    /// Feed the next chunk of the body, returning the payloads it completed
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut payloads = Vec::new();
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let mut line = std::mem::take(&mut self.line);
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            // Lines are only decoded once whole, so characters split across
+            // chunks survive
+            let line = String::from_utf8_lossy(&line);
+            payloads.extend(self.line_complete(&line));
+        }
+        payloads
+    }
+
+    /// The payload left over when the body ends without a final line break
+    pub fn finish(&mut self) -> Option<String> {
+        if !self.line.is_empty() {
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+            if let Some(payload) = self.line_complete(&line) {
+                return Some(payload);
+            }
+        }
+        self.data.take()
+    }
+
+    fn line_complete(&mut self, line: &str) -> Option<String> {
+        match self.framing {
+            StreamFraming::JsonLines => Some(line.trim()).filter(|line| !line.is_empty()).map(str::to_string),
+            StreamFraming::ServerSentEvents => {
+                if line.is_empty() {
+                    return self.data.take();
+                }
+                // Comments start with a colon; `event`, `id` and `retry` are not needed
+                if let Some(value) = line.strip_prefix("data:") {
+                    let value = value.strip_prefix(' ').unwrap_or(value);
+                    match &mut self.data {
+                        Some(data) => {
+                            data.push('\n');
+                            data.push_str(value);
+                        }
+                        None => self.data = Some(value.to_string()),
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_payloads_survive_any_chunking() {
+        let body = "event: ping\ndata: {\"a\":1}\n\n: comment\r\ndata: {\"b\":\r\ndata: \"é\"}\r\n\r\ndata: [DONE]\n\n";
+        let expected = vec!["{\"a\":1}", "{\"b\":\n\"é\"}", "[DONE]"];
+
+        for chunk_size in 1..body.len() {
+            let mut decoder = FrameDecoder::new(StreamFraming::ServerSentEvents);
+            let mut payloads: Vec<String> = body.as_bytes()
+                .chunks(chunk_size)
+                .flat_map(|chunk| decoder.feed(chunk))
+                .collect();
+            payloads.extend(decoder.finish());
+            assert_eq!(payloads, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_payloads_are_flushed() {
+        let mut decoder = FrameDecoder::new(StreamFraming::ServerSentEvents);
+        assert!(decoder.feed(b"data: {\"last\":true}").is_empty());
+        assert_eq!(decoder.finish().as_deref(), Some("{\"last\":true}"));
+
+        let mut decoder = FrameDecoder::new(StreamFraming::JsonLines);
+        assert_eq!(decoder.feed(b"{\"a\":1}\n\n{\"b\""), vec!["{\"a\":1}"]);
+        assert_eq!(decoder.feed(b":2}"), Vec::<String>::new());
+        assert_eq!(decoder.finish().as_deref(), Some("{\"b\":2}"));
+        assert_eq!(decoder.finish(), None);
+    }
+}
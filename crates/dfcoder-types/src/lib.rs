@@ -3,9 +3,204 @@
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-/// Agent status enumeration
+pub mod framing;
+pub mod protocol;
+
+/// Four clear agent roles that map to specific behaviors
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AgentRole {
+    /// Creates project structure and boilerplate
+    Scaffolder,
+    /// Writes feature code according to specifications
+    Implementer,
+    /// Finds and fixes bugs, improves code quality
+    Debugger,
+    /// Writes comprehensive test coverage
+    Tester,
+}
+
+impl std::fmt::Display for AgentRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentRole::Scaffolder => write!(f, "Scaffolder"),
+            AgentRole::Implementer => write!(f, "Implementer"),
+            AgentRole::Debugger => write!(f, "Debugger"),
+            AgentRole::Tester => write!(f, "Tester"),
+        }
+    }
+}
+
+/// Task priority levels
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TaskPriority {
+    Low,
+    Normal,
+    High,
+    Critical,
+}
+
+impl std::fmt::Display for TaskPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskPriority::Low => write!(f, "Low"),
+            TaskPriority::Normal => write!(f, "Normal"),
+            TaskPriority::High => write!(f, "High"),
+            TaskPriority::Critical => write!(f, "Critical"),
+        }
+    }
+}
+
+/// Which concurrency limit a capacity setting applies to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CapacityScope {
+    /// Every task running in the workshop
+    Global,
+    /// Tasks run by agents of one role
+    Role(AgentRole),
+    /// Tasks run by agents in one pane
+    Pane(u32),
+}
+
+impl std::fmt::Display for CapacityScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CapacityScope::Global => write!(f, "workshop"),
+            CapacityScope::Role(role) => write!(f, "role {}", role),
+            CapacityScope::Pane(pane_id) => write!(f, "pane {}", pane_id),
+        }
+    }
+}
+
+/// What happens to running tasks when a limit drops below the current load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ShrinkPolicy {
+    /// Running tasks finish; nothing new starts until the load is under the limit
+    #[default]
+    Drain,
+    /// The most recently started tasks over the limit are stopped and requeued
+    Preempt,
+}
+
+/// Policy for retrying failed operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Maximum number of retry attempts
+    pub max_attempts: u32,
+    /// Initial backoff duration
+    pub initial_backoff: Duration,
+    /// Multiplier for exponential backoff
+    pub backoff_multiplier: f32,
+    /// Maximum backoff duration
+    pub max_backoff: Duration,
+    /// Error types that should trigger retries
+    pub retry_on: Vec<ErrorType>,
+}
+
+/// Types of errors that can occur during task execution
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorType {
+    /// Network/API connection issues
+    NetworkError,
+    /// Rate limiting from external services
+    RateLimitError,
+    /// Authentication/authorization failures
+    AuthError,
+    /// Temporary resource unavailability
+    ResourceUnavailable,
+    /// Parse/format errors in output
+    ParseError,
+    /// Task complexity exceeds agent capability
+    ComplexityError,
+    /// Generic retryable error
+    Retryable,
+    /// Non-retryable logic errors
+    Fatal,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(30),
+            retry_on: vec![
+                ErrorType::NetworkError,
+                ErrorType::RateLimitError,
+                ErrorType::ResourceUnavailable,
+                ErrorType::Retryable,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a conservative retry policy
+    pub fn conservative() -> Self {
+        Self {
+            max_attempts: 2,
+            initial_backoff: Duration::from_secs(2),
+            backoff_multiplier: 3.0,
+            max_backoff: Duration::from_secs(60),
+            retry_on: vec![ErrorType::NetworkError, ErrorType::RateLimitError],
+        }
+    }
+
+    /// Create an aggressive retry policy
+    pub fn aggressive() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            backoff_multiplier: 1.5,
+            max_backoff: Duration::from_secs(15),
+            retry_on: vec![
+                ErrorType::NetworkError,
+                ErrorType::RateLimitError,
+                ErrorType::ResourceUnavailable,
+                ErrorType::ParseError,
+                ErrorType::Retryable,
+            ],
+        }
+    }
+
+    /// Calculate backoff duration for given attempt
+    pub fn calculate_backoff(&self, attempt: u32) -> Duration {
+        if attempt == 0 {
+            return Duration::from_secs(0);
+        }
+
+        let backoff_secs = self.initial_backoff.as_secs_f32() 
+            * self.backoff_multiplier.powi((attempt - 1) as i32);
+        
+        let backoff = Duration::from_secs_f32(backoff_secs);
+        std::cmp::min(backoff, self.max_backoff)
+    }
+
+    /// Check if an error type should trigger a retry
+    pub fn should_retry(&self, error: &ErrorType) -> bool {
+        self.retry_on.contains(error)
+    }
+}
+
+/// Urgency levels for supervision requests
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SupervisionUrgency {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// Unique identifier for agents
+pub type AgentId = String;
+
+/// Unique identifier for tasks
+pub type TaskId = String;
+
+/// Current status of an agent
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum AgentStatus {
+    #[default]
     Idle,
     Working,
     Stuck,
@@ -13,33 +208,24 @@ pub enum AgentStatus {
     Error,
 }
 
-impl Default for AgentStatus {
-    fn default() -> Self {
-        AgentStatus::Idle
-    }
-}
-
-/// Task status enumeration
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Task execution status
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum TaskStatus {
+    #[default]
     Pending,
+    /// Handed to an agent that has not started on it yet
+    Assigned,
     InProgress,
     Completed,
     Failed,
     Cancelled,
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        TaskStatus::Pending
-    }
-}
-
 /// Agent state representation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentState {
     pub status: AgentStatus,
-    pub current_task: Option<String>,
+    pub current_task: Option<TaskId>,
     #[serde(with = "chrono::serde::ts_seconds")]
     pub last_activity: chrono::DateTime<chrono::Utc>,
     pub tasks_completed: u32,
@@ -58,17 +244,27 @@ impl Default for AgentState {
     }
 }
 
-/// Agent performance metrics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AgentState {
+    /// A fresh state with the given status
+    pub fn with_status(status: AgentStatus) -> Self {
+        Self {
+            status,
+            ..Self::default()
+        }
+    }
+}
+
+/// Performance metrics for agents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AgentMetrics {
     pub tasks_completed: u32,
-    pub success_rate: f32,
+    pub tasks_failed: u32,
     #[serde(with = "duration_serde")]
-    pub average_task_duration: Duration,
-    pub error_count: u32,
-    pub errors_encountered: u32,
+    pub average_completion_time: Duration,
+    pub success_rate: f32,
     pub help_requests: u32,
-    pub response_time_ms: u64,
+    pub last_error: Option<String>,
 }
 
 pub mod duration_serde {
@@ -91,18 +287,80 @@ pub mod duration_serde {
     }
 }
 
-impl Default for AgentMetrics {
-    fn default() -> Self {
-        Self {
-            tasks_completed: 0,
-            success_rate: 0.0,
-            average_task_duration: Duration::from_secs(0),
-            error_count: 0,
-            errors_encountered: 0,
-            help_requests: 0,
-            response_time_ms: 0,
+/// Serialize a monotonic `Instant` as a wall-clock timestamp
+///
+/// Instants are mapped through a single anchor taken once per process, so the
+/// same instant always serializes to the same timestamp. Timestamps from a
+/// previous run map back to an instant the same distance in the past.
+pub mod instant_serde {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    fn anchor() -> (Instant, DateTime<Utc>) {
+        static ANCHOR: OnceLock<(Instant, DateTime<Utc>)> = OnceLock::new();
+        *ANCHOR.get_or_init(|| (Instant::now(), Utc::now()))
+    }
+
+    /// Wall-clock time of a monotonic instant
+    pub fn to_wall_clock(instant: Instant) -> DateTime<Utc> {
+        let (anchor_instant, anchor_wall) = anchor();
+        let offset = |duration| chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero());
+        if instant >= anchor_instant {
+            anchor_wall + offset(instant - anchor_instant)
+        } else {
+            anchor_wall - offset(anchor_instant - instant)
+        }
+    }
+
+    /// Monotonic instant for a wall-clock time, clamped to what the platform can represent
+    pub fn from_wall_clock(time: DateTime<Utc>) -> Instant {
+        let (anchor_instant, anchor_wall) = anchor();
+        match (time - anchor_wall).to_std() {
+            Ok(ahead) => anchor_instant.checked_add(ahead).unwrap_or(anchor_instant),
+            Err(_) => {
+                let behind = (anchor_wall - time).to_std().unwrap_or_default();
+                anchor_instant.checked_sub(behind).unwrap_or(anchor_instant)
+            }
         }
     }
+
+    pub fn serialize<S>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        to_wall_clock(*instant).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Instant, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        DateTime::<Utc>::deserialize(deserializer).map(from_wall_clock)
+    }
+}
+
+/// `instant_serde` for optional instants
+pub mod option_instant_serde {
+    use super::instant_serde::{from_wall_clock, to_wall_clock};
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Instant;
+
+    pub fn serialize<S>(instant: &Option<Instant>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        instant.map(to_wall_clock).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Instant>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<DateTime<Utc>>::deserialize(deserializer)?.map(from_wall_clock))
+    }
 }
 
 /// Pane state representation
@@ -151,6 +409,19 @@ pub enum SystemEvent {
         error_message: String,
         context: String,
     },
+    /// A concurrency limit changed; `None` means the scope has no explicit limit
+    CapacityChanged {
+        scope: CapacityScope,
+        old_limit: Option<usize>,
+        new_limit: Option<usize>,
+        load: usize,
+    },
+    /// A running task was stopped and requeued to bring a scope under its limit
+    TaskPreempted {
+        agent_id: String,
+        task_id: String,
+        scope: CapacityScope,
+    },
 }
 
 /// Result of task execution
@@ -160,6 +431,3 @@ pub enum TaskResult {
     Failed(String),
     Cancelled,
 }
-
-/// Agent identifier type
-pub type AgentId = String;
\ No newline at end of file
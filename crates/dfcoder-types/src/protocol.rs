@@ -0,0 +1,554 @@
+//! Local control protocol between dfcoderd and its clients
+//!
+//! # Framing
+//!
+//! Messages are JSON objects, one per line (`\n`-terminated, UTF-8), over the
+//! daemon's Unix socket. Clients send [`ClientMessage`]s; the daemon sends
+//! [`ServerMessage`]s.
+//!
+//! # Requests and responses
+//!
+//! Every client message carries a caller-chosen `id`. The daemon answers each
+//! request with exactly one `response` message echoing that id, so a client
+//! can keep several requests in flight:
+//!
+//! ```text
+//! -> {"id":1,"request":{"type":"hello","protocol_version":2,"client":"dfcoder-tui"}}
+//! <- {"kind":"response","id":1,"response":{"type":"hello","protocol_version":2,"server":"dfcoderd 0.1.0"}}
+//! -> {"id":2,"request":{"type":"register_agent","role":"Implementer","pane_id":3}}
+//! <- {"kind":"response","id":2,"response":{"type":"agent_registered","agent_id":"9f0c..."}}
+//! ```
+//!
+//! `hello` is optional but recommended: the daemon rejects a client whose
+//! [`PROTOCOL_VERSION`] differs with [`ErrorCode::UnsupportedVersion`].
+//! Failures come back as a `response` of type `error` with an [`ErrorCode`].
+//!
+//! # Events
+//!
+//! After a `subscribe` request the daemon also pushes `event` messages
+//! carrying a [`SystemEvent`] as they happen, interleaved with responses,
+//! until the client sends `unsubscribe` or disconnects:
+//!
+//! ```text
+//! <- {"kind":"event","event":{"SupervisionRequested":{"agent_id":"9f0c...","message":"...","context":"..."}}}
+//! ```
+//!
+//! # Spool
+//!
+//! Clients that cannot reach the socket, such as the WASI Zellij plugin, may
+//! append the same `ClientMessage` lines to the daemon's configured spool
+//! file. Spooled requests are handled normally but never answered.
+//!
+//! # Versioning
+//!
+//! Adding request, response or event variants, or optional fields, is
+//! backwards compatible. Removing or renaming anything bumps
+//! [`PROTOCOL_VERSION`].
+
+use crate::{AgentRole, AgentStatus, CapacityScope, ShrinkPolicy, SupervisionUrgency, SystemEvent, TaskPriority, TaskStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Version of the wire protocol spoken by this build
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// A request from a client, tagged with an id the response will echo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientMessage {
+    pub id: u64,
+    pub request: DaemonRequest,
+}
+
+/// Everything the daemon writes to a client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Answer to the client message with the same id
+    Response { id: u64, response: DaemonResponse },
+    /// Pushed to subscribed clients
+    Event { event: SystemEvent },
+}
+
+/// Requests a client can send to dfcoderd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Version handshake
+    Hello { protocol_version: u32, client: String },
+    /// Liveness check
+    Ping,
+    /// Current workshop status
+    GetStatus,
+    /// Every registered agent
+    ListAgents,
+    /// Tasks waiting in the queue, highest priority first
+    ListQueue,
+    /// Open supervision requests, most urgent first
+    ListSupervision,
+    /// Add an agent working in the given pane; answers with the existing agent
+    /// if one is already registered for that pane
+    RegisterAgent { role: AgentRole, pane_id: u32 },
+    /// Remove an idle agent
+    RemoveAgent { agent_id: String },
+    /// Add a task to the queue
+    QueueTask { task: TaskSpec },
+    /// Remove a task that has not been assigned yet
+    CancelTask { task_id: String },
+    /// The agent finished its current task, freeing it for the next one
+    CompleteTask { agent_id: String, task_id: String },
+    /// The agent gave up on its current task for good; queued tasks that
+    /// depend on it are cancelled
+    FailTask { agent_id: String, task_id: String, error: String },
+    /// Set or clear a concurrency limit; `policy` decides what happens to
+    /// running tasks if the scope is now over it
+    SetCapacity {
+        scope: CapacityScope,
+        limit: Option<usize>,
+        #[serde(default)]
+        policy: ShrinkPolicy,
+    },
+    /// Resolve an agent's open supervision request with one of its options
+    AnswerSupervision { agent_id: String, option_id: u32 },
+    /// New output captured from an agent's pane
+    ReportOutput { agent_id: String, output: String },
+    /// New output captured from a pane, for clients that only know pane ids
+    ReportPaneOutput { pane_id: u32, output: String },
+    /// Start receiving `SystemEvent`s on this connection
+    Subscribe,
+    /// Stop receiving events
+    Unsubscribe,
+}
+
+/// Responses sent back by dfcoderd, one per request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Hello { protocol_version: u32, server: String },
+    Pong,
+    Status { status: Box<WorkshopSummary> },
+    Agents { agents: Vec<AgentSummary> },
+    Queue { tasks: Vec<TaskSummary> },
+    Supervision { requests: Vec<SupervisionPrompt> },
+    AgentRegistered { agent_id: String },
+    AgentRemoved { agent_id: String },
+    TaskQueued { task_id: String },
+    /// `cascaded` lists queued tasks cancelled because they depended on it
+    TaskCancelled {
+        task_id: String,
+        #[serde(default)]
+        cascaded: Vec<String>,
+    },
+    TaskCompleted { task_id: String },
+    /// `cascaded` lists queued tasks cancelled because they depended on it
+    TaskFailed {
+        task_id: String,
+        #[serde(default)]
+        cascaded: Vec<String>,
+    },
+    /// `preempted` lists tasks put back on the queue to honour the new limit
+    CapacitySet {
+        scope: CapacityScope,
+        limit: Option<usize>,
+        load: usize,
+        #[serde(default)]
+        preempted: Vec<String>,
+    },
+    /// The chosen option, with a description of the action it triggers
+    SupervisionAnswered { agent_id: String, option_id: u32, action: String },
+    /// Output was classified; carries the supervision request it raised, if any
+    OutputAccepted { supervision: Option<SupervisionPrompt> },
+    Subscribed,
+    Unsubscribed,
+    Error { code: ErrorCode, message: String },
+}
+
+impl DaemonResponse {
+    /// Build an error response
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        DaemonResponse::Error { code, message: message.into() }
+    }
+}
+
+/// Machine-readable reason a request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The line was not a valid client message
+    InvalidRequest,
+    /// The client speaks a different protocol version
+    UnsupportedVersion,
+    AgentNotFound,
+    /// The agent has a task and cannot be removed
+    AgentBusy,
+    TaskNotFound,
+    /// The task has already been handed to an agent
+    TaskAlreadyAssigned,
+    /// The task's dependencies are unknown, failed or form a cycle
+    InvalidDependencies,
+    /// No open supervision request for the agent
+    NoSupervisionRequest,
+    /// The option id is not one of the request's options
+    InvalidOption,
+    Internal,
+}
+
+/// Description of a task to queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSpec {
+    pub title: String,
+    pub description: String,
+    pub required_role: AgentRole,
+    #[serde(default = "default_priority")]
+    pub priority: TaskPriority,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub estimated_duration_secs: Option<u64>,
+}
+
+fn default_priority() -> TaskPriority {
+    TaskPriority::Normal
+}
+
+/// An agent as seen by clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSummary {
+    pub id: String,
+    pub role: AgentRole,
+    pub pane_id: u32,
+    pub status: AgentStatus,
+    pub current_task: Option<String>,
+    pub tasks_completed: u32,
+    pub tasks_failed: u32,
+    pub help_requests: u32,
+}
+
+/// A task as seen by clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskSummary {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub required_role: AgentRole,
+    pub priority: TaskPriority,
+    pub status: TaskStatus,
+    pub assignee: Option<String>,
+    pub dependencies: Vec<String>,
+}
+
+/// An open supervision request as seen by clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisionPrompt {
+    pub agent_id: String,
+    pub context: String,
+    pub urgency: SupervisionUrgency,
+    pub options: Vec<SupervisionChoice>,
+    /// Seconds left before the request times out
+    pub remaining_secs: u64,
+}
+
+/// One numbered answer to a supervision request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisionChoice {
+    pub id: u32,
+    pub text: String,
+    pub icon: String,
+    pub estimated_time_secs: u64,
+}
+
+/// Workshop status as seen by clients
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkshopSummary {
+    pub total_agents: usize,
+    pub active_agents: usize,
+    pub queue_length: usize,
+    pub capacity_per_role: HashMap<AgentRole, usize>,
+    /// Cap on tasks running across the workshop, if any
+    #[serde(default)]
+    pub global_capacity: Option<usize>,
+    pub active_per_role: HashMap<AgentRole, usize>,
+    /// Tasks that have finished, successfully or not
+    #[serde(default)]
+    pub tasks_processed: u32,
+    pub tasks_completed: u32,
+    pub tasks_failed: u32,
+    pub tasks_retried: u32,
+    pub success_rate: f32,
+    pub throughput: f32,
+    /// Share of each role's capacity in use, from 0.0 to 1.0
+    #[serde(default)]
+    pub agent_utilization: HashMap<AgentRole, f32>,
+    pub bottleneck_role: Option<AgentRole>,
+    /// Classification model spend per completed task, in US dollars
+    #[serde(default)]
+    pub cost_per_task: f32,
+    /// Queued tasks mapped to the unfinished tasks they wait on
+    #[serde(default)]
+    pub blocked_by: HashMap<String, Vec<String>>,
+    /// Longest chain of unfinished tasks, in the order they have to run
+    #[serde(default)]
+    pub critical_path: Vec<String>,
+    /// Estimated length of the critical path
+    #[serde(default)]
+    pub critical_path_secs: u64,
+    /// Classification model spend so far, in US dollars
+    #[serde(default)]
+    pub model_cost_usd: f64,
+    /// Classification model tokens, input and output, used so far
+    #[serde(default)]
+    pub model_tokens: u64,
+    /// Predicted time left on each running task
+    #[serde(default)]
+    pub completion_etas: HashMap<String, CompletionEta>,
+}
+
+/// Predicted time left on a running task
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionEta {
+    /// Most likely time left
+    pub remaining_secs: u64,
+    /// Range expected to hold four outcomes in five
+    pub low_secs: u64,
+    pub high_secs: u64,
+    /// Completed tasks of the agent's the prediction draws on
+    pub samples: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentState, TaskResult};
+
+    /// Serialize, parse back, and check the second encoding is identical
+    fn assert_round_trip<T>(value: &T) -> String
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+    {
+        let encoded = serde_json::to_string(value).unwrap();
+        assert!(!encoded.contains('\n'), "messages must fit on one line");
+        let decoded: T = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), encoded);
+        encoded
+    }
+
+    fn sample_prompt() -> SupervisionPrompt {
+        SupervisionPrompt {
+            agent_id: "agent-1".to_string(),
+            context: "Stuck on the borrow checker".to_string(),
+            urgency: SupervisionUrgency::High,
+            options: vec![SupervisionChoice {
+                id: 1,
+                text: "Provide step-by-step guidance".to_string(),
+                icon: "🔍".to_string(),
+                estimated_time_secs: 300,
+            }],
+            remaining_secs: 25,
+        }
+    }
+
+    #[test]
+    fn test_requests_round_trip() {
+        let requests = vec![
+            DaemonRequest::Hello { protocol_version: PROTOCOL_VERSION, client: "test".to_string() },
+            DaemonRequest::Ping,
+            DaemonRequest::GetStatus,
+            DaemonRequest::ListAgents,
+            DaemonRequest::ListQueue,
+            DaemonRequest::ListSupervision,
+            DaemonRequest::RegisterAgent { role: AgentRole::Tester, pane_id: 4 },
+            DaemonRequest::RemoveAgent { agent_id: "agent-1".to_string() },
+            DaemonRequest::QueueTask {
+                task: TaskSpec {
+                    title: "Parser".to_string(),
+                    description: "Implement the parser".to_string(),
+                    required_role: AgentRole::Implementer,
+                    priority: TaskPriority::High,
+                    dependencies: vec!["task-0".to_string()],
+                    files: vec!["src/parser.rs".to_string()],
+                    estimated_duration_secs: Some(3600),
+                },
+            },
+            DaemonRequest::CancelTask { task_id: "task-1".to_string() },
+            DaemonRequest::CompleteTask { agent_id: "agent-1".to_string(), task_id: "task-1".to_string() },
+            DaemonRequest::FailTask {
+                agent_id: "agent-1".to_string(),
+                task_id: "task-1".to_string(),
+                error: "tests keep failing".to_string(),
+            },
+            DaemonRequest::SetCapacity {
+                scope: CapacityScope::Role(AgentRole::Tester),
+                limit: Some(1),
+                policy: ShrinkPolicy::Preempt,
+            },
+            DaemonRequest::SetCapacity { scope: CapacityScope::Global, limit: None, policy: ShrinkPolicy::Drain },
+            DaemonRequest::AnswerSupervision { agent_id: "agent-1".to_string(), option_id: 2 },
+            DaemonRequest::ReportOutput { agent_id: "agent-1".to_string(), output: "line one\nline two".to_string() },
+            DaemonRequest::ReportPaneOutput { pane_id: 4, output: "line three".to_string() },
+            DaemonRequest::Subscribe,
+            DaemonRequest::Unsubscribe,
+        ];
+
+        for (id, request) in requests.into_iter().enumerate() {
+            assert_round_trip(&ClientMessage { id: id as u64, request });
+        }
+    }
+
+    #[test]
+    fn test_responses_round_trip() {
+        let mut capacity = HashMap::new();
+        capacity.insert(AgentRole::Implementer, 3);
+
+        let responses = vec![
+            DaemonResponse::Hello { protocol_version: PROTOCOL_VERSION, server: "dfcoderd".to_string() },
+            DaemonResponse::Pong,
+            DaemonResponse::Status {
+                status: Box::new(WorkshopSummary {
+                    total_agents: 2,
+                    capacity_per_role: capacity,
+                    bottleneck_role: Some(AgentRole::Implementer),
+                    completion_etas: HashMap::from([(
+                        "task-1".to_string(),
+                        CompletionEta { remaining_secs: 1200, low_secs: 600, high_secs: 2400, samples: 3 },
+                    )]),
+                    ..WorkshopSummary::default()
+                }),
+            },
+            DaemonResponse::Agents {
+                agents: vec![AgentSummary {
+                    id: "agent-1".to_string(),
+                    role: AgentRole::Debugger,
+                    pane_id: 2,
+                    status: AgentStatus::Working,
+                    current_task: Some("task-1".to_string()),
+                    tasks_completed: 3,
+                    tasks_failed: 1,
+                    help_requests: 0,
+                }],
+            },
+            DaemonResponse::Queue {
+                tasks: vec![TaskSummary {
+                    id: "task-1".to_string(),
+                    title: "Parser".to_string(),
+                    description: "Implement the parser".to_string(),
+                    required_role: AgentRole::Implementer,
+                    priority: TaskPriority::Critical,
+                    status: TaskStatus::Pending,
+                    assignee: None,
+                    dependencies: Vec::new(),
+                }],
+            },
+            DaemonResponse::Supervision { requests: vec![sample_prompt()] },
+            DaemonResponse::AgentRegistered { agent_id: "agent-1".to_string() },
+            DaemonResponse::AgentRemoved { agent_id: "agent-1".to_string() },
+            DaemonResponse::TaskQueued { task_id: "task-1".to_string() },
+            DaemonResponse::TaskCancelled { task_id: "task-1".to_string(), cascaded: vec!["task-2".to_string()] },
+            DaemonResponse::TaskCompleted { task_id: "task-1".to_string() },
+            DaemonResponse::TaskFailed { task_id: "task-1".to_string(), cascaded: vec!["task-2".to_string()] },
+            DaemonResponse::CapacitySet {
+                scope: CapacityScope::Pane(3),
+                limit: Some(1),
+                load: 2,
+                preempted: vec!["task-3".to_string()],
+            },
+            DaemonResponse::SupervisionAnswered {
+                agent_id: "agent-1".to_string(),
+                option_id: 1,
+                action: "ProvideGuidance".to_string(),
+            },
+            DaemonResponse::OutputAccepted { supervision: Some(sample_prompt()) },
+            DaemonResponse::OutputAccepted { supervision: None },
+            DaemonResponse::Subscribed,
+            DaemonResponse::Unsubscribed,
+            DaemonResponse::error(ErrorCode::AgentNotFound, "Agent agent-9 not found"),
+        ];
+
+        for (id, response) in responses.into_iter().enumerate() {
+            assert_round_trip(&ServerMessage::Response { id: id as u64, response });
+        }
+    }
+
+    #[test]
+    fn test_events_round_trip() {
+        let events = vec![
+            SystemEvent::AgentStateChanged {
+                agent_id: "agent-1".to_string(),
+                old_state: AgentState::default(),
+                new_state: AgentState {
+                    status: AgentStatus::Working,
+                    current_task: Some("task-1".to_string()),
+                    ..AgentState::default()
+                },
+            },
+            SystemEvent::SupervisionRequested {
+                agent_id: "agent-1".to_string(),
+                message: "High".to_string(),
+                context: "Stuck".to_string(),
+            },
+            SystemEvent::TaskCompleted {
+                agent_id: "agent-1".to_string(),
+                task_id: "task-1".to_string(),
+                result: TaskResult::Failed("boom".to_string()),
+            },
+            SystemEvent::ErrorOccurred {
+                agent_id: "agent-1".to_string(),
+                error_message: "panic".to_string(),
+                context: "build".to_string(),
+            },
+            SystemEvent::CapacityChanged {
+                scope: CapacityScope::Global,
+                old_limit: None,
+                new_limit: Some(4),
+                load: 5,
+            },
+            SystemEvent::TaskPreempted {
+                agent_id: "agent-1".to_string(),
+                task_id: "task-1".to_string(),
+                scope: CapacityScope::Global,
+            },
+        ];
+
+        for event in events {
+            assert_round_trip(&ServerMessage::Event { event });
+        }
+    }
+
+    #[test]
+    fn test_wire_format_is_stable() {
+        let message = ClientMessage {
+            id: 7,
+            request: DaemonRequest::AnswerSupervision { agent_id: "a".to_string(), option_id: 2 },
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"id":7,"request":{"type":"answer_supervision","agent_id":"a","option_id":2}}"#
+        );
+
+        let message = ServerMessage::Response {
+            id: 7,
+            response: DaemonResponse::error(ErrorCode::InvalidOption, "Invalid supervision option: 9"),
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"kind":"response","id":7,"response":{"type":"error","code":"invalid_option","message":"Invalid supervision option: 9"}}"#
+        );
+    }
+
+    #[test]
+    fn test_task_spec_defaults() {
+        let message: ClientMessage = serde_json::from_str(
+            r#"{"id":1,"request":{"type":"queue_task","task":{"title":"T","description":"D","required_role":"Tester"}}}"#,
+        )
+        .unwrap();
+
+        match message.request {
+            DaemonRequest::QueueTask { task } => {
+                assert_eq!(task.priority, TaskPriority::Normal);
+                assert!(task.dependencies.is_empty());
+                assert_eq!(task.estimated_duration_secs, None);
+            }
+            other => panic!("unexpected request: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Pane capture diffing
+
+use dfcoder_types::PaneState;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// What changed between two captures of the same pane
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaneDelta {
+    /// Nothing new
+    Unchanged,
+    /// New output following what was already seen
+    Appended(String),
+    /// The capture no longer lines up with the previous one (cleared, rotated, redrawn)
+    Replaced(String),
+}
+
+impl PaneDelta {
+    /// The new text, if any
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            PaneDelta::Unchanged => None,
+            PaneDelta::Appended(text) | PaneDelta::Replaced(text) => Some(text),
+        }
+    }
+}
+
+/// Work out what is new in `current` given the previous capture
+///
+/// Captures are tails of a growing log, so the window start moves as output
+/// scrolls: the longest run of trailing lines in `previous` that opens
+/// `current` is treated as already seen.
+pub fn diff_capture(previous: &str, current: &str) -> PaneDelta {
+    if current == previous {
+        return PaneDelta::Unchanged;
+    }
+    if previous.is_empty() {
+        return PaneDelta::Appended(current.to_string());
+    }
+    if let Some(rest) = current.strip_prefix(previous) {
+        return PaneDelta::Appended(rest.to_string());
+    }
+
+    let previous_lines: Vec<&str> = previous.lines().collect();
+    let current_lines: Vec<&str> = current.lines().collect();
+    let max_overlap = previous_lines.len().min(current_lines.len());
+
+    for overlap in (1..=max_overlap).rev() {
+        if previous_lines[previous_lines.len() - overlap..] == current_lines[..overlap] {
+            let appended = &current_lines[overlap..];
+            if appended.is_empty() {
+                return PaneDelta::Unchanged;
+            }
+            let mut text = appended.join("\n");
+            if current.ends_with('\n') {
+                text.push('\n');
+            }
+            return PaneDelta::Appended(text);
+        }
+    }
+
+    PaneDelta::Replaced(current.to_string())
+}
+
+/// Rolling capture of one agent pane
+#[derive(Debug, Clone)]
+pub struct PaneCapture {
+    state: PaneState,
+    last_capture: String,
+    max_bytes: usize,
+}
+
+impl PaneCapture {
+    /// Start capturing a pane, keeping at most `max_bytes` of content
+    pub fn new(pane_id: u32, max_bytes: usize) -> Self {
+        Self {
+            state: PaneState { id: pane_id, ..PaneState::default() },
+            last_capture: String::new(),
+            max_bytes,
+        }
+    }
+
+    /// Record a fresh capture and return what changed since the last one
+    pub fn apply(&mut self, capture: &str, is_focused: bool) -> PaneDelta {
+        let delta = diff_capture(&self.last_capture, capture);
+        self.last_capture = capture.to_string();
+        self.state.is_active = is_focused;
+
+        if let Some(text) = delta.text() {
+            if matches!(delta, PaneDelta::Replaced(_)) {
+                self.state.content.clear();
+            }
+            self.state.content.push_str(text);
+            truncate_front(&mut self.state.content, self.max_bytes);
+            self.state.has_errors = contains_error(&self.state.content);
+            self.state.last_update = chrono::Utc::now();
+        }
+
+        delta
+    }
+
+    /// Current view of the pane
+    pub fn state(&self) -> &PaneState {
+        &self.state
+    }
+}
+
+/// Read at most the last `max_bytes` of a capture file, starting on a line boundary
+pub fn read_tail(path: &Path, max_bytes: usize) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(max_bytes as u64);
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+
+    if start > 0 {
+        // The first line was cut by the window, drop it
+        Ok(text.split_once('\n').map(|(_, rest)| rest.to_string()).unwrap_or_default())
+    } else {
+        Ok(text)
+    }
+}
+
+fn truncate_front(text: &mut String, max_bytes: usize) {
+    if text.len() <= max_bytes {
+        return;
+    }
+    let mut cut = text.len() - max_bytes;
+    while !text.is_char_boundary(cut) {
+        cut += 1;
+    }
+    // Prefer dropping whole lines, unless a single line is longer than the window
+    if text.as_bytes()[cut - 1] != b'\n' {
+        if let Some(newline) = text[cut..].find('\n') {
+            if cut + newline + 1 < text.len() {
+                cut += newline + 1;
+            }
+        }
+    }
+    text.drain(..cut);
+}
+
+fn contains_error(text: &str) -> bool {
+    text.lines().any(|line| {
+        let line = line.to_lowercase();
+        line.contains("error") || line.contains("panicked") || line.contains("failed")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_diff_appended_output() {
+        assert_eq!(diff_capture("", "hello\n"), PaneDelta::Appended("hello\n".to_string()));
+        assert_eq!(diff_capture("a\nb\n", "a\nb\nc\n"), PaneDelta::Appended("c\n".to_string()));
+        assert_eq!(diff_capture("a\nb\n", "a\nb\n"), PaneDelta::Unchanged);
+        // A partially written line that is later completed
+        assert_eq!(diff_capture("Compil", "Compiling dfcoder\n"), PaneDelta::Appended("ing dfcoder\n".to_string()));
+    }
+
+    #[test]
+    fn test_diff_scrolled_window() {
+        let previous = "one\ntwo\nthree\nfour\n";
+        let current = "three\nfour\nfive\nsix\n";
+        assert_eq!(diff_capture(previous, current), PaneDelta::Appended("five\nsix\n".to_string()));
+
+        // Window moved but nothing new arrived
+        assert_eq!(diff_capture("one\ntwo\nthree\n", "two\nthree\n"), PaneDelta::Unchanged);
+    }
+
+    #[test]
+    fn test_diff_replaced_output() {
+        assert_eq!(
+            diff_capture("old screen\n", "cleared\nfresh\n"),
+            PaneDelta::Replaced("cleared\nfresh\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_capture_tracks_state() {
+        let mut capture = PaneCapture::new(7, 40);
+
+        capture.apply("Writing parser\n", true);
+        assert_eq!(capture.state().id, 7);
+        assert!(capture.state().is_active);
+        assert!(!capture.state().has_errors);
+
+        capture.apply("Writing parser\nerror[E0382]: use of moved value\n", false);
+        assert!(capture.state().has_errors);
+        assert!(!capture.state().is_active);
+        assert_eq!(capture.state().content, "error[E0382]: use of moved value\n");
+
+        capture.apply("$ \n", false);
+        assert_eq!(capture.state().content, "$ \n");
+        assert!(!capture.state().has_errors);
+    }
+
+    #[test]
+    fn test_read_tail_starts_on_line_boundary() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "first line\nsecond line\nthird line\n").unwrap();
+
+        assert_eq!(read_tail(file.path(), 1024).unwrap(), "first line\nsecond line\nthird line\n");
+        assert_eq!(read_tail(file.path(), 15).unwrap(), "third line\n");
+    }
+}
@@ -1,14 +1,23 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Zellij plugin that watches agent panes and reports their output to dfcoderd
+//!
+//! Zellij plugins cannot read another pane's scrollback directly, so agent
+//! panes are expected to tee their output into `<capture_dir>/<pane id>.log`
+//! (for example `claude 2>&1 | tee .dfcoder/panes/$ZELLIJ_PANE_ID.log`).
+//! The plugin tails those files, diffs each capture against the previous one
+//! and, since WASI plugins cannot open Unix sockets, appends control protocol
+//! messages to a spool file that dfcoderd ingests.
+//!
+//! Everything except the thin [`plugin`] shell is plain Rust and runs natively.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod capture;
+pub mod mapping;
+pub mod outbox;
+pub mod watcher;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+#[cfg(target_arch = "wasm32")]
+pub mod plugin;
+
+pub use capture::*;
+pub use mapping::*;
+pub use outbox::*;
+pub use watcher::*;
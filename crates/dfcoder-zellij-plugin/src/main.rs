@@ -0,0 +1,10 @@
+#[cfg(target_arch = "wasm32")]
+use zellij_tile::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+register_plugin!(dfcoder_zellij_plugin::plugin::DfcoderPlugin);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("dfcoder-zellij-plugin is a Zellij plugin: build it for wasm32-wasi and load it from a layout");
+}
@@ -0,0 +1,169 @@
+//! Mapping Zellij panes to agents
+
+use dfcoder_types::AgentRole;
+use std::collections::BTreeMap;
+use zellij_tile::prelude::{PaneInfo, PaneManifest};
+
+/// A terminal pane running an agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentPane {
+    pub pane_id: u32,
+    pub role: AgentRole,
+    pub title: String,
+    pub is_focused: bool,
+}
+
+/// Panes that appeared or disappeared since the last manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PaneChanges {
+    pub added: Vec<AgentPane>,
+    pub removed: Vec<u32>,
+}
+
+/// Tracks which panes hold agents across manifest updates
+#[derive(Debug, Clone)]
+pub struct AgentPaneMap {
+    pattern: String,
+    panes: BTreeMap<u32, AgentPane>,
+}
+
+impl AgentPaneMap {
+    /// Treat terminal panes whose title or command contains `pattern` as agents
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into().to_lowercase(),
+            panes: BTreeMap::new(),
+        }
+    }
+
+    /// Apply a new manifest, returning the panes that were added or removed
+    pub fn update(&mut self, manifest: &PaneManifest) -> PaneChanges {
+        let current: BTreeMap<u32, AgentPane> = manifest
+            .panes
+            .values()
+            .flatten()
+            .filter(|pane| self.is_agent_pane(pane))
+            .map(|pane| (pane.id, agent_pane(pane)))
+            .collect();
+
+        let mut changes = PaneChanges::default();
+        for (id, pane) in &current {
+            if !self.panes.contains_key(id) {
+                changes.added.push(pane.clone());
+            }
+        }
+        changes.removed = self.panes.keys().filter(|id| !current.contains_key(id)).copied().collect();
+
+        self.panes = current;
+        changes
+    }
+
+    /// Look up an agent pane
+    pub fn get(&self, pane_id: u32) -> Option<&AgentPane> {
+        self.panes.get(&pane_id)
+    }
+
+    /// All agent panes, ordered by pane id
+    pub fn panes(&self) -> impl Iterator<Item = &AgentPane> {
+        self.panes.values()
+    }
+
+    fn is_agent_pane(&self, pane: &PaneInfo) -> bool {
+        if pane.is_plugin || pane.exited {
+            return false;
+        }
+        let command = pane.terminal_command.as_deref().unwrap_or("");
+        pane.title.to_lowercase().contains(&self.pattern) || command.to_lowercase().contains(&self.pattern)
+    }
+}
+
+impl Default for AgentPaneMap {
+    fn default() -> Self {
+        Self::new("claude")
+    }
+}
+
+fn agent_pane(pane: &PaneInfo) -> AgentPane {
+    AgentPane {
+        pane_id: pane.id,
+        role: role_from_title(&pane.title).unwrap_or(AgentRole::Implementer),
+        title: pane.title.clone(),
+        is_focused: pane.is_focused,
+    }
+}
+
+/// Infer an agent's role from keywords in its pane title
+pub fn role_from_title(title: &str) -> Option<AgentRole> {
+    let title = title.to_lowercase();
+    if title.contains("scaffold") {
+        Some(AgentRole::Scaffolder)
+    } else if title.contains("debug") {
+        Some(AgentRole::Debugger)
+    } else if title.contains("test") {
+        Some(AgentRole::Tester)
+    } else if title.contains("implement") {
+        Some(AgentRole::Implementer)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn pane(id: u32, title: &str) -> PaneInfo {
+        PaneInfo {
+            id,
+            title: title.to_string(),
+            ..PaneInfo::default()
+        }
+    }
+
+    fn manifest(panes: Vec<PaneInfo>) -> PaneManifest {
+        PaneManifest { panes: HashMap::from([(0, panes)]) }
+    }
+
+    #[test]
+    fn test_role_from_title() {
+        assert_eq!(role_from_title("claude: debugger"), Some(AgentRole::Debugger));
+        assert_eq!(role_from_title("Claude Tester"), Some(AgentRole::Tester));
+        assert_eq!(role_from_title("claude scaffold"), Some(AgentRole::Scaffolder));
+        assert_eq!(role_from_title("claude"), None);
+    }
+
+    #[test]
+    fn test_selects_agent_panes_only() {
+        let mut map = AgentPaneMap::default();
+        let mut plugin = pane(9, "claude status");
+        plugin.is_plugin = true;
+        let mut by_command = pane(3, "zsh");
+        by_command.terminal_command = Some("claude --resume".to_string());
+
+        let changes = map.update(&manifest(vec![pane(1, "Claude debugger"), pane(2, "htop"), plugin, by_command]));
+
+        let ids: Vec<u32> = changes.added.iter().map(|pane| pane.pane_id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(map.get(1).unwrap().role, AgentRole::Debugger);
+        assert_eq!(map.get(3).unwrap().role, AgentRole::Implementer);
+        assert!(map.get(2).is_none());
+    }
+
+    #[test]
+    fn test_reports_added_and_removed_panes() {
+        let mut map = AgentPaneMap::default();
+        map.update(&manifest(vec![pane(1, "claude"), pane(2, "claude tester")]));
+
+        let changes = map.update(&manifest(vec![pane(2, "claude tester"), pane(4, "claude")]));
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].pane_id, 4);
+        assert_eq!(changes.removed, vec![1]);
+
+        let mut exited = pane(2, "claude tester");
+        exited.exited = true;
+        let changes = map.update(&manifest(vec![exited, pane(4, "claude")]));
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed, vec![2]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Spool file the plugin writes control messages to
+
+use dfcoder_types::protocol::ClientMessage;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Appends JSON-lines `ClientMessage`s for dfcoderd to pick up
+///
+/// The spool is one-way: the daemon ingests it but never answers, so the
+/// plugin only sends requests that are safe to repeat.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    path: PathBuf,
+}
+
+impl Outbox {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append messages in a single write so the daemon never sees half a batch
+    pub fn send(&self, messages: &[ClientMessage]) -> std::io::Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let mut buffer = String::new();
+        for message in messages {
+            buffer.push_str(&serde_json::to_string(message)?);
+            buffer.push('\n');
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(buffer.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfcoder_types::protocol::DaemonRequest;
+
+    #[test]
+    fn test_send_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = Outbox::new(dir.path().join("spool.jsonl"));
+
+        outbox.send(&[ClientMessage { id: 1, request: DaemonRequest::Ping }]).unwrap();
+        outbox.send(&[]).unwrap();
+        outbox.send(&[ClientMessage { id: 2, request: DaemonRequest::ListAgents }]).unwrap();
+
+        let contents = std::fs::read_to_string(outbox.path()).unwrap();
+        let messages: Vec<ClientMessage> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].id, 2);
+    }
+}
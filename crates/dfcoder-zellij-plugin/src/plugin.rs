@@ -0,0 +1,92 @@
+//! Zellij host glue; only built for wasm32
+
+use crate::capture::read_tail;
+use crate::outbox::Outbox;
+use crate::watcher::{PaneWatcher, WatcherConfig};
+use dfcoder_types::protocol::ClientMessage;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use zellij_tile::prelude::*;
+
+#[derive(Default)]
+pub struct DfcoderPlugin {
+    watcher: Option<PaneWatcher>,
+    outbox: Option<Outbox>,
+    last_error: Option<String>,
+}
+
+impl ZellijPlugin for DfcoderPlugin {
+    fn load(&mut self, configuration: BTreeMap<String, String>) {
+        let config = WatcherConfig::from_configuration(&configuration);
+
+        request_permission(&[PermissionType::ReadApplicationState]);
+        subscribe(&[EventType::PaneUpdate, EventType::Timer]);
+        set_timeout(config.poll_interval.as_secs_f64());
+
+        self.outbox = Some(Outbox::new(config.spool_path.clone()));
+        self.watcher = Some(PaneWatcher::new(config));
+    }
+
+    fn update(&mut self, event: Event) -> bool {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return false;
+        };
+
+        let messages = match event {
+            Event::PaneUpdate(manifest) => watcher.on_pane_update(&manifest),
+            Event::Timer(elapsed) => {
+                set_timeout(watcher.config().poll_interval.as_secs_f64());
+                // Registrations go first so the reports in the same batch find their agents
+                let mut messages = watcher.on_timer(Duration::from_secs_f64(elapsed));
+                messages.extend(poll_captures(watcher));
+                messages
+            }
+            _ => return false,
+        };
+
+        self.send(&messages);
+        true
+    }
+
+    fn render(&mut self, _rows: usize, cols: usize) {
+        let Some(watcher) = self.watcher.as_ref() else {
+            return;
+        };
+
+        println!("dfcoder: {} agent panes", watcher.pane_states().len());
+        for state in watcher.pane_states() {
+            let role = watcher.panes().get(state.id).map(|pane| pane.role.to_string()).unwrap_or_default();
+            let marker = if state.has_errors { "X" } else if state.is_active { "☼" } else { "z" };
+            let line = format!("{} pane {:<3} {}", marker, state.id, role);
+            println!("{}", line.chars().take(cols).collect::<String>());
+        }
+        if let Some(error) = &self.last_error {
+            println!("{}", error.chars().take(cols).collect::<String>());
+        }
+    }
+}
+
+impl DfcoderPlugin {
+    fn send(&mut self, messages: &[ClientMessage]) {
+        if let Some(outbox) = &self.outbox {
+            self.last_error = outbox
+                .send(messages)
+                .err()
+                .map(|err| format!("spool {}: {}", outbox.path().display(), err));
+        }
+    }
+}
+
+fn poll_captures(watcher: &mut PaneWatcher) -> Vec<ClientMessage> {
+    let pane_ids: Vec<u32> = watcher.panes().panes().map(|pane| pane.pane_id).collect();
+    let max_bytes = watcher.config().max_capture_bytes;
+
+    pane_ids
+        .into_iter()
+        .filter_map(|pane_id| {
+            // Panes that haven't started teeing output yet simply have no capture
+            let capture = read_tail(&watcher.capture_path(pane_id), max_bytes).ok()?;
+            watcher.on_capture(pane_id, &capture)
+        })
+        .collect()
+}
@@ -0,0 +1,268 @@
+//! Host-independent core of the plugin
+
+use crate::capture::{PaneCapture, PaneDelta};
+use crate::mapping::AgentPaneMap;
+use dfcoder_types::protocol::{ClientMessage, DaemonRequest};
+use dfcoder_types::{AgentRole, PaneState};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use zellij_tile::prelude::PaneManifest;
+
+/// Plugin settings, read from the `plugin` block of a Zellij layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatcherConfig {
+    /// Directory holding `<pane id>.log` captures
+    pub capture_dir: PathBuf,
+    /// Spool file dfcoderd ingests
+    pub spool_path: PathBuf,
+    /// Substring identifying agent panes by title or command
+    pub pane_pattern: String,
+    /// How often capture files are polled
+    pub poll_interval: Duration,
+    /// How often every agent pane is registered again, in case dfcoderd missed it
+    pub register_interval: Duration,
+    /// How much of each capture is read and kept
+    pub max_capture_bytes: usize,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            // Zellij mounts the session's working directory at /host
+            capture_dir: PathBuf::from("/host/.dfcoder/panes"),
+            spool_path: PathBuf::from("/host/.dfcoder/spool.jsonl"),
+            pane_pattern: "claude".to_string(),
+            poll_interval: Duration::from_secs(1),
+            register_interval: Duration::from_secs(10),
+            max_capture_bytes: 64 * 1024,
+        }
+    }
+}
+
+impl WatcherConfig {
+    /// Build from layout configuration, falling back to defaults for missing or invalid keys
+    pub fn from_configuration(configuration: &BTreeMap<String, String>) -> Self {
+        let mut config = Self::default();
+        if let Some(dir) = configuration.get("capture_dir") {
+            config.capture_dir = PathBuf::from(dir);
+        }
+        if let Some(path) = configuration.get("spool_path") {
+            config.spool_path = PathBuf::from(path);
+        }
+        if let Some(pattern) = configuration.get("pane_pattern") {
+            config.pane_pattern = pattern.clone();
+        }
+        if let Some(secs) = configuration.get("poll_interval").and_then(|value| value.parse::<f64>().ok()) {
+            if secs > 0.0 {
+                config.poll_interval = Duration::from_secs_f64(secs);
+            }
+        }
+        if let Some(secs) = configuration.get("register_interval").and_then(|value| value.parse::<f64>().ok()) {
+            if secs > 0.0 {
+                config.register_interval = Duration::from_secs_f64(secs);
+            }
+        }
+        if let Some(bytes) = configuration.get("max_capture_bytes").and_then(|value| value.parse().ok()) {
+            config.max_capture_bytes = bytes;
+        }
+        config
+    }
+}
+
+/// Turns pane manifests and captures into control protocol messages
+#[derive(Debug)]
+pub struct PaneWatcher {
+    config: WatcherConfig,
+    panes: AgentPaneMap,
+    captures: HashMap<u32, PaneCapture>,
+    next_id: u64,
+    /// Time since agent panes were last registered
+    since_registration: Duration,
+}
+
+impl PaneWatcher {
+    pub fn new(config: WatcherConfig) -> Self {
+        Self {
+            panes: AgentPaneMap::new(config.pane_pattern.clone()),
+            config,
+            captures: HashMap::new(),
+            next_id: 1,
+            since_registration: Duration::ZERO,
+        }
+    }
+
+    pub fn config(&self) -> &WatcherConfig {
+        &self.config
+    }
+
+    pub fn panes(&self) -> &AgentPaneMap {
+        &self.panes
+    }
+
+    /// Register newly seen agent panes
+    ///
+    /// The daemon treats registration as idempotent per pane, so re-sending after
+    /// a plugin reload or from `on_timer` is harmless. Closed panes only stop being
+    /// captured; the daemon's stuck detection handles agents that go quiet.
+    pub fn on_pane_update(&mut self, manifest: &PaneManifest) -> Vec<ClientMessage> {
+        let changes = self.panes.update(manifest);
+
+        for pane_id in &changes.removed {
+            self.captures.remove(pane_id);
+        }
+
+        changes
+            .added
+            .into_iter()
+            .map(|pane| {
+                self.captures.insert(pane.pane_id, PaneCapture::new(pane.pane_id, self.config.max_capture_bytes));
+                self.message(DaemonRequest::RegisterAgent { role: pane.role, pane_id: pane.pane_id })
+            })
+            .collect()
+    }
+
+    /// Register every agent pane again once `register_interval` has passed
+    ///
+    /// The spool is one-way, so the plugin can't tell whether dfcoderd saw the
+    /// first registration; a daemon started after the plugin skips everything
+    /// already spooled and would otherwise reject every output report.
+    pub fn on_timer(&mut self, elapsed: Duration) -> Vec<ClientMessage> {
+        self.since_registration += elapsed;
+        if self.since_registration < self.config.register_interval {
+            return Vec::new();
+        }
+        self.since_registration = Duration::ZERO;
+
+        let panes: Vec<(AgentRole, u32)> = self.panes.panes().map(|pane| (pane.role.clone(), pane.pane_id)).collect();
+        panes
+            .into_iter()
+            .map(|(role, pane_id)| self.message(DaemonRequest::RegisterAgent { role, pane_id }))
+            .collect()
+    }
+
+    /// Record a capture of an agent pane, returning the delta to forward if anything is new
+    pub fn on_capture(&mut self, pane_id: u32, capture: &str) -> Option<ClientMessage> {
+        let is_focused = self.panes.get(pane_id)?.is_focused;
+        let delta = self.captures.get_mut(&pane_id)?.apply(capture, is_focused);
+
+        match delta {
+            PaneDelta::Unchanged => None,
+            PaneDelta::Appended(output) | PaneDelta::Replaced(output) => {
+                Some(self.message(DaemonRequest::ReportPaneOutput { pane_id, output }))
+            }
+        }
+    }
+
+    /// Where the capture for a pane is expected
+    pub fn capture_path(&self, pane_id: u32) -> PathBuf {
+        self.config.capture_dir.join(format!("{}.log", pane_id))
+    }
+
+    /// Current state of every captured pane, ordered by pane id
+    pub fn pane_states(&self) -> Vec<&PaneState> {
+        let mut states: Vec<&PaneState> = self.captures.values().map(|capture| capture.state()).collect();
+        states.sort_by_key(|state| state.id);
+        states
+    }
+
+    fn message(&mut self, request: DaemonRequest) -> ClientMessage {
+        let id = self.next_id;
+        self.next_id += 1;
+        ClientMessage { id, request }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zellij_tile::prelude::PaneInfo;
+
+    fn manifest(titles: &[(u32, &str)]) -> PaneManifest {
+        let panes = titles
+            .iter()
+            .map(|(id, title)| PaneInfo {
+                id: *id,
+                title: title.to_string(),
+                ..PaneInfo::default()
+            })
+            .collect();
+        PaneManifest { panes: HashMap::from([(0, panes)]) }
+    }
+
+    #[test]
+    fn test_config_from_configuration() {
+        let configuration = BTreeMap::from([
+            ("pane_pattern".to_string(), "agent".to_string()),
+            ("poll_interval".to_string(), "0.5".to_string()),
+            ("max_capture_bytes".to_string(), "oops".to_string()),
+        ]);
+        let config = WatcherConfig::from_configuration(&configuration);
+
+        assert_eq!(config.pane_pattern, "agent");
+        assert_eq!(config.poll_interval, Duration::from_millis(500));
+        assert_eq!(config.max_capture_bytes, WatcherConfig::default().max_capture_bytes);
+    }
+
+    #[test]
+    fn test_registers_new_agent_panes_once() {
+        let mut watcher = PaneWatcher::new(WatcherConfig::default());
+
+        let messages = watcher.on_pane_update(&manifest(&[(1, "claude tester"), (2, "vim")]));
+        assert_eq!(messages.len(), 1);
+        match &messages[0].request {
+            DaemonRequest::RegisterAgent { role, pane_id } => {
+                assert_eq!(*role, AgentRole::Tester);
+                assert_eq!(*pane_id, 1);
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+
+        assert!(watcher.on_pane_update(&manifest(&[(1, "claude tester")])).is_empty());
+        assert_eq!(watcher.capture_path(1), PathBuf::from("/host/.dfcoder/panes/1.log"));
+    }
+
+    #[test]
+    fn test_reregisters_agent_panes_on_timer() {
+        let mut watcher = PaneWatcher::new(WatcherConfig::default());
+        watcher.on_pane_update(&manifest(&[(1, "claude tester"), (3, "claude debugger"), (2, "vim")]));
+
+        assert!(watcher.on_timer(Duration::from_secs(6)).is_empty());
+        let messages = watcher.on_timer(Duration::from_secs(6));
+        let registered: Vec<u32> = messages
+            .iter()
+            .map(|message| match &message.request {
+                DaemonRequest::RegisterAgent { pane_id, .. } => *pane_id,
+                other => panic!("unexpected request {:?}", other),
+            })
+            .collect();
+        assert_eq!(registered, vec![1, 3]);
+
+        // The interval starts over after each round
+        assert!(watcher.on_timer(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_forwards_only_new_output() {
+        let mut watcher = PaneWatcher::new(WatcherConfig::default());
+        watcher.on_pane_update(&manifest(&[(1, "claude")]));
+
+        let first = watcher.on_capture(1, "Compiling\n").unwrap();
+        let second = watcher.on_capture(1, "Compiling\nerror: linker failed\n").unwrap();
+        assert!(watcher.on_capture(1, "Compiling\nerror: linker failed\n").is_none());
+        assert!(watcher.on_capture(2, "not an agent").is_none());
+
+        assert!(second.id > first.id);
+        match second.request {
+            DaemonRequest::ReportPaneOutput { pane_id, output } => {
+                assert_eq!(pane_id, 1);
+                assert_eq!(output, "error: linker failed\n");
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+        assert!(watcher.pane_states()[0].has_errors);
+
+        watcher.on_pane_update(&manifest(&[]));
+        assert!(watcher.pane_states().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! `dfcoder eval-classifier`: score the activity classifier against a labelled corpus
+
+use anyhow::{bail, Context, Result};
+use dfcoder_baml::{ActivityClassifier, BamlConfig, ProviderKind, RuleSource};
+use dfcoder_test_utils::TranscriptCorpus;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: dfcoder eval-classifier [--corpus <path>] [--rules <file.toml>]... \
+[--llm <anthropic|openai|ollama>] [--model <name>] [--endpoint <url>]
+
+Scores the rule-based classifier, and the model too with --llm, against a
+corpus of labelled pane transcripts; the corpus built into dfcoder is used
+without --corpus. The API key is read from
+ANTHROPIC_API_KEY or OPENAI_API_KEY.";
+
+/// Options of `eval-classifier`
+#[derive(Debug)]
+pub struct EvalOptions {
+    /// `None` uses the built-in corpus
+    corpus: Option<PathBuf>,
+    rule_files: Vec<PathBuf>,
+    llm: Option<BamlConfig>,
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<EvalOptions> {
+    let mut corpus = None;
+    let mut rule_files = Vec::new();
+    let mut provider = None;
+    let mut model = None;
+    let mut endpoint = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--corpus" => corpus = Some(PathBuf::from(args.next().context(USAGE)?)),
+            "--rules" => rule_files.push(PathBuf::from(args.next().context(USAGE)?)),
+            "--llm" => provider = Some(parse_provider(&args.next().context(USAGE)?)?),
+            "--model" => model = Some(args.next().context(USAGE)?),
+            "--endpoint" => endpoint = Some(args.next().context(USAGE)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => bail!("unknown argument '{}'\n{}", other, USAGE),
+        }
+    }
+
+    let llm = match provider {
+        Some(provider) => {
+            let mut config = BamlConfig::for_provider(provider);
+            config.api_key = api_key(provider);
+            if let Some(model) = model {
+                config.model = model;
+            }
+            if let Some(endpoint) = endpoint {
+                config.endpoint = endpoint;
+            }
+            Some(config)
+        }
+        None if model.is_some() || endpoint.is_some() => bail!("--model and --endpoint need --llm\n{}", USAGE),
+        None => None,
+    };
+
+    Ok(EvalOptions { corpus, rule_files, llm })
+}
+
+fn parse_provider(name: &str) -> Result<ProviderKind> {
+    match name {
+        "anthropic" => Ok(ProviderKind::Anthropic),
+        "openai" => Ok(ProviderKind::OpenAi),
+        "ollama" => Ok(ProviderKind::Ollama),
+        other => bail!("unknown provider '{}'\n{}", other, USAGE),
+    }
+}
+
+fn api_key(provider: ProviderKind) -> Option<String> {
+    std::env::var(provider.api_key_var()?).ok().filter(|key| !key.is_empty())
+}
+
+pub async fn run(options: EvalOptions) -> Result<()> {
+    let corpus = match &options.corpus {
+        Some(path) => TranscriptCorpus::load(path).with_context(|| format!("failed to load corpus {}", path.display()))?,
+        None => TranscriptCorpus::fixtures().context("failed to load the built-in corpus")?,
+    };
+    if corpus.is_empty() {
+        bail!("no transcripts in the corpus");
+    }
+    let rules = RuleSource::load(options.rule_files).context("failed to load rules")?;
+
+    let rule_based = ActivityClassifier::rule_based().with_rules(rules.clone());
+    println!("== Rules, {} transcripts ==\n", corpus.len());
+    println!("{}", corpus.evaluate(&rule_based).await);
+
+    if let Some(config) = options.llm {
+        let name = format!("{} {}", config.provider, config.model);
+        let classifier = ActivityClassifier::from_config(config).with_rules(rules);
+        if !classifier.uses_llm() {
+            bail!("could not set up {}", name);
+        }
+        println!("\n== Model {}, {} transcripts ==\n", name, corpus.len());
+        println!("{}", corpus.evaluate(&classifier).await);
+        if let Some(usage) = classifier.usage() {
+            println!(
+                "\n{} calls, {} input and {} output tokens, ${:.4}",
+                usage.total.calls, usage.total.input_tokens, usage.total.output_tokens, usage.total.cost_usd
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_defaults_to_shipped_corpus_and_rules_only() {
+        let options = parse_args(args(&[])).unwrap();
+        assert_eq!(options.corpus, None);
+        assert!(options.rule_files.is_empty());
+        assert!(options.llm.is_none());
+    }
+
+    #[test]
+    fn test_llm_options() {
+        let options = parse_args(args(&[
+            "--corpus", "corpus", "--rules", "a.toml", "--rules", "b.toml",
+            "--llm", "ollama", "--model", "llama3", "--endpoint", "http://localhost:11434",
+        ])).unwrap();
+        assert_eq!(options.corpus, Some(PathBuf::from("corpus")));
+        assert_eq!(options.rule_files, vec![PathBuf::from("a.toml"), PathBuf::from("b.toml")]);
+
+        let llm = options.llm.unwrap();
+        assert_eq!(llm.provider, ProviderKind::Ollama);
+        assert_eq!(llm.model, "llama3");
+        assert_eq!(llm.endpoint, "http://localhost:11434");
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        assert!(parse_args(args(&["--model", "gpt-4o"])).is_err());
+        assert!(parse_args(args(&["--llm", "palm"])).is_err());
+        assert!(parse_args(args(&["--corpus"])).is_err());
+        assert!(parse_args(args(&["--verbose"])).is_err());
+    }
+}
@@ -1,13 +1,103 @@
-use anyhow::Result;
-use tracing_subscriber;
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+mod eval_classifier;
+
+const USAGE: &str = "usage: dfcoder [--socket <path> | --local]
+       dfcoder eval-classifier [<options>]
+
+Without a command, opens the dashboard. It attaches to dfcoderd when the
+daemon's socket exists and shows an empty workshop of its own otherwise.
+
+  --socket <path>   attach to the dfcoderd listening on <path>
+  --local           never attach; use a workshop inside the dashboard
+
+eval-classifier scores the activity classifier against a corpus of labelled
+pane transcripts; see `dfcoder eval-classifier --help`.";
+
+/// Where the dashboard gets its workshop from
+#[derive(Debug, PartialEq)]
+enum Workshop {
+    /// dfcoderd at its default socket if it's running, else a local workshop
+    Auto,
+    /// dfcoderd at the given socket
+    Attach(PathBuf),
+    Local,
+}
+
+/// What the command line asks for
+#[derive(Debug)]
+enum Command {
+    Dashboard(Workshop),
+    EvalClassifier(Box<eval_classifier::EvalOptions>),
+    Help,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    
-    tracing::info!("Starting DFCoder...");
-    
-    dfcoder_tui::run().await?;
-    
+    // Log to stderr so the dashboard owns stdout
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    match parse_command(std::env::args().skip(1))? {
+        Command::Dashboard(workshop) => {
+            tracing::info!("Starting DFCoder...");
+            match workshop {
+                Workshop::Auto => dfcoder_tui::run().await?,
+                Workshop::Attach(socket_path) => {
+                    dfcoder_tui::attach(&socket_path, dfcoder_tui::TuiConfig::default()).await?
+                }
+                Workshop::Local => dfcoder_tui::run_local().await?,
+            }
+        }
+        Command::EvalClassifier(options) => eval_classifier::run(*options).await?,
+        Command::Help => println!("{}", USAGE),
+    }
+
     Ok(())
 }
+
+fn parse_command(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let workshop = match args.next().as_deref() {
+        None => Workshop::Auto,
+        Some("eval-classifier") => return Ok(Command::EvalClassifier(Box::new(eval_classifier::parse_args(args)?))),
+        Some("-h" | "--help") => return Ok(Command::Help),
+        Some("--socket") => Workshop::Attach(args.next().map(PathBuf::from).context(USAGE)?),
+        Some("--local") => Workshop::Local,
+        Some(other) => bail!("unknown command '{}'\n{}", other, USAGE),
+    };
+
+    if let Some(extra) = args.next() {
+        bail!("unexpected argument '{}'\n{}", extra, USAGE);
+    }
+    Ok(Command::Dashboard(workshop))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert!(matches!(parse_command(args(&[])).unwrap(), Command::Dashboard(Workshop::Auto)));
+        assert!(matches!(parse_command(args(&["--local"])).unwrap(), Command::Dashboard(Workshop::Local)));
+        match parse_command(args(&["--socket", "/run/dfcoderd.sock"])).unwrap() {
+            Command::Dashboard(workshop) => assert_eq!(workshop, Workshop::Attach(PathBuf::from("/run/dfcoderd.sock"))),
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(parse_command(args(&["--socket"])).is_err());
+        assert!(parse_command(args(&["--local", "--socket", "x"])).is_err());
+        assert!(matches!(parse_command(args(&["--help"])).unwrap(), Command::Help));
+        assert!(matches!(
+            parse_command(args(&["eval-classifier", "--corpus", "corpus"])).unwrap(),
+            Command::EvalClassifier(_)
+        ));
+        assert!(parse_command(args(&["eval-classifier", "--llm"])).is_err());
+        assert!(parse_command(args(&["serve"])).is_err());
+    }
+}
@@ -46,8 +46,8 @@ async fn test_complete_agent_workflow() {
     );
     
     // Queue tasks
-    workshop.queue_task(setup_task);
-    workshop.queue_task(implement_task);
+    workshop.queue_task(setup_task).unwrap();
+    workshop.queue_task(implement_task).unwrap();
     
     assert_eq!(workshop.get_queue().len(), 2);
     println!("✅ Tasks queued successfully");
@@ -104,7 +104,7 @@ async fn test_end_to_end_agent_supervision_flow() {
         TaskPriority::High,
     );
     let task_id = task.id.clone();
-    workshop.queue_task(task);
+    workshop.queue_task(task).unwrap();
     
     let assignment = workshop.try_assign_next_task().unwrap().unwrap();
     assert_eq!(assignment.0, agent_id);
@@ -4,7 +4,8 @@
 //! retry mechanisms, and task execution resilience features.
 
 use dfcoder_core::*;
-use dfcoder_test_utils::TestSystem;
+use dfcoder_test_utils::{ScriptedRunner, TestSystem};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio;
 
@@ -48,10 +49,13 @@ async fn test_error_recovery_and_retry_logic() {
     println!("✅ Smart retry logic verified - only retries appropriate error types");
     
     // Test 1.4: Verify actual retry execution with delays
-    let executor = RetryExecutor::new(RetryPolicy::default());
+    // The agent loses its connection on the first run, succeeds on the second,
+    // then hits an error retrying won't fix
+    let runner = Arc::new(ScriptedRunner::new().network_error().succeed("Network task done\n").fatal());
+    let executor = RetryExecutor::with_runner(RetryPolicy::default(), runner.clone());
     let mut agent = Agent::new(AgentRole::Implementer, 1);
     
-    // Task that will fail on first attempt but succeed on second (simulated network issue)
+    // Task that will fail on first attempt but succeed on second
     let network_task = Task::new(
         "Network Task".to_string(),
         "Task with network issues".to_string(),
@@ -85,6 +89,7 @@ async fn test_error_recovery_and_retry_logic() {
     
     assert!(fatal_result.is_err(), "Fatal errors should not be retried");
     assert!(fast_elapsed < Duration::from_millis(500), "Fatal errors should fail fast");
+    assert_eq!(runner.runs().len(), 3, "Each attempt should run the agent");
     
     println!("✅ Non-retryable errors fail fast as expected");
     println!("🎉 TEST PASSED: Error Recovery & Retry Logic fully implemented");
@@ -96,6 +101,7 @@ async fn test_task_prioritization_system() {
     println!("🔍 TESTING: Task Prioritization System");
     
     let mut workshop = WorkshopManager::new();
+    workshop.set_agent_runner(Arc::new(ScriptedRunner::new()));
     let agent = Agent::new(AgentRole::Implementer, 1);
     let agent_id = agent.id.clone();
     workshop.register_agent(agent).unwrap();
@@ -115,8 +121,8 @@ async fn test_task_prioritization_system() {
     let normal_priority_task = Task::new("Normal".to_string(), "Normal task".to_string(), AgentRole::Implementer, TaskPriority::Normal);
     
     // Queue in reverse priority order
-    workshop.queue_task(normal_priority_task);
-    workshop.queue_task(high_priority_task);
+    workshop.queue_task(normal_priority_task).unwrap();
+    workshop.queue_task(high_priority_task).unwrap();
     
     // Should assign high priority task first
     let assignment = workshop.assign_by_priority().unwrap();
@@ -127,13 +133,14 @@ async fn test_task_prioritization_system() {
     println!("✅ Priority-based task assignment verified");
     
     // Test 2.3: Verify agent expertise tracking exists
-    let task_result = TaskResult {
+    let task_attempt = TaskAttempt {
         success: true,
         output: "Task completed successfully".to_string(),
         error: None,
         duration: Duration::from_secs(30),
         attempt_number: 1,
     };
+    assert!(matches!(TaskResult::from(&task_attempt), TaskResult::Success), "A successful attempt should report success");
     
     // Complete the task to trigger expertise update
     workshop.complete_task(assigned_agent.clone(), assigned_task.id).unwrap();
@@ -3,7 +3,8 @@
 //! Basic tests to verify system integration and core functionality.
 
 use dfcoder_core::*;
-use dfcoder_test_utils::TestSystem;
+use dfcoder_test_utils::{ScriptedRunner, TestSystem};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Test 1: Error Recovery & Retry Logic Integration
@@ -33,7 +34,8 @@ async fn test_error_recovery_retry_logic() {
     println!("✅ Smart error type filtering");
     
     // 1.4: RetryExecutor can execute tasks with actual retries
-    let executor = RetryExecutor::new(RetryPolicy::default());
+    let runner = Arc::new(ScriptedRunner::new().network_error());
+    let executor = RetryExecutor::with_runner(RetryPolicy::default(), runner);
     let mut agent = Agent::new(AgentRole::Implementer, 1);
     let task = Task::new("Test".to_string(), "Task with network issues".to_string(), AgentRole::Implementer, TaskPriority::Normal);
     
@@ -55,6 +57,7 @@ async fn test_task_prioritization_system() {
     println!("🔍 Testing Task Prioritization System Integration");
     
     let mut workshop = WorkshopManager::new();
+    workshop.set_agent_runner(Arc::new(ScriptedRunner::new()));
     let agent = Agent::new(AgentRole::Implementer, 1);
     let agent_id = agent.id.clone();
     workshop.register_agent(agent).unwrap();
@@ -63,8 +66,8 @@ async fn test_task_prioritization_system() {
     let high_task = Task::new("Critical".to_string(), "Critical task".to_string(), AgentRole::Implementer, TaskPriority::High);
     let normal_task = Task::new("Normal".to_string(), "Normal task".to_string(), AgentRole::Implementer, TaskPriority::Normal);
     
-    workshop.queue_task(normal_task);
-    workshop.queue_task(high_task);
+    workshop.queue_task(normal_task).unwrap();
+    workshop.queue_task(high_task).unwrap();
     
     let assignment = workshop.assign_by_priority().unwrap();
     assert!(assignment.is_some());
@@ -34,6 +34,7 @@ async fn test_supervision_system() {
         needs_help: true,
         emotional_state: EmotionalState::Frustrated,
         estimated_completion: None,
+        source: ClassificationSource::Rules,
     };
     
     let stuck_options = generate_dialogue_options(&agent, &stuck_activity, "I'm stuck");
@@ -74,6 +75,7 @@ async fn test_urgency_determination() {
         needs_help: true,
         emotional_state: EmotionalState::Desperate,
         estimated_completion: None,
+        source: ClassificationSource::Rules,
     };
     assert_eq!(determine_urgency(&desperate), SupervisionUrgency::Critical);
 
@@ -83,6 +85,7 @@ async fn test_urgency_determination() {
         needs_help: true,
         emotional_state: EmotionalState::Frustrated,
         estimated_completion: None,
+        source: ClassificationSource::Rules,
     };
     assert_eq!(determine_urgency(&frustrated_stuck), SupervisionUrgency::High);
 
@@ -92,6 +95,7 @@ async fn test_urgency_determination() {
         needs_help: true,
         emotional_state: EmotionalState::Cautious,
         estimated_completion: None,
+        source: ClassificationSource::Rules,
     };
     assert_eq!(determine_urgency(&low_confidence), SupervisionUrgency::Medium);
     
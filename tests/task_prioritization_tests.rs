@@ -3,7 +3,8 @@
 //! Systematic verification of task prioritization, error recovery, and coordination systems.
 
 use dfcoder_core::*;
-use dfcoder_test_utils::TestSystem;
+use dfcoder_test_utils::{ScriptedRunner, TestSystem};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Test 1: Error Recovery & Retry Logic
@@ -34,7 +35,8 @@ async fn test_error_recovery_implementation() {
     println!("✅ Smart retry logic verified");
     
     // Verify 1.4: Actual retry execution with real delays
-    let executor = RetryExecutor::new(RetryPolicy::default());
+    let runner = Arc::new(ScriptedRunner::new().network_error());
+    let executor = RetryExecutor::with_runner(RetryPolicy::default(), runner);
     let mut agent = Agent::new(AgentRole::Implementer, 1);
     let network_task = Task::new(
         "Network Test".to_string(),
@@ -63,6 +65,7 @@ async fn test_task_prioritization_implementation() {
     println!("🔍 TESTING 2: Task Prioritization System");
     
     let mut workshop = WorkshopManager::new();
+    workshop.set_agent_runner(Arc::new(ScriptedRunner::new()));
     let agent = Agent::new(AgentRole::Implementer, 1);
     let agent_id = agent.id.clone();
     workshop.register_agent(agent).unwrap();
@@ -72,8 +75,8 @@ async fn test_task_prioritization_implementation() {
     let normal_task = Task::new("Normal".to_string(), "Normal task".to_string(), AgentRole::Implementer, TaskPriority::Normal);
     
     // Queue in reverse priority order
-    workshop.queue_task(normal_task);
-    workshop.queue_task(high_task);
+    workshop.queue_task(normal_task).unwrap();
+    workshop.queue_task(high_task).unwrap();
     
     let assignment = workshop.assign_by_priority().unwrap();
     assert!(assignment.is_some());
@@ -28,7 +28,7 @@ async fn test_workshop_capacity_and_bottleneck_detection() {
             AgentRole::Implementer,
             TaskPriority::Normal,
         );
-        workshop.queue_task(task);
+        workshop.queue_task(task).unwrap();
     }
     
     // Assign all available implementer capacity
@@ -64,7 +64,7 @@ async fn test_workshop_capacity_and_bottleneck_detection() {
         AgentRole::Debugger,
         TaskPriority::High,
     );
-    workshop.queue_task(debugger_task);
+    workshop.queue_task(debugger_task).unwrap();
     
     let debugger_assignment = workshop.try_assign_next_task().unwrap();
     assert!(debugger_assignment.is_some()); // Should work
@@ -106,4 +106,35 @@ async fn test_capacity_limits_per_role() {
     
     println!("✅ Implementer capacity limits working");
     println!("🎉 Capacity Limits Per Role Test PASSED");
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_dependency_graph_orders_work() {
+    println!("🧪 Testing Task Dependency Graph");
+
+    let mut workshop = WorkshopManager::new();
+    workshop.register_agent(Agent::new(AgentRole::Scaffolder, 1)).unwrap();
+    workshop.register_agent(Agent::new(AgentRole::Implementer, 2)).unwrap();
+
+    let setup = Task::new("Setup".to_string(), "Scaffold the crate".to_string(), AgentRole::Scaffolder, TaskPriority::Low);
+    let mut feature = Task::new("Feature".to_string(), "Implement the feature".to_string(), AgentRole::Implementer, TaskPriority::Critical);
+    feature.context.dependencies = vec![setup.id.clone()];
+    let (setup_id, feature_id) = (setup.id.clone(), feature.id.clone());
+    workshop.queue_tasks(vec![feature, setup]).unwrap();
+
+    // The critical feature waits for its low-priority dependency
+    let (scaffolder, first) = workshop.try_assign_next_task().unwrap().unwrap();
+    assert!(workshop.try_assign_next_task().unwrap().is_none());
+    assert_eq!(workshop.get_status().blocked_by[&feature_id], vec![setup_id.clone()]);
+    assert_eq!(workshop.get_agent(&scaffolder).unwrap().current_task, Some(setup_id.clone()));
+    println!("✅ Blocked task held back until its dependency finishes");
+
+    workshop.complete_task(scaffolder, setup_id).unwrap();
+    assert!(workshop.get_status().blocked_by.is_empty());
+    let (implementer, _) = workshop.try_assign_next_task().unwrap().unwrap();
+    assert_ne!(implementer, first);
+    assert_eq!(workshop.critical_path().tasks, vec![feature_id]);
+    println!("✅ Dependent task assigned once unblocked");
+
+    println!("🎉 Task Dependency Graph Test PASSED");
+}